@@ -0,0 +1,72 @@
+//! Renders a frame of a generated world without a window and saves it as PNG, like the visual
+//! regression tests do. Run it from the root of the repository, the textures and shaders are read from there
+
+#[macro_use]
+extern crate log;
+extern crate pretty_env_logger;
+
+use nalgebra_glm::{Vec3, vec3};
+
+use meinkraft::offscreen::Scene;
+
+const USAGE: &str = "Usage: meinkraft-render [--seed <seed>] [--view-distance <columns>] [--position <x,y,z>] \
+                     [--target <x,y,z>] [--size <width>x<height>] [--output <file.png>]";
+
+fn parse_vec3(text: &str) -> Option<Vec3> {
+    let coords = text.split(',').map(|coord| coord.trim().parse::<f32>().ok()).collect::<Option<Vec<_>>>()?;
+    match coords.as_slice() {
+        &[x, y, z] => Some(vec3(x, y, z)),
+        _ => None,
+    }
+}
+
+fn parse_size(text: &str) -> Option<(u32, u32)> {
+    let mut parts = text.splitn(2, 'x');
+    let width = parts.next()?.parse().ok()?;
+    let height = parts.next()?.parse().ok()?;
+    Some((width, height))
+}
+
+fn main() {
+    if std::env::var("RUST_LOG").is_err() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    pretty_env_logger::init();
+
+    let mut scene = Scene {
+        seed: 0,
+        view_distance: 4,
+        camera_position: vec3(8.0, 180.0, 8.0),
+        camera_target: vec3(40.0, 90.0, 40.0),
+    };
+    let mut size = (800, 600);
+    let mut output = "frame.png".to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--seed", Some(value)) => scene.seed = value.parse().unwrap_or(scene.seed),
+            ("--view-distance", Some(value)) => scene.view_distance = value.parse().unwrap_or(scene.view_distance),
+            ("--position", Some(value)) => scene.camera_position = parse_vec3(&value).unwrap_or(scene.camera_position),
+            ("--target", Some(value)) => scene.camera_target = parse_vec3(&value).unwrap_or(scene.camera_target),
+            ("--size", Some(value)) => size = parse_size(&value).unwrap_or(size),
+            ("--output", Some(value)) => output = value,
+            _ => {
+                error!("{}", USAGE);
+                return;
+            }
+        }
+    }
+
+    let frame = match scene.render(size.0, size.1) {
+        Ok(frame) => frame,
+        Err(err) => {
+            error!("Couldn't render the frame: {}", err);
+            return;
+        }
+    };
+    match frame.save(&output) {
+        Ok(()) => info!("Saved the frame in {}", output),
+        Err(err) => error!("Couldn't save the frame in {}: {}", output, err),
+    }
+}
@@ -0,0 +1,98 @@
+//! Dedicated server: simulates the world for remote clients without opening a window.
+//! Admin commands are read from stdin, type `help` for the list
+//!
+//! Build it with `--no-default-features`: without `rendering` it links neither GLFW nor OpenGL
+
+#[macro_use]
+extern crate log;
+extern crate pretty_env_logger;
+
+use std::io::BufRead;
+use std::sync::Arc;
+use std::thread;
+
+use crossbeam_channel::unbounded;
+
+use meinkraft::block_model::BlockModels;
+use meinkraft::constants::{ASSET_DIRECTORY, DEFAULT_SERVER_PORT, RENDER_DISTANCE};
+use meinkraft::network::server::Server;
+use meinkraft::world_storage::WorldStorage;
+
+fn main() {
+    if std::env::var("RUST_LOG").is_err() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    pretty_env_logger::init();
+
+    let mut bind_address = "0.0.0.0".to_string();
+    let mut port = DEFAULT_SERVER_PORT;
+    let mut world_directory = "world".to_string();
+    let mut view_distance = RENDER_DISTANCE;
+    let mut allow_flying = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--allow-flying" {
+            allow_flying = true;
+            continue;
+        }
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--bind", Some(value)) => bind_address = value,
+            ("--port", Some(value)) => port = value.parse().unwrap_or(port),
+            ("--world", Some(value)) => world_directory = value,
+            ("--view-distance", Some(value)) => view_distance = value.parse().unwrap_or(view_distance),
+            _ => {
+                error!("Usage: meinkraft-server [--bind <address>] [--port <port>] [--world <directory>] [--view-distance <columns>] [--allow-flying]");
+                return;
+            }
+        }
+    }
+
+    let storage = match WorldStorage::open(&world_directory) {
+        Ok(storage) => storage,
+        Err(err) => {
+            error!("Couldn't open the world in {}: {}", world_directory, err);
+            return;
+        }
+    };
+
+    let block_models = match BlockModels::load(ASSET_DIRECTORY) {
+        Ok(block_models) => Arc::new(block_models),
+        Err(err) => {
+            error!("Couldn't load the block models: {}", err);
+            return;
+        }
+    };
+
+    let (commands_tx, commands_rx) = unbounded();
+    thread::Builder::new()
+        .name("console".to_string())
+        .spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) => if commands_tx.send(line).is_err() {
+                        break;
+                    },
+                    Err(err) => {
+                        error!("{}", err);
+                        break;
+                    }
+                }
+            }
+        })
+        .unwrap();
+
+    // Chunk columns are built on the stack before being boxed
+    let server_thread = thread::Builder::new()
+        .name("server".to_string())
+        .stack_size(16 * 1024 * 1024)
+        .spawn(move || {
+            match Server::bind((bind_address.as_str(), port), view_distance, storage, block_models) {
+                Ok(server) if allow_flying => server.allow_flying().run(commands_rx),
+                Ok(server) => server.run(commands_rx),
+                Err(err) => error!("Couldn't start the server: {}", err),
+            }
+        })
+        .unwrap();
+    server_thread.join().unwrap();
+}
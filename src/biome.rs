@@ -0,0 +1,200 @@
+//! Biomes tint the grass and the leaves. Every column of blocks has a climate, a temperature and a
+//! humidity, which picks the colour of the tinted faces in a colour map, like in Minecraft:
+//! `textures/colormap/grass.png` and `textures/colormap/foliage.png`.
+//!
+//! The faces of the models with a `tintindex` are tinted, with the colour map of their block. The colour
+//! of a block is blended with the columns around it so that the biomes fade into each other
+
+use crate::chunk::BlockID;
+use crate::chunk_manager::ChunkManager;
+
+/// How many columns on each side of a block its tint is averaged over
+pub const TINT_BLEND_RADIUS: i32 = 2;
+
+/// The climate of a column of blocks, from 0 to 255
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Climate {
+    pub temperature: u8,
+    pub humidity: u8,
+}
+
+impl Default for Climate {
+    /// The plains
+    fn default() -> Self {
+        Self::new(0.8, 0.4)
+    }
+}
+
+impl Climate {
+    /// `temperature` and `humidity` are clamped between 0 and 1
+    pub fn new(temperature: f32, humidity: f32) -> Self {
+        let to_u8 = |value: f32| (value.max(0.0).min(1.0) * 255.0).round() as u8;
+        Self {
+            temperature: to_u8(temperature),
+            humidity: to_u8(humidity),
+        }
+    }
+
+    /// From 0 to 1
+    pub fn temperature(&self) -> f32 {
+        self.temperature as f32 / 255.0
+    }
+
+    /// From 0 to 1
+    pub fn humidity(&self) -> f32 {
+        self.humidity as f32 / 255.0
+    }
+}
+
+/// The colour map a tinted face takes its colour from
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Tint {
+    Grass,
+    Foliage,
+}
+
+impl Tint {
+    pub const ALL: [Tint; 2] = [Tint::Grass, Tint::Foliage];
+
+    /// The colour map in the resource packs
+    pub fn color_map_path(&self) -> &'static str {
+        match self {
+            Tint::Grass => "textures/colormap/grass.png",
+            Tint::Foliage => "textures/colormap/foliage.png",
+        }
+    }
+
+    /// The colour of the items, which aren't in any biome. The colour of the plains
+    pub fn item_color(&self) -> [f32; 3] {
+        match self {
+            Tint::Grass => [0.569, 0.741, 0.349],
+            Tint::Foliage => [0.467, 0.671, 0.184],
+        }
+    }
+}
+
+impl BlockID {
+    /// The colour map of the tinted faces of the block
+    pub fn tint(&self) -> Option<Tint> {
+        match self {
+            BlockID::GrassBlock | BlockID::TallGrass => Some(Tint::Grass),
+            BlockID::OakLeaves => Some(Tint::Foliage),
+            _ => None,
+        }
+    }
+}
+
+/// A colour map, indexed by the temperature from right to left and by the humidity times the temperature
+/// from bottom to top: hot and wet is in the top left corner, cold in the bottom right one
+#[derive(Debug, Clone)]
+pub struct ColorMap {
+    width: u32,
+    height: u32,
+    colors: Vec<[f32; 3]>,
+}
+
+impl ColorMap {
+    /// From the RGBA pixels of an image, row by row from the top
+    pub fn from_rgba(width: u32, height: u32, pixels: &[u8]) -> Self {
+        assert_eq!(pixels.len(), 4 * (width * height) as usize);
+        Self {
+            width,
+            height,
+            colors: pixels.chunks_exact(4)
+                .map(|pixel| [pixel[0] as f32 / 255.0, pixel[1] as f32 / 255.0, pixel[2] as f32 / 255.0])
+                .collect(),
+        }
+    }
+
+    /// The same colour in every climate
+    pub fn uniform(color: [f32; 3]) -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            colors: vec![color],
+        }
+    }
+
+    pub fn color(&self, climate: Climate) -> [f32; 3] {
+        let temperature = climate.temperature();
+        let humidity = climate.humidity() * temperature;
+        let x = ((1.0 - temperature) * (self.width - 1) as f32).round() as u32;
+        let y = ((1.0 - humidity) * (self.height - 1) as f32).round() as u32;
+        self.colors[(self.width * y + x) as usize]
+    }
+}
+
+/// The colour map of each tint
+#[derive(Debug, Clone)]
+pub struct ColorMaps {
+    grass: ColorMap,
+    foliage: ColorMap,
+}
+
+impl Default for ColorMaps {
+    /// The colours of the items everywhere
+    fn default() -> Self {
+        Self::new(ColorMap::uniform(Tint::Grass.item_color()), ColorMap::uniform(Tint::Foliage.item_color()))
+    }
+}
+
+impl ColorMaps {
+    pub fn new(grass: ColorMap, foliage: ColorMap) -> Self {
+        Self {
+            grass,
+            foliage,
+        }
+    }
+
+    pub fn get(&self, tint: Tint) -> &ColorMap {
+        match tint {
+            Tint::Grass => &self.grass,
+            Tint::Foliage => &self.foliage,
+        }
+    }
+}
+
+/// The tint of every column of blocks of the chunk column (c_x, c_z), in `16 * z + x` order. Each is the
+/// average colour of the columns within `TINT_BLEND_RADIUS` of it, the columns that aren't loaded are left out
+pub fn blended_tints(chunk_manager: &ChunkManager, color_map: &ColorMap, c_x: i32, c_z: i32) -> [[f32; 3]; 256] {
+    // The colours of the columns of the chunk column and of the border around it
+    let side = 16 + 2 * TINT_BLEND_RADIUS;
+    let mut colors = vec![None; (side * side) as usize];
+    for n_x in c_x - 1..=c_x + 1 {
+        for n_z in c_z - 1..=c_z + 1 {
+            let climate = match chunk_manager.get_column(n_x, n_z) {
+                Some(column) => column.climate.read().clone(),
+                None => continue,
+            };
+            for b_x in 0..16 {
+                for b_z in 0..16 {
+                    let x = 16 * (n_x - c_x) + b_x + TINT_BLEND_RADIUS;
+                    let z = 16 * (n_z - c_z) + b_z + TINT_BLEND_RADIUS;
+                    if (0..side).contains(&x) && (0..side).contains(&z) {
+                        colors[(side * z + x) as usize] = Some(color_map.color(climate[(16 * b_z + b_x) as usize]));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut tints = [[1.0; 3]; 256];
+    for b_x in 0..16 {
+        for b_z in 0..16 {
+            let mut sum = [0.0; 3];
+            let mut count = 0;
+            for x in b_x..=b_x + 2 * TINT_BLEND_RADIUS {
+                for z in b_z..=b_z + 2 * TINT_BLEND_RADIUS {
+                    if let Some(color) = colors[(side * z + x) as usize] {
+                        (0..3).for_each(|i| sum[i] += color[i]);
+                        count += 1;
+                    }
+                }
+            }
+            if count > 0 {
+                tints[(16 * b_z + b_x) as usize] = [sum[0] / count as f32, sum[1] / count as f32, sum[2] / count as f32];
+            }
+        }
+    }
+    tints
+}
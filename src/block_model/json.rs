@@ -0,0 +1,412 @@
+//! The JSON files describing the blocks, close to the format of Minecraft:
+//! * `blockstates/<block>.json` maps the properties of a block (`"facing=east,half=top"`, `""` without
+//!   properties) to a model, rotated by `x` and `y` degrees in steps of 90
+//! * `models/<model>.json` is made of elements, boxes going from 0 to 16 with a texture, a UV rectangle
+//!   and a cull face on each of their faces. Each element can be rotated around an axis by up to 45°.
+//!   The faces with a `tintindex` are tinted by the biome, see [`biome`](crate::biome)
+//!
+//! A model inherits the elements and the textures of its `parent`. Textures starting with `#` name
+//! another texture of the model, the others are images: `blocks/stone` is `textures/blocks/stone.png`.
+//!
+//! Unlike Minecraft, the collision and selection boxes are part of the models: `collision` and
+//! `selection` are lists of `from`/`to` boxes, which default to the elements that aren't rotated
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use nalgebra_glm::{Vec3, vec3};
+use serde::Deserialize;
+
+use crate::aabb::AABB;
+use crate::block_model::{BACK, BakedQuad, BlockModel, BlockShape, BOTTOM, box_face_corners, face_normal,
+                         face_of_direction, FRONT, LEFT, RIGHT, TOP};
+use crate::types::TextureLayer;
+
+/// Models including each other deeper than this are considered to be a loop
+const MAX_PARENTS: usize = 16;
+
+const FACE_NAMES: [&str; 6] = ["down", "up", "north", "south", "west", "east"];
+
+#[derive(Deserialize)]
+struct BlockStateFile {
+    variants: HashMap<String, VariantFile>,
+}
+
+#[derive(Deserialize)]
+struct VariantFile {
+    model: String,
+    #[serde(default)]
+    x: i32,
+    #[serde(default)]
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct ModelFile {
+    parent: Option<String>,
+    ambientocclusion: Option<bool>,
+    #[serde(default)]
+    textures: HashMap<String, String>,
+    elements: Option<Vec<ElementFile>>,
+    collision: Option<Vec<BoxFile>>,
+    selection: Option<Vec<BoxFile>>,
+}
+
+#[derive(Deserialize, Clone)]
+struct BoxFile {
+    from: [f32; 3],
+    to: [f32; 3],
+}
+
+#[derive(Deserialize, Clone)]
+struct ElementFile {
+    from: [f32; 3],
+    to: [f32; 3],
+    rotation: Option<ElementRotationFile>,
+    #[serde(default = "default_shade")]
+    shade: bool,
+    faces: HashMap<String, FaceFile>,
+}
+
+fn default_shade() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone)]
+struct ElementRotationFile {
+    origin: [f32; 3],
+    axis: String,
+    angle: f32,
+    #[serde(default)]
+    rescale: bool,
+}
+
+#[derive(Deserialize, Clone)]
+struct FaceFile {
+    texture: String,
+    uv: Option<[f32; 4]>,
+    cullface: Option<String>,
+    /// Of the texture, in steps of 90 degrees
+    #[serde(default)]
+    rotation: i32,
+    /// Any index tints the face with the colour map of the block
+    tintindex: Option<i32>,
+}
+
+/// A model merged with all of its parents
+struct ResolvedModel {
+    textures: HashMap<String, String>,
+    elements: Vec<ElementFile>,
+    ambient_occlusion: bool,
+    collision: Option<Vec<BoxFile>>,
+    selection: Option<Vec<BoxFile>>,
+}
+
+/// Reads and bakes the blockstates and the models, giving a layer to each texture they use
+pub struct ModelLoader<'a> {
+    root: &'a Path,
+    textures: Vec<String>,
+    texture_layers: HashMap<String, TextureLayer>,
+}
+
+impl<'a> ModelLoader<'a> {
+    pub fn new(root: &'a Path) -> Self {
+        Self {
+            root,
+            textures: Vec::new(),
+            texture_layers: HashMap::new(),
+        }
+    }
+
+    /// The shapes of every variant of `blockstates/<name>.json`, by their normalized properties
+    pub fn load_blockstate(&mut self, name: &str) -> Result<HashMap<String, BlockShape>, String> {
+        let path = format!("blockstates/{}.json", name);
+        let blockstate: BlockStateFile = self.read_json(&path)?;
+
+        let mut variants = HashMap::new();
+        let mut blockstate_variants = blockstate.variants.into_iter().collect::<Vec<_>>();
+        blockstate_variants.sort_by(|a, b| a.0.cmp(&b.0));
+        for (properties, variant) in blockstate_variants {
+            if variant.x % 90 != 0 || variant.y % 90 != 0 {
+                return Err(format!("{}: the rotation of {} isn't a multiple of 90", path, properties));
+            }
+            let model = self.resolve_model(&variant.model, 0)?;
+            let shape = self.bake(&model, variant.x, variant.y)
+                .map_err(|err| format!("models/{}.json: {}", variant.model, err))?;
+            variants.insert(normalize_properties(&properties), shape);
+        }
+        Ok(variants)
+    }
+
+    /// The texture of each layer, in the order they were first used
+    pub fn into_textures(self) -> Vec<String> {
+        self.textures
+    }
+
+    fn read_json<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, String> {
+        let text = fs::read_to_string(self.root.join(path))
+            .map_err(|err| format!("{}: {}", path, err))?;
+        serde_json::from_str(&text).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    fn resolve_model(&self, name: &str, depth: usize) -> Result<ResolvedModel, String> {
+        if depth > MAX_PARENTS {
+            return Err(format!("models/{}.json: too many parents, they might include each other", name));
+        }
+        let file: ModelFile = self.read_json(&format!("models/{}.json", name))?;
+        let mut model = match &file.parent {
+            Some(parent) => self.resolve_model(parent, depth + 1)?,
+            None => ResolvedModel {
+                textures: HashMap::new(),
+                elements: Vec::new(),
+                ambient_occlusion: true,
+                collision: None,
+                selection: None,
+            },
+        };
+
+        // What the child defines replaces what it inherits
+        model.textures.extend(file.textures);
+        if let Some(elements) = file.elements {
+            model.elements = elements;
+        }
+        if let Some(ambient_occlusion) = file.ambientocclusion {
+            model.ambient_occlusion = ambient_occlusion;
+        }
+        if file.collision.is_some() {
+            model.collision = file.collision;
+        }
+        if file.selection.is_some() {
+            model.selection = file.selection;
+        }
+        Ok(model)
+    }
+
+    fn bake(&mut self, model: &ResolvedModel, x_rotation: i32, y_rotation: i32) -> Result<BlockShape, String> {
+        let center = vec3(0.5, 0.5, 0.5);
+        let rotate_variant = |position: &Vec3| {
+            let position = rotate(&(position - center), 0, x_rotation as f32);
+            rotate(&position, 1, y_rotation as f32) + center
+        };
+        let rotate_direction = |direction: &Vec3| rotate(&rotate(direction, 0, x_rotation as f32), 1, y_rotation as f32);
+
+        let mut quads = Vec::new();
+        for element in &model.elements {
+            let (from, to) = (to_block_coordinates(&element.from), to_block_coordinates(&element.to));
+            for direction in element.faces.keys() {
+                parse_face(direction)?;
+            }
+            // In a fixed order, so that the quads and the layers don't depend on the order of the hash map
+            for &direction in FACE_NAMES.iter() {
+                let face = match element.faces.get(direction) {
+                    Some(face) => face,
+                    None => continue,
+                };
+                let face_index = parse_face(direction)?;
+                let mut positions = box_face_corners(&from, &to, face_index);
+                let mut normal = face_normal(face_index);
+                if let Some(rotation) = &element.rotation {
+                    let (origin, axis, angle, scale) = parse_element_rotation(rotation)?;
+                    for position in positions.iter_mut() {
+                        *position = rotate(&(*position - origin), axis, angle).component_mul(&scale) + origin;
+                    }
+                    normal = rotate(&normal, axis, angle);
+                }
+                for position in positions.iter_mut() {
+                    *position = rotate_variant(&*position);
+                }
+                normal = rotate_direction(&normal);
+
+                let cull_face = match &face.cullface {
+                    Some(cull_face) => face_of_direction(&rotate_direction(&face_normal(parse_face(cull_face)?))),
+                    None => None,
+                };
+                let ao = face_of_direction(&normal)
+                    .filter(|_| model.ambient_occlusion)
+                    .map(|ao_face| (ao_face, nearest_corners(ao_face, &positions)));
+
+                let texture = resolve_texture(&model.textures, &face.texture)?;
+                quads.push(BakedQuad {
+                    positions,
+                    uvs: face_uvs(face, &from, &to, face_index)?,
+                    layer: self.layer_of(&texture),
+                    normal: if element.shade { normal } else { vec3(0.0, 1.0, 0.0) },
+                    cull_face,
+                    ao,
+                    tinted: face.tintindex.is_some(),
+                });
+            }
+        }
+        let mut covered_faces = [false; 6];
+        for (face, covered) in covered_faces.iter_mut().enumerate() {
+            let area: f32 = quads.iter()
+                .filter(|quad| quad.cull_face == Some(face))
+                .map(|quad| (quad.positions[1] - quad.positions[0]).cross(&(quad.positions[3] - quad.positions[0])).norm())
+                .sum();
+            *covered = area >= 1.0 - 1e-4;
+        }
+
+        let particle = match model.textures.get("particle") {
+            Some(_) => self.layer_of(&resolve_texture(&model.textures, "#particle")?),
+            None => quads.first().map_or(0, |quad| quad.layer),
+        };
+
+        let rotate_box = |box_file: &BoxFile| {
+            let a = rotate_variant(&to_block_coordinates(&box_file.from));
+            let b = rotate_variant(&to_block_coordinates(&box_file.to));
+            AABB::new(a.zip_map(&b, f32::min), a.zip_map(&b, f32::max))
+        };
+        let element_boxes = || model.elements.iter()
+            .filter(|element| element.rotation.is_none())
+            .filter(|element| (0..3).all(|axis| element.from[axis] < element.to[axis]))
+            .map(|element| BoxFile { from: element.from, to: element.to })
+            .collect::<Vec<_>>();
+        let collision_boxes = model.collision.clone().unwrap_or_else(element_boxes);
+        let selection_boxes = model.selection.clone().unwrap_or_else(element_boxes);
+
+        Ok(BlockShape {
+            model: BlockModel {
+                quads,
+                covered_faces,
+                particle,
+            },
+            collision_boxes: collision_boxes.iter().map(rotate_box).collect(),
+            selection_boxes: selection_boxes.iter().map(rotate_box).collect(),
+        })
+    }
+
+    fn layer_of(&mut self, texture: &str) -> TextureLayer {
+        if let Some(&layer) = self.texture_layers.get(texture) {
+            return layer;
+        }
+        let layer = self.textures.len() as TextureLayer;
+        self.textures.push(texture.to_string());
+        self.texture_layers.insert(texture.to_string(), layer);
+        layer
+    }
+}
+
+/// Sorts the `name=value` pairs of a variant, so that they can be written in any order
+pub fn normalize_properties(properties: &str) -> String {
+    let mut pairs = properties.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs.join(",")
+}
+
+fn to_block_coordinates(v: &[f32; 3]) -> Vec3 {
+    vec3(v[0], v[1], v[2]) / 16.0
+}
+
+fn parse_face(direction: &str) -> Result<usize, String> {
+    match direction {
+        "east" => Ok(RIGHT),
+        "west" => Ok(LEFT),
+        "up" => Ok(TOP),
+        "down" => Ok(BOTTOM),
+        "south" => Ok(FRONT),
+        "north" => Ok(BACK),
+        _ => Err(format!("unknown face {}", direction)),
+    }
+}
+
+fn parse_axis(axis: &str) -> Result<usize, String> {
+    match axis {
+        "x" => Ok(0),
+        "y" => Ok(1),
+        "z" => Ok(2),
+        _ => Err(format!("unknown axis {}", axis)),
+    }
+}
+
+/// The origin, axis and angle of the rotation of an element, and how it's stretched afterwards
+fn parse_element_rotation(rotation: &ElementRotationFile) -> Result<(Vec3, usize, f32, Vec3), String> {
+    if rotation.angle.abs() > 45.0 {
+        return Err(format!("the elements can only be rotated by up to 45°, not {}", rotation.angle));
+    }
+    let axis = parse_axis(&rotation.axis)?;
+    let mut scale = vec3(1.0, 1.0, 1.0);
+    if rotation.rescale {
+        // Stretched across the whole block, like the diagonals of the plants
+        let factor = 1.0 / (rotation.angle * PI / 180.0).cos();
+        for other_axis in (0..3).filter(|&other_axis| other_axis != axis) {
+            scale[other_axis] = factor;
+        }
+    }
+    Ok((to_block_coordinates(&rotation.origin), axis, rotation.angle, scale))
+}
+
+/// Rotates `v` around the origin, clockwise when looking towards the negative end of `axis`
+/// (so a rotation of 90° around y turns the north to the east)
+fn rotate(v: &Vec3, axis: usize, degrees: f32) -> Vec3 {
+    if degrees == 0.0 {
+        return *v;
+    }
+    let (sin, cos) = (degrees * PI / 180.0).sin_cos();
+    // Rounded so that the right angles don't leave the blocks slightly open
+    let (sin, cos) = if degrees % 90.0 == 0.0 { (sin.round(), cos.round()) } else { (sin, cos) };
+    match axis {
+        0 => vec3(v.x, v.y * cos + v.z * sin, v.z * cos - v.y * sin),
+        1 => vec3(v.x * cos - v.z * sin, v.y, v.z * cos + v.x * sin),
+        _ => vec3(v.x * cos - v.y * sin, v.y * cos + v.x * sin, v.z),
+    }
+}
+
+/// Follows the `#name` references until an image
+fn resolve_texture(textures: &HashMap<String, String>, texture: &str) -> Result<String, String> {
+    let mut texture = texture;
+    for _ in 0..MAX_PARENTS {
+        match texture.strip_prefix('#') {
+            Some(name) => {
+                texture = textures.get(name).map(String::as_str).ok_or_else(|| format!("no texture #{}", name))?;
+            }
+            None => return Ok(texture.to_string()),
+        }
+    }
+    Err(format!("the texture {} refers to itself", texture))
+}
+
+/// The UVs of the corners of a face, given from the top left of the texture in pixels like in Minecraft.
+/// By default the texture is projected on the face, so a slab shows the bottom half of its side texture
+fn face_uvs(face: &FaceFile, from: &Vec3, to: &Vec3, face_index: usize) -> Result<[(f32, f32); 4], String> {
+    let (m, n) = (from * 16.0, to * 16.0);
+    let [u1, v1, u2, v2] = face.uv.unwrap_or_else(|| match face_index {
+        RIGHT => [16.0 - n.z, 16.0 - n.y, 16.0 - m.z, 16.0 - m.y],
+        LEFT => [m.z, 16.0 - n.y, n.z, 16.0 - m.y],
+        TOP => [m.x, m.z, n.x, n.z],
+        BOTTOM => [m.x, 16.0 - n.z, n.x, 16.0 - m.z],
+        FRONT => [m.x, 16.0 - n.y, n.x, 16.0 - m.y],
+        _ => [16.0 - n.x, 16.0 - n.y, 16.0 - m.x, 16.0 - m.y],
+    });
+    if face.rotation % 90 != 0 {
+        return Err(format!("the rotation of a texture must be a multiple of 90, not {}", face.rotation));
+    }
+
+    // The images are flipped for OpenGL, the v axis goes up
+    let corners = [(u1, v2), (u2, v2), (u2, v1), (u1, v1)];
+    let steps = (face.rotation / 90).rem_euclid(4) as usize;
+    let mut uvs = [(0.0, 0.0); 4];
+    for (i, uv) in uvs.iter_mut().enumerate() {
+        let (u, v) = corners[(i + steps) % 4];
+        *uv = (u / 16.0, 1.0 - v / 16.0);
+    }
+    Ok(uvs)
+}
+
+/// For each corner of a quad, the corner of `face` of the unit cube nearest to it
+fn nearest_corners(face: usize, positions: &[Vec3; 4]) -> [usize; 4] {
+    let face_corners = box_face_corners(&vec3(0.0, 0.0, 0.0), &vec3(1.0, 1.0, 1.0), face);
+    let mut corners = [0; 4];
+    for (corner, position) in corners.iter_mut().zip(positions) {
+        *corner = (0..4)
+            .min_by(|&a, &b| (face_corners[a] - position).norm()
+                .partial_cmp(&(face_corners[b] - position).norm())
+                .unwrap())
+            .unwrap();
+    }
+    corners
+}
@@ -0,0 +1,239 @@
+//! The shapes of the blocks: the geometry they are meshed with, the boxes the entities collide with
+//! and the boxes the player targets.
+//!
+//! The shapes are baked from the blockstate and model files of the blocks, see [`json`], with a
+//! variant for each state of a block. They are loaded at startup from the asset directory into
+//! `BlockModels`, shared as an `Arc` by the `ChunkManager` and the systems drawing the items.
+//! Faces are indexed like the active faces and the AO of a block: right, left, top, bottom, front, back
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use nalgebra_glm::{Vec3, vec3};
+
+use crate::aabb::AABB;
+use crate::block_state::{BlockState, Facing, Property};
+use crate::chunk::BlockID;
+use crate::types::TextureLayer;
+
+pub mod json;
+
+pub const RIGHT: usize = 0;
+pub const LEFT: usize = 1;
+pub const TOP: usize = 2;
+pub const BOTTOM: usize = 3;
+pub const FRONT: usize = 4;
+pub const BACK: usize = 5;
+
+/// The face of the neighbouring block touching `face`
+#[inline]
+pub fn opposite_face(face: usize) -> usize {
+    face ^ 1
+}
+
+/// The outward direction of a face of the unit cube
+pub fn face_normal(face: usize) -> Vec3 {
+    match face {
+        RIGHT => vec3(1.0, 0.0, 0.0),
+        LEFT => vec3(-1.0, 0.0, 0.0),
+        TOP => vec3(0.0, 1.0, 0.0),
+        BOTTOM => vec3(0.0, -1.0, 0.0),
+        FRONT => vec3(0.0, 0.0, 1.0),
+        _ => vec3(0.0, 0.0, -1.0),
+    }
+}
+
+/// The face of the unit cube towards `facing`
+pub fn face_of_facing(facing: Facing) -> usize {
+    match facing {
+        Facing::East => RIGHT,
+        Facing::West => LEFT,
+        Facing::Up => TOP,
+        Facing::Down => BOTTOM,
+        Facing::South => FRONT,
+        Facing::North => BACK,
+    }
+}
+
+/// The face of the unit cube pointing to `direction`, if it's parallel to an axis
+pub fn face_of_direction(direction: &Vec3) -> Option<usize> {
+    (0..6).find(|&face| (face_normal(face) - direction).norm() < 1e-4)
+}
+
+/// The corners of `face` of the box going from `m` to `n`, counterclockwise seen from outside of the box.
+/// The corners of the faces of the unit cube are in the order of their AO
+pub fn box_face_corners(m: &Vec3, n: &Vec3, face: usize) -> [Vec3; 4] {
+    match face {
+        RIGHT => [vec3(n.x, m.y, n.z), vec3(n.x, m.y, m.z), vec3(n.x, n.y, m.z), vec3(n.x, n.y, n.z)],
+        LEFT => [vec3(m.x, m.y, m.z), vec3(m.x, m.y, n.z), vec3(m.x, n.y, n.z), vec3(m.x, n.y, m.z)],
+        TOP => [vec3(m.x, n.y, n.z), vec3(n.x, n.y, n.z), vec3(n.x, n.y, m.z), vec3(m.x, n.y, m.z)],
+        BOTTOM => [vec3(m.x, m.y, m.z), vec3(n.x, m.y, m.z), vec3(n.x, m.y, n.z), vec3(m.x, m.y, n.z)],
+        FRONT => [vec3(m.x, m.y, n.z), vec3(n.x, m.y, n.z), vec3(n.x, n.y, n.z), vec3(m.x, n.y, n.z)],
+        _ => [vec3(n.x, m.y, m.z), vec3(m.x, m.y, m.z), vec3(m.x, n.y, m.z), vec3(n.x, n.y, m.z)],
+    }
+}
+
+/// A textured quad of a baked model
+#[derive(Debug, Clone)]
+pub struct BakedQuad {
+    /// In block coordinates, counterclockwise seen from the front of the quad
+    pub positions: [Vec3; 4],
+    pub uvs: [(f32, f32); 4],
+    pub layer: TextureLayer,
+    /// What the quad is lit with, up for the unshaded quads
+    pub normal: Vec3,
+    /// The quad isn't drawn when the neighbour touching this face of the block hides it
+    pub cull_face: Option<usize>,
+    /// The face of the block the quad takes its AO from and the corner of that face nearest to each
+    /// corner of the quad. None for the quads that aren't facing an axis
+    pub ao: Option<(usize, [usize; 4])>,
+    /// Multiplied by the colour of the biome, with the colour map of the block
+    pub tinted: bool,
+}
+
+/// The quads of a block, ready to be meshed
+#[derive(Debug, Clone)]
+pub struct BlockModel {
+    pub quads: Vec<BakedQuad>,
+    /// Whether the quads culled by each face fill that face of the unit cube
+    covered_faces: [bool; 6],
+    /// The texture of the particles of the block
+    pub particle: TextureLayer,
+}
+
+impl BlockModel {
+    /// Nothing to draw, like air
+    pub fn empty() -> Self {
+        Self {
+            quads: Vec::new(),
+            covered_faces: [false; 6],
+            particle: 0,
+        }
+    }
+
+    /// Whether the model fills `face` of the unit cube entirely, hiding the face of the block behind it
+    pub fn covers_face(&self, face: usize) -> bool {
+        self.covered_faces[face]
+    }
+
+    /// The quads to draw, without the ones culled by the inactive faces of the block
+    pub fn visible_quads<'a>(&'a self, active_faces: &'a [bool; 6]) -> impl Iterator<Item=&'a BakedQuad> + 'a {
+        self.quads.iter().filter(move |quad| quad.cull_face.map_or(true, |face| active_faces[face]))
+    }
+
+    /// Number of vertices of the mesh of the model, without the culled faces of the block
+    pub fn vertex_count(&self, active_faces: &[bool; 6]) -> u32 {
+        6 * self.visible_quads(active_faces).count() as u32
+    }
+}
+
+pub struct BlockShape {
+    pub model: BlockModel,
+    /// What the entities collide with, empty for the blocks they walk through.
+    /// The boxes can go above the block, up to 1.5 blocks high like fences
+    pub collision_boxes: Vec<AABB>,
+    /// What the player targets and what the outline is drawn around
+    pub selection_boxes: Vec<AABB>,
+}
+
+impl BlockShape {
+    fn empty() -> Self {
+        Self {
+            model: BlockModel::empty(),
+            collision_boxes: Vec::new(),
+            selection_boxes: Vec::new(),
+        }
+    }
+
+    pub fn is_full_cube(&self) -> bool {
+        (0..6).all(|face| self.model.covers_face(face))
+    }
+}
+
+/// The baked shapes of every block and the textures they use
+pub struct BlockModels {
+    /// The shape of each state of each block
+    shapes: HashMap<(BlockID, BlockState), BlockShape>,
+    /// The texture of each layer of the array texture, like `blocks/stone`
+    textures: Vec<String>,
+}
+
+impl BlockModels {
+    /// Bakes `blockstates/<block>.json` of every block and the models they use, found under `root`.
+    /// Each state of a block needs the variant with its properties, `""` for the blocks without properties
+    pub fn load<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+        let mut loader = json::ModelLoader::new(root.as_ref());
+        let mut shapes = HashMap::new();
+        for &block in BlockID::ALL.iter() {
+            if block.is_air() {
+                shapes.insert((block, BlockState::default()), BlockShape::empty());
+                continue;
+            }
+            let mut variants = loader.load_blockstate(block.name())?;
+            for state in block.states() {
+                let key = state.variant_key(block.properties());
+                let shape = variants.remove(&key)
+                    .ok_or_else(|| format!("blockstates/{}.json: no variant for \"{}\"", block.name(), key))?;
+                shapes.insert((block, state), shape);
+            }
+        }
+        Ok(Self {
+            shapes,
+            textures: loader.into_textures(),
+        })
+    }
+
+    /// The shape of `block` in `state`, the states the block can't be in have the default shape
+    pub fn shape(&self, block: BlockID, state: BlockState) -> &BlockShape {
+        self.shapes.get(&(block, state))
+            .unwrap_or_else(|| &self.shapes[&(block, BlockState::default())])
+    }
+
+    /// The shape of `block` in its default state, like in the inventory
+    pub fn default_shape(&self, block: BlockID) -> &BlockShape {
+        self.shape(block, BlockState::default())
+    }
+
+    /// Whether `block` in `state` hides the face of its neighbour that touches its own `face`
+    pub fn hides_face(&self, block: BlockID, state: BlockState, face: usize) -> bool {
+        !block.is_transparent() && self.shape(block, state).model.covers_face(face)
+    }
+
+    /// Whether `block` in `state` darkens the corners of its neighbours
+    pub fn casts_ambient_occlusion(&self, block: BlockID, state: BlockState) -> bool {
+        !block.is_transparent_no_leaves() && self.shape(block, state).is_full_cube()
+    }
+
+    /// Whether a block with connections reaches out to `neighbour`, the block touching its `face`: they
+    /// connect to each other and to the blocks hiding the face they touch, like full opaque blocks
+    pub fn connects_to(&self, neighbour: BlockID, neighbour_state: BlockState, face: usize) -> bool {
+        neighbour.has_connections() || self.hides_face(neighbour, neighbour_state, opposite_face(face))
+    }
+
+    /// `state` of `block` with its connections chosen from its horizontal neighbours, None where there's
+    /// no neighbour loaded
+    pub fn connected_state(&self, block: BlockID, state: BlockState,
+                           neighbour: &dyn Fn(Facing) -> Option<(BlockID, BlockState)>) -> BlockState {
+        block.properties().iter().fold(state, |state, property| match *property {
+            Property::Connected(facing) => {
+                let connected = match neighbour(facing) {
+                    Some((neighbour, neighbour_state)) => self.connects_to(neighbour, neighbour_state, face_of_facing(facing)),
+                    None => false,
+                };
+                state.with_connection(facing, connected)
+            }
+            _ => state,
+        })
+    }
+
+    /// The collision boxes of `block` in `state` at (x, y, z), in world coordinates
+    pub fn collision_boxes_at(&self, block: BlockID, state: BlockState, x: i32, y: i32, z: i32) -> impl Iterator<Item = AABB> + '_ {
+        let position = vec3(x as f32, y as f32, z as f32);
+        self.shape(block, state).collision_boxes.iter().map(move |b| AABB::new(b.mins + position, b.maxs + position))
+    }
+
+    /// The texture of each layer of the array texture
+    pub fn textures(&self) -> &[String] {
+        &self.textures
+    }
+}
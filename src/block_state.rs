@@ -0,0 +1,312 @@
+//! The state of a block: the properties that change its shape but not what it is, like the axis of
+//! a log or the direction stairs are facing. The state is stored next to the `BlockID` in the chunks
+//! and picks the variant of the blockstate file the block is drawn with.
+//!
+//! Every property has its own bits, so that the default value of all of them is the state 0
+
+use nalgebra_glm::{IVec3, Vec3};
+
+use crate::chunk::BlockID;
+
+const AXIS_SHIFT: u16 = 0;
+const AXIS_MASK: u16 = 0b11;
+const FACING_SHIFT: u16 = 2;
+const FACING_MASK: u16 = 0b111;
+const HALF_SHIFT: u16 = 5;
+const OPEN_SHIFT: u16 = 6;
+const LEVEL_SHIFT: u16 = 7;
+const LEVEL_MASK: u16 = 0b1111;
+/// One bit for each horizontal direction, in the order of `Facing::HORIZONTAL`
+const CONNECTIONS_SHIFT: u16 = 11;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Axis {
+    Y,
+    X,
+    Z,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Facing {
+    North,
+    South,
+    East,
+    West,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Half {
+    Bottom,
+    Top,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::Y, Axis::X, Axis::Z];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        }
+    }
+}
+
+impl Facing {
+    pub const ALL: [Facing; 6] = [Facing::North, Facing::South, Facing::East, Facing::West, Facing::Up, Facing::Down];
+    pub const HORIZONTAL: [Facing; 4] = [Facing::North, Facing::South, Facing::East, Facing::West];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Facing::North => "north",
+            Facing::South => "south",
+            Facing::East => "east",
+            Facing::West => "west",
+            Facing::Up => "up",
+            Facing::Down => "down",
+        }
+    }
+
+    /// The offset to the neighbouring block in this direction, north is -z and east is +x
+    pub fn offset(&self) -> (i32, i32, i32) {
+        match self {
+            Facing::North => (0, 0, -1),
+            Facing::South => (0, 0, 1),
+            Facing::East => (1, 0, 0),
+            Facing::West => (-1, 0, 0),
+            Facing::Up => (0, 1, 0),
+            Facing::Down => (0, -1, 0),
+        }
+    }
+
+    /// The horizontal direction closest to `direction`, north is -z and east is +x
+    pub fn horizontal_of_direction(direction: &Vec3) -> Self {
+        if direction.x.abs() > direction.z.abs() {
+            if direction.x > 0.0 { Facing::East } else { Facing::West }
+        } else if direction.z > 0.0 {
+            Facing::South
+        } else {
+            Facing::North
+        }
+    }
+}
+
+impl Half {
+    pub const ALL: [Half; 2] = [Half::Bottom, Half::Top];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Half::Bottom => "bottom",
+            Half::Top => "top",
+        }
+    }
+}
+
+/// A property of the state of a block, named like in the blockstate files
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Property {
+    Axis,
+    Facing,
+    /// `facing` without up and down
+    HorizontalFacing,
+    Half,
+    Open,
+    /// From 0 to 15
+    Level,
+    /// Whether the block reaches out to its neighbour in a horizontal direction, like the arms of fences.
+    /// Chosen from the neighbours when the block is meshed or collided with, never stored
+    Connected(Facing),
+}
+
+impl Property {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Property::Axis => "axis",
+            Property::Facing | Property::HorizontalFacing => "facing",
+            Property::Half => "half",
+            Property::Open => "open",
+            Property::Level => "level",
+            Property::Connected(facing) => facing.name(),
+        }
+    }
+
+    /// The value of the property in `state`, like in the blockstate files
+    pub fn value(&self, state: BlockState) -> String {
+        match self {
+            Property::Axis => state.axis().name().to_string(),
+            Property::Facing | Property::HorizontalFacing => state.facing().name().to_string(),
+            Property::Half => state.half().name().to_string(),
+            Property::Open => state.is_open().to_string(),
+            Property::Level => state.level().to_string(),
+            Property::Connected(facing) => state.is_connected(*facing).to_string(),
+        }
+    }
+
+    /// `state` with each possible value of the property
+    fn values(&self, state: BlockState) -> Vec<BlockState> {
+        match self {
+            Property::Axis => Axis::ALL.iter().map(|&axis| state.with_axis(axis)).collect(),
+            Property::Facing => Facing::ALL.iter().map(|&facing| state.with_facing(facing)).collect(),
+            Property::HorizontalFacing => Facing::HORIZONTAL.iter().map(|&facing| state.with_facing(facing)).collect(),
+            Property::Half => Half::ALL.iter().map(|&half| state.with_half(half)).collect(),
+            Property::Open => vec![state.with_open(false), state.with_open(true)],
+            Property::Level => (0..=LEVEL_MASK as u8).map(|level| state.with_level(level)).collect(),
+            Property::Connected(facing) => vec![state.with_connection(*facing, false), state.with_connection(*facing, true)],
+        }
+    }
+}
+
+/// The properties of a block packed in 16 bits, the default state is 0
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct BlockState(u16);
+
+impl BlockState {
+    pub fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    fn get(&self, shift: u16, mask: u16) -> u16 {
+        (self.0 >> shift) & mask
+    }
+
+    fn with(&self, shift: u16, mask: u16, value: u16) -> Self {
+        Self((self.0 & !(mask << shift)) | ((value & mask) << shift))
+    }
+
+    pub fn axis(&self) -> Axis {
+        match self.get(AXIS_SHIFT, AXIS_MASK) {
+            1 => Axis::X,
+            2 => Axis::Z,
+            _ => Axis::Y,
+        }
+    }
+
+    pub fn with_axis(&self, axis: Axis) -> Self {
+        self.with(AXIS_SHIFT, AXIS_MASK, axis as u16)
+    }
+
+    pub fn facing(&self) -> Facing {
+        Facing::ALL.get(self.get(FACING_SHIFT, FACING_MASK) as usize).cloned().unwrap_or(Facing::North)
+    }
+
+    pub fn with_facing(&self, facing: Facing) -> Self {
+        self.with(FACING_SHIFT, FACING_MASK, facing as u16)
+    }
+
+    pub fn half(&self) -> Half {
+        if self.get(HALF_SHIFT, 1) == 1 { Half::Top } else { Half::Bottom }
+    }
+
+    pub fn with_half(&self, half: Half) -> Self {
+        self.with(HALF_SHIFT, 1, half as u16)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.get(OPEN_SHIFT, 1) == 1
+    }
+
+    pub fn with_open(&self, open: bool) -> Self {
+        self.with(OPEN_SHIFT, 1, open as u16)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.get(LEVEL_SHIFT, LEVEL_MASK) as u8
+    }
+
+    /// `level` is clamped to 15
+    pub fn with_level(&self, level: u8) -> Self {
+        self.with(LEVEL_SHIFT, LEVEL_MASK, level.min(LEVEL_MASK as u8) as u16)
+    }
+
+    fn connection_shift(facing: Facing) -> u16 {
+        CONNECTIONS_SHIFT + Facing::HORIZONTAL.iter().position(|&f| f == facing).unwrap_or(0) as u16
+    }
+
+    /// Whether the block reaches out to its neighbour towards `facing`, false for up and down
+    pub fn is_connected(&self, facing: Facing) -> bool {
+        Facing::HORIZONTAL.contains(&facing) && self.get(Self::connection_shift(facing), 1) == 1
+    }
+
+    /// Up and down can't be connected, the state is returned unchanged
+    pub fn with_connection(&self, facing: Facing, connected: bool) -> Self {
+        if !Facing::HORIZONTAL.contains(&facing) {
+            return *self;
+        }
+        self.with(Self::connection_shift(facing), 1, connected as u16)
+    }
+
+    /// The key of the variant of the blockstate file with the given properties of the state,
+    /// like `facing=east,half=bottom`. Empty without properties
+    pub fn variant_key(&self, properties: &[Property]) -> String {
+        let mut pairs = properties.iter()
+            .map(|property| format!("{}={}", property.name(), property.value(*self)))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        pairs.join(",")
+    }
+}
+
+impl BlockID {
+    /// The properties the state of the block is made of, the other bits of the state stay at 0
+    pub fn properties(&self) -> &'static [Property] {
+        match self {
+            BlockID::OakLog => &[Property::Axis],
+            BlockID::OakSlab => &[Property::Half],
+            BlockID::OakStairs => &[Property::HorizontalFacing, Property::Half],
+            BlockID::OakFence => &[
+                Property::Connected(Facing::North),
+                Property::Connected(Facing::South),
+                Property::Connected(Facing::East),
+                Property::Connected(Facing::West),
+            ],
+            _ => &[],
+        }
+    }
+
+    /// Whether the state of the block has connections to choose from its neighbours
+    pub fn has_connections(&self) -> bool {
+        self.properties().iter().any(|property| matches!(property, Property::Connected(_)))
+    }
+
+    /// Every state the block can be in, starting with the default one
+    pub fn states(&self) -> Vec<BlockState> {
+        self.properties().iter().fold(vec![BlockState::default()], |states, property| {
+            states.into_iter().flat_map(|state| property.values(state)).collect()
+        })
+    }
+
+    pub fn is_valid_state(&self, state: BlockState) -> bool {
+        self.states().contains(&state)
+    }
+
+    /// The state of the block placed against the face of a block with the `normal` pointing to it,
+    /// by a player looking in `looking_direction`:
+    /// * logs lie along the normal, so a log placed against a wall lies sideways
+    /// * stairs face the same way as the player, their steps going up away from them
+    /// * slabs and stairs placed against the underside of a block are upside down
+    pub fn placement_state(&self, normal: &IVec3, looking_direction: &Vec3) -> BlockState {
+        let mut state = BlockState::default();
+        for property in self.properties() {
+            state = match property {
+                Property::Axis => state.with_axis(if normal.x != 0 {
+                    Axis::X
+                } else if normal.z != 0 {
+                    Axis::Z
+                } else {
+                    Axis::Y
+                }),
+                Property::HorizontalFacing => state.with_facing(Facing::horizontal_of_direction(looking_direction)),
+                Property::Half => state.with_half(if normal.y < 0 { Half::Top } else { Half::Bottom }),
+                // The connections are chosen from the neighbours, not placed
+                Property::Facing | Property::Open | Property::Level | Property::Connected(_) => state,
+            };
+        }
+        state
+    }
+}
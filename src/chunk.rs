@@ -2,12 +2,12 @@ use bit_vec::BitVec;
 use rand::{random, Rng};
 use rand::distributions::Standard;
 use rand::prelude::Distribution;
-use std::ptr::null;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::biome::Climate;
+use crate::block_state::BlockState;
 use crate::chunk_manager::{CHUNK_SIZE, CHUNK_VOLUME};
-use crate::types::TexturePack;
-use crate::shapes::write_unit_cube_to_ptr;
-use parking_lot::RwLock;
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum BlockID {
@@ -26,9 +26,64 @@ pub enum BlockID {
     Hitler,
     Debug,
     Debug2,
+    OakSlab,
+    OakStairs,
+    OakFence,
+    TallGrass,
+    Dandelion,
 }
 
 impl BlockID {
+    /// Every block, in the order of their ids in the network protocol and the world saves
+    pub const ALL: [BlockID; 20] = [
+        BlockID::Air,
+        BlockID::Dirt,
+        BlockID::GrassBlock,
+        BlockID::Stone,
+        BlockID::Cobblestone,
+        BlockID::Bedrock,
+        BlockID::Obsidian,
+        BlockID::OakLog,
+        BlockID::OakLeaves,
+        BlockID::OakPlanks,
+        BlockID::Glass,
+        BlockID::Urss,
+        BlockID::Hitler,
+        BlockID::Debug,
+        BlockID::Debug2,
+        BlockID::OakSlab,
+        BlockID::OakStairs,
+        BlockID::OakFence,
+        BlockID::TallGrass,
+        BlockID::Dandelion,
+    ];
+
+    /// The name of the blockstate file of the block
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockID::Air => "air",
+            BlockID::Dirt => "dirt",
+            BlockID::GrassBlock => "grass_block",
+            BlockID::Stone => "stone",
+            BlockID::Cobblestone => "cobblestone",
+            BlockID::Bedrock => "bedrock",
+            BlockID::Obsidian => "obsidian",
+            BlockID::OakLog => "oak_log",
+            BlockID::OakLeaves => "oak_leaves",
+            BlockID::OakPlanks => "oak_planks",
+            BlockID::Glass => "glass",
+            BlockID::Urss => "urss",
+            BlockID::Hitler => "hitler",
+            BlockID::Debug => "debug",
+            BlockID::Debug2 => "debug2",
+            BlockID::OakSlab => "oak_slab",
+            BlockID::OakStairs => "oak_stairs",
+            BlockID::OakFence => "oak_fence",
+            BlockID::TallGrass => "tall_grass",
+            BlockID::Dandelion => "dandelion",
+        }
+    }
+
     #[inline]
     pub fn is_air(&self) -> bool {
         self == &BlockID::Air
@@ -38,7 +93,9 @@ impl BlockID {
         match self {
             &BlockID::Air |
             &BlockID::OakLeaves |
-            &BlockID::Glass => true,
+            &BlockID::Glass |
+            &BlockID::TallGrass |
+            &BlockID::Dandelion => true,
             _ => false
         }
     }
@@ -50,7 +107,9 @@ impl BlockID {
     pub fn is_transparent_not_air(&self) -> bool {
         match self {
             &BlockID::OakLeaves |
-            &BlockID::Glass => true,
+            &BlockID::Glass |
+            &BlockID::TallGrass |
+            &BlockID::Dandelion => true,
             _ => false
         }
     }
@@ -58,7 +117,9 @@ impl BlockID {
     pub fn is_transparent_no_leaves(&self) -> bool {
         match self {
             &BlockID::Air |
-            &BlockID::Glass => true,
+            &BlockID::Glass |
+            &BlockID::TallGrass |
+            &BlockID::Dandelion => true,
             _ => false
         }
     }
@@ -76,42 +137,14 @@ impl Distribution<BlockID> for Standard {
     }
 }
 
-fn create_vao_vbo() -> (u32, u32) {
-    let mut vao = 0;
-    gl_call!(gl::CreateVertexArrays(1, &mut vao));
-
-    // Position
-    gl_call!(gl::EnableVertexArrayAttrib(vao, 0));
-    gl_call!(gl::VertexArrayAttribFormat(vao, 0, 3 as i32, gl::FLOAT, gl::FALSE, 0));
-    gl_call!(gl::VertexArrayAttribBinding(vao, 0, 0));
-
-    // Texture coords
-    gl_call!(gl::EnableVertexArrayAttrib(vao, 1));
-    gl_call!(gl::VertexArrayAttribFormat(vao, 1, 3 as i32, gl::FLOAT, gl::FALSE, 3 * std::mem::size_of::<f32>() as u32));
-    gl_call!(gl::VertexArrayAttribBinding(vao, 1, 0));
-
-    // Normals
-    gl_call!(gl::EnableVertexArrayAttrib(vao, 2));
-    gl_call!(gl::VertexArrayAttribFormat(vao, 2, 3 as i32, gl::FLOAT, gl::FALSE, 6 * std::mem::size_of::<f32>() as u32));
-    gl_call!(gl::VertexArrayAttribBinding(vao, 2, 0));
-
-    // Ambient occlusion
-    gl_call!(gl::EnableVertexArrayAttrib(vao, 3));
-    gl_call!(gl::VertexArrayAttribFormat(vao, 3, 1 as i32, gl::FLOAT, gl::FALSE, 9 * std::mem::size_of::<f32>() as u32));
-    gl_call!(gl::VertexArrayAttribBinding(vao, 3, 0));
-
-    let mut vbo = 0;
-    gl_call!(gl::CreateBuffers(1, &mut vbo));
-    // We intentionally don't initialize the buffer's data store because it's dynamically created
-    // when the chunk is invalidated
-
-    gl_call!(gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, (10 * std::mem::size_of::<f32>()) as i32));
-    (vao, vbo)
-}
+/// Number of blocks in a chunk column, stored in `256 * y + 16 * z + x` order outside of the chunks
+pub const COLUMN_VOLUME: usize = 16 * 16 * 256;
 
 pub struct ChunkColumn {
     pub heighest_blocks: RwLock<Box<[u8; 16 * 16]>>,
-    pub has_foliage: RwLock<bool>,
+    /// The climate of each column of blocks, in `16 * z + x` order. Set by the world generation or
+    /// received with the blocks, it's kept when the blocks are reset
+    pub climate: RwLock<Box<[Climate; 16 * 16]>>,
     pub chunks: Box<[Chunk; 16]>,
 }
 
@@ -119,7 +152,7 @@ impl ChunkColumn {
     pub fn new() -> Self {
         Self {
             heighest_blocks: RwLock::new(Box::new([0; 16 * 16])),
-            has_foliage: RwLock::new(false),
+            climate: RwLock::new(Box::new([Climate::default(); 16 * 16])),
             chunks: Box::new([
                 Chunk::empty(),
                 Chunk::empty(),
@@ -144,7 +177,7 @@ impl ChunkColumn {
     pub fn random() -> Self {
         Self {
             heighest_blocks: RwLock::new(Box::new([0; 16 * 16])),
-            has_foliage: RwLock::new(false),
+            climate: RwLock::new(Box::new([Climate::default(); 16 * 16])),
             chunks: Box::new([
                 Chunk::random(),
                 Chunk::random(),
@@ -169,7 +202,7 @@ impl ChunkColumn {
     pub fn full_of_block(block: BlockID) -> Self {
         Self {
             heighest_blocks: RwLock::new(Box::new([0; 16 * 16])),
-            has_foliage: RwLock::new(false),
+            climate: RwLock::new(Box::new([Climate::default(); 16 * 16])),
             chunks: Box::new([
                 Chunk::full_of_block(block),
                 Chunk::full_of_block(block),
@@ -194,7 +227,7 @@ impl ChunkColumn {
     pub fn alternating() -> Self {
         Self {
             heighest_blocks: RwLock::new(Box::new([0; 16 * 16])),
-            has_foliage: RwLock::new(false),
+            climate: RwLock::new(Box::new([Climate::default(); 16 * 16])),
             chunks: Box::new([
                 Chunk::full_of_block(BlockID::Dirt),
                 Chunk::full_of_block(BlockID::Cobblestone),
@@ -216,6 +249,61 @@ impl ChunkColumn {
         }
     }
 
+    /// Clears every block of the column so that it can be reused for another position
+    pub fn reset(&self) {
+        for chunk in self.chunks.iter() {
+            chunk.reset();
+        }
+        self.heighest_blocks.write().fill(0);
+    }
+
+    /// Every block of the column, in `256 * y + 16 * z + x` order
+    pub fn blocks(&self) -> Vec<BlockID> {
+        let mut blocks = vec![BlockID::Air; COLUMN_VOLUME];
+        for (c_y, chunk) in self.chunks.iter().enumerate() {
+            let data = chunk.read();
+            for (b_x, b_y, b_z) in BlockIterator::new() {
+                let y = 16 * c_y + b_y as usize;
+                blocks[256 * y + 16 * b_z as usize + b_x as usize] = data.get_block(b_x, b_y, b_z);
+            }
+        }
+        blocks
+    }
+
+    /// The state of every block of the column, in the order of `blocks`
+    pub fn states(&self) -> Vec<BlockState> {
+        let mut states = vec![BlockState::default(); COLUMN_VOLUME];
+        for (c_y, chunk) in self.chunks.iter().enumerate() {
+            let data = chunk.read();
+            for (b_x, b_y, b_z) in BlockIterator::new() {
+                let y = 16 * c_y + b_y as usize;
+                states[256 * y + 16 * b_z as usize + b_x as usize] = data.get_state(b_x, b_y, b_z);
+            }
+        }
+        states
+    }
+
+    /// Replaces every block of the column, `blocks` and `states` are in the order returned by `blocks`
+    pub fn set_blocks(&self, blocks: &[BlockID], states: &[BlockState]) {
+        self.reset();
+        for (i, (&block, &state)) in blocks.iter().zip(states).enumerate() {
+            if !block.is_air() {
+                let (b_x, b_z, y) = (i % 16, (i / 16) % 16, i / 256);
+                self.set_block_with_state(block, state, b_x as u32, y as u32, b_z as u32);
+            }
+        }
+    }
+
+    /// The climate of every column of blocks, in `16 * z + x` order
+    pub fn climate(&self) -> Vec<Climate> {
+        self.climate.read().to_vec()
+    }
+
+    /// `climate` is in the order returned by `climate`
+    pub fn set_climate(&self, climate: &[Climate]) {
+        self.climate.write().copy_from_slice(climate);
+    }
+
     #[inline]
     pub fn get_chunk(&self, y: i32) -> &Chunk {
         &self.chunks[y as usize]
@@ -223,7 +311,11 @@ impl ChunkColumn {
 
     #[inline]
     pub fn set_block(&self, block: BlockID, x: u32, y: u32, z: u32) {
-        self.chunks[(y / 16) as usize].set_block(block, x, y % 16, z);
+        self.set_block_with_state(block, BlockState::default(), x, y, z);
+    }
+
+    pub fn set_block_with_state(&self, block: BlockID, state: BlockState, x: u32, y: u32, z: u32) {
+        self.chunks[(y / 16) as usize].set_block_with_state(block, state, x, y % 16, z);
         let mut heighest_blocks = self.heighest_blocks.write();
         let y = y as u8;
         let i = (16 * z + x) as usize;
@@ -233,18 +325,96 @@ impl ChunkColumn {
     }
 }
 
+/// Block data of a chunk. Everything in here is guarded by a single lock so that the blocks,
+/// the block counters and the mesh data (active faces and AO) are always consistent
+pub struct ChunkData {
+    pub blocks: [BlockID; CHUNK_VOLUME as usize],
+    pub states: [BlockState; CHUNK_VOLUME as usize],
+    pub number_of_opaque_blocks: u32,
+    pub number_of_transparent_blocks: u32,
+    pub active_faces: BitVec,
+    pub ao_vertices: [[[u8; 4]; 6]; CHUNK_VOLUME as usize],
+}
+
+impl ChunkData {
+    fn full_of_block(block: BlockID) -> Self {
+        let (opaque, transparent) = match block {
+            BlockID::Air => (0, 0),
+            block => if block.is_transparent() {
+                (0, 16 * 16 * 16)
+            } else {
+                (16 * 16 * 16, 0)
+            }
+        };
+
+        Self {
+            blocks: [block; CHUNK_VOLUME as usize],
+            states: [BlockState::default(); CHUNK_VOLUME as usize],
+            number_of_opaque_blocks: opaque,
+            number_of_transparent_blocks: transparent,
+            active_faces: BitVec::from_elem(6 * CHUNK_VOLUME as usize, false),
+            ao_vertices: [[[0; 4]; 6]; CHUNK_VOLUME as usize],
+        }
+    }
+
+    #[inline]
+    pub fn get_block(&self, x: u32, y: u32, z: u32) -> BlockID {
+        self.blocks[Chunk::chunk_coords_to_array_index(x, y, z)]
+    }
+
+    #[inline]
+    pub fn get_state(&self, x: u32, y: u32, z: u32) -> BlockState {
+        self.states[Chunk::chunk_coords_to_array_index(x, y, z)]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.number_of_opaque_blocks + self.number_of_transparent_blocks == 0
+    }
+
+    /// Replaces a block and its state and updates the block counters accordingly
+    fn set_block(&mut self, block: BlockID, state: BlockState, x: u32, y: u32, z: u32) {
+        let index = Chunk::chunk_coords_to_array_index(x, y, z);
+
+        let target = self.blocks[index];
+        if target.is_air() {
+            if block.is_transparent_not_air() {
+                self.number_of_transparent_blocks += 1;
+            } else if block.is_opaque() {
+                self.number_of_opaque_blocks += 1;
+            }
+        } else if target.is_transparent_not_air() {
+            if block.is_air() {
+                self.number_of_transparent_blocks -= 1;
+            } else if block.is_opaque() {
+                self.number_of_transparent_blocks -= 1;
+                self.number_of_opaque_blocks += 1;
+            }
+        } else if target.is_opaque() {
+            if block.is_air() {
+                self.number_of_opaque_blocks -= 1;
+            } else if block.is_transparent_not_air() {
+                self.number_of_transparent_blocks += 1;
+                self.number_of_opaque_blocks -= 1;
+            }
+        }
+
+        self.blocks[index] = block;
+        self.states[index] = state;
+    }
+}
+
+/// A 16x16x16 section of a chunk column.
+///
+/// Ownership rules:
+/// * `data` can be written by any thread (world generation, block updates). Its lock is never
+///   held while acquiring the lock of another chunk, so chunk locks can't deadlock.
+/// * `is_generated` and `is_uploaded_to_gpu` are status flags that can be read without locking.
+/// * The mesh lives in the `ChunkMeshArena`, which is only touched by the main thread.
 pub struct Chunk {
-    pub is_generated: RwLock<bool>,
-    pub is_uploaded_to_gpu: RwLock<bool>,
-    pub blocks: RwLock<[BlockID; CHUNK_VOLUME as usize]>,
-    pub number_of_opaque_blocks: RwLock<u32>,
-    pub number_of_transparent_blocks: RwLock<u32>,
-    pub active_faces: RwLock<BitVec>,
-    pub ao_vertices: RwLock<[[[u8; 4]; 6]; CHUNK_VOLUME as usize]>,
-
-    pub vao: RwLock<u32>,
-    pub vbo: RwLock<u32>,
-    pub vertices_drawn: RwLock<u32>,
+    data: RwLock<ChunkData>,
+
+    is_generated: AtomicBool,
+    is_uploaded_to_gpu: AtomicBool,
 }
 
 impl Default for Chunk {
@@ -258,39 +428,31 @@ impl Chunk {
         Self::empty()
     }
 
+    fn from_data(data: ChunkData) -> Self {
+        Self {
+            data: RwLock::new(data),
+            is_generated: AtomicBool::new(false),
+            is_uploaded_to_gpu: AtomicBool::new(false),
+        }
+    }
+
+    /// Empties the chunk and forgets its mesh data, its arena slot is freed by whoever unloads it
     pub fn reset(&self) {
-        // self.unload_from_gpu();
-        *self.is_generated.write() = false;
-        *self.blocks.write() = [BlockID::Air; CHUNK_VOLUME as usize];
-        *self.number_of_opaque_blocks.write() = 0;
-        *self.number_of_transparent_blocks.write() = 0;
-        *self.vertices_drawn.write() = 0;
+        self.is_generated.store(false, Ordering::Release);
+        {
+            let mut data = self.data.write();
+            data.blocks = [BlockID::Air; CHUNK_VOLUME as usize];
+            data.states = [BlockState::default(); CHUNK_VOLUME as usize];
+            data.number_of_opaque_blocks = 0;
+            data.number_of_transparent_blocks = 0;
+            data.active_faces.clear();
+            data.ao_vertices = [[[0; 4]; 6]; CHUNK_VOLUME as usize];
+        }
     }
 
     /// Creates a chunk where every block is the same
     pub fn full_of_block(block: BlockID) -> Self {
-        let (opaque, transparent) = match block {
-            BlockID::Air => (0, 0),
-            block => if block.is_transparent() {
-                (0, 16 * 16 * 16)
-            } else {
-                (16 * 16 * 16, 0)
-            }
-        };
-
-        Self {
-            is_generated: RwLock::new(false),
-            is_uploaded_to_gpu: RwLock::new(false),
-            blocks: RwLock::new([block; CHUNK_VOLUME as usize]),
-            number_of_opaque_blocks: RwLock::new(opaque),
-            number_of_transparent_blocks: RwLock::new(transparent),
-            active_faces: RwLock::new(BitVec::from_elem(6 * CHUNK_VOLUME as usize, false)),
-            ao_vertices: RwLock::new([[[0; 4]; 6]; CHUNK_VOLUME as usize]),
-
-            vao: RwLock::new(0),
-            vbo: RwLock::new(0),
-            vertices_drawn: RwLock::new(0),
-        }
+        Self::from_data(ChunkData::full_of_block(block))
     }
 
     /// Creates an empty chunk with no blocks
@@ -300,33 +462,50 @@ impl Chunk {
 
     /// Creates a chunk where every block is random
     pub fn random() -> Self {
-        Self {
-            is_generated: RwLock::new(false),
-            is_uploaded_to_gpu: RwLock::new(false),
-            blocks: RwLock::new({
-                let mut blocks = [BlockID::Air; CHUNK_VOLUME as usize];
-                for i in 0..blocks.len() {
-                    blocks[i] = random::<BlockID>();
-                }
-                blocks
-            }),
-            number_of_opaque_blocks: RwLock::new(16 * 16 * 16),
-            number_of_transparent_blocks: RwLock::new(0),
-            active_faces: RwLock::new(BitVec::from_elem(6 * CHUNK_VOLUME as usize, false)),
-            ao_vertices: RwLock::new([[[0; 4]; 6]; CHUNK_VOLUME as usize]),
-
-            vao: RwLock::new(0),
-            vbo: RwLock::new(0),
-            vertices_drawn: RwLock::new(0),
+        let chunk = Self::empty();
+        {
+            let mut data = chunk.data.write();
+            for (x, y, z) in BlockIterator::new() {
+                data.set_block(random::<BlockID>(), BlockState::default(), x, y, z);
+            }
         }
+        chunk
+    }
+
+    /// Locks the block data for reading
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, ChunkData> {
+        self.data.read()
+    }
+
+    /// Locks the block data for writing
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, ChunkData> {
+        self.data.write()
+    }
+
+    pub fn is_generated(&self) -> bool {
+        self.is_generated.load(Ordering::Acquire)
+    }
+
+    pub fn set_generated(&self, is_generated: bool) {
+        self.is_generated.store(is_generated, Ordering::Release);
+    }
+
+    pub fn is_uploaded_to_gpu(&self) -> bool {
+        self.is_uploaded_to_gpu.load(Ordering::Acquire)
+    }
+
+    pub fn set_uploaded_to_gpu(&self, is_uploaded_to_gpu: bool) {
+        self.is_uploaded_to_gpu.store(is_uploaded_to_gpu, Ordering::Release);
     }
 
     pub fn is_fully_opaque(&self) -> bool {
-        *self.number_of_opaque_blocks.read() == 16 * 16 * 16
+        self.data.read().number_of_opaque_blocks == 16 * 16 * 16
     }
 
     pub fn is_empty(&self) -> bool {
-        *self.number_of_opaque_blocks.read() + *self.number_of_transparent_blocks.read() == 0
+        self.data.read().is_empty()
     }
 
     #[inline]
@@ -336,106 +515,25 @@ impl Chunk {
 
     #[inline]
     pub fn get_block(&self, x: u32, y: u32, z: u32) -> BlockID {
-        self.blocks.read()[Chunk::chunk_coords_to_array_index(x, y, z)]
+        self.data.read().get_block(x, y, z)
     }
 
-    /// Sets a block at some given coordinates
-    /// The coordinates must be within the chunk size
     #[inline]
-    pub fn set_block(&self, block: BlockID, x: u32, y: u32, z: u32) {
-        let index = Chunk::chunk_coords_to_array_index(x, y, z);
-
-        let target = self.blocks.read()[index];
-        if target.is_air() {
-            if block.is_transparent_not_air() {
-                *self.number_of_transparent_blocks.write() += 1;
-            } else if block.is_opaque() {
-                *self.number_of_opaque_blocks.write() += 1;
-            }
-        } else if target.is_transparent_not_air() {
-            if block.is_air() {
-                *self.number_of_transparent_blocks.write() -= 1;
-            } else if block.is_opaque() {
-                *self.number_of_transparent_blocks.write() -= 1;
-                *self.number_of_opaque_blocks.write() += 1;
-            }
-        } else if target.is_opaque() {
-            if block.is_air() {
-                *self.number_of_opaque_blocks.write() -= 1;
-            } else if block.is_transparent_not_air() {
-                *self.number_of_transparent_blocks.write() += 1;
-                *self.number_of_opaque_blocks.write() -= 1;
-            }
-        }
-
-        self.blocks.write()[index] = block;
+    pub fn get_block_with_state(&self, x: u32, y: u32, z: u32) -> (BlockID, BlockState) {
+        let data = self.data.read();
+        (data.get_block(x, y, z), data.get_state(x, y, z))
     }
 
-    pub fn unload_from_gpu(&self) {
-        *self.is_uploaded_to_gpu.write() = false;
-        let vbo = *self.vbo.read();
-        if vbo != 0 {
-            gl_call!(gl::NamedBufferData(vbo,
-                0,
-                null(),
-                gl::DYNAMIC_DRAW));
-        }
+    /// Sets a block in its default state at some given coordinates
+    /// The coordinates must be within the chunk size
+    #[inline]
+    pub fn set_block(&self, block: BlockID, x: u32, y: u32, z: u32) {
+        self.set_block_with_state(block, BlockState::default(), x, y, z);
     }
 
-    pub fn upload_to_gpu(&self, texture_pack: &TexturePack) {
-        if *self.vao.read() == 0 && *self.vbo.read() == 0 {
-            let (vao, vbo) = create_vao_vbo();
-            *self.vao.write() = vao;
-            *self.vbo.write() = vbo;
-        }
-
-        let n_visible_faces = self.active_faces.read().iter().fold(0, |acc, b| acc + b as i32);
-        if n_visible_faces == 0 {
-            return;
-        }
-
-        // Initialize the VBO
-        gl_call!(gl::NamedBufferData(*self.vbo.read(),
-                (6 * 10 * std::mem::size_of::<f32>() * n_visible_faces as usize) as isize,
-                null(),
-                gl::DYNAMIC_DRAW));
-
-        // Map VBO to virtual memory
-        let vbo_ptr: *mut f32 = gl_call!(gl::MapNamedBuffer(*self.vbo.read(), gl::WRITE_ONLY)) as *mut f32;
-        let mut vbo_offset = 0;
-
-        let mut vertices_drawn = 0;
-        let sides_vec = &self.active_faces.read();
-        let ao_vec = &self.ao_vertices.read();
-        let mut j = 0;
-
-        for (x, y, z) in BlockIterator::new() {
-            let block = self.get_block(x, y, z);
-            if block != BlockID::Air {
-                let active_sides = [
-                    sides_vec[6 * j],
-                    sides_vec[6 * j + 1],
-                    sides_vec[6 * j + 2],
-                    sides_vec[6 * j + 3],
-                    sides_vec[6 * j + 4],
-                    sides_vec[6 * j + 5],
-                ];
-
-                let ao_block = ao_vec[j];
-
-                let uvs = texture_pack.get(&block).unwrap().clone();
-                let uvs = uvs.get_uv_of_every_face();
-
-                let copied_vertices = unsafe { write_unit_cube_to_ptr(vbo_ptr.offset(vbo_offset), x as f32, y as f32, z as f32, uvs, active_sides, ao_block) };
-                // let cube_array = unit_cube_array(x as f32, y as f32, z as f32, uv_bl, uv_tr, active_sides);
-                // gl_call!(gl::NamedBufferSubData(self.vbo, (i * std::mem::size_of::<f32>()) as isize, (cube_array.len() * std::mem::size_of::<f32>()) as isize, cube_array.as_ptr() as *mut c_void));
-                vertices_drawn += copied_vertices;
-                vbo_offset += copied_vertices as isize * 10; // 5 floats per vertex
-            }
-            j += 1;
-        }
-        *self.vertices_drawn.write() = vertices_drawn;
-        gl_call!(gl::UnmapNamedBuffer(*self.vbo.read()));
+    #[inline]
+    pub fn set_block_with_state(&self, block: BlockID, state: BlockState, x: u32, y: u32, z: u32) {
+        self.data.write().set_block(block, state, x, y, z);
     }
 }
 
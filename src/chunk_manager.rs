@@ -1,11 +1,9 @@
 use std::collections::{HashMap, HashSet};
 
-use nalgebra::Matrix4;
-use nalgebra_glm::{Mat4, vec3};
-
 use crate::ambient_occlusion::compute_ao_of_block;
+use crate::block_model::{BACK, BlockModels, BOTTOM, FRONT, LEFT, opposite_face, RIGHT, TOP};
+use crate::block_state::BlockState;
 use crate::chunk::{BlockID, Chunk, ChunkColumn};
-use crate::shader_compilation::ShaderProgram;
 use std::sync::Arc;
 use parking_lot::RwLock;
 use owning_ref::OwningRef;
@@ -13,25 +11,39 @@ use owning_ref::OwningRef;
 pub const CHUNK_SIZE: u32 = 16;
 pub const CHUNK_VOLUME: u32 = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
 
-#[derive(Default)]
 pub struct ChunkManager {
     pub loaded_chunk_columns: RwLock<HashMap<(i32, i32), Arc<ChunkColumn>>>,
-    pub(crate) block_changelist: RwLock<HashSet<(i32, BlockID, i32, i32, i32)>>,
+    block_changelist: RwLock<HashSet<(i32, BlockID, i32, i32, i32)>>,
+    block_models: Arc<BlockModels>,
 }
 
 impl ChunkManager {
-    pub fn new() -> ChunkManager {
+    pub fn new(block_models: Arc<BlockModels>) -> ChunkManager {
         ChunkManager {
             loaded_chunk_columns: RwLock::new(HashMap::new()),
             block_changelist: RwLock::new(HashSet::new()),
+            block_models,
         }
     }
 
+    /// The shapes the blocks of the world are meshed and collided with
+    pub fn block_models(&self) -> &BlockModels {
+        &self.block_models
+    }
+
     #[inline]
     pub fn get_column(&self, x: i32, z: i32) -> Option<Arc<ChunkColumn>> {
         self.loaded_chunk_columns.read().get(&(x, z)).map(|col| Arc::clone(col))
     }
 
+    /// Whether the column of the block at (x, z) and the 8 around it are loaded,
+    /// so that a body there collides with every block it can reach during a tick
+    pub fn is_loaded_around(&self, x: i32, z: i32) -> bool {
+        let (c_x, _, c_z, _, _, _) = ChunkManager::get_chunk_coords(x, 0, z);
+        let columns = self.loaded_chunk_columns.read();
+        (-1..=1).all(|dx| (-1..=1).all(|dz| columns.contains_key(&(c_x + dx, c_z + dz))))
+    }
+
     #[inline]
     pub fn get_chunk(&self, x: i32, y: i32, z: i32) -> Option<OwningRef<Arc<ChunkColumn>, Chunk>> {
         if y < 0 || y >= 16 {
@@ -103,29 +115,68 @@ impl ChunkManager {
                 chunk.get_block(block_x, block_y, block_z))
     }
 
-    /// Replaces the block at (x, y, z) with `block`.
-    fn _set_block(&self, priority: i32, block: BlockID, x: i32, y: i32, z: i32) -> bool {
+    pub fn get_block_with_state(&self, x: i32, y: i32, z: i32) -> Option<(BlockID, BlockState)> {
+        let (chunk_x, chunk_y, chunk_z, block_x, block_y, block_z)
+            = ChunkManager::get_chunk_coords(x, y, z);
+
+        self.get_chunk(chunk_x, chunk_y, chunk_z)
+            .map(|chunk| chunk.get_block_with_state(block_x, block_y, block_z))
+    }
+
+    /// The block at (x, y, z) in its state, with the connections of the state chosen from the neighbours
+    /// of the block, like the arms of a fence. This is the state the block is meshed and collided with
+    pub fn get_block_with_connections(&self, x: i32, y: i32, z: i32) -> Option<(BlockID, BlockState)> {
+        let (block, state) = self.get_block_with_state(x, y, z)?;
+        Some((block, self.connected_state(block, state, x, y, z)))
+    }
+
+    /// `state` of `block` at (x, y, z) with its connections chosen from the blocks around
+    pub fn connected_state(&self, block: BlockID, state: BlockState, x: i32, y: i32, z: i32) -> BlockState {
+        if !block.has_connections() {
+            return state;
+        }
+        self.block_models.connected_state(block, state, &|facing| {
+            let (d_x, d_y, d_z) = facing.offset();
+            self.get_block_with_state(x + d_x, y + d_y, z + d_z)
+        })
+    }
+
+    /// Replaces the block at (x, y, z) with `block` in `state`.
+    fn _set_block(&self, priority: i32, block: BlockID, state: BlockState, x: i32, y: i32, z: i32) -> bool {
         let (chunk_x, chunk_y, chunk_z, block_x, block_y, block_z)
             = ChunkManager::get_chunk_coords(x, y, z);
 
         match self.get_chunk(chunk_x, chunk_y, chunk_z) {
             None => false,
             Some(chunk) => {
-                chunk.set_block(block, block_x, block_y, block_z);
-                if *chunk.is_uploaded_to_gpu.read() {
-                    self.block_changelist.write().insert((priority, block, x, y, z));
-                }
+                chunk.set_block_with_state(block, state, block_x, block_y, block_z);
+                // Even when the chunk isn't uploaded yet: its mesh may be waiting for the upload already
+                self.block_changelist.write().insert((priority, block, x, y, z));
                 true
             }
         }
     }
 
     pub fn set_block(&self, block: BlockID, x: i32, y: i32, z: i32) -> bool {
-        self._set_block(0, block, x, y, z)
+        self._set_block(0, block, BlockState::default(), x, y, z)
+    }
+
+    pub fn set_block_with_state(&self, block: BlockID, state: BlockState, x: i32, y: i32, z: i32) -> bool {
+        self._set_block(0, block, state, x, y, z)
     }
 
     pub fn put_block(&self, block: BlockID, x: i32, y: i32, z: i32) -> bool {
-        self._set_block(1, block, x, y, z)
+        self._set_block(1, block, BlockState::default(), x, y, z)
+    }
+
+    pub fn put_block_with_state(&self, block: BlockID, state: BlockState, x: i32, y: i32, z: i32) -> bool {
+        self._set_block(1, block, state, x, y, z)
+    }
+
+    /// The blocks changed since the last call, as (priority, block, x, y, z).
+    /// Their chunks (and the neighbouring ones) need to be meshed again
+    pub fn take_block_changelist(&self) -> HashSet<(i32, BlockID, i32, i32, i32)> {
+        std::mem::take(&mut *self.block_changelist.write())
     }
 
     pub fn is_solid_block_at(&self, x: i32, y: i32, z: i32) -> bool {
@@ -164,7 +215,7 @@ impl ChunkManager {
         }
 
         #[inline]
-        fn block_at(column: &ChunkColumn, neighbourhood: &[Option<Arc<ChunkColumn>>; 9], c_x: i32, c_z: i32, w_x: i32, w_y: i32, w_z: i32) -> BlockID {
+        fn block_at(column: &ChunkColumn, neighbourhood: &[Option<Arc<ChunkColumn>>; 9], c_x: i32, c_z: i32, w_x: i32, w_y: i32, w_z: i32) -> (BlockID, BlockState) {
             let to_index = |x: i32, z: i32| -> usize {
                 3 * (x - c_x + 1) as usize + (z - c_z + 1) as usize
             };
@@ -172,57 +223,66 @@ impl ChunkManager {
             let (c_x_n, c_y_n, c_z_n, b_x, b_y, b_z) = ChunkManager::get_chunk_coords(w_x, w_y, w_z);
 
             if c_y_n < 0 || c_y_n >= 16 {
-                return BlockID::Air;
+                return (BlockID::Air, BlockState::default());
             }
 
             if c_x == c_x_n && c_z == c_z_n {
-                column.get_chunk(c_y_n).get_block(b_x, b_y, b_z)
+                column.get_chunk(c_y_n).get_block_with_state(b_x, b_y, b_z)
             } else {
                 if let Some(neighbour_column) = neighbourhood[to_index(c_x_n, c_z_n)].as_ref() {
-                    neighbour_column.get_chunk(c_y_n).get_block(b_x, b_y, b_z)
+                    neighbour_column.get_chunk(c_y_n).get_block_with_state(b_x, b_y, b_z)
                 } else {
-                    BlockID::Air
+                    (BlockID::Air, BlockState::default())
                 }
             }
         };
 
         #[inline]
-        fn compute_active_faces(column: &ChunkColumn, neighbourhood: &[Option<Arc<ChunkColumn>>; 9], c_x: i32, c_z: i32, x: i32, y: i32, z: i32) -> [bool; 6] {
-            let right = block_at(&column, &neighbourhood, c_x, c_z, x + 1, y, z).is_transparent();
-            let left = block_at(&column, &neighbourhood, c_x, c_z, x - 1, y, z).is_transparent();
-            let top = block_at(&column, &neighbourhood, c_x, c_z, x, y + 1, z).is_transparent();
-            let bottom = block_at(&column, &neighbourhood, c_x, c_z, x, y - 1, z).is_transparent();
-            let front = block_at(&column, &neighbourhood, c_x, c_z, x, y, z + 1).is_transparent();
-            let back = block_at(&column, &neighbourhood, c_x, c_z, x, y, z - 1).is_transparent();
+        fn compute_active_faces(models: &BlockModels, column: &ChunkColumn, neighbourhood: &[Option<Arc<ChunkColumn>>; 9], c_x: i32, c_z: i32, (x, y, z): (i32, i32, i32)) -> [bool; 6] {
+            let is_active = |face: usize, (rx, ry, rz): (i32, i32, i32)| {
+                let (block, state) = block_at(column, neighbourhood, c_x, c_z, x + rx, y + ry, z + rz);
+                !models.hides_face(block, state, opposite_face(face))
+            };
+            let right = is_active(RIGHT, (1, 0, 0));
+            let left = is_active(LEFT, (-1, 0, 0));
+            let top = is_active(TOP, (0, 1, 0));
+            let bottom = is_active(BOTTOM, (0, -1, 0));
+            let front = is_active(FRONT, (0, 0, 1));
+            let back = is_active(BACK, (0, 0, -1));
             [right, left, top, bottom, front, back]
         };
 
-        let mut active_faces = this_chunk.active_faces.write();
-        let mut ao_vertices = this_chunk.ao_vertices.write();
-
+        // Compute everything first and only then lock the chunk for writing. block_at() takes
+        // the read lock of this chunk and of its neighbours, they must not be nested in a write lock
+        let mut updates = Vec::new();
         for (b_x, b_y, b_z) in blocks {
             if this_chunk.get_block(b_x, b_y, b_z) == BlockID::Air {
                 continue;
             }
             let (w_x, w_y, w_z) = ChunkManager::get_global_coords((c_x, c_y, c_z, b_x, b_y, b_z));
 
-            let af = compute_active_faces(&this_column, &neighbourhood, c_x, c_z, w_x, w_y, w_z);
+            let af = compute_active_faces(&self.block_models, &this_column, &neighbourhood, c_x, c_z, (w_x, w_y, w_z));
             let array_index = (b_y * CHUNK_SIZE * CHUNK_SIZE + b_z * CHUNK_SIZE + b_x) as usize;
 
-            active_faces.set(6 * array_index, af[0]);
-            active_faces.set(6 * array_index + 1, af[1]);
-            active_faces.set(6 * array_index + 2, af[2]);
-            active_faces.set(6 * array_index + 3, af[3]);
-            active_faces.set(6 * array_index + 4, af[4]);
-            active_faces.set(6 * array_index + 5, af[5]);
-
             // Ambient Occlusion
 
             let block_ao = compute_ao_of_block(&|rx: i32, ry: i32, rz: i32| {
-                !block_at(&this_column, &neighbourhood, c_x, c_z, w_x + rx, w_y + ry, w_z + rz).is_transparent_no_leaves()
+                let (block, state) = block_at(&this_column, &neighbourhood, c_x, c_z, w_x + rx, w_y + ry, w_z + rz);
+                self.block_models.casts_ambient_occlusion(block, state)
             });
 
-            ao_vertices[array_index] = block_ao;
+            updates.push((array_index, af, block_ao));
+        }
+
+        let mut data = this_chunk.write();
+        for (array_index, af, block_ao) in updates {
+            data.active_faces.set(6 * array_index, af[0]);
+            data.active_faces.set(6 * array_index + 1, af[1]);
+            data.active_faces.set(6 * array_index + 2, af[2]);
+            data.active_faces.set(6 * array_index + 3, af[3]);
+            data.active_faces.set(6 * array_index + 4, af[4]);
+            data.active_faces.set(6 * array_index + 5, af[5]);
+            data.ao_vertices[array_index] = block_ao;
         }
     }
 
@@ -236,67 +296,38 @@ impl ChunkManager {
         let array_index = (b_y * CHUNK_SIZE * CHUNK_SIZE + b_z * CHUNK_SIZE + b_x) as usize;
 
         let active_faces_of_block = self.get_active_faces_of_block(w_x, w_y, w_z);
-        {
-            let mut active_faces = chunk.active_faces.write();
-            active_faces.set(6 * array_index, active_faces_of_block[0]);
-            active_faces.set(6 * array_index + 1, active_faces_of_block[1]);
-            active_faces.set(6 * array_index + 2, active_faces_of_block[2]);
-            active_faces.set(6 * array_index + 3, active_faces_of_block[3]);
-            active_faces.set(6 * array_index + 4, active_faces_of_block[4]);
-            active_faces.set(6 * array_index + 5, active_faces_of_block[5]);
-        }
 
         // Ambient Occlusion
 
         let block_ao = compute_ao_of_block(&|rx: i32, ry: i32, rz: i32| {
-            self.get_block(w_x + rx, w_y + ry, w_z + rz)
-                .filter(|b| !b.is_transparent_no_leaves())
+            self.get_block_with_state(w_x + rx, w_y + ry, w_z + rz)
+                .filter(|&(block, state)| self.block_models.casts_ambient_occlusion(block, state))
                 .is_some()
         });
-        self.get_chunk(c_x, c_y, c_z).unwrap().ao_vertices.write()[array_index] = block_ao;
+
+        let mut data = chunk.write();
+        data.active_faces.set(6 * array_index, active_faces_of_block[0]);
+        data.active_faces.set(6 * array_index + 1, active_faces_of_block[1]);
+        data.active_faces.set(6 * array_index + 2, active_faces_of_block[2]);
+        data.active_faces.set(6 * array_index + 3, active_faces_of_block[3]);
+        data.active_faces.set(6 * array_index + 4, active_faces_of_block[4]);
+        data.active_faces.set(6 * array_index + 5, active_faces_of_block[5]);
+        data.ao_vertices[array_index] = block_ao;
     }
 
-    // An active face is a block face next to a transparent block that needs to be rendered
+    // An active face is a block face that isn't hidden by its neighbour and needs to be rendered
     pub fn get_active_faces_of_block(&self, x: i32, y: i32, z: i32) -> [bool; 6] {
-        let right = self.get_block(x + 1, y, z).filter(|&b| !b.is_transparent()).is_none();
-        let left = self.get_block(x - 1, y, z).filter(|&b| !b.is_transparent()).is_none();
-        let top = self.get_block(x, y + 1, z).filter(|&b| !b.is_transparent()).is_none();
-        let bottom = self.get_block(x, y - 1, z).filter(|&b| !b.is_transparent()).is_none();
-        let front = self.get_block(x, y, z + 1).filter(|&b| !b.is_transparent()).is_none();
-        let back = self.get_block(x, y, z - 1).filter(|&b| !b.is_transparent()).is_none();
+        let is_active = |face: usize, (rx, ry, rz): (i32, i32, i32)| {
+            self.get_block_with_state(x + rx, y + ry, z + rz)
+                .filter(|&(block, state)| self.block_models.hides_face(block, state, opposite_face(face)))
+                .is_none()
+        };
+        let right = is_active(RIGHT, (1, 0, 0));
+        let left = is_active(LEFT, (-1, 0, 0));
+        let top = is_active(TOP, (0, 1, 0));
+        let bottom = is_active(BOTTOM, (0, -1, 0));
+        let front = is_active(FRONT, (0, 0, 1));
+        let back = is_active(BACK, (0, 0, -1));
         [right, left, top, bottom, front, back]
     }
-
-    pub fn render_loaded_chunks(&self, program: &mut ShaderProgram) {
-        for ((x, z), chunk_column) in self.loaded_chunk_columns.read().iter() {
-            for (ref y, chunk) in chunk_column.chunks.iter().enumerate() {
-                // Skip rendering the chunk if there is nothing to draw
-                let vao = *chunk.vao.read();
-                if !*chunk.is_uploaded_to_gpu.read() || chunk.is_empty() || vao == 0 {
-                    continue;
-                }
-
-                let model_matrix = {
-                    let translate_matrix = Matrix4::new_translation(&vec3(
-                        *x as f32, *y as f32, *z as f32).scale(16.0));
-                    let rotate_matrix = Matrix4::from_euler_angles(
-                        0.0f32,
-                        0.0,
-                        0.0,
-                    );
-                    let scale_matrix: Mat4 = Matrix4::new_nonuniform_scaling(&vec3(1.0f32, 1.0f32, 1.0f32));
-                    translate_matrix * rotate_matrix * scale_matrix
-                };
-
-                gl_call!(gl::BindVertexArray(vao));
-                if vao == 0 {
-                    dbg!(vao);
-                    dbg!(*chunk.is_uploaded_to_gpu.read());
-                    dbg!(chunk.is_empty());
-                }
-                program.set_uniform_matrix4fv("model", model_matrix.as_ptr());
-                gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, *chunk.vertices_drawn.read() as i32));
-            }
-        }
-    }
-}
\ No newline at end of file
+}
@@ -0,0 +1,362 @@
+use std::collections::{BTreeMap, HashMap};
+use std::os::raw::c_void;
+use std::ptr::null;
+
+use nalgebra_glm::vec3;
+
+use crate::aabb::AABB;
+use crate::biome::{blended_tints, ColorMaps};
+use crate::chunk::{BlockID, BlockIterator, Chunk};
+use crate::chunk_manager::ChunkManager;
+use crate::frustum::Frustum;
+use crate::shapes::write_model_to_ptr;
+
+/// Size in floats of a chunk vertex: position, texture coords, normal, AO and tint
+pub const CHUNK_VERTEX_SIZE: usize = 13;
+
+/// Same layout as the command expected by glMultiDrawArraysIndirect
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct DrawArraysIndirectCommand {
+    count: u32,
+    instance_count: u32,
+    first: u32,
+    base_instance: u32,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Allocation {
+    first: u32,
+    capacity: u32,
+    count: u32,
+}
+
+/// Stores the meshes of every chunk in a single vertex buffer.
+/// The buffer is sub-allocated in vertices with a first-fit free list and grows when it's full.
+/// All the visible chunks are drawn with one glMultiDrawArraysIndirect call, the position of
+/// each chunk is read in the vertex shader from an SSBO indexed by gl_DrawID.
+/// Must only be used from the main thread.
+pub struct ChunkMeshArena {
+    vao: u32,
+    vbo: u32,
+    capacity: u32,
+    // Free ranges of the vertex buffer, offset -> length (in vertices)
+    free_ranges: BTreeMap<u32, u32>,
+    allocations: HashMap<(i32, i32, i32), Allocation>,
+
+    indirect_buffer: u32,
+    chunk_positions_ssbo: u32,
+    commands: Vec<DrawArraysIndirectCommand>,
+    chunk_positions: Vec<f32>,
+    culled_chunks: usize,
+}
+
+impl ChunkMeshArena {
+    pub fn new(capacity: u32) -> Self {
+        let mut vao = 0;
+        gl_call!(gl::CreateVertexArrays(1, &mut vao));
+
+        // Position
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 0));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 0, 3 as i32, gl::FLOAT, gl::FALSE, 0));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 0, 0));
+
+        // Texture coords
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 1));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 1, 3 as i32, gl::FLOAT, gl::FALSE, 3 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 1, 0));
+
+        // Normals
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 2));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 2, 3 as i32, gl::FLOAT, gl::FALSE, 6 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 2, 0));
+
+        // Ambient occlusion
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 3));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 3, 1 as i32, gl::FLOAT, gl::FALSE, 9 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 3, 0));
+
+        // Tint
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 4));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 4, 3 as i32, gl::FLOAT, gl::FALSE, 10 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 4, 0));
+
+        let vbo = Self::create_vertex_buffer(capacity);
+        gl_call!(gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, (CHUNK_VERTEX_SIZE * std::mem::size_of::<f32>()) as i32));
+
+        let mut indirect_buffer = 0;
+        gl_call!(gl::CreateBuffers(1, &mut indirect_buffer));
+        let mut chunk_positions_ssbo = 0;
+        gl_call!(gl::CreateBuffers(1, &mut chunk_positions_ssbo));
+
+        let mut free_ranges = BTreeMap::new();
+        free_ranges.insert(0, capacity);
+
+        Self {
+            vao,
+            vbo,
+            capacity,
+            free_ranges,
+            allocations: HashMap::new(),
+            indirect_buffer,
+            chunk_positions_ssbo,
+            commands: Vec::new(),
+            chunk_positions: Vec::new(),
+            culled_chunks: 0,
+        }
+    }
+
+    fn create_vertex_buffer(capacity: u32) -> u32 {
+        let mut vbo = 0;
+        gl_call!(gl::CreateBuffers(1, &mut vbo));
+        gl_call!(gl::NamedBufferStorage(vbo,
+                (capacity as usize * CHUNK_VERTEX_SIZE * std::mem::size_of::<f32>()) as isize,
+                null(),
+                gl::DYNAMIC_STORAGE_BIT | gl::MAP_WRITE_BIT));
+        vbo
+    }
+
+    /// Doubles the vertex buffer until `needed` more vertices fit at its end
+    fn grow(&mut self, needed: u32) {
+        let mut new_capacity = self.capacity.max(1);
+        while new_capacity - self.capacity < needed {
+            new_capacity *= 2;
+        }
+        info!("Growing the chunk mesh arena from {} to {} vertices", self.capacity, new_capacity);
+
+        let new_vbo = Self::create_vertex_buffer(new_capacity);
+        let vertex_bytes = CHUNK_VERTEX_SIZE * std::mem::size_of::<f32>();
+        gl_call!(gl::CopyNamedBufferSubData(self.vbo, new_vbo, 0, 0, (self.capacity as usize * vertex_bytes) as isize));
+        gl_call!(gl::DeleteBuffers(1, &self.vbo));
+        gl_call!(gl::VertexArrayVertexBuffer(self.vao, 0, new_vbo, 0, vertex_bytes as i32));
+
+        self.vbo = new_vbo;
+        self.release_range(self.capacity, new_capacity - self.capacity);
+        self.capacity = new_capacity;
+    }
+
+    /// Finds the first free range that fits `count` vertices
+    fn allocate_range(&mut self, count: u32) -> u32 {
+        let found = self.free_ranges.iter()
+            .find(|(_, &length)| length >= count)
+            .map(|(&offset, &length)| (offset, length));
+
+        let (offset, length) = match found {
+            Some(range) => range,
+            None => {
+                self.grow(count);
+                return self.allocate_range(count);
+            }
+        };
+
+        self.free_ranges.remove(&offset);
+        if length > count {
+            self.free_ranges.insert(offset + count, length - count);
+        }
+        offset
+    }
+
+    /// Gives a range back to the free list and merges it with the adjacent free ranges
+    fn release_range(&mut self, mut offset: u32, mut length: u32) {
+        let previous = self.free_ranges.range(..offset).next_back()
+            .map(|(&o, &l)| (o, l));
+        if let Some((previous_offset, previous_length)) = previous {
+            if previous_offset + previous_length == offset {
+                self.free_ranges.remove(&previous_offset);
+                offset = previous_offset;
+                length += previous_length;
+            }
+        }
+        if let Some(next_length) = self.free_ranges.remove(&(offset + length)) {
+            length += next_length;
+        }
+        self.free_ranges.insert(offset, length);
+    }
+
+    /// Replaces the mesh of a chunk. `write` receives a pointer to `count` vertices of mapped
+    /// GPU memory and returns how many vertices it actually wrote
+    pub fn upload(&mut self, chunk: (i32, i32, i32), count: u32, write: &mut dyn FnMut(*mut f32) -> u32) {
+        self.free(chunk);
+        if count == 0 {
+            return;
+        }
+
+        let first = self.allocate_range(count);
+        let vertex_bytes = CHUNK_VERTEX_SIZE * std::mem::size_of::<f32>();
+        let ptr = gl_call!(gl::MapNamedBufferRange(self.vbo,
+                (first as usize * vertex_bytes) as isize,
+                (count as usize * vertex_bytes) as isize,
+                gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT)) as *mut f32;
+        let written = write(ptr);
+        gl_call!(gl::UnmapNamedBuffer(self.vbo));
+
+        self.allocations.insert(chunk, Allocation {
+            first,
+            capacity: count,
+            count: written.min(count),
+        });
+    }
+
+    /// Meshes a chunk from its block models, active faces, AO and the tints of the biomes around it,
+    /// and uploads the mesh
+    pub fn upload_chunk(&mut self, coords: (i32, i32, i32), chunk: &Chunk, chunk_manager: &ChunkManager, color_maps: &ColorMaps) {
+        let (c_x, c_y, c_z) = coords;
+        // The connections look at the neighbours of the blocks, whose chunks can't be locked under the lock
+        // of this one. A block changed in between is meshed again anyway
+        let connected_blocks = {
+            let data = chunk.read();
+            BlockIterator::new()
+                .map(|(x, y, z)| ((x, y, z), data.get_block(x, y, z), data.get_state(x, y, z)))
+                .filter(|(_, block, _)| block.has_connections())
+                .collect::<Vec<_>>()
+        };
+        let connected_states = connected_blocks.into_iter()
+            .map(|((x, y, z), block, state)| {
+                let (w_x, w_y, w_z) = ChunkManager::get_global_coords((c_x, c_y, c_z, x, y, z));
+                ((x, y, z), chunk_manager.connected_state(block, state, w_x, w_y, w_z))
+            })
+            .collect::<HashMap<_, _>>();
+
+        // A single read lock for the whole meshing, the blocks can't change under our feet
+        let data = chunk.read();
+        let state_at = |x: u32, y: u32, z: u32| {
+            connected_states.get(&(x, y, z)).copied().unwrap_or_else(|| data.get_state(x, y, z))
+        };
+
+        let models = chunk_manager.block_models();
+        let active_sides_of = |j: usize| {
+            let sides_vec = &data.active_faces;
+            [
+                sides_vec[6 * j],
+                sides_vec[6 * j + 1],
+                sides_vec[6 * j + 2],
+                sides_vec[6 * j + 3],
+                sides_vec[6 * j + 4],
+                sides_vec[6 * j + 5],
+            ]
+        };
+
+        let n_vertices = BlockIterator::new().enumerate()
+            .map(|(j, (x, y, z))| models.shape(data.get_block(x, y, z), state_at(x, y, z)).model.vertex_count(&active_sides_of(j)))
+            .sum();
+
+        // Blended for the whole column the first time a block needs them
+        let mut tints: [Option<[[f32; 3]; 256]>; 2] = [None, None];
+
+        self.upload(coords, n_vertices, &mut |vbo_ptr: *mut f32| {
+            let mut vbo_offset = 0;
+            let mut vertices_drawn = 0;
+            let ao_vec = &data.ao_vertices;
+
+            for (j, (x, y, z)) in BlockIterator::new().enumerate() {
+                let block = data.get_block(x, y, z);
+                if block != BlockID::Air {
+                    let active_sides = active_sides_of(j);
+                    let ao_block = ao_vec[j];
+                    let model = &models.shape(block, state_at(x, y, z)).model;
+                    let tint = block.tint().map_or([1.0; 3], |tint| {
+                        tints[tint as usize].get_or_insert_with(|| blended_tints(chunk_manager, color_maps.get(tint), c_x, c_z))
+                            [(16 * z + x) as usize]
+                    });
+
+                    let ptr = unsafe { vbo_ptr.offset(vbo_offset) };
+                    let copied_vertices = unsafe {
+                        write_model_to_ptr(ptr, (x as f32, y as f32, z as f32), model, active_sides, ao_block, tint)
+                    };
+                    vertices_drawn += copied_vertices;
+                    vbo_offset += copied_vertices as isize * CHUNK_VERTEX_SIZE as isize;
+                }
+            }
+            vertices_drawn
+        });
+        chunk.set_uploaded_to_gpu(true);
+    }
+
+    /// Releases the mesh of a chunk, if it has one
+    pub fn free(&mut self, chunk: (i32, i32, i32)) {
+        if let Some(allocation) = self.allocations.remove(&chunk) {
+            self.release_range(allocation.first, allocation.capacity);
+        }
+    }
+
+    pub fn contains(&self, chunk: (i32, i32, i32)) -> bool {
+        self.allocations.contains_key(&chunk)
+    }
+
+    /// Number of vertices of the mesh of a chunk
+    pub fn vertices_drawn(&self, chunk: (i32, i32, i32)) -> u32 {
+        self.allocations.get(&chunk).map_or(0, |allocation| allocation.count)
+    }
+
+    /// Number of chunks drawn by the last `draw`
+    pub fn drawn_chunks(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Number of non-empty chunks outside of the view frustum during the last `draw_uploaded_chunks`
+    pub fn culled_chunks(&self) -> usize {
+        self.culled_chunks
+    }
+
+    /// Draws every uploaded chunk of the world in the view frustum with a single multi-draw call
+    pub fn draw_uploaded_chunks(&mut self, chunk_manager: &ChunkManager, frustum: &Frustum) {
+        let loaded_chunk_columns = chunk_manager.loaded_chunk_columns.read();
+        let mut culled_chunks = 0;
+        let chunks = loaded_chunk_columns.iter()
+            .flat_map(|(&(x, z), chunk_column)| {
+                chunk_column.chunks.iter().enumerate()
+                    .filter(|(_, chunk)| chunk.is_uploaded_to_gpu() && !chunk.is_empty())
+                    .map(move |(y, _)| (x, y as i32, z))
+            })
+            .filter(|&(x, y, z)| {
+                let mins = vec3(16.0 * x as f32, 16.0 * y as f32, 16.0 * z as f32);
+                let is_visible = frustum.intersects_aabb(&AABB::new(mins, mins + vec3(16.0, 16.0, 16.0)));
+                culled_chunks += !is_visible as usize;
+                is_visible
+            });
+        self.draw(chunks);
+        self.culled_chunks = culled_chunks;
+    }
+
+    /// Draws the meshes of the given chunks with a single draw call
+    pub fn draw<I>(&mut self, chunks: I)
+        where I: Iterator<Item = (i32, i32, i32)> {
+        self.commands.clear();
+        self.chunk_positions.clear();
+
+        for (x, y, z) in chunks {
+            if let Some(allocation) = self.allocations.get(&(x, y, z)) {
+                if allocation.count == 0 {
+                    continue;
+                }
+                self.commands.push(DrawArraysIndirectCommand {
+                    count: allocation.count,
+                    instance_count: 1,
+                    first: allocation.first,
+                    base_instance: 0,
+                });
+                self.chunk_positions.extend_from_slice(&[
+                    16.0 * x as f32, 16.0 * y as f32, 16.0 * z as f32, 0.0]);
+            }
+        }
+
+        if self.commands.is_empty() {
+            return;
+        }
+
+        gl_call!(gl::NamedBufferData(self.indirect_buffer,
+                (self.commands.len() * std::mem::size_of::<DrawArraysIndirectCommand>()) as isize,
+                self.commands.as_ptr() as *const c_void,
+                gl::STREAM_DRAW));
+        gl_call!(gl::NamedBufferData(self.chunk_positions_ssbo,
+                (self.chunk_positions.len() * std::mem::size_of::<f32>()) as isize,
+                self.chunk_positions.as_ptr() as *const c_void,
+                gl::STREAM_DRAW));
+
+        gl_call!(gl::BindVertexArray(self.vao));
+        gl_call!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.indirect_buffer));
+        gl_call!(gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, self.chunk_positions_ssbo));
+        gl_call!(gl::MultiDrawArraysIndirect(gl::TRIANGLES, null(), self.commands.len() as i32, 0));
+        gl_call!(gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0));
+    }
+}
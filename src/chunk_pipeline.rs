@@ -0,0 +1,479 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use noise::{Seedable, SuperSimplex};
+use num_traits::abs;
+
+use crate::biome::Climate;
+use crate::chunk::{BlockIterator, ChunkColumn};
+use crate::chunk_manager::ChunkManager;
+use crate::block_state::BlockState;
+use crate::chunk::BlockID;
+use crate::constants::WORLD_GENERATION_THREAD_POOL_SIZE;
+use crate::world_generation::{carve_caves, compute_sunlight_heightmap, generate_climate, generate_terrain, place_trees};
+use crate::world_storage::WorldStorage;
+
+#[derive(Eq)]
+pub struct PrioritizedItem<T> {
+    pub item: T,
+    pub priority: i32,
+}
+
+impl<T: Eq> Ord for PrioritizedItem<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+    }
+}
+
+impl<T: Eq> PartialOrd for PrioritizedItem<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for PrioritizedItem<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<T> Deref for PrioritizedItem<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.item
+    }
+}
+
+/// The generation stages a chunk column goes through, in order.
+/// A column is at stage S once the job of stage S has completed for it
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ColumnStage {
+    Empty,
+    Terrain,
+    Carved,
+    Decorated,
+    Lit,
+    Meshed,
+}
+
+impl ColumnStage {
+    pub const STAGES: [ColumnStage; 5] = [
+        ColumnStage::Terrain,
+        ColumnStage::Carved,
+        ColumnStage::Decorated,
+        ColumnStage::Lit,
+        ColumnStage::Meshed,
+    ];
+
+    pub fn next(&self) -> Option<ColumnStage> {
+        match self {
+            ColumnStage::Empty => Some(ColumnStage::Terrain),
+            ColumnStage::Terrain => Some(ColumnStage::Carved),
+            ColumnStage::Carved => Some(ColumnStage::Decorated),
+            ColumnStage::Decorated => Some(ColumnStage::Lit),
+            ColumnStage::Lit => Some(ColumnStage::Meshed),
+            ColumnStage::Meshed => None,
+        }
+    }
+
+    /// Index of the stage in `ColumnStage::STAGES`
+    pub fn index(&self) -> usize {
+        *self as usize - 1
+    }
+
+    /// How many rings of neighbour columns must have reached the previous stage
+    /// before this stage can run on a column
+    pub fn neighbour_radius(&self) -> i32 {
+        match self {
+            // Terrain, caves, trees and the heightmap only read and write the column itself,
+            // the trees of the neighbours are computed from the seed
+            ColumnStage::Empty | ColumnStage::Terrain | ColumnStage::Carved | ColumnStage::Decorated | ColumnStage::Lit => 0,
+            // Face culling and AO read the blocks of the neighbours
+            ColumnStage::Meshed => 1,
+        }
+    }
+
+    /// How many extra rings of columns must be loaded around the view distance
+    /// so that every column inside of it can reach `last_stage`
+    pub fn loading_margin(last_stage: ColumnStage) -> i32 {
+        ColumnStage::STAGES.iter()
+            .filter(|&&stage| stage <= last_stage)
+            .map(|stage| stage.neighbour_radius())
+            .sum()
+    }
+}
+
+/// Queue depths of the chunk generation pipeline, indexed like `ColumnStage::STAGES`, and the threads working on them
+#[derive(Default, Debug, Clone)]
+pub struct ChunkPipelineStats {
+    /// Columns waiting for their neighbours before they can run the stage
+    pub waiting: [usize; 5],
+    /// Columns currently running the stage on the thread pool
+    pub running: [usize; 5],
+    /// Chunks meshed but not yet uploaded to the GPU
+    pub pending_uploads: usize,
+    /// Threads of the pool running the stages
+    pub generation_threads: usize,
+    /// Threads meshing the chunks again after their blocks change, set by `ChunkLoading`
+    pub remeshing_threads: usize,
+}
+
+/// Where the blocks of the columns come from
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ColumnSource {
+    /// Generated locally from the world seed
+    Generated,
+    /// Received fully generated from a server, only meshed locally
+    Received,
+}
+
+struct ColumnProgress {
+    column: Arc<ChunkColumn>,
+    stage: ColumnStage,
+    in_flight: bool,
+}
+
+/// Drives chunk columns through their `ColumnStage`s on a thread pool.
+/// The columns are kept within `view_distance` of a set of centers (the players),
+/// plus the margin their neighbour dependencies need
+pub struct ChunkPipeline {
+    source: ColumnSource,
+    last_stage: ColumnStage,
+    view_distance: i32,
+
+    noise_fn: SuperSimplex,
+    cave_noise_fn: SuperSimplex,
+    /// Saved columns replace the generated ones
+    storage: Option<Arc<WorldStorage>>,
+    chunk_column_pool: Vec<Arc<ChunkColumn>>,
+    columns: HashMap<(i32, i32), ColumnProgress>,
+
+    finished_stages_tx: Sender<((i32, i32), ColumnStage)>,
+    finished_stages_rx: Receiver<((i32, i32), ColumnStage)>,
+
+    meshed_chunks_tx: Sender<PrioritizedItem<(i32, i32, i32)>>,
+    meshed_chunks_rx: Receiver<PrioritizedItem<(i32, i32, i32)>>,
+
+    world_generation_thread_pool: rayon::ThreadPool,
+    stats: ChunkPipelineStats,
+}
+
+impl ChunkPipeline {
+    /// A pipeline that generates its columns from `seed`, up to `last_stage`
+    pub fn generated(view_distance: i32, last_stage: ColumnStage, seed: u32) -> Self {
+        Self::new(ColumnSource::Generated, view_distance, last_stage, seed)
+    }
+
+    /// A pipeline that only meshes the columns passed to `receive_column`
+    pub fn received(view_distance: i32) -> Self {
+        Self::new(ColumnSource::Received, view_distance, ColumnStage::Meshed, 0)
+    }
+
+    /// Loads the columns saved in `storage` instead of the generated ones.
+    /// They are applied at the `Lit` stage, once the neighbours placed their trees
+    pub fn with_storage(mut self, storage: Arc<WorldStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    fn new(source: ColumnSource, view_distance: i32, last_stage: ColumnStage, seed: u32) -> Self {
+        let (finished_stages_tx, finished_stages_rx) = unbounded();
+        let (meshed_chunks_tx, meshed_chunks_rx) = unbounded();
+
+        let mut pipeline = Self {
+            source,
+            last_stage,
+            view_distance,
+            noise_fn: SuperSimplex::new().set_seed(seed),
+            cave_noise_fn: SuperSimplex::new().set_seed(seed.wrapping_add(1)),
+            storage: None,
+            chunk_column_pool: Vec::new(),
+            columns: HashMap::new(),
+            finished_stages_tx,
+            finished_stages_rx,
+            meshed_chunks_tx,
+            meshed_chunks_rx,
+            world_generation_thread_pool: rayon::ThreadPoolBuilder::new()
+                .stack_size(4 * 1024 * 1024)
+                .num_threads(*WORLD_GENERATION_THREAD_POOL_SIZE)
+                .build().unwrap(),
+            stats: ChunkPipelineStats::default(),
+        };
+
+        let matrix_width = (2 * pipeline.loaded_distance() + 1) as usize;
+        let reserved_columns = matrix_width * matrix_width;
+        pipeline.chunk_column_pool.reserve(reserved_columns);
+        for _ in 0..reserved_columns {
+            pipeline.chunk_column_pool.push(Arc::new(ChunkColumn::new()));
+        }
+        pipeline
+    }
+
+    /// Distance from the centers beyond which columns are dropped
+    pub fn loaded_distance(&self) -> i32 {
+        match self.source {
+            ColumnSource::Generated => self.view_distance + ColumnStage::loading_margin(self.last_stage),
+            // Keep one more ring than the server sends, so that a column it sends again
+            // is never one we already dropped
+            ColumnSource::Received => self.view_distance + ColumnStage::Meshed.neighbour_radius() + 1,
+        }
+    }
+
+    pub fn stats(&self) -> &ChunkPipelineStats {
+        &self.stats
+    }
+
+    /// The column at (x, z) if it went through every stage of the pipeline
+    pub fn finished_column(&self, x: i32, z: i32) -> Option<&Arc<ChunkColumn>> {
+        self.columns.get(&(x, z))
+            .filter(|progress| progress.stage == self.last_stage)
+            .map(|progress| &progress.column)
+    }
+
+    /// Whether the columns around the block at (x, z) went through every stage of the pipeline
+    pub fn is_finished_around(&self, x: i32, z: i32) -> bool {
+        let (c_x, _, c_z, _, _, _) = ChunkManager::get_chunk_coords(x, 0, z);
+        (-1..=1).all(|dx| (-1..=1).all(|dz| self.finished_column(c_x + dx, c_z + dz).is_some()))
+    }
+
+    /// The last stage that completed on the column at (x, z), if it's loaded
+    pub fn column_stage(&self, x: i32, z: i32) -> Option<ColumnStage> {
+        self.columns.get(&(x, z)).map(|progress| progress.stage)
+    }
+
+    /// Chunks whose mesh data is ready to be uploaded, with their upload priority
+    pub fn meshed_chunks(&self) -> &Receiver<PrioritizedItem<(i32, i32, i32)>> {
+        &self.meshed_chunks_rx
+    }
+
+    /// Used to queue the upload of chunks meshed outside of the pipeline
+    pub fn meshed_chunks_sender(&self) -> &Sender<PrioritizedItem<(i32, i32, i32)>> {
+        &self.meshed_chunks_tx
+    }
+
+    pub fn world_generation_thread_pool(&self) -> &rayon::ThreadPool {
+        &self.world_generation_thread_pool
+    }
+
+    /// Adds a column generated elsewhere. It goes straight to the meshing stage
+    pub fn receive_column(&mut self, (x, z): (i32, i32), chunk_manager: &ChunkManager,
+                          blocks: &[BlockID], states: &[BlockState], climate: &[Climate]) {
+        assert_eq!(self.source, ColumnSource::Received);
+        if self.columns.contains_key(&(x, z)) {
+            // The server sent it again after forgetting about it, we kept ours up to date
+            return;
+        }
+
+        let column = self.chunk_column_pool.pop()
+            .unwrap_or_else(|| Arc::new(ChunkColumn::new()));
+        column.set_blocks(blocks, states);
+        column.set_climate(climate);
+        compute_sunlight_heightmap(&column);
+
+        chunk_manager.add_chunk_column((x, z), Arc::clone(&column));
+        self.columns.insert((x, z), ColumnProgress {
+            column,
+            stage: ColumnStage::Lit,
+            in_flight: false,
+        });
+    }
+
+    /// Whether every column within `radius` of (x, z) has reached at least `stage`
+    fn neighbours_reached(&self, x: i32, z: i32, radius: i32, stage: ColumnStage) -> bool {
+        for n_x in x - radius..=x + radius {
+            for n_z in z - radius..=z + radius {
+                match self.columns.get(&(n_x, n_z)) {
+                    Some(progress) if progress.stage >= stage => {}
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    /// Whether a job is running on (x, z) or on a column it may write into
+    fn is_column_busy(&self, x: i32, z: i32) -> bool {
+        for n_x in x - 1..=x + 1 {
+            for n_z in z - 1..=z + 1 {
+                if let Some(progress) = self.columns.get(&(n_x, n_z)) {
+                    if progress.in_flight {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Runs a single stage of the pipeline on a column. Called on the world generation thread pool
+    fn run_stage(((x, z), stage): ((i32, i32), ColumnStage), column: &ChunkColumn, chunk_manager: &ChunkManager,
+                 (noise_fn, cave_noise_fn): (&SuperSimplex, &SuperSimplex), storage: Option<&WorldStorage>, upload_priority: i32,
+                 meshed_chunks_tx: &Sender<PrioritizedItem<(i32, i32, i32)>>) {
+        match stage {
+            ColumnStage::Empty => {}
+            ColumnStage::Terrain => {
+                column.reset();
+                generate_terrain(column, x, z, noise_fn);
+                generate_climate(column, x, z, noise_fn);
+            }
+            ColumnStage::Carved => carve_caves(column, x, z, cave_noise_fn),
+            ColumnStage::Decorated => place_trees(column, x, z, noise_fn),
+            ColumnStage::Lit => {
+                match storage.map(|storage| storage.load_column((x, z))) {
+                    Some(Ok(Some((blocks, states)))) => column.set_blocks(&blocks, &states),
+                    Some(Err(err)) => error!("Couldn't load the chunk column ({}, {}): {}", x, z, err),
+                    _ => {}
+                }
+                compute_sunlight_heightmap(column);
+            }
+            ColumnStage::Meshed => {
+                // Chunk face culling & AO
+                rayon::scope(|s| {
+                    for (y, chunk) in column.chunks.iter().enumerate() {
+                        let y = y as i32;
+                        s.spawn(move |_s| {
+                            if chunk.is_empty() {
+                                chunk.set_generated(true);
+                                chunk.set_uploaded_to_gpu(true);
+                                return;
+                            }
+                            chunk_manager.update_blocks(x, y, z, BlockIterator::new());
+                            chunk.set_generated(true);
+
+                            if let Err(err) = meshed_chunks_tx.send(PrioritizedItem {
+                                item: (x, y, z),
+                                priority: upload_priority,
+                            }) {
+                                error!("{}", err);
+                            }
+                        });
+                    }
+                });
+            }
+        }
+    }
+
+    /// Advances the pipeline. `centers` are the chunk column coordinates the world is loaded around,
+    /// `on_remove` is called on every column before it's dropped and recycled
+    pub fn update(&mut self, centers: &[(i32, i32)], chunk_manager: &Arc<ChunkManager>,
+                  on_remove: &mut dyn FnMut((i32, i32), &ChunkColumn)) {
+        let loaded_distance = self.loaded_distance();
+        let distance_to_centers = |x: i32, z: i32| {
+            centers.iter()
+                .map(|&(c_x, c_z)| abs(x - c_x).max(abs(z - c_z)))
+                .min()
+                .unwrap_or(i32::MAX)
+        };
+
+        // Collect the stages that finished since the last update
+        for ((x, z), stage) in self.finished_stages_rx.try_iter() {
+            if let Some(progress) = self.columns.get_mut(&(x, z)) {
+                progress.stage = stage;
+                progress.in_flight = false;
+                if stage == ColumnStage::Terrain {
+                    chunk_manager.add_chunk_column((x, z), Arc::clone(&progress.column));
+                }
+            }
+        }
+
+        // Remove distant chunk columns
+        {
+            let columns_to_remove = self.columns.keys()
+                .filter(|&&(x, z)| distance_to_centers(x, z) > loaded_distance)
+                .filter(|&&(x, z)| !self.is_column_busy(x, z))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            for xz in columns_to_remove {
+                if let Some(progress) = self.columns.remove(&xz) {
+                    chunk_manager.remove_chunk_column(&xz);
+                    on_remove(xz, &progress.column);
+                    self.chunk_column_pool.push(progress.column);
+                }
+            }
+        }
+
+        // Request the missing columns around the centers
+        if self.source == ColumnSource::Generated {
+            for &(c_x, c_z) in centers {
+                for x in c_x - loaded_distance..=c_x + loaded_distance {
+                    for z in c_z - loaded_distance..=c_z + loaded_distance {
+                        if !self.columns.contains_key(&(x, z)) {
+                            let column = self.chunk_column_pool.pop()
+                                .unwrap_or_else(|| Arc::new(ChunkColumn::new()));
+                            self.columns.insert((x, z), ColumnProgress {
+                                column,
+                                stage: ColumnStage::Empty,
+                                in_flight: false,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        // Find the columns whose next stage has its neighbour dependencies satisfied
+        let mut stats = ChunkPipelineStats::default();
+        let mut ready_columns = Vec::new();
+        for (&(x, z), progress) in &self.columns {
+            let next_stage = match progress.stage.next() {
+                Some(stage) if stage <= self.last_stage => stage,
+                _ => continue,
+            };
+            if progress.in_flight {
+                stats.running[next_stage.index()] += 1;
+            } else if self.neighbours_reached(x, z, next_stage.neighbour_radius(), progress.stage) {
+                ready_columns.push(((x, z), next_stage));
+            } else {
+                stats.waiting[next_stage.index()] += 1;
+            }
+        }
+
+        // Closest columns first
+        let distance = |&((x, z), _): &((i32, i32), ColumnStage)| {
+            centers.iter()
+                .map(|&(c_x, c_z)| (x - c_x) * (x - c_x) + (z - c_z) * (z - c_z))
+                .min()
+                .unwrap_or(0)
+        };
+        ready_columns.sort_by_key(distance);
+
+        let running_jobs = stats.running.iter().sum::<usize>();
+        let available_jobs = (4 * *WORLD_GENERATION_THREAD_POOL_SIZE).saturating_sub(running_jobs);
+        for (i, job) in ready_columns.iter().enumerate() {
+            let ((x, z), stage) = *job;
+            if i >= available_jobs {
+                stats.waiting[stage.index()] += 1;
+                continue;
+            }
+            stats.running[stage.index()] += 1;
+
+            let progress = self.columns.get_mut(&(x, z)).unwrap();
+            progress.in_flight = true;
+
+            let column = Arc::clone(&progress.column);
+            let chunk_manager = Arc::clone(chunk_manager);
+            let noise_fn = self.noise_fn;
+            let cave_noise_fn = self.cave_noise_fn;
+            let storage = self.storage.clone();
+            let upload_priority = -distance(job);
+            let meshed_chunks_tx = self.meshed_chunks_tx.clone();
+            let finished_stages_tx = self.finished_stages_tx.clone();
+
+            self.world_generation_thread_pool.spawn(move || {
+                Self::run_stage(((x, z), stage), &column, &chunk_manager,
+                                (&noise_fn, &cave_noise_fn), storage.as_deref(), upload_priority, &meshed_chunks_tx);
+                if let Err(err) = finished_stages_tx.send(((x, z), stage)) {
+                    error!("{}", err);
+                }
+            });
+        }
+
+        stats.generation_threads = self.world_generation_thread_pool.current_num_threads();
+        self.stats = stats;
+    }
+}
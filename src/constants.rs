@@ -19,6 +19,10 @@ pub const GUI_SCALING: f32 = 2.0;
 pub const CROSSHAIR_SIZE: f32 = 40.0;
 pub const BLOCK_OUTLINE_WIDTH: f32 = 3.0;
 
+// Assets
+/// Holds `blockstates/`, `models/` and the builtin `textures/`, relative to the working directory
+pub const ASSET_DIRECTORY: &str = ".";
+
 lazy_static! {
     pub static ref WORLD_SEED: u32 = {
         let seed = thread_rng().next_u32();
@@ -31,6 +35,17 @@ lazy_static! {
 pub const RENDER_DISTANCE: i32 = 10;
 pub const ENABLE_FOG: bool = true;
 pub const CHUNK_UPLOADS_PER_FRAME: usize = 2;
+/// Initial size in vertices of the buffer holding every chunk mesh, it grows when needed
+pub const CHUNK_MESH_ARENA_CAPACITY: u32 = 1 << 22;
+pub const MAX_PARTICLES: usize = 500;
+/// Where the screenshots are saved
+pub const SCREENSHOT_DIRECTORY: &str = "screenshots";
+/// The high resolution screenshots are this many times the size of the window on each side
+pub const SCREENSHOT_TILES: u32 = 4;
+/// Where the shaders and the files they include are read from
+pub const SHADER_DIRECTORY: &str = "src/shaders";
+/// How often the files of the shaders are checked for changes
+pub const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(500);
 lazy_static! {
     pub static ref WORLD_GENERATION_THREAD_POOL_SIZE: usize = {
         let cpus = num_cpus::get();
@@ -38,26 +53,42 @@ lazy_static! {
     };
     // pub static ref WORLD_GENERATION_THREAD_POOL_SIZE: usize = 2;
 }
+// Network
+pub const DEFAULT_SERVER_PORT: u16 = 25565;
+/// Chunk columns sent to each client per tick
+pub const COLUMNS_SENT_PER_TICK: usize = 6;
+/// How many seconds the clock of a client may run ahead of the server's, covers network jitter
+pub const MOVEMENT_TOLERANCE: f32 = 0.25;
+/// Seconds between two automatic saves of the world by the server
+pub const AUTOSAVE_INTERVAL: f32 = 60.0;
+
 // Input
 pub const MOUSE_SENSITIVITY_X: f32 = 0.5;
 pub const MOUSE_SENSITIVITY_Y: f32 = 0.5;
 
 // Physics
-pub const PHYSICS_TICKRATE: f32 = 60.0;
+/// Ticks per second of the simulation, on the client and the server
+pub const TICKRATE: f32 = 60.0;
 pub const GRAVITY: f32 = -28.0;
 pub const MAX_VERTICAL_VELOCITY: f32 = 90.0;
 
 // Texture pack
-pub const ITEM_ARRAY_TEXTURE_LAYERS: u32 = 50;
-pub const BLOCK_TEXTURE_SIZE: u32 = 16;
+/// The larger block textures are scaled down to it
+pub const MAX_BLOCK_TEXTURE_SIZE: u32 = 512;
 
 // Some values are taken from the minecraft gamepedia
 // Player
 pub const PLAYER_WIDTH: f32 = 0.6;
 pub const PLAYER_HEIGHT: f32 = 1.8;
 pub const PLAYER_EYES_HEIGHT: f32 = 1.62;
+/// Size of the head of the other players, the rest of their height is their body
+pub const PLAYER_HEAD_SIZE: f32 = 0.5;
+/// Height of the ledges the player walks onto without jumping: slabs, but not full blocks
+pub const PLAYER_STEP_HEIGHT: f32 = 0.6;
 pub const REACH_DISTANCE: f32 = 7.0;
 pub const JUMP_HEIGHT: f32 = 1.3;
+/// Minimum number of ticks between two jumps while the jump key is held
+pub const JUMP_COOLDOWN_TICKS: u64 = 29;
 pub const HORIZONTAL_ACCELERATION: f32 = 30.0;
 pub const WALKING_SPEED: f32 = 4.317;
 pub const SPRINTING_SPEED: f32 = 6.0;
@@ -71,8 +102,12 @@ pub const IN_AIR_FRICTION: f32 = 2.0;
 // Calculation of the initial velocity in order to reach the jump height
 lazy_static! {
     pub static ref JUMP_IMPULSE: f32 = (JUMP_HEIGHT * 2.0 * -GRAVITY).sqrt();
-    pub static ref FLYING_TRIGGER_INTERVAL: Duration = Duration::from_millis(250);
-    pub static ref SPRINTING_TRIGGER_INTERVAL: Duration = Duration::from_millis(250);
 }
+/// Maximum number of ticks between the two presses of jump that toggle flying
+pub const FLYING_TRIGGER_TICKS: u64 = 15;
+/// Maximum number of ticks between the two presses of forward that start sprinting
+pub const SPRINTING_TRIGGER_TICKS: u64 = 15;
+/// Number of ticks between two blocks placed or broken while the mouse button is held
+pub const BLOCK_PLACING_COOLDOWN_TICKS: u64 = 15;
 pub const PLAYER_HALF_WIDTH: f32 = PLAYER_WIDTH / 2.0;
 pub const PLAYER_HALF_HEIGHT: f32 = PLAYER_HEIGHT / 2.0;
@@ -0,0 +1,151 @@
+//! What the debug overlay (F3) shows: where the player is and looks, the state of the chunks and
+//! how long the frames take. It's gathered every frame while the overlay is shown, the text is laid out here
+//! and drawn by `RenderDebugOverlay`
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use nalgebra_glm::Vec3;
+
+use crate::chunk::BlockID;
+use crate::chunk_manager::ChunkManager;
+use crate::chunk_pipeline::{ChunkPipelineStats, ColumnStage};
+use crate::util::Forward;
+
+/// How many frames `FrameTimes` remembers, a bar each in the graph of the overlay
+pub const FRAME_TIME_HISTORY: usize = 240;
+
+/// The durations of the last frames and the frame rate of the last second, measured by `FpsCounter`
+#[derive(Default, Debug)]
+pub struct FrameTimes {
+    /// The oldest first
+    times: VecDeque<Duration>,
+    fps: u64,
+}
+
+impl FrameTimes {
+    pub fn push(&mut self, frame_time: Duration) {
+        if self.times.len() == FRAME_TIME_HISTORY {
+            self.times.pop_front();
+        }
+        self.times.push_back(frame_time);
+    }
+
+    pub fn set_fps(&mut self, fps: u64) {
+        self.fps = fps;
+    }
+
+    pub fn fps(&self) -> u64 {
+        self.fps
+    }
+
+    /// The oldest first
+    pub fn times(&self) -> impl Iterator<Item = &Duration> {
+        self.times.iter()
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.times.is_empty() {
+            return Duration::default();
+        }
+        self.times.iter().sum::<Duration>() / self.times.len() as u32
+    }
+
+    pub fn max(&self) -> Duration {
+        self.times.iter().max().copied().unwrap_or_default()
+    }
+}
+
+/// The state of the game shown by the overlay
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    pub fps: u64,
+    pub average_frame_time: Duration,
+    pub max_frame_time: Duration,
+    /// Of the feet of the player
+    pub position: Vec3,
+    /// Pitch and yaw of the player, in radians like `PlayerState::rotation`
+    pub rotation: Vec3,
+    pub targeted_block: Option<((i32, i32, i32), BlockID)>,
+    pub loaded_columns: usize,
+    /// Chunks of the loaded columns whose mesh is on the GPU, even if it's empty
+    pub uploaded_chunks: usize,
+    /// Chunks drawn in the last frame
+    pub drawn_chunks: usize,
+    /// Chunks with blocks outside of the view frustum in the last frame
+    pub culled_chunks: usize,
+    pub pipeline: ChunkPipelineStats,
+    pub particles: usize,
+}
+
+impl DebugInfo {
+    /// Uploaded chunks that weren't drawn though they weren't culled: the empty ones and the ones
+    /// without a visible face
+    pub fn empty_chunks(&self) -> usize {
+        self.uploaded_chunks.saturating_sub(self.drawn_chunks + self.culled_chunks)
+    }
+
+    /// Chunks of the loaded columns still waiting for their mesh
+    pub fn not_uploaded_chunks(&self) -> usize {
+        (16 * self.loaded_columns).saturating_sub(self.uploaded_chunks)
+    }
+
+    /// The lines on the left of the screen, about the player and the world
+    pub fn world_lines(&self) -> Vec<String> {
+        let (x, y, z) = (self.position.x.floor() as i32, self.position.y.floor() as i32, self.position.z.floor() as i32);
+        let (c_x, c_y, c_z, b_x, b_y, b_z) = ChunkManager::get_chunk_coords(x, y, z);
+        let (direction, axis) = facing(&self.rotation.forward());
+        let targeted_block = match self.targeted_block {
+            Some(((x, y, z), block)) => format!("{} {} {}: {} (id {})", x, y, z, block.name(), block as u8),
+            None => "none".to_string(),
+        };
+        vec![
+            format!("XYZ: {:.3} / {:.3} / {:.3}", self.position.x, self.position.y, self.position.z),
+            format!("Block: {} {} {}", x, y, z),
+            format!("Chunk: {} {} {} in {} {} {}", b_x, b_y, b_z, c_x, c_y, c_z),
+            format!("Facing: {} ({}) ({:.1} / {:.1})",
+                    direction, axis, self.rotation.y.to_degrees(), self.rotation.x.to_degrees()),
+            format!("Targeted block: {}", targeted_block),
+            String::new(),
+            format!("Loaded columns: {}", self.loaded_columns),
+            format!("Chunks: {} drawn, {} culled, {} empty, {} not uploaded",
+                    self.drawn_chunks, self.culled_chunks, self.empty_chunks(), self.not_uploaded_chunks()),
+            format!("Particles: {}", self.particles),
+        ]
+    }
+
+    /// The lines on the right of the screen, about the performance
+    pub fn performance_lines(&self) -> Vec<String> {
+        let millis = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        let mut lines = vec![
+            format!("{} fps", self.fps),
+            format!("Frame time: {:.1} ms avg, {:.1} ms max", millis(self.average_frame_time), millis(self.max_frame_time)),
+            String::new(),
+            "Chunk pipeline (waiting / running):".to_string(),
+        ];
+        for (i, stage) in ColumnStage::STAGES.iter().enumerate() {
+            lines.push(format!("{:?}: {} / {}", stage, self.pipeline.waiting[i], self.pipeline.running[i]));
+        }
+        lines.push(format!("Pending uploads: {}", self.pipeline.pending_uploads));
+        lines.push(String::new());
+        lines.push(format!("Threads: {} generating, {} remeshing",
+                           self.pipeline.generation_threads, self.pipeline.remeshing_threads));
+        lines
+    }
+}
+
+/// The cardinal direction closest to `forward` on the horizontal plane, and the axis it goes along.
+/// North is towards negative Z and east towards positive X, like in Minecraft
+pub fn facing(forward: &Vec3) -> (&'static str, &'static str) {
+    if forward.x.abs() > forward.z.abs() {
+        if forward.x > 0.0 {
+            ("east", "Towards positive X")
+        } else {
+            ("west", "Towards negative X")
+        }
+    } else if forward.z > 0.0 {
+        ("south", "Towards positive Z")
+    } else {
+        ("north", "Towards negative Z")
+    }
+}
@@ -4,9 +4,10 @@ use specs::NullStorage;
 
 use crate::inventory::Inventory;
 use crate::physics::Interpolator;
-use crate::player::{PlayerPhysicsState, PlayerState};
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
 
-impl Component for Interpolator<PlayerPhysicsState> {
+impl Component for Interpolator<PhysicsBody> {
     type Storage = DenseVecStorage<Self>;
 }
 
@@ -1,2 +1,3 @@
 pub mod components;
+pub mod simulation;
 pub mod systems;
\ No newline at end of file
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use specs::{DispatcherBuilder, World, WorldExt};
+
+use crate::block_model::BlockModels;
+use crate::chunk_manager::ChunkManager;
+use crate::ecs::components::MainHandItemChanged;
+use crate::ecs::systems::*;
+use crate::inventory::Inventory;
+use crate::physics::Interpolator;
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
+use crate::replay::Replay;
+use crate::timer::Timer;
+
+/// The gameplay systems of the client without the window, the GPU or the network.
+/// The input comes from `script` and the world from the `IncomingColumns` resource,
+/// every dispatch is a frame of the fixed step `Timer` given to `simulation_world`,
+/// simulating the `Ticks` that fit in it
+pub fn simulation_dispatcher<'a, 'b>(script: InputScript) -> DispatcherBuilder<'a, 'b> {
+    gameplay_dispatcher(DispatcherBuilder::new()
+        .with_thread_local(ReadScriptedInput::new(script))
+        .with_thread_local(AdvanceTicks))
+}
+
+/// Same as `simulation_dispatcher` with the input, the frame times and the ticks of `replay`,
+/// `simulation_world` must be given a `Timer::manual`
+pub fn replay_dispatcher<'a, 'b>(replay: Replay) -> DispatcherBuilder<'a, 'b> {
+    gameplay_dispatcher(DispatcherBuilder::new().with_thread_local(ReadReplayedInput::new(replay)))
+}
+
+/// Adds the gameplay systems after the ones reading the input and scheduling the ticks
+fn gameplay_dispatcher<'a, 'b>(dispatcher: DispatcherBuilder<'a, 'b>) -> DispatcherBuilder<'a, 'b> {
+    dispatcher
+        .with_thread_local(InventoryHandleInput)
+        .with_thread_local(HandlePlayerInput)
+        .with_thread_local(UpdatePlayerPhysics)
+        .with_thread_local(UpdatePhysics)
+        .with_thread_local(UpdatePlayerState)
+        .with_thread_local(PlaceAndBreakBlocks)
+        .with_thread_local(ChunkLoading::new())
+        .with_thread_local(AdvanceGlobalTime)
+}
+
+/// A world with the components used by `simulation_dispatcher`, the other resources are
+/// created by `Dispatcher::setup`
+pub fn simulation_world(timer: Timer, block_models: Arc<BlockModels>) -> World {
+    let mut world = World::new();
+    world.register::<PlayerState>();
+    world.register::<Interpolator<PhysicsBody>>();
+    world.register::<Inventory>();
+    world.register::<MainHandItemChanged>();
+    world.insert(timer);
+    world.insert(Arc::new(ChunkManager::new(block_models)));
+    world
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write};
+
+use crate::chunk_manager::ChunkManager;
+use crate::chunk_mesh_arena::ChunkMeshArena;
+use crate::chunk_pipeline::ChunkPipelineStats;
+use crate::constants::{GUI_SCALING, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::debug_info::{DebugInfo, FrameTimes, FRAME_TIME_HISTORY};
+use crate::input::{Action, InputCache};
+use crate::keybindings::InputAction;
+use crate::particle_system::ParticleSystem;
+use crate::physics::Interpolator;
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
+use crate::screenshot::Screenshots;
+use crate::text::{Font, TextMesh, TextRenderer, LINE_HEIGHT};
+use crate::texture_pack::TexturePack;
+use crate::ticks::Ticks;
+use crate::types::{ParticleSystems, Shaders};
+
+const TEXT_COLOR: [f32; 4] = [0.88, 0.88, 0.88, 1.0];
+const LINE_BACKGROUND_COLOR: [f32; 4] = [0.31, 0.31, 0.31, 0.56];
+const GRAPH_BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+/// Frames under 60 fps are yellow, under 30 fps red
+const FAST_FRAME_COLOR: [f32; 4] = [0.2, 0.9, 0.2, 0.9];
+const SLOW_FRAME_COLOR: [f32; 4] = [0.9, 0.9, 0.2, 0.9];
+const VERY_SLOW_FRAME_COLOR: [f32; 4] = [0.9, 0.2, 0.2, 0.9];
+/// The height of a millisecond in the frame time graph, in pixels
+const GRAPH_PIXELS_PER_MILLISECOND: f32 = 2.0;
+/// The graph is cut above 50 ms
+const GRAPH_HEIGHT: f32 = 100.0;
+/// Between the text and the sides of the screen, in font pixels
+const MARGIN: f32 = 2.0;
+
+/// Toggles the debug overlay with F3 and draws it over the GUI: the `DebugInfo` of the frame on both
+/// sides of the screen, and the last frame times as a graph in the bottom left corner
+pub struct RenderDebugOverlay {
+    visible: bool,
+    text_renderer: TextRenderer,
+    mesh: TextMesh,
+}
+
+impl RenderDebugOverlay {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            text_renderer: TextRenderer::new(),
+            mesh: TextMesh::new(),
+        }
+    }
+
+    /// Adds a line of text with its background, from the left of the screen or aligned to its right
+    fn add_line(&mut self, font: &Font, text: &str, line: usize, right_aligned: bool) {
+        if text.is_empty() {
+            return;
+        }
+        let width = font.width(text) * GUI_SCALING;
+        let x = if right_aligned {
+            WINDOW_WIDTH as f32 - width - MARGIN * GUI_SCALING
+        } else {
+            MARGIN * GUI_SCALING
+        };
+        let y = (MARGIN + line as f32 * LINE_HEIGHT) * GUI_SCALING;
+        self.mesh.add_rectangle((x - GUI_SCALING, y - GUI_SCALING, width + 2.0 * GUI_SCALING, LINE_HEIGHT * GUI_SCALING),
+                                LINE_BACKGROUND_COLOR);
+        self.mesh.add_text(font, text, (x, y), GUI_SCALING, TEXT_COLOR);
+    }
+
+    /// A bar per frame, the oldest on the left, with a line at 60 fps
+    fn add_frame_time_graph(&mut self, frame_times: &FrameTimes) {
+        let bottom = WINDOW_HEIGHT as f32;
+        self.mesh.add_rectangle((0.0, bottom - GRAPH_HEIGHT, FRAME_TIME_HISTORY as f32, GRAPH_HEIGHT), GRAPH_BACKGROUND_COLOR);
+        for (i, &frame_time) in frame_times.times().enumerate() {
+            let color = if frame_time <= Duration::from_secs(1) / 60 {
+                FAST_FRAME_COLOR
+            } else if frame_time <= Duration::from_secs(1) / 30 {
+                SLOW_FRAME_COLOR
+            } else {
+                VERY_SLOW_FRAME_COLOR
+            };
+            let height = (frame_time.as_secs_f32() * 1000.0 * GRAPH_PIXELS_PER_MILLISECOND).min(GRAPH_HEIGHT);
+            self.mesh.add_rectangle((i as f32, bottom - height, 1.0, height), color);
+        }
+        let sixty_fps = 1000.0 / 60.0 * GRAPH_PIXELS_PER_MILLISECOND;
+        self.mesh.add_rectangle((0.0, bottom - sixty_fps, FRAME_TIME_HISTORY as f32, 1.0), TEXT_COLOR);
+    }
+}
+
+impl<'a> System<'a> for RenderDebugOverlay {
+    type SystemData = (
+        Read<'a, InputCache>,
+        ReadStorage<'a, PlayerState>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
+        Read<'a, Ticks>,
+        ReadExpect<'a, Arc<ChunkManager>>,
+        Read<'a, ChunkPipelineStats>,
+        ReadExpect<'a, ChunkMeshArena>,
+        Read<'a, ParticleSystems>,
+        Read<'a, FrameTimes>,
+        ReadExpect<'a, TexturePack>,
+        Write<'a, Shaders>,
+        Read<'a, Screenshots>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            input_cache,
+            player_state,
+            player_physics_state,
+            ticks,
+            chunk_manager,
+            pipeline_stats,
+            chunk_mesh_arena,
+            particle_systems,
+            frame_times,
+            texture_pack,
+            mut shaders,
+            screenshots,
+        ) = data;
+
+        for (action, state) in input_cache.action_events() {
+            if let (InputAction::ToggleDebugOverlay, Action::Press) = (action, state) {
+                self.visible = !self.visible;
+            }
+        }
+        if !self.visible || screenshots.is_tiling() {
+            return;
+        }
+
+        self.mesh.clear();
+        let (loaded_columns, uploaded_chunks) = {
+            let loaded_chunk_columns = chunk_manager.loaded_chunk_columns.read();
+            let uploaded_chunks = loaded_chunk_columns.values()
+                .map(|column| column.chunks.iter().filter(|chunk| chunk.is_uploaded_to_gpu()).count())
+                .sum();
+            (loaded_chunk_columns.len(), uploaded_chunks)
+        };
+        for (player_state, player_physics_state) in (&player_state, &player_physics_state).join() {
+            let info = DebugInfo {
+                fps: frame_times.fps(),
+                average_frame_time: frame_times.average(),
+                max_frame_time: frame_times.max(),
+                position: player_physics_state.get_interpolated_state(ticks.alpha).position,
+                rotation: player_state.rotation,
+                targeted_block: player_state.targeted_block.and_then(|((x, y, z), _)| {
+                    chunk_manager.get_block(x, y, z).map(|block| ((x, y, z), block))
+                }),
+                loaded_columns,
+                uploaded_chunks,
+                drawn_chunks: chunk_mesh_arena.drawn_chunks(),
+                culled_chunks: chunk_mesh_arena.culled_chunks(),
+                pipeline: pipeline_stats.clone(),
+                particles: particle_systems.values().map(ParticleSystem::active_count).sum(),
+            };
+
+            let font = texture_pack.font();
+            for (i, line) in info.world_lines().iter().enumerate() {
+                self.add_line(font, line, i, false);
+            }
+            for (i, line) in info.performance_lines().iter().enumerate() {
+                self.add_line(font, line, i, true);
+            }
+        }
+        self.add_frame_time_graph(&frame_times);
+
+        gl_call!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
+        gl_call!(gl::Disable(gl::DEPTH_TEST));
+        let text_shader = shaders.get_mut("text_shader").unwrap();
+        self.text_renderer.draw(&self.mesh, text_shader, WINDOW_WIDTH as f32, WINDOW_HEIGHT as f32);
+        gl_call!(gl::Enable(gl::DEPTH_TEST));
+    }
+}
@@ -1,8 +1,11 @@
 use std::time::Instant;
-use specs::System;
+use specs::{System, Write};
+
+use crate::debug_info::FrameTimes;
 
 const MAXIMUM_OPTIMAL_FRAMETIME: f32 = 1.0 / 60.0;
 
+/// Measures the frames into `FrameTimes`, and warns about the slow ones
 pub struct FpsCounter {
     nb_frames: u64,
     last_frame: Instant,
@@ -10,14 +13,18 @@ pub struct FpsCounter {
 }
 
 impl<'a> System<'a> for FpsCounter {
-    type SystemData = ();
+    type SystemData = (
+        Write<'a, FrameTimes>,
+    );
 
-    fn run(&mut self, _data: Self::SystemData) {
+    fn run(&mut self, (mut frame_times, ): Self::SystemData) {
         let current_time = Instant::now();
         self.nb_frames += 1;
 
         {
-            let frame_time = current_time.duration_since(self.last_frame).as_secs_f32();
+            let frame_time = current_time.duration_since(self.last_frame);
+            frame_times.push(frame_time);
+            let frame_time = frame_time.as_secs_f32();
             if frame_time > MAXIMUM_OPTIMAL_FRAMETIME {
                 warn!("Suboptimal frame time: {:.2} ms", frame_time * 1000.0);
             }
@@ -26,6 +33,7 @@ impl<'a> System<'a> for FpsCounter {
 
         if current_time.duration_since(self.last_second).as_secs_f32() >= 1.0 {
             info!("{} fps", self.nb_frames);
+            frame_times.set_fps(self.nb_frames);
             self.nb_frames = 0;
             self.last_second = current_time;
         }
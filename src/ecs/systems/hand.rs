@@ -1,17 +1,20 @@
-use std::time::Instant;
+use std::sync::Arc;
 
 use nalgebra::{Matrix4, Vector3};
 use nalgebra_glm::vec3;
-use specs::{Join, Read, ReadStorage, System, Write, WriteStorage};
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write, WriteStorage};
 
+use crate::block_model::BlockModels;
 use crate::constants::{FAR_PLANE, NEAR_PLANE, WINDOW_HEIGHT, WINDOW_WIDTH};
 use crate::ecs::components::MainHandItemChanged;
 use crate::inventory::Inventory;
 use crate::main_hand::MainHand;
 use crate::physics::Interpolator;
-use crate::player::{PlayerPhysicsState, PlayerState};
-use crate::timer::Timer;
-use crate::types::{Shaders, TexturePack};
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
+use crate::screenshot::Screenshots;
+use crate::ticks::Ticks;
+use crate::types::Shaders;
 use crate::util::Forward;
 
 pub struct UpdateMainHand;
@@ -47,7 +50,7 @@ impl RenderMainHand {
     pub fn new() -> Self {
         Self {
             y_velocity: 0.0,
-            y_offset: Interpolator::new(1.0 / 30.0, 0.0),
+            y_offset: Interpolator::new(0.0),
         }
     }
 }
@@ -56,10 +59,11 @@ impl<'a> System<'a> for RenderMainHand {
     type SystemData = (
         WriteStorage<'a, MainHand>,
         ReadStorage<'a, PlayerState>,
-        ReadStorage<'a, Interpolator<PlayerPhysicsState>>,
-        Read<'a, TexturePack>,
-        Read<'a, Timer>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
+        Read<'a, Ticks>,
         Write<'a, Shaders>,
+        Read<'a, Screenshots>,
+        ReadExpect<'a, Arc<BlockModels>>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -67,9 +71,10 @@ impl<'a> System<'a> for RenderMainHand {
             mut main_hand,
             player_state,
             player_physics_state,
-            texture_pack,
-            global_timer,
+            ticks,
             mut shaders,
+            screenshots,
+            block_models,
         ) = data;
 
         for (player_state, player_physics_state, main_hand) in (&player_state, &player_physics_state, &mut main_hand).join() {
@@ -80,35 +85,37 @@ impl<'a> System<'a> for RenderMainHand {
                 }
             }
 
-            self.y_offset.interpolate_hand(global_timer.time(), self.y_velocity);
-            let y_offset_latest = self.y_offset.get_latest_state_mut();
+            for _ in ticks.pending_ticks() {
+                self.y_offset.interpolate_hand(self.y_velocity);
+                let y_offset_latest = self.y_offset.get_latest_state_mut();
 
-            if *y_offset_latest < -1.2 {
-                *y_offset_latest = -1.2;
-                self.y_velocity *= -1.0;
-                main_hand.set_showing_item(main_hand.switching_to);
-            }
+                if *y_offset_latest < -1.2 {
+                    *y_offset_latest = -1.2;
+                    self.y_velocity *= -1.0;
+                    main_hand.set_showing_item(main_hand.switching_to);
+                }
 
-            if *y_offset_latest > 0.0 {
-                *y_offset_latest = 0.0;
-                self.y_velocity = 0.0;
+                if *y_offset_latest > 0.0 {
+                    *y_offset_latest = 0.0;
+                    self.y_velocity = 0.0;
+                }
             }
 
-            if main_hand.showing_item.is_none() {
+            if main_hand.showing_item.is_none() || screenshots.is_tiling() {
                 return;
             }
 
             let view_matrix = {
-                let player_physics_state = player_physics_state.get_interpolated_state();
-                let camera_position = player_physics_state.position + vec3(0., *player_state.camera_height.get_interpolated_state(), 0.);
+                let player_physics_state = player_physics_state.get_interpolated_state(ticks.alpha);
+                let camera_position = player_physics_state.position + vec3(0., player_state.camera_height.get_interpolated_state(ticks.alpha), 0.);
                 let looking_dir = player_state.rotation.forward();
                 nalgebra_glm::look_at(&camera_position, &(camera_position + looking_dir), &Vector3::y())
             };
 
-            main_hand.update_if_dirty(&texture_pack);
+            main_hand.update_if_dirty(&block_models);
 
-            let player_pos = player_physics_state.get_interpolated_state().position;
-            let camera_height = *player_state.camera_height.get_interpolated_state();
+            let player_pos = player_physics_state.get_interpolated_state(ticks.alpha).position;
+            let camera_height = player_state.camera_height.get_interpolated_state(ticks.alpha);
             let camera_pos = player_pos + vec3(0., camera_height, 0.);
 
             let forward = &player_state.rotation.forward().normalize();
@@ -117,7 +124,7 @@ impl<'a> System<'a> for RenderMainHand {
 
             let model_matrix = {
                 let translate_matrix = Matrix4::new_translation(&(vec3(
-                    camera_pos.x, camera_pos.y, camera_pos.z) + up * -1.2 + up * *self.y_offset.get_interpolated_state()));
+                    camera_pos.x, camera_pos.y, camera_pos.z) + up * -1.2 + up * self.y_offset.get_interpolated_state(ticks.alpha)));
 
                 let translate_matrix2 = Matrix4::new_translation(&(vec3(2.0, 0.0, 0.0)));
 
@@ -144,15 +151,15 @@ impl<'a> System<'a> for RenderMainHand {
             gl_call!(gl::BindVertexArray(main_hand.render.vao));
 
             gl_call!(gl::Disable(gl::DEPTH_TEST));
-            gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, 36 as i32));
+            gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, main_hand.render.vertex_count));
             gl_call!(gl::Enable(gl::DEPTH_TEST));
         }
     }
 }
 
 impl Interpolator<f32> {
-    pub fn interpolate_hand(&mut self, time: Instant, add: f32) {
-        self.step(time, &mut |offset, _t, dt| {
+    pub fn interpolate_hand(&mut self, add: f32) {
+        self.step(&mut |offset, dt| {
             offset + add * dt
         });
     }
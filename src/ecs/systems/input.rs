@@ -1,28 +1,48 @@
 use std::process::exit;
+use std::sync::Arc;
 use std::sync::mpsc::Receiver;
 
-use glfw::{Action, Context, Glfw, Key, Window, WindowEvent};
-use specs::{System, Write};
+use glfw::{Context, Glfw, Window, WindowEvent};
+use specs::{ReadExpect, ReadStorage, System, Write};
 
-use crate::input::InputCache;
+use crate::chunk_manager::ChunkManager;
+use crate::ecs::systems::scripted_input::is_loaded_around_players;
+use crate::input::{Action, InputCache, InputEvent};
+use crate::keybindings::InputAction;
+use crate::physics::Interpolator;
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
+use crate::replay::ReplayPlayer;
+use crate::ticks::Ticks;
 use crate::timer::Timer;
 
 pub struct ReadWindowEvents {
     pub glfw: Glfw,
     pub window: Window,
     pub events: Receiver<(f64, WindowEvent)>,
+    /// Reads the events from a replay instead of the window, which is closed at the end of the replay.
+    /// The replay schedules the ticks in place of `AdvanceTicks`, the global `Timer` must be a `Timer::manual`
+    pub replay: Option<ReplayPlayer>,
 }
 
 impl<'a> System<'a> for ReadWindowEvents {
     type SystemData = (
         Write<'a, InputCache>,
         Write<'a, Timer>,
+        Write<'a, Ticks>,
+        ReadExpect<'a, Arc<ChunkManager>>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
+        ReadStorage<'a, PlayerState>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
             mut input_cache,
             mut global_timer,
+            mut ticks,
+            chunk_manager,
+            bodies,
+            players,
         ) = data;
 
         if self.window.should_close() {
@@ -31,17 +51,33 @@ impl<'a> System<'a> for ReadWindowEvents {
 
         self.window.swap_buffers();
 
-        input_cache.events.clear();
+        input_cache.clear_events();
         self.glfw.poll_events();
-        for (_, event) in glfw::flush_messages(&self.events) {
-            input_cache.handle_event(&event);
+        match &mut self.replay {
+            Some(replay) => {
+                // Only the close button of the window works during a replay
+                for _ in glfw::flush_messages(&self.events) {}
+                if !is_loaded_around_players(&chunk_manager, &bodies, &players) {
+                    replay.hold_frame(&mut ticks);
+                } else if !replay.play_frame(&mut input_cache, &mut global_timer, &mut ticks) {
+                    info!("End of the replay");
+                    self.window.set_should_close(true);
+                }
+            }
+            None => for (_, event) in glfw::flush_messages(&self.events) {
+                if let Some(event) = InputEvent::from_window_event(&event) {
+                    input_cache.handle_event(&event);
+                }
+            },
+        }
 
-            match event {
-                WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+        for (action, state) in input_cache.action_events() {
+            match (action, state) {
+                (InputAction::Quit, Action::Press) => {
                     self.window.set_should_close(true);
                 }
 
-                WindowEvent::Key(Key::P, _, Action::Press, _) => {
+                (InputAction::Pause, Action::Press) => {
                     if global_timer.is_paused() {
                         global_timer.resume()
                     } else {
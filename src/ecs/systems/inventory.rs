@@ -1,11 +1,13 @@
-use glfw::{MouseButton, WindowEvent};
-use specs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, Write, WriteStorage};
 
 use crate::chunk_manager::ChunkManager;
 use crate::ecs::components::MainHandItemChanged;
-use crate::input::InputCache;
+use crate::ecs::systems::network::NetworkOutbox;
+use crate::input::{Action, InputCache, InputEvent};
 use crate::inventory::Inventory;
+use crate::keybindings::InputAction;
 use crate::inventory::item::ItemStack;
+use crate::network::protocol::ClientMessage;
 use crate::player::PlayerState;
 use std::sync::Arc;
 
@@ -24,7 +26,8 @@ impl<'a> System<'a> for InventoryHandleInput {
     type SystemData = (
         Entities<'a>,
         Read<'a, InputCache>,
-        Read<'a, Arc<ChunkManager>>,
+        ReadExpect<'a, Arc<ChunkManager>>,
+        Write<'a, NetworkOutbox>,
         ReadStorage<'a, PlayerState>,
         WriteStorage<'a, Inventory>,
         WriteStorage<'a, MainHandItemChanged>,
@@ -35,12 +38,14 @@ impl<'a> System<'a> for InventoryHandleInput {
             entities,
             input_cache,
             chunk_manager,
+            mut network_outbox,
             player_state,
             mut inventory,
             mut main_hand_item_changed,
         ) = data;
 
         for (e, inventory, player_state) in (&entities, &mut inventory, &player_state).join() {
+            let selected_hotbar_slot = inventory.selected_hotbar_slot;
             let mut f = || {
                 if let Err(e) = main_hand_item_changed.insert(e, MainHandItemChanged) {
                     error!("{}", e);
@@ -48,36 +53,37 @@ impl<'a> System<'a> for InventoryHandleInput {
             };
 
             for event in &input_cache.events {
-                use glfw::{Key, Action};
-                match event {
-                    WindowEvent::Scroll(_, y) => {
-                        if y.is_sign_positive() {
-                            inventory.select_previous_item();
-                        } else {
-                            inventory.select_next_item();
-                        }
-                        f();
+                if let InputEvent::Scroll(_, y) = event {
+                    if y.is_sign_positive() {
+                        inventory.select_previous_item();
+                    } else {
+                        inventory.select_next_item();
                     }
-                    WindowEvent::MouseButton(MouseButton::Button3, Action::Press, _) => {
+                    f();
+                }
+            }
+
+            for (action, state) in input_cache.action_events() {
+                match (action, state) {
+                    (InputAction::PickBlock, Action::Press) => {
                         if let Some(((x, y, z), _)) = player_state.targeted_block {
                             if let Some(block) = chunk_manager.get_block(x, y, z) {
                                 inventory.slots[inventory.selected_hotbar_slot] = Some(ItemStack::new(1, block));
+                                network_outbox.messages.push(ClientMessage::PickBlock { x, y, z });
                                 f();
                             }
                         }
                     }
-                    WindowEvent::Key(Key::Num1, _, Action::Press, _) => Self::select_item(inventory, 0, &mut f),
-                    WindowEvent::Key(Key::Num2, _, Action::Press, _) => Self::select_item(inventory, 1, &mut f),
-                    WindowEvent::Key(Key::Num3, _, Action::Press, _) => Self::select_item(inventory, 2, &mut f),
-                    WindowEvent::Key(Key::Num4, _, Action::Press, _) => Self::select_item(inventory, 3, &mut f),
-                    WindowEvent::Key(Key::Num5, _, Action::Press, _) => Self::select_item(inventory, 4, &mut f),
-                    WindowEvent::Key(Key::Num6, _, Action::Press, _) => Self::select_item(inventory, 5, &mut f),
-                    WindowEvent::Key(Key::Num7, _, Action::Press, _) => Self::select_item(inventory, 6, &mut f),
-                    WindowEvent::Key(Key::Num8, _, Action::Press, _) => Self::select_item(inventory, 7, &mut f),
-                    WindowEvent::Key(Key::Num9, _, Action::Press, _) => Self::select_item(inventory, 8, &mut f),
+                    (InputAction::HotbarSlot(slot), Action::Press) => Self::select_item(inventory, slot as usize, &mut f),
                     _ => {}
                 }
             }
+
+            if inventory.selected_hotbar_slot != selected_hotbar_slot {
+                network_outbox.messages.push(ClientMessage::SelectHotbarSlot {
+                    slot: inventory.selected_hotbar_slot as u8,
+                });
+            }
         }
     }
 }
@@ -1,23 +1,39 @@
-use specs::{System, Write};
+use specs::{Read, System, Write};
 
+pub use chunk_loading::*;
+#[cfg(feature = "rendering")]
+pub use debug_overlay::*;
 pub use fps_counter::*;
+#[cfg(feature = "rendering")]
 pub use hand::*;
+#[cfg(feature = "rendering")]
 pub use input::*;
 pub use inventory::*;
+pub use network::*;
 pub use physics::*;
 pub use player::*;
+pub use scripted_input::*;
+#[cfg(feature = "rendering")]
 pub use rendering::*;
 
+use crate::ticks::Ticks;
 use crate::timer::Timer;
 
+#[cfg(feature = "rendering")]
 pub mod input;
 pub mod physics;
 pub mod player;
 pub mod fps_counter;
+#[cfg(feature = "rendering")]
 pub mod hand;
 pub mod inventory;
+#[cfg(feature = "rendering")]
 pub mod rendering;
+#[cfg(feature = "rendering")]
+pub mod debug_overlay;
 pub mod chunk_loading;
+pub mod network;
+pub mod scripted_input;
 
 pub struct AdvanceGlobalTime;
 
@@ -31,3 +47,16 @@ impl<'a> System<'a> for AdvanceGlobalTime {
     }
 }
 
+/// Schedules the ticks of the frame, runs after the input is read and before any simulation
+pub struct AdvanceTicks;
+
+impl<'a> System<'a> for AdvanceTicks {
+    type SystemData = (
+        Read<'a, Timer>,
+        Write<'a, Ticks>,
+    );
+
+    fn run(&mut self, (global_timer, mut ticks): Self::SystemData) {
+        ticks.advance(global_timer.time());
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use nalgebra_glm::Vec3;
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, System, Write, WriteExpect, WriteStorage};
+
+use crate::biome::Climate;
+use crate::block_state::BlockState;
+use crate::chunk::BlockID;
+use crate::chunk_manager::ChunkManager;
+use crate::ecs::components::MainHandItemChanged;
+use crate::inventory::Inventory;
+use crate::inventory::item::ItemStack;
+use crate::network::client::ServerConnection;
+use crate::network::protocol::{ClientMessage, ServerMessage};
+use crate::physics::Interpolator;
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
+use crate::ticks::Ticks;
+
+/// Messages queued by the gameplay systems, sent to the server at the end of the frame
+#[derive(Default)]
+pub struct NetworkOutbox {
+    pub messages: Vec<ClientMessage>,
+}
+
+/// Chunk columns received from the server, consumed by `ChunkLoading`
+#[derive(Default)]
+pub struct IncomingColumns {
+    pub columns: Vec<((i32, i32), Vec<BlockID>, Vec<BlockState>, Vec<Climate>)>,
+}
+
+/// Set by the network systems when the connection to the server is lost, the game quits at the end of the frame
+#[derive(Default)]
+pub struct ConnectionLost {
+    pub error: Option<String>,
+}
+
+/// Longest time in ticks over which a remote player moves to its new position, so that it catches up
+/// at once after standing still
+const MAX_INTERPOLATION_TICKS: f64 = 6.0;
+
+/// Another player, drawn moving from where it was when its last position arrived to that position,
+/// over the time between its last two positions
+pub struct RemotePlayer {
+    previous: (Vec3, Vec3),
+    current: (Vec3, Vec3),
+    received_at: f64,
+    interval: f64,
+}
+
+impl RemotePlayer {
+    pub fn new(position: Vec3, rotation: Vec3, time: f64) -> Self {
+        Self {
+            previous: (position, rotation),
+            current: (position, rotation),
+            received_at: time,
+            interval: 1.0,
+        }
+    }
+
+    /// Moves the player towards the position and rotation received at `time`, in ticks (`Ticks::time`)
+    pub fn update(&mut self, position: Vec3, rotation: Vec3, time: f64) {
+        self.previous = self.interpolated(time);
+        self.current = (position, rotation);
+        self.interval = (time - self.received_at).clamp(1.0, MAX_INTERPOLATION_TICKS);
+        self.received_at = time;
+    }
+
+    /// The position and rotation to draw the player with at `time`
+    pub fn interpolated(&self, time: f64) -> (Vec3, Vec3) {
+        let alpha = ((time - self.received_at) / self.interval).clamp(0.0, 1.0) as f32;
+        (self.previous.0.lerp(&self.current.0, alpha), self.previous.1.lerp(&self.current.1, alpha))
+    }
+}
+
+/// The other players on the server
+#[derive(Default)]
+pub struct RemotePlayers {
+    pub players: HashMap<u32, RemotePlayer>,
+}
+
+pub struct ReceiveServerMessages;
+
+impl<'a> System<'a> for ReceiveServerMessages {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, ServerConnection>,
+        ReadExpect<'a, Arc<ChunkManager>>,
+        Read<'a, Ticks>,
+        Write<'a, IncomingColumns>,
+        Write<'a, RemotePlayers>,
+        Write<'a, ConnectionLost>,
+        WriteStorage<'a, Interpolator<PhysicsBody>>,
+        WriteStorage<'a, PlayerState>,
+        WriteStorage<'a, Inventory>,
+        WriteStorage<'a, MainHandItemChanged>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut server_connection,
+            chunk_manager,
+            ticks,
+            mut incoming_columns,
+            mut remote_players,
+            mut connection_lost,
+            mut player_physics_state,
+            mut player_state,
+            mut inventory,
+            mut main_hand_item_changed,
+        ) = data;
+
+        if connection_lost.error.is_some() {
+            return;
+        }
+        let messages = match server_connection.receive() {
+            Ok(messages) => messages,
+            Err(err) => {
+                connection_lost.error = Some(err.to_string());
+                return;
+            }
+        };
+
+        for message in messages {
+            match message {
+                ServerMessage::ChunkColumn { x, z, blocks, states, climate } => {
+                    incoming_columns.columns.push(((x, z), blocks, states, climate));
+                }
+
+                ServerMessage::BlockChange { x, y, z, block, state } => {
+                    if chunk_manager.get_block_with_state(x, y, z) != Some((block, state)) {
+                        chunk_manager.put_block_with_state(block, state, x, y, z);
+                    }
+                }
+
+                ServerMessage::PlayerCorrection { correction, position, velocity, .. } => {
+                    server_connection.last_correction = correction;
+                    for player_physics_state in (&mut player_physics_state).join() {
+                        *player_physics_state.get_latest_state_mut() = PhysicsBody {
+                            velocity,
+                            ..PhysicsBody::new_player(position)
+                        };
+                    }
+                }
+
+                ServerMessage::FlyingAllowed { can_fly } => {
+                    for player_state in (&mut player_state).join() {
+                        player_state.can_fly = can_fly;
+                        player_state.is_flying &= can_fly;
+                    }
+                }
+
+                ServerMessage::PlayerPosition { player_id, position, rotation } => {
+                    if player_id != server_connection.player_id {
+                        let time = ticks.time();
+                        remote_players.players.entry(player_id)
+                            .and_modify(|player| player.update(position, rotation, time))
+                            .or_insert_with(|| RemotePlayer::new(position, rotation, time));
+                    }
+                }
+
+                ServerMessage::PlayerLeft { player_id } => {
+                    remote_players.players.remove(&player_id);
+                }
+
+                ServerMessage::Inventory { selected_hotbar_slot, slots } => {
+                    for (e, inventory) in (&entities, &mut inventory).join() {
+                        inventory.select_item(selected_hotbar_slot as usize);
+                        for (slot, &item) in inventory.slots.iter_mut().zip(slots.iter()) {
+                            if slot.map(|item_stack| item_stack.item) != item {
+                                *slot = item.map(|item| ItemStack::new(1, item));
+                            }
+                        }
+                        if let Err(e) = main_hand_item_changed.insert(e, MainHandItemChanged) {
+                            error!("{}", e);
+                        }
+                    }
+                }
+
+                ServerMessage::LoginAccepted { .. } | ServerMessage::LoginRejected { .. } => {
+                    warn!("Unexpected login message from the server");
+                }
+            }
+        }
+    }
+}
+
+pub struct SendClientMessages;
+
+impl<'a> System<'a> for SendClientMessages {
+    type SystemData = (
+        WriteExpect<'a, ServerConnection>,
+        Write<'a, NetworkOutbox>,
+        Write<'a, ConnectionLost>,
+        Read<'a, Ticks>,
+        ReadStorage<'a, PlayerState>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut server_connection,
+            mut network_outbox,
+            mut connection_lost,
+            ticks,
+            player_state,
+            player_physics_state,
+        ) = data;
+
+        if connection_lost.error.is_some() {
+            return;
+        }
+
+        // Predicted movement of the player, on every frame that simulated ticks for the server to simulate them too
+        for (player_state, player_physics_state) in (&player_state, &player_physics_state).join() {
+            if ticks.pending == 0 {
+                continue;
+            }
+            server_connection.movement_sequence += 1;
+            let message = ClientMessage::PlayerMovement {
+                sequence: server_connection.movement_sequence,
+                tick: ticks.tick,
+                ticks: ticks.pending.min(u16::MAX as u32) as u16,
+                last_correction: server_connection.last_correction,
+                input: player_state.movement_input,
+                position: player_physics_state.get_latest_state().position,
+                rotation: player_state.rotation,
+            };
+            server_connection.send(&message);
+        }
+
+        for message in network_outbox.messages.drain(..) {
+            server_connection.send(&message);
+        }
+
+        if let Err(err) = server_connection.flush() {
+            connection_lost.error = Some(err.to_string());
+        }
+    }
+}
@@ -1,91 +1,82 @@
-use std::time::Instant;
-
-use glfw::{Action, MouseButton};
 use nalgebra::Vector3;
-use nalgebra_glm::{IVec3, vec3};
-use specs::{Join, Read, ReadStorage, System, Write, WriteStorage};
+use nalgebra_glm::{IVec3, Vec3, vec3};
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write, WriteStorage};
 
-use crate::aabb::{AABB, get_block_aabb};
+use crate::aabb::AABB;
+use crate::block_state::BlockState;
 use crate::chunk::BlockID;
 use crate::chunk_manager::ChunkManager;
-use crate::constants::{FAR_PLANE, FLYING_TRIGGER_INTERVAL, FOV, JUMP_IMPULSE, NEAR_PLANE, PLAYER_EYES_HEIGHT, REACH_DISTANCE, SPRINTING_TRIGGER_INTERVAL, WINDOW_HEIGHT, WINDOW_WIDTH};
-use crate::input::InputCache;
+use crate::constants::{BLOCK_PLACING_COOLDOWN_TICKS, FAR_PLANE, FLYING_TRIGGER_TICKS, FOV, NEAR_PLANE, PLAYER_EYES_HEIGHT, REACH_DISTANCE, SPRINTING_TRIGGER_TICKS, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::ecs::systems::network::NetworkOutbox;
+use crate::input::{Action, InputCache, InputEvent};
 use crate::inventory::Inventory;
+use crate::keybindings::InputAction;
+use crate::network::protocol::ClientMessage;
 use crate::particle_system::ParticleSystem;
 use crate::physics::Interpolator;
-use crate::player::{PlayerPhysicsState, PlayerState};
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
 use crate::raycast;
-use crate::timer::Timer;
-use crate::types::{ParticleSystems, TexturePack};
+use crate::ticks::Ticks;
+use crate::types::ParticleSystems;
 use crate::util::Forward;
 use std::sync::Arc;
 
+/// Toggles the movement modes of the player on the input events of the frame, the movement itself
+/// (walking, jumping, flying) is simulated on every tick by `UpdatePlayerPhysics`
 pub struct HandlePlayerInput;
 
 impl<'a> System<'a> for HandlePlayerInput {
     type SystemData = (
+        Read<'a, Ticks>,
         Read<'a, InputCache>,
         WriteStorage<'a, PlayerState>,
-        WriteStorage<'a, Interpolator<PlayerPhysicsState>>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
+            ticks,
             input_cache,
             mut player_state,
-            mut player_physics_state,
+            player_physics_state,
         ) = data;
 
-        for (player_state, player_physics_state) in (&mut player_state, &mut player_physics_state).join() {
+        for (player_state, player_physics_state) in (&mut player_state, &player_physics_state).join() {
             let mut player_state = player_state as &mut PlayerState;
-            let player_physics_state = player_physics_state as &mut Interpolator<PlayerPhysicsState>;
-            let player_physics_state = player_physics_state.get_latest_state_mut();
+            let player_physics_state = player_physics_state.get_latest_state();
 
-            for event in &input_cache.events {
-                match &event {
-                    glfw::WindowEvent::CursorPos(_, _) => {
-                        player_state.rotate_camera(
-                            input_cache.cursor_rel_pos.x as f32,
-                            input_cache.cursor_rel_pos.y as f32);
-                    }
+            // Once per frame, the cursor can move several times during one
+            if input_cache.events.iter().any(|event| matches!(event, InputEvent::CursorPos(_, _))) {
+                player_state.rotate_camera(
+                    input_cache.cursor_rel_pos.x as f32,
+                    input_cache.cursor_rel_pos.y as f32);
+            }
 
-                    glfw::WindowEvent::Key(glfw::Key::Space, _, glfw::Action::Press, _) => {
-                        // Player state
-                        if player_state.fly_throttle {
-                            player_state.fly_throttle = false;
-                        } else if Instant::now().duration_since(player_state.fly_last_toggled) < *FLYING_TRIGGER_INTERVAL {
+            for (action, state) in input_cache.action_events() {
+                match (action, state) {
+                    (InputAction::Jump, Action::Press) => {
+                        if player_state.fly_double_tap.press(ticks.tick, FLYING_TRIGGER_TICKS) && player_state.can_fly {
                             player_state.is_flying = !player_state.is_flying;
                             info!("Flying: {}", player_state.is_flying);
-                            player_state.fly_throttle = true;
-                        }
-                        player_state.fly_last_toggled = Instant::now();
-
-                        // Player physics state
-                        if player_state.is_on_ground {
-                            player_physics_state.velocity.y = *JUMP_IMPULSE;
-                            player_state.jump_last_executed = Instant::now();
                         }
                     }
 
                     // Cancel sneaking
-                    glfw::WindowEvent::Key(glfw::Key::LeftShift, _, glfw::Action::Release, _) => {
+                    (InputAction::Sneak, Action::Release) => {
                         player_state.is_sneaking = false;
                     }
 
                     // Cancel sprinting
-                    glfw::WindowEvent::Key(glfw::Key::W, _, glfw::Action::Release, _) => {
+                    (InputAction::MoveForward, Action::Release) => {
                         player_state.is_sprinting = false;
                     }
 
                     // Sprint on double press
-                    glfw::WindowEvent::Key(glfw::Key::W, _, glfw::Action::Press, _) => {
-                        if player_state.sprint_throttle {
-                            player_state.sprint_throttle = false;
-                        } else if Instant::now().duration_since(player_state.sprint_last_toggled) < *SPRINTING_TRIGGER_INTERVAL {
+                    (InputAction::MoveForward, Action::Press) => {
+                        if player_state.sprint_double_tap.press(ticks.tick, SPRINTING_TRIGGER_TICKS) {
                             player_state.is_sprinting = true;
-                            player_state.sprint_throttle = true;
                         }
-                        player_state.sprint_last_toggled = Instant::now();
                     }
 
                     _ => {}
@@ -93,14 +84,14 @@ impl<'a> System<'a> for HandlePlayerInput {
             }
 
             // Sneaking
-            if input_cache.is_key_pressed(glfw::Key::LeftShift) && player_state.is_on_ground {
+            if input_cache.is_action_active(InputAction::Sneak) && player_physics_state.is_on_ground {
                 player_state.is_sneaking = true;
                 player_state.is_sprinting = false;
             }
 
             // Sprinting
-            if input_cache.is_key_pressed(glfw::Key::LeftControl)
-                && input_cache.is_key_pressed(glfw::Key::W)
+            if input_cache.is_action_active(InputAction::Sprint)
+                && input_cache.is_action_active(InputAction::MoveForward)
                 && !player_state.is_sneaking {
                 player_state.is_sprinting = true;
             }
@@ -112,15 +103,15 @@ pub struct UpdatePlayerState;
 
 impl<'a> System<'a> for UpdatePlayerState {
     type SystemData = (
-        Read<'a, Timer>,
-        Read<'a, Arc<ChunkManager>>,
+        Read<'a, Ticks>,
+        ReadExpect<'a, Arc<ChunkManager>>,
         WriteStorage<'a, PlayerState>,
-        ReadStorage<'a, Interpolator<PlayerPhysicsState>>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
-            global_timer,
+            ticks,
             chunk_manager,
             mut player_state,
             player_physics_state,
@@ -128,8 +119,7 @@ impl<'a> System<'a> for UpdatePlayerState {
 
         for (player_state, player_physics_state) in (&mut player_state, &player_physics_state).join() {
             let mut player_state = player_state as &mut PlayerState;
-            let player_physics_state = player_physics_state as &Interpolator<PlayerPhysicsState>;
-            let t = global_timer.time();
+            let player_physics_state = player_physics_state as &Interpolator<PhysicsBody>;
 
             // Camera height
             let target_camera_height = if player_state.is_sneaking {
@@ -137,7 +127,9 @@ impl<'a> System<'a> for UpdatePlayerState {
             } else {
                 PLAYER_EYES_HEIGHT
             };
-            player_state.camera_height.interpolate_camera_height(t, target_camera_height);
+            for _ in ticks.pending_ticks() {
+                player_state.camera_height.interpolate_camera_height(target_camera_height);
+            }
 
             // FOV
             let target_fov = if player_state.is_flying {
@@ -153,33 +145,36 @@ impl<'a> System<'a> for UpdatePlayerState {
                     *FOV
                 }
             };
-            player_state.fov.interpolate_fov(t, target_fov);
+            for _ in ticks.pending_ticks() {
+                player_state.fov.interpolate_fov(target_fov);
+            }
 
             // Targeted block
             player_state.targeted_block = {
-                let is_solid_block_at = |x: i32, y: i32, z: i32| {
-                    chunk_manager.is_solid_block_at(x, y, z)
+                let selection_boxes_at = |x: i32, y: i32, z: i32| {
+                    chunk_manager.get_block_with_connections(x, y, z)
+                        .map_or(&[][..], |(block, state)| chunk_manager.block_models().shape(block, state).selection_boxes.as_slice())
                 };
 
                 let fw = player_state.rotation.forward();
-                let player = player_physics_state.get_interpolated_state();
+                let player = player_physics_state.get_interpolated_state(ticks.alpha);
                 raycast::raycast(
-                    &is_solid_block_at,
-                    &(player.position + vec3(0., *player_state.camera_height.get_interpolated_state(), 0.)),
+                    &selection_boxes_at,
+                    &(player.position + vec3(0., player_state.camera_height.get_interpolated_state(ticks.alpha), 0.)),
                     &fw.normalize(),
                     REACH_DISTANCE)
             };
 
             // View and projection matrix
             player_state.view_matrix = {
-                let player_physics_state = player_physics_state.get_interpolated_state();
-                let camera_position = player_physics_state.position + vec3(0., *player_state.camera_height.get_interpolated_state(), 0.);
+                let player_physics_state = player_physics_state.get_interpolated_state(ticks.alpha);
+                let camera_position = player_physics_state.position + vec3(0., player_state.camera_height.get_interpolated_state(ticks.alpha), 0.);
                 let looking_dir = player_state.rotation.forward();
                 nalgebra_glm::look_at(&camera_position, &(camera_position + looking_dir), &Vector3::y())
             };
 
             player_state.projection_matrix = {
-                let fov = *player_state.fov.get_interpolated_state();
+                let fov = player_state.fov.get_interpolated_state(ticks.alpha);
                 nalgebra_glm::perspective(WINDOW_WIDTH as f32 / WINDOW_HEIGHT as f32, fov, NEAR_PLANE, FAR_PLANE)
             };
         }
@@ -190,21 +185,23 @@ pub struct PlaceAndBreakBlocks;
 
 impl<'a> System<'a> for PlaceAndBreakBlocks {
     type SystemData = (
-        Write<'a, Arc<ChunkManager>>,
+        Read<'a, Ticks>,
+        ReadExpect<'a, Arc<ChunkManager>>,
         Write<'a, ParticleSystems>,
+        Write<'a, NetworkOutbox>,
         Read<'a, InputCache>,
-        Read<'a, TexturePack>,
         WriteStorage<'a, PlayerState>,
-        ReadStorage<'a, Interpolator<PlayerPhysicsState>>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
         ReadStorage<'a, Inventory>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
-            mut chunk_manager,
+            ticks,
+            chunk_manager,
             mut particle_systems,
+            mut network_outbox,
             input_cache,
-            texture_pack,
             mut player_state,
             player_physics_state,
             inventory,
@@ -212,26 +209,23 @@ impl<'a> System<'a> for PlaceAndBreakBlocks {
 
         for (player_state, player_physics_state, inventory) in (&mut player_state, &player_physics_state, &inventory).join() {
             let player_physics_state = player_physics_state.get_latest_state();
-
-            // Place or break a block by clicking on a mouse button
-            for event in &input_cache.events {
-                match event {
-                    glfw::WindowEvent::MouseButton(button, Action::Press, _) => {
-                        player_state.block_placing_last_executed = Instant::now();
-
-                        match button {
-                            MouseButton::Button1 => {
-                                if let &Some(((x, y, z), _)) = &player_state.targeted_block {
-                                    let mut particle_system = particle_systems.get_mut("block_particles").unwrap();
-                                    break_block((x, y, z), &chunk_manager, &mut particle_system, &texture_pack);
-                                }
-                            }
-                            MouseButton::Button2 => {
-                                if let &Some(((x, y, z), normal)) = &player_state.targeted_block {
-                                    place_block((x, y, z), &normal, &player_physics_state.aabb, &inventory, &chunk_manager);
-                                }
-                            }
-                            _ => {}
+            let now = ticks.tick;
+
+            // Place or break a block on a click
+            for (action, state) in input_cache.action_events() {
+                match (action, state) {
+                    (InputAction::Attack, Action::Press) => {
+                        player_state.block_placing_last_executed = now;
+                        if let &Some(((x, y, z), _)) = &player_state.targeted_block {
+                            let particle_system = particle_systems.get_mut("block_particles");
+                            break_block((x, y, z), &chunk_manager, &mut network_outbox, particle_system);
+                        }
+                    }
+                    (InputAction::Use, Action::Press) => {
+                        player_state.block_placing_last_executed = now;
+                        if let &Some(((x, y, z), normal)) = &player_state.targeted_block {
+                            let looking_direction = player_state.rotation.forward();
+                            place_block((x, y, z), &normal, &looking_direction, &player_physics_state.aabb, &inventory, &chunk_manager, &mut network_outbox);
                         }
                     }
                     _ => {}
@@ -239,44 +233,60 @@ impl<'a> System<'a> for PlaceAndBreakBlocks {
             }
 
             // Repeated block placing or breaking while the mouse button is pressed
-            let now = Instant::now();
-            if now.duration_since(player_state.block_placing_last_executed).as_secs_f32() >= 0.25 {
-                if input_cache.is_mouse_button_pressed(glfw::MouseButtonLeft) {
+            if now.saturating_sub(player_state.block_placing_last_executed) >= BLOCK_PLACING_COOLDOWN_TICKS {
+                if input_cache.is_action_active(InputAction::Attack) {
                     if let &Some(((x, y, z), _)) = &player_state.targeted_block {
-                        let mut particle_system = particle_systems.get_mut("block_particles").unwrap();
-                        break_block((x, y, z), &chunk_manager, &mut particle_system, &texture_pack);
+                        let particle_system = particle_systems.get_mut("block_particles");
+                        break_block((x, y, z), &chunk_manager, &mut network_outbox, particle_system);
                     }
-                    player_state.block_placing_last_executed = Instant::now();
-                } else if input_cache.is_mouse_button_pressed(glfw::MouseButtonRight) {
+                    player_state.block_placing_last_executed = now;
+                } else if input_cache.is_action_active(InputAction::Use) {
                     if let &Some(((x, y, z), normal)) = &player_state.targeted_block {
-                        place_block((x, y, z), &normal, &player_physics_state.aabb, &inventory, &mut chunk_manager);
+                        let looking_direction = player_state.rotation.forward();
+                        place_block((x, y, z), &normal, &looking_direction, &player_physics_state.aabb, &inventory, &chunk_manager, &mut network_outbox);
                     }
-                    player_state.block_placing_last_executed = Instant::now();
+                    player_state.block_placing_last_executed = now;
                 }
             }
         }
     }
 }
 
-fn break_block((x, y, z): (i32, i32, i32), chunk_manager: &ChunkManager, particle_system: &mut ParticleSystem, uv_map: &TexturePack) {
+/// The particles are skipped when there is no particle system, like in the headless simulation
+fn break_block((x, y, z): (i32, i32, i32), chunk_manager: &ChunkManager, network_outbox: &mut NetworkOutbox, particle_system: Option<&mut ParticleSystem>) {
     let block = chunk_manager.get_block(x, y, z).unwrap();
     if block != BlockID::Air {
         chunk_manager.put_block(BlockID::Air, x, y, z);
-        particle_system.spawn_block_breaking_particles(vec3(x as f32, y as f32, z as f32), &uv_map, block);
+        network_outbox.messages.push(ClientMessage::SetBlock { x, y, z, block: BlockID::Air, state: BlockState::default() });
+        if let Some(particle_system) = particle_system {
+            let particle = chunk_manager.block_models().default_shape(block).model.particle;
+            particle_system.spawn_block_breaking_particles(vec3(x as f32, y as f32, z as f32), particle);
+        }
         info!("Destroyed block at ({} {} {})", x, y, z);
     }
 }
 
-fn place_block((x, y, z): (i32, i32, i32), normal: &IVec3, player_aabb: &AABB, inventory: &Inventory, chunk_manager: &ChunkManager) {
+/// The block is oriented by the face it's placed against and the direction the player is looking in
+fn place_block((x, y, z): (i32, i32, i32), normal: &IVec3, looking_direction: &Vec3, player_aabb: &AABB, inventory: &Inventory,
+               chunk_manager: &ChunkManager, network_outbox: &mut NetworkOutbox) {
     let adjacent_block = IVec3::new(x, y, z) + normal;
-    let adjacent_block_aabb = get_block_aabb(&vec3(
-        adjacent_block.x as f32,
-        adjacent_block.y as f32,
-        adjacent_block.z as f32));
-    if !player_aabb.intersects(&adjacent_block_aabb) {
-        if let Some(block) = inventory.get_selected_item() {
-            chunk_manager.put_block(block, adjacent_block.x, adjacent_block.y, adjacent_block.z);
+    if let Some(block) = inventory.get_selected_item() {
+        let state = block.placement_state(normal, looking_direction);
+        let connected_state = chunk_manager.connected_state(block, state, adjacent_block.x, adjacent_block.y, adjacent_block.z);
+        // The blocks without collision boxes, like plants, can be placed at the player's feet
+        let intersects_player = chunk_manager.block_models()
+            .collision_boxes_at(block, connected_state, adjacent_block.x, adjacent_block.y, adjacent_block.z)
+            .any(|block_aabb| player_aabb.intersects(&block_aabb));
+        if !intersects_player {
+            chunk_manager.put_block_with_state(block, state, adjacent_block.x, adjacent_block.y, adjacent_block.z);
+            network_outbox.messages.push(ClientMessage::SetBlock {
+                x: adjacent_block.x,
+                y: adjacent_block.y,
+                z: adjacent_block.z,
+                block,
+                state,
+            });
+            info!("Put block at ({} {} {})", adjacent_block.x, adjacent_block.y, adjacent_block.z);
         }
-        info!("Put block at ({} {} {})", adjacent_block.x, adjacent_block.y, adjacent_block.z);
     }
-}
\ No newline at end of file
+}
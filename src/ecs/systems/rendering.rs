@@ -1,23 +1,171 @@
-use nalgebra::Matrix4;
+use nalgebra::{Matrix4, Vector3};
 use nalgebra_glm::vec3;
-use specs::{Join, Read, ReadStorage, System, Write, WriteStorage};
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write, WriteExpect};
 
+use crate::block_model::BlockModels;
 use crate::chunk_manager::ChunkManager;
-use crate::constants::{BACKGROUND_COLOR, BLOCK_OUTLINE_WIDTH, RENDER_DISTANCE, ENABLE_FOG};
-use crate::gui::{create_block_outline_vao, create_crosshair_vao, create_hotbar_selection_vao, create_hotbar_vao, draw_crosshair};
+use crate::chunk_mesh_arena::ChunkMeshArena;
+use crate::chunk_pipeline::PrioritizedItem;
+use crate::constants::{BACKGROUND_COLOR, BLOCK_OUTLINE_WIDTH, CHUNK_UPLOADS_PER_FRAME, RENDER_DISTANCE, MAX_PARTICLES,
+                       PLAYER_HEAD_SIZE, PLAYER_HEIGHT, PLAYER_WIDTH, SCREENSHOT_DIRECTORY, SCREENSHOT_TILES,
+                       SHADER_POLL_INTERVAL, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::ecs::systems::chunk_loading::ChunkUploads;
+use crate::ecs::systems::network::RemotePlayers;
+use crate::frustum::Frustum;
+use crate::gui::{create_block_outline_vao, create_crosshair_vao, create_cube_vao, create_hotbar_selection_vao, create_hotbar_vao,
+                 draw_crosshair};
+use crate::input::{Action, InputCache};
 use crate::inventory::Inventory;
+use crate::inventory::render::HotbarRender;
+use crate::keybindings::InputAction;
+use crate::particle_renderer::ParticleRenderer;
 use crate::player::PlayerState;
+use crate::resource_pack::ResourcePacks;
+use crate::screenshot::{read_framebuffer, save_screenshot, Screenshots};
+use crate::shader_compilation::ShaderWatcher;
+use crate::shader_preprocessor::ShaderPreprocessor;
+use crate::texture_pack::TexturePack;
+use crate::ticks::Ticks;
 use crate::timer::Timer;
-use crate::types::{ParticleSystems, Shaders, TexturePack};
+use crate::types::{ParticleSystems, Shaders};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Swaps the resource packs for the ones enabled in `resourcepacks.txt` when asked to.
+/// The chunks are meshed again with the new colour maps
+pub struct ReloadResourcePacks;
+
+impl<'a> System<'a> for ReloadResourcePacks {
+    type SystemData = (
+        Read<'a, InputCache>,
+        ReadExpect<'a, Arc<ChunkManager>>,
+        Write<'a, ChunkUploads>,
+        WriteExpect<'a, TexturePack>,
+    );
+
+    fn run(&mut self, (input_cache, chunk_manager, mut chunk_uploads, mut texture_pack): Self::SystemData) {
+        let reload = input_cache.action_events()
+            .any(|(action, state)| action == InputAction::ReloadResourcePacks && state == Action::Press);
+        if reload {
+            info!("Reloading the resource packs");
+            texture_pack.reload(ResourcePacks::load_enabled());
+
+            for (&(x, z), column) in chunk_manager.loaded_chunk_columns.read().iter() {
+                for (y, chunk) in column.chunks.iter().enumerate() {
+                    if chunk.is_uploaded_to_gpu() && !chunk.is_empty() {
+                        chunk_uploads.meshed.push(PrioritizedItem {
+                            item: (x, y as i32, z),
+                            priority: i32::MIN,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Compiles the shaders again when their files change or when asked to. A shader that doesn't compile
+/// keeps its previous program
+pub struct ReloadShaders {
+    preprocessor: ShaderPreprocessor,
+    watcher: ShaderWatcher,
+    last_poll: Instant,
+}
+
+impl ReloadShaders {
+    pub fn new(preprocessor: ShaderPreprocessor) -> Self {
+        Self {
+            preprocessor,
+            watcher: ShaderWatcher::new(),
+            last_poll: Instant::now(),
+        }
+    }
+}
+
+impl<'a> System<'a> for ReloadShaders {
+    type SystemData = (
+        Read<'a, InputCache>,
+        Write<'a, Shaders>,
+    );
+
+    fn run(&mut self, (input_cache, mut shaders): Self::SystemData) {
+        let forced = input_cache.action_events()
+            .any(|(action, state)| action == InputAction::ReloadShaders && state == Action::Press);
+        if !forced && self.last_poll.elapsed() < SHADER_POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = self.watcher.modified_programs(&shaders);
+        for (name, program) in shaders.iter_mut() {
+            if forced || modified.contains(name) {
+                match program.reload(&self.preprocessor) {
+                    Ok(()) => info!("Reloaded the shader {}", name),
+                    Err(err) => error!("Couldn't reload the shader {}, keeping the previous one: {}", name, err),
+                }
+            }
+        }
+    }
+}
+
+/// Shows the current frame of the animated block textures
+pub struct AnimateTextures;
+
+impl<'a> System<'a> for AnimateTextures {
+    type SystemData = (
+        Read<'a, Timer>,
+        WriteExpect<'a, TexturePack>,
+    );
+
+    fn run(&mut self, (timer, mut texture_pack): Self::SystemData) {
+        texture_pack.animate(timer.time());
+    }
+}
+
+/// Uploads the chunks meshed by `ChunkLoading` to the GPU, the most urgent first
+pub struct UploadChunks;
+
+impl<'a> System<'a> for UploadChunks {
+    type SystemData = (
+        ReadExpect<'a, Arc<ChunkManager>>,
+        Write<'a, ChunkUploads>,
+        WriteExpect<'a, ChunkMeshArena>,
+        ReadExpect<'a, TexturePack>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            chunk_manager,
+            mut chunk_uploads,
+            mut chunk_mesh_arena,
+            texture_pack,
+        ) = data;
+
+        for coords in chunk_uploads.unloaded.drain(..) {
+            chunk_mesh_arena.free(coords);
+        }
+
+        for _ in 0..CHUNK_UPLOADS_PER_FRAME {
+            if let Some(prioritized_chunk) = chunk_uploads.meshed.pop() {
+                let (c_x, c_y, c_z) = *prioritized_chunk;
+                if let Some(chunk) = chunk_manager.get_chunk(c_x, c_y, c_z) {
+                    chunk_mesh_arena.upload_chunk((c_x, c_y, c_z), &chunk, &chunk_manager, texture_pack.color_maps());
+                }
+            }
+        }
+    }
+}
 
 pub struct RenderChunks;
 
 impl<'a> System<'a> for RenderChunks {
     type SystemData = (
         ReadStorage<'a, PlayerState>,
-        Read<'a, Arc<ChunkManager>>,
+        ReadExpect<'a, Arc<ChunkManager>>,
         Write<'a, Shaders>,
+        WriteExpect<'a, ChunkMeshArena>,
+        Read<'a, Screenshots>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -25,13 +173,14 @@ impl<'a> System<'a> for RenderChunks {
             player_state,
             chunk_manager,
             mut shaders,
+            mut chunk_mesh_arena,
+            screenshots,
         ) = data;
 
-        let mut voxel_shader = shaders.get_mut("voxel_shader").unwrap();
+        let voxel_shader = shaders.get_mut("voxel_shader").unwrap();
         voxel_shader.use_program();
         voxel_shader.set_uniform1i("array_texture", 0);
         let (r, g, b, a) = BACKGROUND_COLOR;
-        voxel_shader.set_uniform1i("enable_fog", ENABLE_FOG as i32);
         voxel_shader.set_uniform3f("sky_color", &[r, g, b]);
         voxel_shader.set_uniform1f("render_distance", RENDER_DISTANCE as f32);
 
@@ -41,30 +190,42 @@ impl<'a> System<'a> for RenderChunks {
 
         for player_state in (&player_state).join() {
             voxel_shader.set_uniform_matrix4fv("view", player_state.view_matrix.as_ptr());
-            voxel_shader.set_uniform_matrix4fv("projection", player_state.projection_matrix.as_ptr());
-            chunk_manager.render_loaded_chunks(&mut voxel_shader);
+            let projection_matrix = screenshots.projection(&player_state.projection_matrix);
+            voxel_shader.set_uniform_matrix4fv("projection", projection_matrix.as_ptr());
+            let frustum = Frustum::from_matrix(&(projection_matrix * player_state.view_matrix));
+            chunk_mesh_arena.draw_uploaded_chunks(&chunk_manager, &frustum);
         }
     }
 }
 
-pub struct RenderParticles;
+pub struct RenderParticles {
+    particle_renderer: ParticleRenderer,
+}
+
+impl RenderParticles {
+    pub fn new() -> Self {
+        Self {
+            particle_renderer: ParticleRenderer::new(MAX_PARTICLES),
+        }
+    }
+}
 
 impl<'a> System<'a> for RenderParticles {
     type SystemData = (
-        Read<'a, Timer>,
         ReadStorage<'a, PlayerState>,
-        Write<'a, Arc<ChunkManager>>,
         Write<'a, Shaders>,
-        Write<'a, ParticleSystems>,
+        Read<'a, ParticleSystems>,
+        Read<'a, Ticks>,
+        Read<'a, Screenshots>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
-            global_timer,
             player_state,
-            chunk_manager,
             mut shaders,
-            mut particle_systems,
+            particle_systems,
+            ticks,
+            screenshots,
         ) = data;
 
         gl_call!(gl::Disable(gl::CULL_FACE));
@@ -73,9 +234,9 @@ impl<'a> System<'a> for RenderParticles {
         particle_shader.set_uniform1i("array_texture", 0);
 
         for player_state in (&player_state).join() {
-            for particle_system in particle_systems.values_mut() {
-                particle_system.update_all_particles(global_timer.time(), &chunk_manager);
-                particle_system.render_all_particles(&mut particle_shader, &player_state.view_matrix, &player_state.projection_matrix);
+            let projection_matrix = screenshots.projection(&player_state.projection_matrix);
+            for particle_system in particle_systems.values() {
+                self.particle_renderer.render(particle_system, ticks.alpha, &mut particle_shader, &player_state.view_matrix, &projection_matrix);
             }
         }
 
@@ -83,6 +244,101 @@ impl<'a> System<'a> for RenderParticles {
     }
 }
 
+/// Colours of the bodies of the other players, picked from their ids
+const REMOTE_PLAYER_COLORS: [[f32; 3]; 6] = [
+    [0.2, 0.4, 0.8],
+    [0.8, 0.3, 0.2],
+    [0.3, 0.7, 0.3],
+    [0.8, 0.7, 0.2],
+    [0.6, 0.3, 0.7],
+    [0.2, 0.7, 0.7],
+];
+const REMOTE_PLAYER_HEAD_COLOR: [f32; 3] = [0.9, 0.75, 0.6];
+const REMOTE_PLAYER_EYES_COLOR: [f32; 3] = [0.15, 0.15, 0.2];
+
+/// Draws the other players as boxes: a body turned towards where they look, and a head that also
+/// looks up and down. They move between the positions received from the server, see `RemotePlayer`
+pub struct RenderRemotePlayers {
+    vao: u32,
+}
+
+impl RenderRemotePlayers {
+    pub fn new() -> Self {
+        Self {
+            vao: create_cube_vao()
+        }
+    }
+}
+
+impl Default for RenderRemotePlayers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> System<'a> for RenderRemotePlayers {
+    type SystemData = (
+        ReadStorage<'a, PlayerState>,
+        Read<'a, RemotePlayers>,
+        Read<'a, Ticks>,
+        Write<'a, Shaders>,
+        Read<'a, Screenshots>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_state,
+            remote_players,
+            ticks,
+            mut shaders,
+            screenshots,
+        ) = data;
+
+        if remote_players.players.is_empty() {
+            return;
+        }
+
+        let player_shader = shaders.get_mut("player_shader").unwrap();
+        player_shader.use_program();
+        gl_call!(gl::BindVertexArray(self.vao));
+
+        for player_state in (&player_state).join() {
+            player_shader.set_uniform_matrix4fv("view", player_state.view_matrix.as_ptr());
+            let projection_matrix = screenshots.projection(&player_state.projection_matrix);
+            player_shader.set_uniform_matrix4fv("projection", projection_matrix.as_ptr());
+
+            for (&id, remote_player) in &remote_players.players {
+                let (position, rotation) = remote_player.interpolated(ticks.time());
+                // The unit cube faces +x, the direction of a yaw of 0
+                let yaw = Matrix4::new_rotation(Vector3::new(0.0, -rotation.y, 0.0));
+                let pitch = Matrix4::new_rotation(Vector3::new(0.0, 0.0, rotation.x));
+                let body_height = PLAYER_HEIGHT - PLAYER_HEAD_SIZE;
+                let neck = Matrix4::new_translation(&(position + vec3(0.0, body_height, 0.0))) * yaw * pitch;
+
+                let parts = [
+                    (Matrix4::new_translation(&position) * yaw
+                         * Matrix4::new_translation(&vec3(-PLAYER_WIDTH / 4.0, 0.0, -PLAYER_WIDTH / 2.0))
+                         * Matrix4::new_nonuniform_scaling(&vec3(PLAYER_WIDTH / 2.0, body_height, PLAYER_WIDTH)),
+                     REMOTE_PLAYER_COLORS[id as usize % REMOTE_PLAYER_COLORS.len()]),
+                    (neck
+                         * Matrix4::new_translation(&vec3(-PLAYER_HEAD_SIZE / 2.0, 0.0, -PLAYER_HEAD_SIZE / 2.0))
+                         * Matrix4::new_scaling(PLAYER_HEAD_SIZE),
+                     REMOTE_PLAYER_HEAD_COLOR),
+                    (neck
+                         * Matrix4::new_translation(&vec3(PLAYER_HEAD_SIZE / 2.0, PLAYER_HEAD_SIZE * 0.5, -PLAYER_HEAD_SIZE * 0.35))
+                         * Matrix4::new_nonuniform_scaling(&vec3(0.02, PLAYER_HEAD_SIZE * 0.15, PLAYER_HEAD_SIZE * 0.7)),
+                     REMOTE_PLAYER_EYES_COLOR),
+                ];
+                for (model_matrix, color) in &parts {
+                    player_shader.set_uniform_matrix4fv("model", model_matrix.as_ptr());
+                    player_shader.set_uniform3f("color", color);
+                    gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, 36));
+                }
+            }
+        }
+    }
+}
+
 pub struct RenderBlockOutline {
     vao: u32,
 }
@@ -97,39 +353,86 @@ impl RenderBlockOutline {
 
 impl<'a> System<'a> for RenderBlockOutline {
     type SystemData = (
+        ReadExpect<'a, Arc<ChunkManager>>,
         ReadStorage<'a, PlayerState>,
         Write<'a, Shaders>,
+        Read<'a, Screenshots>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
+            chunk_manager,
             player_state,
             mut shaders,
+            screenshots,
         ) = data;
 
         for player_state in (&player_state).join() {
             if let Some(((x, y, z), _)) = player_state.targeted_block {
+                let (block, state) = match chunk_manager.get_block_with_connections(x, y, z) {
+                    Some(block) => block,
+                    None => continue,
+                };
                 let (x, y, z) = (x as f32, y as f32, z as f32);
-                let model_matrix = Matrix4::new_translation(&vec3(x, y, z));
 
                 let outline_shader = shaders.get_mut("outline_shader").unwrap();
                 outline_shader.use_program();
-                outline_shader.set_uniform_matrix4fv("model", model_matrix.as_ptr());
                 outline_shader.set_uniform_matrix4fv("view", player_state.view_matrix.as_ptr());
-                outline_shader.set_uniform_matrix4fv("projection", player_state.projection_matrix.as_ptr());
+                let projection_matrix = screenshots.projection(&player_state.projection_matrix);
+                outline_shader.set_uniform_matrix4fv("projection", projection_matrix.as_ptr());
 
                 gl_call!(gl::LineWidth(BLOCK_OUTLINE_WIDTH));
                 gl_call!(gl::BindVertexArray(self.vao));
-                gl_call!(gl::DrawArrays(gl::LINES, 0, 24));
+
+                // The outline is a unit cube, scaled to each selection box of the block
+                for b in &chunk_manager.block_models().shape(block, state).selection_boxes {
+                    let model_matrix = Matrix4::new_translation(&(vec3(x, y, z) + b.mins))
+                        * Matrix4::new_nonuniform_scaling(&(b.maxs - b.mins));
+                    outline_shader.set_uniform_matrix4fv("model", model_matrix.as_ptr());
+                    gl_call!(gl::DrawArrays(gl::LINES, 0, 24));
+                }
+            }
+        }
+    }
+}
+
+/// Saves the screenshots asked for, at the end of the frame before the buffers are swapped
+pub struct TakeScreenshots;
+
+impl<'a> System<'a> for TakeScreenshots {
+    type SystemData = (
+        Read<'a, InputCache>,
+        Write<'a, Screenshots>,
+    );
+
+    fn run(&mut self, (input_cache, mut screenshots): Self::SystemData) {
+        if screenshots.is_tiling() {
+            if let Some(image) = screenshots.capture_tile() {
+                save_screenshot(image, Path::new(SCREENSHOT_DIRECTORY));
+            }
+            return;
+        }
+
+        for (action, state) in input_cache.action_events() {
+            match (action, state) {
+                (InputAction::Screenshot, Action::Press) => {
+                    save_screenshot(read_framebuffer(0, WINDOW_WIDTH, WINDOW_HEIGHT), Path::new(SCREENSHOT_DIRECTORY));
+                }
+                (InputAction::HighResolutionScreenshot, Action::Press) => {
+                    info!("Rendering a screenshot of {}x{}", SCREENSHOT_TILES * WINDOW_WIDTH, SCREENSHOT_TILES * WINDOW_HEIGHT);
+                    screenshots.start_tiled(SCREENSHOT_TILES, WINDOW_WIDTH, WINDOW_HEIGHT);
+                }
+                _ => {}
             }
         }
     }
 }
 
-pub(crate) struct RenderGUI {
+pub struct RenderGUI {
     crosshair_vao: u32,
     hotbar_vao: u32,
     hotbar_selection_vao: u32,
+    hotbar_render: HotbarRender,
 }
 
 impl RenderGUI {
@@ -138,35 +441,42 @@ impl RenderGUI {
             crosshair_vao: create_crosshair_vao(),
             hotbar_vao: create_hotbar_vao(),
             hotbar_selection_vao: create_hotbar_selection_vao(),
+            hotbar_render: HotbarRender::new(),
         }
     }
 }
 
 impl<'a> System<'a> for RenderGUI {
     type SystemData = (
-        Read<'a, TexturePack>,
         Write<'a, Shaders>,
-        WriteStorage<'a, Inventory>,
+        ReadStorage<'a, Inventory>,
+        Read<'a, Screenshots>,
+        ReadExpect<'a, Arc<BlockModels>>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
-            texture_pack,
             mut shaders,
-            mut inventory,
+            inventory,
+            screenshots,
+            block_models,
         ) = data;
 
-        for inventory in (&mut inventory).join() {
+        if screenshots.is_tiling() {
+            return;
+        }
+
+        for inventory in (&inventory).join() {
             let mut gui_shader = shaders.get_mut("gui_shader").unwrap();
             draw_crosshair(self.crosshair_vao, &mut gui_shader);
             gl_call!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
             gl_call!(gl::Disable(gl::DEPTH_TEST));
-            inventory.update_dirty_items(&texture_pack);
-            inventory.draw_hotbar(self.hotbar_vao, &mut gui_shader);
-            inventory.draw_hotbar_selection_box(self.hotbar_selection_vao, &mut gui_shader);
+            self.hotbar_render.update(inventory, &block_models);
+            self.hotbar_render.draw_hotbar(self.hotbar_vao, &mut gui_shader);
+            self.hotbar_render.draw_hotbar_selection_box(self.hotbar_selection_vao, inventory.selected_hotbar_slot, &mut gui_shader);
 
             let mut item_shader = shaders.get_mut("item_shader").unwrap();
-            inventory.draw_hotbar_items(&mut item_shader);
+            self.hotbar_render.draw_hotbar_items(&mut item_shader);
             gl_call!(gl::Enable(gl::DEPTH_TEST));
         }
     }
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use std::sync::Arc;
+
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write};
+
+use crate::chunk_manager::ChunkManager;
+use crate::input::{Action, InputCache, InputEvent, Key, MouseButton};
+use crate::physics::Interpolator;
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
+use crate::replay::{Replay, ReplayPlayer, ReplayRecorder};
+use crate::ticks::Ticks;
+use crate::timer::Timer;
+
+/// Input events to replay at fixed times, in seconds since the first frame
+#[derive(Debug, Clone, Default)]
+pub struct InputScript {
+    events: Vec<(Duration, InputEvent)>,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn event(mut self, at: f32, event: InputEvent) -> Self {
+        let at = Duration::from_secs_f32(at);
+        // Events at the same time keep the order they were added in
+        let index = self.events.iter().position(|(time, _)| *time > at).unwrap_or(self.events.len());
+        self.events.insert(index, (at, event));
+        self
+    }
+
+    pub fn press_key(self, at: f32, key: Key) -> Self {
+        self.event(at, InputEvent::Key(key, Action::Press))
+    }
+
+    pub fn release_key(self, at: f32, key: Key) -> Self {
+        self.event(at, InputEvent::Key(key, Action::Release))
+    }
+
+    /// Presses `key` at `from` and releases it at `to`
+    pub fn hold_key(self, from: f32, to: f32, key: Key) -> Self {
+        self.press_key(from, key).release_key(to, key)
+    }
+
+    pub fn click(self, at: f32, button: MouseButton) -> Self {
+        self.event(at, InputEvent::MouseButton(button, Action::Press))
+            .event(at, InputEvent::MouseButton(button, Action::Release))
+    }
+
+    pub fn move_cursor(self, at: f32, x: f64, y: f64) -> Self {
+        self.event(at, InputEvent::CursorPos(x, y))
+    }
+}
+
+/// Feeds an `InputScript` to the `InputCache`, replaces `ReadWindowEvents` when there is no window
+pub struct ReadScriptedInput {
+    events: VecDeque<(Duration, InputEvent)>,
+    start: Option<Instant>,
+}
+
+impl ReadScriptedInput {
+    pub fn new(script: InputScript) -> Self {
+        Self {
+            events: script.events.into(),
+            start: None,
+        }
+    }
+}
+
+impl<'a> System<'a> for ReadScriptedInput {
+    type SystemData = (
+        Write<'a, InputCache>,
+        Read<'a, Timer>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut input_cache,
+            global_timer,
+        ) = data;
+
+        let now = global_timer.time();
+        let elapsed = now.saturating_duration_since(*self.start.get_or_insert(now));
+
+        input_cache.clear_events();
+        while let Some((time, _)) = self.events.front() {
+            if *time > elapsed {
+                break;
+            }
+            let (_, event) = self.events.pop_front().unwrap();
+            input_cache.handle_event(&event);
+        }
+    }
+}
+
+/// Whether the columns around the players are loaded. The columns arrive from the server at a
+/// different time on every run, so a replay holds its frames until they are there
+pub fn is_loaded_around_players(
+    chunk_manager: &ChunkManager,
+    bodies: &ReadStorage<Interpolator<PhysicsBody>>,
+    players: &ReadStorage<PlayerState>,
+) -> bool {
+    (bodies, players).join().all(|(body, _)| {
+        let position = body.get_latest_state().position;
+        chunk_manager.is_loaded_around(position.x as i32, position.z as i32)
+    })
+}
+
+/// Plays a `Replay` without a window in place of `AdvanceTicks`, the `Timer` must be a `Timer::manual`
+pub struct ReadReplayedInput {
+    player: ReplayPlayer,
+}
+
+impl ReadReplayedInput {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            player: ReplayPlayer::new(replay),
+        }
+    }
+}
+
+impl<'a> System<'a> for ReadReplayedInput {
+    type SystemData = (
+        Write<'a, InputCache>,
+        Write<'a, Timer>,
+        Write<'a, Ticks>,
+        ReadExpect<'a, Arc<ChunkManager>>,
+        ReadStorage<'a, Interpolator<PhysicsBody>>,
+        ReadStorage<'a, PlayerState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut input_cache,
+            mut global_timer,
+            mut ticks,
+            chunk_manager,
+            bodies,
+            players,
+        ) = data;
+
+        input_cache.clear_events();
+        if is_loaded_around_players(&chunk_manager, &bodies, &players) {
+            self.player.play_frame(&mut input_cache, &mut global_timer, &mut ticks);
+        } else {
+            self.player.hold_frame(&mut ticks);
+        }
+    }
+}
+
+/// Saves the events of every frame to a replay file. Runs after `AdvanceTicks`, so that each frame
+/// is recorded with its tick
+pub struct RecordReplay {
+    recorder: Option<ReplayRecorder>,
+}
+
+impl RecordReplay {
+    pub fn new(recorder: Option<ReplayRecorder>) -> Self {
+        Self {
+            recorder,
+        }
+    }
+}
+
+impl<'a> System<'a> for RecordReplay {
+    type SystemData = (
+        Read<'a, InputCache>,
+        Read<'a, Timer>,
+        Read<'a, Ticks>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            input_cache,
+            global_timer,
+            ticks,
+        ) = data;
+
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.record_frame(global_timer.time(), &ticks, &input_cache.events) {
+                error!("Couldn't record the frame, stopping the recording: {}", err);
+                self.recorder = None;
+            }
+        }
+    }
+}
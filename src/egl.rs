@@ -0,0 +1,181 @@
+//! An OpenGL context without a window or a display, through the surfaceless platform of EGL.
+//! Machines without a GPU get one from the software renderer of Mesa (llvmpipe), which is how the
+//! visual regression tests run in CI.
+//!
+//! libEGL is loaded when the context is created rather than linked, so the game still starts on
+//! machines without it.
+
+use std::ffi::{c_void, CString};
+use std::mem;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::constants::{OPENGL_MAJOR_VERSION, OPENGL_MINOR_VERSION};
+
+type EGLDisplay = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLSurface = *mut c_void;
+
+const EGL_LIBRARY: &str = "libEGL.so.1";
+
+const EGL_TRUE: u32 = 1;
+const EGL_NONE: i32 = 0x3038;
+const EGL_SURFACE_TYPE: i32 = 0x3033;
+const EGL_PBUFFER_BIT: i32 = 0x0001;
+const EGL_RENDERABLE_TYPE: i32 = 0x3040;
+const EGL_OPENGL_BIT: i32 = 0x0008;
+const EGL_OPENGL_API: u32 = 0x30A2;
+const EGL_CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: i32 = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: i32 = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: i32 = 0x0001;
+const EGL_CONTEXT_OPENGL_DEBUG: i32 = 0x31B0;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+
+/// The functions of libEGL used to create the context
+struct Egl {
+    library: *mut c_void,
+    get_proc_address: unsafe extern "C" fn(*const c_char) -> *const c_void,
+    get_error: unsafe extern "C" fn() -> i32,
+    get_platform_display: unsafe extern "C" fn(u32, *mut c_void, *const i32) -> EGLDisplay,
+    initialize: unsafe extern "C" fn(EGLDisplay, *mut i32, *mut i32) -> u32,
+    terminate: unsafe extern "C" fn(EGLDisplay) -> u32,
+    bind_api: unsafe extern "C" fn(u32) -> u32,
+    choose_config: unsafe extern "C" fn(EGLDisplay, *const i32, *mut EGLConfig, i32, *mut i32) -> u32,
+    create_context: unsafe extern "C" fn(EGLDisplay, EGLConfig, EGLContext, *const i32) -> EGLContext,
+    destroy_context: unsafe extern "C" fn(EGLDisplay, EGLContext) -> u32,
+    make_current: unsafe extern "C" fn(EGLDisplay, EGLSurface, EGLSurface, EGLContext) -> u32,
+}
+
+impl Egl {
+    fn load() -> Result<Self, String> {
+        let name = CString::new(EGL_LIBRARY).unwrap();
+        let library = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if library.is_null() {
+            return Err(format!("Couldn't load {}", EGL_LIBRARY));
+        }
+        unsafe {
+            let symbol = |name: *const c_char| libc::dlsym(library, name) as *const c_void;
+            let get_proc_address: unsafe extern "C" fn(*const c_char) -> *const c_void =
+                load_function(symbol, "eglGetProcAddress")?;
+            Ok(Self {
+                library,
+                get_proc_address,
+                get_error: load_function(symbol, "eglGetError")?,
+                // An extension, only available through eglGetProcAddress
+                get_platform_display: load_function(|name| get_proc_address(name), "eglGetPlatformDisplayEXT")?,
+                initialize: load_function(symbol, "eglInitialize")?,
+                terminate: load_function(symbol, "eglTerminate")?,
+                bind_api: load_function(symbol, "eglBindAPI")?,
+                choose_config: load_function(symbol, "eglChooseConfig")?,
+                create_context: load_function(symbol, "eglCreateContext")?,
+                destroy_context: load_function(symbol, "eglDestroyContext")?,
+                make_current: load_function(symbol, "eglMakeCurrent")?,
+            })
+        }
+    }
+
+    /// `message` followed by the error of the last EGL call
+    fn error(&self, message: &str) -> String {
+        format!("{}: EGL error {:#x}", message, unsafe { (self.get_error)() })
+    }
+}
+
+impl Drop for Egl {
+    fn drop(&mut self) {
+        unsafe { libc::dlclose(self.library) };
+    }
+}
+
+/// Looks a function up by name with `lookup`, `T` must be its `extern "C" fn` type
+unsafe fn load_function<T>(lookup: impl Fn(*const c_char) -> *const c_void, name: &str) -> Result<T, String> {
+    assert_eq!(mem::size_of::<T>(), mem::size_of::<*const c_void>());
+    let c_name = CString::new(name).unwrap();
+    let address = lookup(c_name.as_ptr());
+    if address.is_null() {
+        return Err(format!("{} doesn't have {}", EGL_LIBRARY, name));
+    }
+    Ok(mem::transmute_copy(&address))
+}
+
+/// An OpenGL context current on the thread that created it, without a surface to draw to.
+/// Everything is drawn to framebuffer objects, see `OffscreenTarget`
+pub struct HeadlessContext {
+    display: EGLDisplay,
+    context: EGLContext,
+    // Dropped last, the display and the context are released through it
+    egl: Egl,
+}
+
+impl HeadlessContext {
+    /// Creates a context of the OpenGL version of the game and makes it current
+    pub fn new() -> Result<Self, String> {
+        let egl = Egl::load()?;
+        let display = unsafe { (egl.get_platform_display)(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null()) };
+        if display.is_null() {
+            return Err(egl.error("No surfaceless EGL display"));
+        }
+        if unsafe { (egl.initialize)(display, ptr::null_mut(), ptr::null_mut()) } != EGL_TRUE {
+            return Err(egl.error("Couldn't initialize the surfaceless EGL display"));
+        }
+        // From now on the display is released on drop
+        let mut headless = Self {
+            display,
+            context: ptr::null_mut(),
+            egl,
+        };
+        let egl = &headless.egl;
+
+        if unsafe { (egl.bind_api)(EGL_OPENGL_API) } != EGL_TRUE {
+            return Err(egl.error("EGL doesn't support OpenGL"));
+        }
+        let config_attributes = [
+            EGL_SURFACE_TYPE, EGL_PBUFFER_BIT,
+            EGL_RENDERABLE_TYPE, EGL_OPENGL_BIT,
+            EGL_NONE,
+        ];
+        let mut config = ptr::null_mut();
+        let mut configs = 0;
+        let chosen = unsafe { (egl.choose_config)(display, config_attributes.as_ptr(), &mut config, 1, &mut configs) };
+        if chosen != EGL_TRUE || configs == 0 {
+            return Err(egl.error("No EGL config renders with OpenGL"));
+        }
+
+        let context_attributes = [
+            EGL_CONTEXT_MAJOR_VERSION, OPENGL_MAJOR_VERSION as i32,
+            EGL_CONTEXT_MINOR_VERSION, OPENGL_MINOR_VERSION as i32,
+            EGL_CONTEXT_OPENGL_PROFILE_MASK, EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            EGL_CONTEXT_OPENGL_DEBUG, EGL_TRUE as i32,
+            EGL_NONE,
+        ];
+        let context = unsafe { (egl.create_context)(display, config, ptr::null_mut(), context_attributes.as_ptr()) };
+        if context.is_null() {
+            return Err(egl.error(&format!("Couldn't create an OpenGL {}.{} context", OPENGL_MAJOR_VERSION, OPENGL_MINOR_VERSION)));
+        }
+        headless.context = context;
+        let egl = &headless.egl;
+
+        // Without a surface, needs EGL_KHR_surfaceless_context which every surfaceless display has
+        if unsafe { (egl.make_current)(display, ptr::null_mut(), ptr::null_mut(), context) } != EGL_TRUE {
+            return Err(egl.error("Couldn't make the OpenGL context current"));
+        }
+        gl::load_with(|name| {
+            let name = CString::new(name).unwrap();
+            unsafe { (egl.get_proc_address)(name.as_ptr()) }
+        });
+        Ok(headless)
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        unsafe {
+            (self.egl.make_current)(self.display, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+            if !self.context.is_null() {
+                (self.egl.destroy_context)(self.display, self.context);
+            }
+            (self.egl.terminate)(self.display);
+        }
+    }
+}
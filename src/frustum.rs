@@ -0,0 +1,36 @@
+use nalgebra_glm::{Mat4, Vec4, vec4};
+
+use crate::aabb::AABB;
+
+/// The volume seen by a camera, bounded by the 6 planes of its projection·view matrix.
+/// Each plane is (a, b, c, d) with a·x + b·y + c·z + d >= 0 on the inside
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from the rows of `projection * view` (Gribb and Hartmann)
+    pub fn from_matrix(projection_view: &Mat4) -> Self {
+        let row = |i: usize| -> Vec4 {
+            let row = projection_view.row(i);
+            vec4(row[0], row[1], row[2], row[3])
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Whether some of the box may be visible. Boxes near the corners of the frustum can pass
+    /// without being seen, but a visible box is never rejected
+    pub fn intersects_aabb(&self, aabb: &AABB) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner of the box the furthest inside this plane
+            let x = if plane.x >= 0.0 { aabb.maxs.x } else { aabb.mins.x };
+            let y = if plane.y >= 0.0 { aabb.maxs.y } else { aabb.mins.y };
+            let z = if plane.z >= 0.0 { aabb.maxs.z } else { aabb.mins.z };
+            plane.x * x + plane.y * y + plane.z * z + plane.w >= 0.0
+        })
+    }
+}
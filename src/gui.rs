@@ -1,36 +1,27 @@
 use std::ffi::c_void;
 
-use image::GenericImageView;
+use image::{DynamicImage, GenericImageView};
 use nalgebra::Matrix4;
 use nalgebra_glm::{Mat4, vec3};
 
 use crate::constants::{CROSSHAIR_SIZE, WINDOW_HEIGHT, WINDOW_WIDTH};
 use crate::shader_compilation::ShaderProgram;
-use crate::shapes::block_outline;
+use crate::shapes::{block_outline, cube};
 use crate::shapes::quad;
 
-pub fn create_gui_icons_texture() -> u32 {
-    let gui_icons_image = match image::open("textures/gui/icons.png") {
-        Ok(img) => img,
-        Err(err) => panic!("Filename: {}, error: {}", "textures/gui/icons.png", err.to_string())
-    };
-    match gui_icons_image.color() {
-        image::RGBA(8) => {}
-        _ => panic!("Texture format not supported")
-    };
-
-    // Upload the image to the GPU
-    let mut gui_icons_texture = 0;
-    gl_call!(gl::CreateTextures(gl::TEXTURE_2D, 1, &mut gui_icons_texture));
-    gl_call!(gl::TextureParameteri(gui_icons_texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_NEAREST as i32));
-    gl_call!(gl::TextureParameteri(gui_icons_texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32));
-    gl_call!(gl::TextureStorage2D(gui_icons_texture, 1, gl::RGBA8, gui_icons_image.width() as i32, gui_icons_image.height() as i32));
+/// Uploads a GUI texture, like the icons or the widgets, to the GPU
+pub fn create_gui_texture(image: &DynamicImage) -> u32 {
+    let mut gui_texture = 0;
+    gl_call!(gl::CreateTextures(gl::TEXTURE_2D, 1, &mut gui_texture));
+    gl_call!(gl::TextureParameteri(gui_texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_NEAREST as i32));
+    gl_call!(gl::TextureParameteri(gui_texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32));
+    gl_call!(gl::TextureStorage2D(gui_texture, 1, gl::RGBA8, image.width() as i32, image.height() as i32));
     gl_call!(gl::TextureSubImage2D(
-            gui_icons_texture, 0,
-            0, 0, gui_icons_image.width() as i32, gui_icons_image.height() as i32,
+            gui_texture, 0,
+            0, 0, image.width() as i32, image.height() as i32,
             gl::RGBA, gl::UNSIGNED_BYTE,
-            gui_icons_image.raw_pixels().as_ptr() as *mut c_void));
-    gui_icons_texture
+            image.raw_pixels().as_ptr() as *mut c_void));
+    gui_texture
 }
 
 pub fn create_crosshair_vao() -> u32 {
@@ -98,30 +89,31 @@ pub fn create_block_outline_vao() -> u32 {
     outline_vao
 }
 
-pub fn create_widgets_texture() -> u32 {
-    let widgets_image = match image::open("textures/gui/widgets.png") {
-        Ok(img) => img,
-        Err(err) => panic!("Filename: {}, error: {}", "textures/gui/widgets.png", err.to_string())
-    };
-    match widgets_image.color() {
-        image::RGBA(8) => {}
-        _ => panic!("Texture format not supported")
-    };
-
-    // quad((0.0, 0.0, 0.0, 0.0)).chunks(3).take(2).collect::<Vec2>();
+/// A unit cube with the normals of its faces, scaled by the model matrix to the parts of the other players
+pub fn create_cube_vao() -> u32 {
+    let mut cube_vao = 0;
+    gl_call!(gl::CreateVertexArrays(1, &mut cube_vao));
 
-    // Upload the image to the GPU
-    let mut widgets_texture = 0;
-    gl_call!(gl::CreateTextures(gl::TEXTURE_2D, 1, &mut widgets_texture));
-    gl_call!(gl::TextureParameteri(widgets_texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_NEAREST as i32));
-    gl_call!(gl::TextureParameteri(widgets_texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32));
-    gl_call!(gl::TextureStorage2D(widgets_texture, 1, gl::RGBA8, widgets_image.width() as i32, widgets_image.height() as i32));
-    gl_call!(gl::TextureSubImage2D(
-            widgets_texture, 0,
-            0, 0, widgets_image.width() as i32, widgets_image.height() as i32,
-            gl::RGBA, gl::UNSIGNED_BYTE,
-            widgets_image.raw_pixels().as_ptr() as *mut c_void));
-    widgets_texture
+    // Position
+    gl_call!(gl::EnableVertexArrayAttrib(cube_vao, 0));
+    gl_call!(gl::VertexArrayAttribFormat(cube_vao, 0, 3, gl::FLOAT, gl::FALSE, 0));
+    gl_call!(gl::VertexArrayAttribBinding(cube_vao, 0, 0));
+
+    // Normal
+    gl_call!(gl::EnableVertexArrayAttrib(cube_vao, 1));
+    gl_call!(gl::VertexArrayAttribFormat(cube_vao, 1, 3, gl::FLOAT, gl::FALSE, (3 * std::mem::size_of::<f32>()) as u32));
+    gl_call!(gl::VertexArrayAttribBinding(cube_vao, 1, 0));
+
+    let vertices = cube();
+    let mut cube_vbo = 0;
+    gl_call!(gl::CreateBuffers(1, &mut cube_vbo));
+
+    gl_call!(gl::VertexArrayVertexBuffer(cube_vao, 0, cube_vbo, 0, (6 * std::mem::size_of::<f32>()) as i32));
+    gl_call!(gl::NamedBufferData(cube_vbo,
+                    (vertices.len() * std::mem::size_of::<f32>() as usize) as isize,
+                    vertices.as_ptr() as *const c_void,
+                    gl::STATIC_DRAW));
+    cube_vao
 }
 
 pub fn create_hotbar_vao() -> u32 {
@@ -174,4 +166,4 @@ pub fn create_hotbar_selection_vao() -> u32 {
                     // quad((0.0, 0.0, 1.0, 1.0)).as_ptr() as *const c_void,
                     gl::STATIC_DRAW));
     hotbar_selection_vao
-}
\ No newline at end of file
+}
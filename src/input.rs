@@ -1,15 +1,140 @@
+//! The input of the player, independent of the window library: the `Key`s, `MouseButton`s and
+//! `InputEvent`s read from the window (converted from GLFW with the `rendering` feature), a script or a replay
+
 use std::collections::HashMap;
 
-use glfw::{Action, Key, WindowEvent, MouseButton};
 use nalgebra_glm::{DVec2, vec2};
 
+use crate::keybindings::{Binding, InputAction, KeyBindings};
+
+macro_rules! keys {
+    ($($key:ident = $code:expr),*) => {
+        /// A key of the keyboard, numbered like the GLFW keys
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum Key {
+            $($key = $code,)*
+        }
+
+        impl Key {
+            /// Parses the GLFW name of a key: `W`, `Space`, `LeftShift`, `Num1`...
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(stringify!($key) => Some(Key::$key),)*
+                    _ => None,
+                }
+            }
+
+            /// The inverse of `key as i32`
+            pub fn from_code(code: i32) -> Option<Self> {
+                match code {
+                    $($code => Some(Key::$key),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+keys!(
+    Space = 32, Apostrophe = 39, Comma = 44, Minus = 45, Period = 46, Slash = 47, Semicolon = 59, Equal = 61,
+    Num0 = 48, Num1 = 49, Num2 = 50, Num3 = 51, Num4 = 52, Num5 = 53, Num6 = 54, Num7 = 55, Num8 = 56, Num9 = 57,
+    A = 65, B = 66, C = 67, D = 68, E = 69, F = 70, G = 71, H = 72, I = 73, J = 74, K = 75, L = 76, M = 77,
+    N = 78, O = 79, P = 80, Q = 81, R = 82, S = 83, T = 84, U = 85, V = 86, W = 87, X = 88, Y = 89, Z = 90,
+    LeftBracket = 91, Backslash = 92, RightBracket = 93, GraveAccent = 96,
+    Escape = 256, Enter = 257, Tab = 258, Backspace = 259, Insert = 260, Delete = 261,
+    Right = 262, Left = 263, Down = 264, Up = 265, PageUp = 266, PageDown = 267, Home = 268, End = 269,
+    CapsLock = 280, ScrollLock = 281, NumLock = 282, PrintScreen = 283, Pause = 284,
+    F1 = 290, F2 = 291, F3 = 292, F4 = 293, F5 = 294, F6 = 295, F7 = 296, F8 = 297, F9 = 298, F10 = 299, F11 = 300, F12 = 301,
+    Kp0 = 320, Kp1 = 321, Kp2 = 322, Kp3 = 323, Kp4 = 324, Kp5 = 325, Kp6 = 326, Kp7 = 327, Kp8 = 328, Kp9 = 329,
+    KpDecimal = 330, KpDivide = 331, KpMultiply = 332, KpSubtract = 333, KpAdd = 334, KpEnter = 335, KpEqual = 336,
+    LeftShift = 340, LeftControl = 341, LeftAlt = 342, LeftSuper = 343,
+    RightShift = 344, RightControl = 345, RightAlt = 346, RightSuper = 347, Menu = 348
+);
+
+/// A button of the mouse, `Button1` is the left one
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Button1,
+    Button2,
+    Button3,
+    Button4,
+    Button5,
+    Button6,
+    Button7,
+    Button8,
+}
+
+impl MouseButton {
+    const ALL: [MouseButton; 8] = [
+        MouseButton::Button1, MouseButton::Button2, MouseButton::Button3, MouseButton::Button4,
+        MouseButton::Button5, MouseButton::Button6, MouseButton::Button7, MouseButton::Button8,
+    ];
+
+    /// The inverse of `button as i32`, from 0
+    pub fn from_code(code: i32) -> Option<Self> {
+        if code < 0 {
+            return None;
+        }
+        Self::ALL.get(code as usize).copied()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Release,
+    Press,
+    Repeat,
+}
+
+impl Action {
+    /// The inverse of `action as u8`
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Action::Release),
+            1 => Some(Action::Press),
+            2 => Some(Action::Repeat),
+            _ => None,
+        }
+    }
+}
+
+/// What the player did during a frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InputEvent {
+    Key(Key, Action),
+    MouseButton(MouseButton, Action),
+    /// The position of the cursor in the window
+    CursorPos(f64, f64),
+    Scroll(f64, f64),
+}
+
+#[cfg(feature = "rendering")]
+impl InputEvent {
+    /// The input events of the window, the others (resizing, focus, unknown keys...) are ignored
+    pub fn from_window_event(event: &glfw::WindowEvent) -> Option<Self> {
+        let action = |action: glfw::Action| Action::from_code(action as u8);
+        match *event {
+            glfw::WindowEvent::Key(key, _, state, _) => Some(InputEvent::Key(Key::from_code(key as i32)?, action(state)?)),
+            glfw::WindowEvent::MouseButton(button, state, _) => {
+                Some(InputEvent::MouseButton(MouseButton::from_code(button as i32)?, action(state)?))
+            }
+            glfw::WindowEvent::CursorPos(x, y) => Some(InputEvent::CursorPos(x, y)),
+            glfw::WindowEvent::Scroll(x, y) => Some(InputEvent::Scroll(x, y)),
+            _ => None,
+        }
+    }
+}
+
 pub struct InputCache {
-    pub events: Vec<WindowEvent>,
+    pub events: Vec<InputEvent>,
     pub last_cursor_pos: DVec2,
+    /// How far the cursor moved during the frame
     pub cursor_rel_pos: DVec2,
 
     pub key_states: HashMap<Key, Action>,
     pub mouse_button_states: HashMap<MouseButton, Action>,
+
+    pub key_bindings: KeyBindings,
 }
 
 impl Default for InputCache {
@@ -20,27 +145,41 @@ impl Default for InputCache {
             cursor_rel_pos: vec2(0.0, 0.0),
             key_states: HashMap::default(),
             mouse_button_states: HashMap::default(),
+            key_bindings: KeyBindings::default(),
         }
     }
 }
 
 impl InputCache {
-    pub fn handle_event(&mut self, event: &WindowEvent) {
-        self.events.push(event.clone());
+    pub fn new(key_bindings: KeyBindings) -> Self {
+        Self {
+            key_bindings,
+            ..Self::default()
+        }
+    }
+
+    /// Forgets the events of the previous frame
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+        self.cursor_rel_pos = vec2(0.0, 0.0);
+    }
+
+    pub fn handle_event(&mut self, event: &InputEvent) {
+        self.events.push(*event);
 
-        match event {
-            &glfw::WindowEvent::CursorPos(x, y) => {
-                self.cursor_rel_pos.x = x - self.last_cursor_pos.x;
-                self.cursor_rel_pos.y = y - self.last_cursor_pos.y;
+        match *event {
+            InputEvent::CursorPos(x, y) => {
+                self.cursor_rel_pos.x += x - self.last_cursor_pos.x;
+                self.cursor_rel_pos.y += y - self.last_cursor_pos.y;
                 self.last_cursor_pos.x = x;
                 self.last_cursor_pos.y = y;
             }
 
-            &glfw::WindowEvent::Key(key, _, action, _) => {
+            InputEvent::Key(key, action) => {
                 self.key_states.insert(key, action);
             }
 
-            &glfw::WindowEvent::MouseButton(button, action, _) => {
+            InputEvent::MouseButton(button, action) => {
                 self.mouse_button_states.insert(button, action);
             }
             _ => {}
@@ -58,4 +197,25 @@ impl InputCache {
         self.mouse_button_states.get(&mouse_button)
             .filter(|&&a| a == Action::Press || a == Action::Repeat).is_some()
     }
-}
\ No newline at end of file
+
+    /// Whether a key or a button bound to `action` is held down
+    pub fn is_action_active(&self, action: InputAction) -> bool {
+        self.key_bindings.bindings(action).iter().any(|&binding| match binding {
+            Binding::Key(key) => self.is_key_pressed(key),
+            Binding::MouseButton(button) => self.is_mouse_button_pressed(button),
+        })
+    }
+
+    /// The actions pressed, repeated or released during this frame, in order
+    pub fn action_events(&self) -> impl Iterator<Item=(InputAction, Action)> + '_ {
+        self.events.iter()
+            .filter_map(|event| match *event {
+                InputEvent::Key(key, action) => Some((Binding::Key(key), action)),
+                InputEvent::MouseButton(button, action) => Some((Binding::MouseButton(button), action)),
+                _ => None,
+            })
+            .flat_map(move |(binding, action)| {
+                self.key_bindings.actions(binding).map(move |input_action| (input_action, action))
+            })
+    }
+}
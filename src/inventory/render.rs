@@ -0,0 +1,188 @@
+use std::os::raw::c_void;
+
+use nalgebra::Matrix4;
+use nalgebra_glm::{Mat4, pi, vec3};
+
+use crate::block_model::BlockModels;
+use crate::chunk::BlockID;
+use crate::constants::{GUI_SCALING, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::inventory::{HOTBAR_SIZE, Inventory};
+use crate::shader_compilation::ShaderProgram;
+use crate::shapes::model_vertices;
+
+/// Draws the hotbar of an `Inventory`. Holds the GL objects of the items,
+/// the inventory itself is plain data
+pub struct HotbarRender {
+    item_renders: Vec<ItemRender>,
+    shown_items: [Option<BlockID>; HOTBAR_SIZE],
+}
+
+impl HotbarRender {
+    pub fn new() -> Self {
+        Self {
+            item_renders: (0..HOTBAR_SIZE).map(|_| ItemRender::new()).collect(),
+            shown_items: [None; HOTBAR_SIZE],
+        }
+    }
+
+    /// Rebuilds the meshes of the slots whose item changed
+    pub fn update(&mut self, inventory: &Inventory, models: &BlockModels) {
+        for (i, item_render) in self.item_renders.iter_mut().enumerate() {
+            let item = inventory.slots[i].map(|item_stack| item_stack.item);
+            if item != self.shown_items[i] {
+                self.shown_items[i] = item;
+                item_render.dirty = true;
+            }
+            if let Some(item) = item {
+                item_render.update_vbo_if_dirty(item, models);
+            }
+        }
+    }
+
+    pub fn draw_hotbar(&self, vao: u32, shader: &mut ShaderProgram) {
+        let model_matrix = {
+            let translate_matrix = Matrix4::new_translation(&vec3(
+                WINDOW_WIDTH as f32 / 2.0, 11.0 * GUI_SCALING, 0.0));
+            let scale_matrix: Mat4 = Matrix4::new_nonuniform_scaling(&vec3(182.0 * GUI_SCALING, 22.0 * GUI_SCALING, 1.0));
+            translate_matrix * scale_matrix
+        };
+        let projection_matrix = nalgebra_glm::ortho(
+            0.0, WINDOW_WIDTH as f32, 0.0, WINDOW_HEIGHT as f32, -5.0, 5.0);
+
+        shader.use_program();
+        shader.set_uniform_matrix4fv("model", model_matrix.as_ptr());
+        shader.set_uniform_matrix4fv("projection", projection_matrix.as_ptr());
+        shader.set_uniform1i("tex", 2);
+
+        gl_call!(gl::BindVertexArray(vao));
+        gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, 6));
+    }
+
+    pub fn draw_hotbar_selection_box(&self, vao: u32, selected_hotbar_slot: usize, shader: &mut ShaderProgram) {
+        let interslot_spacing = 20.0;
+        let hotbar_left_margin = WINDOW_WIDTH as f32 / 2.0 - 4.0 * interslot_spacing * GUI_SCALING;
+        let selection_box_x_pos = hotbar_left_margin + interslot_spacing * selected_hotbar_slot as f32 * GUI_SCALING;
+
+        let model_matrix = {
+            let translate_matrix = Matrix4::new_translation(&vec3(selection_box_x_pos, 11.0 * GUI_SCALING, 0.0));
+            let scale_matrix: Mat4 = Matrix4::new_nonuniform_scaling(&vec3(24.0 * GUI_SCALING, 24.0 * GUI_SCALING, 1.0));
+            translate_matrix * scale_matrix
+        };
+        let projection_matrix = nalgebra_glm::ortho(
+            0.0, WINDOW_WIDTH as f32, 0.0, WINDOW_HEIGHT as f32, -5.0, 5.0);
+
+        shader.use_program();
+        shader.set_uniform_matrix4fv("model", model_matrix.as_ptr());
+        shader.set_uniform_matrix4fv("projection", projection_matrix.as_ptr());
+        shader.set_uniform1i("tex", 2);
+
+        gl_call!(gl::BindVertexArray(vao));
+        gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, 6));
+    }
+
+    pub fn draw_hotbar_items(&self, shader: &mut ShaderProgram) {
+        let interslot_spacing = 20.0;
+        let hotbar_left_margin = WINDOW_WIDTH as f32 / 2.0 - 4.0 * interslot_spacing * GUI_SCALING;
+
+        let y = 11;
+        for (x, item_render) in self.item_renders.iter().enumerate() {
+            if self.shown_items[x].is_some() {
+                let item_x_pos = hotbar_left_margin + (x as f32) * interslot_spacing * GUI_SCALING;
+                item_render.draw(item_x_pos, (y as f32) * GUI_SCALING, shader);
+            }
+        }
+    }
+}
+
+/// Mesh of a block item drawn in the GUI
+pub struct ItemRender {
+    vao: u32,
+    vbo: u32,
+    vertex_count: i32,
+    // This is dirty when the VBO needs to be updated (at creation and when changing the block)
+    pub(crate) dirty: bool,
+    projection_matrix: Mat4,
+}
+
+impl ItemRender {
+    pub fn new() -> Self {
+        let mut vao = 0;
+        gl_call!(gl::CreateVertexArrays(1, &mut vao));
+
+        // Position
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 0));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 0, 3 as i32, gl::FLOAT, gl::FALSE, 0));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 0, 0));
+
+        // Texture coords
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 1));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 1, 3 as i32, gl::FLOAT, gl::FALSE, 3 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 1, 0));
+
+        // Normals
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 2));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 2, 3 as i32, gl::FLOAT, gl::FALSE, 6 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 2, 0));
+
+        // Tint
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 3));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 3, 3 as i32, gl::FLOAT, gl::FALSE, 9 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 3, 0));
+
+        let mut vbo = 0;
+        gl_call!(gl::CreateBuffers(1, &mut vbo));
+        gl_call!(gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, (12 * std::mem::size_of::<f32>()) as i32));
+
+        let projection_matrix = nalgebra_glm::ortho(
+            0.0, WINDOW_WIDTH as f32, 0.0, WINDOW_HEIGHT as f32, -1000.0, 1000.0);
+
+        ItemRender {
+            vao,
+            vbo,
+            vertex_count: 0,
+            dirty: true,
+            projection_matrix
+        }
+    }
+
+    pub fn update_vbo_if_dirty(&mut self, item: BlockID, models: &BlockModels) {
+        if self.dirty {
+            self.update_vbo(item, models);
+            self.dirty = false;
+        }
+    }
+
+    /// Meshes the model of the block, the size of the buffer depends on it
+    pub fn update_vbo(&mut self, item: BlockID, models: &BlockModels) {
+        let tint = item.tint().map_or([1.0; 3], |tint| tint.item_color());
+        let vbo_data = model_vertices(-0.5, -0.5, -0.5, &models.default_shape(item).model, tint);
+        self.vertex_count = (vbo_data.len() / 12) as i32;
+
+        gl_call!(gl::NamedBufferData(self.vbo,
+                    (vbo_data.len() * std::mem::size_of::<f32>()) as isize,
+                    vbo_data.as_ptr() as *const c_void,
+                    gl::DYNAMIC_DRAW));
+    }
+
+    pub fn draw(&self, x: f32, y: f32, shader: &mut ShaderProgram) {
+        let model_matrix = {
+            let translate_matrix = Matrix4::new_translation(&vec3(
+                x, y, 1.0));
+            let rotate_matrix = {
+                let rotate_y = Matrix4::from_euler_angles(0.0, pi::<f32>() / 4.0, 0.0); // 45°
+                let rotate_x = Matrix4::from_euler_angles(pi::<f32>() / 6.0, 0.0, 0.0); // 30°
+                rotate_x * rotate_y
+            };
+            let scale_matrix: Mat4 = Matrix4::new_nonuniform_scaling(&(GUI_SCALING * vec3(10.0, 10.0, 10.0)));
+            translate_matrix * rotate_matrix * scale_matrix
+        };
+
+        shader.use_program();
+        shader.set_uniform_matrix4fv("model", model_matrix.as_ptr());
+        shader.set_uniform_matrix4fv("projection", self.projection_matrix.as_ptr());
+        shader.set_uniform1i("tex", 0);
+
+        gl_call!(gl::BindVertexArray(self.vao));
+        gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, self.vertex_count));
+    }
+}
\ No newline at end of file
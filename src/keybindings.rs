@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::input::{Key, MouseButton};
+
+/// What the player wants to do, independently of the keys bound to it.
+/// The actions bound to the same key happen in the order they are declared in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InputAction {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sneak,
+    Sprint,
+    Attack,
+    Use,
+    PickBlock,
+    /// From 0 to 8
+    HotbarSlot(u8),
+    Pause,
+    /// Loads the resource packs listed in `resourcepacks.txt` again
+    ReloadResourcePacks,
+    /// Compiles the shaders again, they are also reloaded when their files change
+    ReloadShaders,
+    /// Saves a screenshot in `screenshots/`
+    Screenshot,
+    /// Saves a screenshot `SCREENSHOT_TILES` times the size of the window, without the GUI
+    HighResolutionScreenshot,
+    /// Shows or hides the debug overlay
+    ToggleDebugOverlay,
+    Quit,
+}
+
+impl InputAction {
+    /// Parses the name used in the keybinding file: `move_forward`, `hotbar_1`...
+    pub fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "move_forward" => InputAction::MoveForward,
+            "move_backward" => InputAction::MoveBackward,
+            "move_left" => InputAction::MoveLeft,
+            "move_right" => InputAction::MoveRight,
+            "jump" => InputAction::Jump,
+            "sneak" => InputAction::Sneak,
+            "sprint" => InputAction::Sprint,
+            "attack" => InputAction::Attack,
+            "use" => InputAction::Use,
+            "pick_block" => InputAction::PickBlock,
+            "pause" => InputAction::Pause,
+            "reload_resource_packs" => InputAction::ReloadResourcePacks,
+            "reload_shaders" => InputAction::ReloadShaders,
+            "screenshot" => InputAction::Screenshot,
+            "high_resolution_screenshot" => InputAction::HighResolutionScreenshot,
+            "toggle_debug_overlay" => InputAction::ToggleDebugOverlay,
+            "quit" => InputAction::Quit,
+            _ => {
+                let slot = name.strip_prefix("hotbar_")?.parse::<u8>().ok()?;
+                if !(1..=9).contains(&slot) {
+                    return None;
+                }
+                InputAction::HotbarSlot(slot - 1)
+            }
+        };
+        Some(action)
+    }
+}
+
+/// A key or a mouse button
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(Key),
+    MouseButton(MouseButton),
+}
+
+impl Binding {
+    /// Parses the GLFW name of a key (`W`, `Space`, `LeftShift`, `Num1`...) or `Mouse1` to `Mouse8`
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(button) = name.strip_prefix("Mouse") {
+            let button = button.parse::<i32>().ok()?;
+            return MouseButton::from_code(button - 1).map(Binding::MouseButton);
+        }
+        Key::from_name(name).map(Binding::Key)
+    }
+}
+
+/// The keys and mouse buttons bound to every action, ordered so that the scripts and the replays
+/// trigger the actions of a key in the same order on every run
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    bindings: BTreeMap<InputAction, Vec<Binding>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut bindings = BTreeMap::new();
+        bindings.insert(InputAction::MoveForward, vec![Binding::Key(Key::W)]);
+        bindings.insert(InputAction::MoveBackward, vec![Binding::Key(Key::S)]);
+        bindings.insert(InputAction::MoveLeft, vec![Binding::Key(Key::A)]);
+        bindings.insert(InputAction::MoveRight, vec![Binding::Key(Key::D)]);
+        bindings.insert(InputAction::Jump, vec![Binding::Key(Key::Space)]);
+        bindings.insert(InputAction::Sneak, vec![Binding::Key(Key::LeftShift)]);
+        bindings.insert(InputAction::Sprint, vec![Binding::Key(Key::LeftControl)]);
+        bindings.insert(InputAction::Attack, vec![Binding::MouseButton(MouseButton::Button1)]);
+        bindings.insert(InputAction::Use, vec![Binding::MouseButton(MouseButton::Button2)]);
+        bindings.insert(InputAction::PickBlock, vec![Binding::MouseButton(MouseButton::Button3)]);
+        let number_keys = [Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9];
+        for (slot, &key) in number_keys.iter().enumerate() {
+            bindings.insert(InputAction::HotbarSlot(slot as u8), vec![Binding::Key(key)]);
+        }
+        bindings.insert(InputAction::Pause, vec![Binding::Key(Key::P)]);
+        bindings.insert(InputAction::ReloadResourcePacks, vec![Binding::Key(Key::F4)]);
+        bindings.insert(InputAction::ReloadShaders, vec![Binding::Key(Key::F5)]);
+        bindings.insert(InputAction::Screenshot, vec![Binding::Key(Key::F2)]);
+        bindings.insert(InputAction::HighResolutionScreenshot, vec![Binding::Key(Key::F6)]);
+        bindings.insert(InputAction::ToggleDebugOverlay, vec![Binding::Key(Key::F3)]);
+        bindings.insert(InputAction::Quit, vec![Binding::Key(Key::Escape)]);
+        Self { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Reads a keybinding file made of `action = binding, binding...` lines, `#` starts a comment.
+    /// The actions missing from the file keep their default bindings
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut key_bindings = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (action, bindings) = match (parts.next(), parts.next()) {
+                (Some(action), Some(bindings)) => (action.trim(), bindings),
+                _ => return Err(format!("line {}: expected `action = key`", i + 1)),
+            };
+            let action = InputAction::from_name(action)
+                .ok_or_else(|| format!("line {}: unknown action {}", i + 1, action))?;
+            let bindings = bindings.split(',')
+                .map(str::trim)
+                .filter(|binding| !binding.is_empty())
+                .map(|binding| Binding::from_name(binding)
+                    .ok_or_else(|| format!("line {}: unknown key {}", i + 1, binding)))
+                .collect::<Result<Vec<_>, _>>()?;
+            key_bindings.bindings.insert(action, bindings);
+        }
+        Ok(key_bindings)
+    }
+
+    /// The bindings in `path`, or the default ones if there is no such file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn bindings(&self, action: InputAction) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    pub fn bind(&mut self, action: InputAction, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    /// Every action bound to `binding`, in the order of `InputAction`
+    pub fn actions(&self, binding: Binding) -> impl Iterator<Item=InputAction> + '_ {
+        self.bindings.iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(&action, _)| action)
+    }
+}
+
+/// Detects two presses of the same action in a short interval, like double tapping forward to sprint.
+/// A third press doesn't trigger again, it starts a new double tap
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DoubleTap {
+    /// Tick of the last press, None until the first one
+    last_pressed: Option<u64>,
+    triggered: bool,
+}
+
+impl DoubleTap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a press on `tick`. Returns whether it completes a double tap, `interval` ticks at most after the first press
+    pub fn press(&mut self, tick: u64, interval: u64) -> bool {
+        let is_double_tap = if self.triggered {
+            self.triggered = false;
+            false
+        } else {
+            self.triggered = match self.last_pressed {
+                Some(last_pressed) => tick.saturating_sub(last_pressed) <= interval,
+                None => false,
+            };
+            self.triggered
+        };
+        self.last_pressed = Some(tick);
+        is_double_tap
+    }
+}
@@ -0,0 +1,89 @@
+//! The Meinkraft engine: chunk storage, world generation, physics and networking.
+//!
+//! The main entry points are:
+//! * the world: [`ChunkManager`] holding [`ChunkColumn`]s of [`BlockID`]s, generated by a [`ChunkPipeline`]
+//!   and saved by a [`WorldStorage`]
+//! * physics: [`AABB`], [`raycast`](raycast::raycast), the world tick counter [`Ticks`] shared by the client
+//!   and the server, and the [`Interpolator`] of everything simulated on ticks
+//! * networking: the [`network::server::Server`] and the client side [`network::client::ServerConnection`]
+//!
+//! Everything drawn with OpenGL (and the window) is behind the `rendering` feature, which the game needs
+//! but the dedicated server, the tools and the tests don't.
+#![feature(entry_insert)]
+#![feature(vec_remove_item)]
+#![feature(slice_fill)]
+#![feature(binary_heap_drain_sorted)]
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
+extern crate specs;
+
+#[cfg(feature = "rendering")]
+#[macro_use]
+pub mod debugging;
+#[cfg(feature = "rendering")]
+pub mod draw_commands;
+#[cfg(feature = "rendering")]
+pub mod shader_compilation;
+pub mod shader_preprocessor;
+pub mod shapes;
+pub mod util;
+pub mod chunk_manager;
+pub mod chunk;
+#[cfg(feature = "rendering")]
+pub mod chunk_mesh_arena;
+pub mod chunk_pipeline;
+pub mod raycast;
+pub mod block_model;
+pub mod block_state;
+pub mod physics;
+pub mod physics_body;
+pub mod aabb;
+pub mod frustum;
+pub mod constants;
+pub mod input;
+pub mod keybindings;
+pub mod replay;
+#[cfg(feature = "rendering")]
+pub mod window;
+#[cfg(feature = "rendering")]
+pub mod texture_pack;
+#[cfg(feature = "rendering")]
+pub mod screenshot;
+#[cfg(feature = "rendering")]
+pub mod offscreen;
+#[cfg(all(feature = "rendering", target_os = "linux"))]
+pub mod egl;
+#[cfg(feature = "rendering")]
+pub mod text;
+pub mod player;
+pub mod resource_pack;
+pub mod texture_animation;
+pub mod ticks;
+pub mod types;
+#[cfg(feature = "rendering")]
+pub mod gui;
+pub mod inventory;
+pub mod ambient_occlusion;
+pub mod biome;
+pub mod timer;
+pub mod debug_info;
+pub mod particle_system;
+#[cfg(feature = "rendering")]
+pub mod particle_renderer;
+pub mod ecs;
+#[cfg(feature = "rendering")]
+pub mod main_hand;
+pub mod world_generation;
+pub mod world_storage;
+pub mod network;
+
+pub use aabb::AABB;
+pub use chunk::{BlockID, ChunkColumn};
+pub use chunk_manager::ChunkManager;
+pub use chunk_pipeline::{ChunkPipeline, ColumnStage};
+pub use physics::{Interpolatable, Interpolator};
+pub use physics_body::PhysicsBody;
+pub use ticks::Ticks;
+pub use world_storage::WorldStorage;
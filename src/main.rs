@@ -1,180 +1,251 @@
-#![feature(entry_insert)]
-#![feature(vec_remove_item)]
-#![feature(slice_fill)]
-#![feature(binary_heap_drain_sorted)]
-#[macro_use]
-extern crate lazy_static;
 #[macro_use]
 extern crate log;
+extern crate meinkraft;
 extern crate pretty_env_logger;
-extern crate specs;
 
-use core::ffi::c_void;
-use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
 
-use nalgebra_glm::vec3;
 use specs::{Builder, DispatcherBuilder, World, WorldExt};
 
-use ecs::components::*;
-use ecs::systems::*;
-use timer::Timer;
-
-use crate::chunk_manager::ChunkManager;
-use crate::constants::*;
-use crate::debugging::*;
-use crate::fps_counter::FpsCounter;
-use crate::gui::{create_gui_icons_texture, create_widgets_texture};
-use crate::input::InputCache;
-use crate::inventory::Inventory;
-use crate::main_hand::MainHand;
-use crate::particle_system::ParticleSystem;
-use crate::physics::Interpolator;
-use crate::player::{PlayerPhysicsState, PlayerState};
-use crate::shader_compilation::ShaderProgram;
-use crate::texture_pack::generate_array_texture;
-use crate::types::Shaders;
-use crate::window::create_window;
-use crate::ecs::systems::chunk_loading::ChunkLoading;
-use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
+use meinkraft::ecs::components::*;
+use meinkraft::ecs::systems::*;
+use meinkraft::ticks::Ticks;
+use meinkraft::timer::Timer;
 
-#[macro_use]
-pub mod debugging;
-pub mod draw_commands;
-pub mod shader_compilation;
-pub mod shapes;
-pub mod util;
-pub mod chunk_manager;
-pub mod chunk;
-pub mod raycast;
-pub mod block_texture_faces;
-pub mod physics;
-pub mod aabb;
-pub mod constants;
-pub mod input;
-pub mod window;
-pub mod texture_pack;
-pub mod player;
-pub mod types;
-pub mod gui;
-pub mod inventory;
-pub mod ambient_occlusion;
-pub mod timer;
-pub mod particle_system;
-pub mod ecs;
-pub mod main_hand;
-use parking_lot::deadlock;
+use meinkraft::block_model::BlockModels;
+use meinkraft::chunk_manager::ChunkManager;
+use meinkraft::chunk_mesh_arena::ChunkMeshArena;
+use meinkraft::constants::*;
+use meinkraft::debug_info::FrameTimes;
+use meinkraft::input::InputCache;
+use meinkraft::inventory::Inventory;
+use meinkraft::keybindings::KeyBindings;
+use meinkraft::main_hand::MainHand;
+use meinkraft::network::client::ServerConnection;
+use meinkraft::network::protocol::ProtocolError;
+use meinkraft::network::server::Server;
+use meinkraft::particle_system::ParticleSystem;
+use meinkraft::physics::Interpolator;
+use meinkraft::physics_body::PhysicsBody;
+use meinkraft::player::PlayerState;
+use meinkraft::resource_pack::ResourcePacks;
+use meinkraft::replay::{Replay, ReplayPlayer, ReplayRecorder};
+use meinkraft::screenshot::Screenshots;
+use meinkraft::shader_compilation::compile_shaders;
+use meinkraft::shader_preprocessor::ShaderPreprocessor;
+use meinkraft::texture_pack::TexturePack;
+use meinkraft::types::ParticleSystems;
+use meinkraft::window::{create_window, init_gl_state};
+use meinkraft::world_storage::WorldStorage;
+use meinkraft::chunk_pipeline::ChunkPipelineStats;
+use std::sync::Arc;
 
 fn main() {
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(10));
-            let deadlocks = deadlock::check_deadlock();
-            if deadlocks.is_empty() {
-                continue;
+    pretty_env_logger::init();
+
+    // `--connect <address>` joins a server, otherwise one is started in the background on `--world`
+    let mut server_address = None;
+    let mut player_name = "Player".to_string();
+    let mut world_directory = "world".to_string();
+    let mut record_path = None;
+    let mut replay_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--connect" => server_address = args.next(),
+            "--name" => player_name = args.next().unwrap_or(player_name),
+            "--world" => world_directory = args.next().unwrap_or(world_directory),
+            "--record" => record_path = args.next(),
+            "--replay" => replay_path = args.next(),
+            _ => warn!("Unknown argument {}", arg),
+        }
+    }
+
+    // A replay is played in a new world generated from the recorded seed, with the recorded player and columns
+    let replay = match replay_path {
+        Some(path) => match Replay::load(&path) {
+            Ok(replay) => {
+                world_directory = std::env::temp_dir().join("meinkraft-replay").to_string_lossy().into_owned();
+                let _ = std::fs::remove_dir_all(&world_directory);
+                server_address = None;
+                Some(replay)
             }
+            Err(err) => {
+                error!("Couldn't load the replay {}: {}", path, err);
+                return;
+            }
+        },
+        None => None,
+    };
+    let mut recorder = None;
+
+    let block_models = match BlockModels::load(ASSET_DIRECTORY) {
+        Ok(block_models) => Arc::new(block_models),
+        Err(err) => {
+            error!("Couldn't load the block models: {}", err);
+            return;
+        }
+    };
 
-            println!("{} deadlocks detected", deadlocks.len());
-            for (i, threads) in deadlocks.iter().enumerate() {
-                println!("Deadlock #{}", i);
-                for t in threads {
-                    println!("Thread Id {:#?}", t.thread_id());
-                    println!("{:#?}", t.backtrace());
+    // The saved state of the world is only known when playing on a local world
+    if record_path.is_some() && server_address.is_some() {
+        warn!("Only the sessions on a local world can be recorded");
+    }
+
+    let server_address = match server_address {
+        Some(address) if address.contains(':') => address,
+        Some(address) => format!("{}:{}", address, DEFAULT_SERVER_PORT),
+        None => {
+            let storage = match &replay {
+                Some(replay) => WorldStorage::open_with_seed(&world_directory, replay.seed)
+                    .and_then(|storage| replay.restore_world(&storage, &player_name).map(|()| storage)),
+                None => WorldStorage::open(&world_directory),
+            };
+            let server = storage
+                .map_err(ProtocolError::from)
+                .and_then(|storage| {
+                    if let Some(path) = &record_path {
+                        recorder = match ReplayRecorder::create(path, &storage, &player_name) {
+                            Ok(recorder) => {
+                                info!("Recording the session to {}", path);
+                                Some(recorder)
+                            }
+                            Err(err) => {
+                                error!("Couldn't create the replay {}: {}", path, err);
+                                None
+                            }
+                        };
+                    }
+                    Server::bind(("127.0.0.1", 0), RENDER_DISTANCE, storage, Arc::clone(&block_models))
+                })
+                .map(Server::allow_flying)
+                // The server would correct the replayed movements differently on every run
+                .map(|server| if replay.is_some() { server.without_movement_corrections() } else { server });
+            match server {
+                Ok(server) => {
+                    let address = server.local_address().to_string();
+                    // Chunk columns are built on the stack before being boxed
+                    thread::Builder::new()
+                        .name("server".to_string())
+                        .stack_size(16 * 1024 * 1024)
+                        .spawn(move || server.run(crossbeam_channel::never()))
+                        .unwrap();
+                    address
+                }
+                Err(err) => {
+                    error!("Couldn't start the server: {}", err);
+                    return;
                 }
             }
         }
-    });
+    };
 
-    pretty_env_logger::init();
+    let server_connection = match ServerConnection::connect(&server_address, &player_name, Duration::from_secs(10)) {
+        Ok(server_connection) => server_connection,
+        Err(err) => {
+            error!("Couldn't connect to {}: {}", server_address, err);
+            return;
+        }
+    };
+    let spawn_position = server_connection.spawn_position;
+    let can_fly = server_connection.can_fly;
 
     let mut world = World::new();
     world.register::<PlayerState>();
-    world.register::<Interpolator<PlayerPhysicsState>>();
+    world.register::<Interpolator<PhysicsBody>>();
     world.register::<Inventory>();
     world.register::<MainHand>();
     world.register::<MainHandItemChanged>();
 
+    let is_replaying = replay.is_some();
+    let shader_preprocessor = ShaderPreprocessor::from_settings();
+
     let mut dispatcher = DispatcherBuilder::new()
         .with_thread_local({
             let (glfw, window, events) = create_window(WINDOW_WIDTH, WINDOW_HEIGHT, WINDOW_NAME);
-
-            gl_call!(gl::Enable(gl::DEBUG_OUTPUT));
-            gl_call!(gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS));
-            gl_call!(gl::DebugMessageCallback(Some(debug_message_callback), 0 as *const c_void));
-            gl_call!(gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE, 0, 0 as *const u32, gl::TRUE));
-            gl_call!(gl::Enable(gl::CULL_FACE));
-            gl_call!(gl::CullFace(gl::BACK));
-            gl_call!(gl::Enable(gl::DEPTH_TEST));
-            gl_call!(gl::Enable(gl::BLEND));
-            let window_size = window.get_size();
-            gl_call!(gl::Viewport(0, 0, window_size.0, window_size.1));
+            init_gl_state(&window);
 
             ReadWindowEvents {
                 glfw,
                 window,
                 events,
+                replay: replay.map(ReplayPlayer::new),
             }
         })
+        .with_thread_local(ReceiveServerMessages);
+    // A replay schedules its recorded ticks itself
+    if !is_replaying {
+        dispatcher.add_thread_local(AdvanceTicks);
+    }
+    let mut dispatcher = dispatcher
+        .with_thread_local(RecordReplay::new(recorder))
         .with_thread_local(InventoryHandleInput)
         .with_thread_local(HandlePlayerInput)
         .with_thread_local(UpdatePlayerPhysics)
+        .with_thread_local(UpdatePhysics)
         .with_thread_local(UpdatePlayerState)
         .with_thread_local(PlaceAndBreakBlocks)
         .with_thread_local(UpdateMainHand)
+        .with_thread_local(UpdateParticles)
         .with_thread_local(ChunkLoading::new())
+        .with_thread_local(UploadChunks)
+        .with_thread_local(SendClientMessages)
+        .with_thread_local(ReloadResourcePacks)
+        .with_thread_local(ReloadShaders::new(shader_preprocessor.clone()))
+        .with_thread_local(AnimateTextures)
 
         .with_thread_local(RenderChunks)
-        .with_thread_local(RenderParticles)
+        .with_thread_local(RenderRemotePlayers::new())
+        .with_thread_local(RenderParticles::new())
         .with_thread_local(RenderBlockOutline::new())
         .with_thread_local(RenderMainHand::new())
         .with_thread_local(RenderGUI::new())
+        .with_thread_local(RenderDebugOverlay::new())
+        .with_thread_local(TakeScreenshots)
 
         .with_thread_local(AdvanceGlobalTime)
         .with_thread_local(FpsCounter::new())
         .build();
 
 
-    world.insert(InputCache::default());
-    world.insert(Timer::default());
+    world.insert(InputCache::new(KeyBindings::load("keybindings.txt").unwrap_or_else(|err| {
+        error!("Couldn't load keybindings.txt, using the default keys: {}", err);
+        KeyBindings::default()
+    })));
+    world.insert(if is_replaying { Timer::manual() } else { Timer::default() });
+    world.insert(Ticks::starting_at(server_connection.tick));
+    world.insert(TexturePack::load(ResourcePacks::load_enabled(), Arc::clone(&block_models)));
     world.insert({
-        let (item_array_texture, texture_pack) = generate_array_texture();
-        gl_call!(gl::BindTextureUnit(0, item_array_texture));
-        texture_pack
-    });
-    world.insert({
-        let mut particle_systems: HashMap<&str, ParticleSystem> = HashMap::new();
-        particle_systems.insert("block_particles", ParticleSystem::new(500));
+        let mut particle_systems = ParticleSystems::new();
+        particle_systems.insert("block_particles", ParticleSystem::new(MAX_PARTICLES));
         particle_systems
     });
-    world.insert({
-        let mut shaders_resource = Shaders::new();
-        shaders_resource.insert("voxel_shader", ShaderProgram::compile("src/shaders/voxel.vert", "src/shaders/voxel.frag"));
-        shaders_resource.insert("gui_shader", ShaderProgram::compile("src/shaders/gui.vert", "src/shaders/gui.frag"));
-        shaders_resource.insert("outline_shader", ShaderProgram::compile("src/shaders/outline.vert", "src/shaders/outline.frag"));
-        shaders_resource.insert("item_shader", ShaderProgram::compile("src/shaders/item.vert", "src/shaders/item.frag"));
-        shaders_resource.insert("particle_shader", ShaderProgram::compile("src/shaders/particle.vert", "src/shaders/particle.frag"));
-        shaders_resource.insert("hand_shader", ShaderProgram::compile("src/shaders/hand.vert", "src/shaders/hand.frag"));
-        shaders_resource
-    });
-    world.insert(Arc::new(ChunkManager::new()));
-
-    {
-        let gui_icons_texture = create_gui_icons_texture();
-        gl_call!(gl::ActiveTexture(gl::TEXTURE0 + 1));
-        gl_call!(gl::BindTexture(gl::TEXTURE_2D, gui_icons_texture));
-
-        let gui_widgets_texture = create_widgets_texture();
-        gl_call!(gl::ActiveTexture(gl::TEXTURE0 + 2));
-        gl_call!(gl::BindTexture(gl::TEXTURE_2D, gui_widgets_texture));
+    match compile_shaders(&shader_preprocessor) {
+        Ok(shaders) => world.insert(shaders),
+        Err(err) => {
+            error!("Couldn't compile the shaders: {}", err);
+            return;
+        }
     }
+    world.insert(Arc::new(ChunkManager::new(Arc::clone(&block_models))));
+    world.insert(block_models);
+    world.insert(ChunkPipelineStats::default());
+    world.insert(ChunkUploads::default());
+    world.insert(Screenshots::default());
+    world.insert(FrameTimes::default());
+    world.insert(server_connection);
+    world.insert(NetworkOutbox::default());
+    world.insert(IncomingColumns::default());
+    world.insert(RemotePlayers::default());
+    world.insert(ConnectionLost::default());
+    world.insert(ChunkMeshArena::new(CHUNK_MESH_ARENA_CAPACITY));
 
+    let mut player_state = PlayerState::new();
+    player_state.can_fly = can_fly;
     let _player = world.create_entity()
-        .with(PlayerState::new())
-        .with(Interpolator::new(
-            1.0 / PHYSICS_TICKRATE,
-            PlayerPhysicsState::new_at_position(vec3(8.0f32, 195.0, 8.0)),
-        ))
+        .with(player_state)
+        .with(Interpolator::new(PhysicsBody::new_player(spawn_position)))
         .with(Inventory::new())
         .with(MainHand::new())
         .with(MainHandItemChanged)
@@ -182,5 +253,9 @@ fn main() {
 
     loop {
         dispatcher.dispatch(&world);
+        if let Some(err) = &world.read_resource::<ConnectionLost>().error {
+            error!("Lost the connection to the server: {}", err);
+            std::process::exit(1);
+        }
     }
 }
@@ -3,9 +3,9 @@ use core::ffi::c_void;
 use specs::Component;
 use specs::DenseVecStorage;
 
+use crate::block_model::BlockModels;
 use crate::chunk::BlockID;
-use crate::shapes::centered_unit_cube;
-use crate::types::TexturePack;
+use crate::shapes::model_vertices;
 
 #[derive(Component)]
 pub struct MainHand {
@@ -35,9 +35,9 @@ impl MainHand {
         self.render.dirty = true;
     }
 
-    pub fn update_if_dirty(&mut self, texture_pack: &TexturePack) {
+    pub fn update_if_dirty(&mut self, models: &BlockModels) {
         if let Some(item) = self.showing_item {
-            self.render.update_vbo_if_dirty(item, &texture_pack);
+            self.render.update_vbo_if_dirty(item, models);
         }
     }
 }
@@ -45,6 +45,7 @@ impl MainHand {
 pub struct MainHandRender {
     pub vao: u32,
     pub vbo: u32,
+    pub vertex_count: i32,
     pub dirty: bool,
 }
 
@@ -68,28 +69,34 @@ impl MainHandRender {
         gl_call!(gl::VertexArrayAttribFormat(vao, 2, 3 as i32, gl::FLOAT, gl::FALSE, 6 * std::mem::size_of::<f32>() as u32));
         gl_call!(gl::VertexArrayAttribBinding(vao, 2, 0));
 
+        // Tint
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 3));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 3, 3 as i32, gl::FLOAT, gl::FALSE, 9 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 3, 0));
+
         let mut vbo = 0;
         gl_call!(gl::CreateBuffers(1, &mut vbo));
-        gl_call!(gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, (9 * std::mem::size_of::<f32>()) as i32));
+        gl_call!(gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, (12 * std::mem::size_of::<f32>()) as i32));
 
         Self {
             vao,
             vbo,
+            vertex_count: 0,
             dirty: true,
         }
     }
 
-    pub fn update_vbo_if_dirty(&mut self, item: BlockID, texture_pack: &TexturePack) {
+    pub fn update_vbo_if_dirty(&mut self, item: BlockID, models: &BlockModels) {
         if self.dirty {
-            self.update_vbo(item, &texture_pack);
+            self.update_vbo(item, models);
             self.dirty = false;
         }
     }
 
-    pub fn update_vbo(&mut self, item: BlockID, texture_pack: &TexturePack) {
-        let vbo_data = centered_unit_cube(
-            -0.5, -0.5, -0.5,
-            texture_pack.get(&item).unwrap().get_uv_of_every_face());
+    pub fn update_vbo(&mut self, item: BlockID, models: &BlockModels) {
+        let tint = item.tint().map_or([1.0; 3], |tint| tint.item_color());
+        let vbo_data = model_vertices(-0.5, -0.5, -0.5, &models.default_shape(item).model, tint);
+        self.vertex_count = (vbo_data.len() / 12) as i32;
 
         gl_call!(gl::NamedBufferData(self.vbo,
                     (vbo_data.len() * std::mem::size_of::<f32>() as usize) as isize,
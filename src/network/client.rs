@@ -0,0 +1,83 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nalgebra_glm::Vec3;
+
+use crate::network::connection::Connection;
+use crate::network::protocol::{ClientMessage, PROTOCOL_VERSION, ProtocolError, ServerMessage};
+
+/// The connection of the client to the server, plus the state needed for movement prediction
+pub struct ServerConnection {
+    connection: Connection<ClientMessage, ServerMessage>,
+    pub player_id: u32,
+    pub spawn_position: Vec3,
+    /// Tick of the server when it accepted the login, the first tick of the client
+    pub tick: u64,
+    /// Whether the server lets the player fly, when it logged in
+    pub can_fly: bool,
+    /// Sequence number of the last movement sent
+    pub movement_sequence: u32,
+    /// Id of the last correction received from the server
+    pub last_correction: u32,
+    /// Messages received during the login, after the server accepted it
+    pending_messages: Vec<ServerMessage>,
+}
+
+impl ServerConnection {
+    /// Connects and logs in, blocks until the server answers or `timeout` elapses
+    pub fn connect<A: ToSocketAddrs>(address: A, name: &str, timeout: Duration) -> Result<Self, ProtocolError> {
+        let stream = TcpStream::connect(address)?;
+        let mut connection = Connection::new(stream)?;
+        connection.send(&ClientMessage::Login {
+            protocol_version: PROTOCOL_VERSION,
+            name: name.to_string(),
+        });
+
+        let start = Instant::now();
+        loop {
+            connection.flush()?;
+            let mut messages = connection.receive()?.into_iter();
+            if let Some(message) = messages.next() {
+                match message {
+                    ServerMessage::LoginAccepted { player_id, position, tick, can_fly } => {
+                        info!("Logged in as player {}", player_id);
+                        return Ok(Self {
+                            connection,
+                            player_id,
+                            spawn_position: position,
+                            tick,
+                            can_fly,
+                            movement_sequence: 0,
+                            last_correction: 0,
+                            pending_messages: messages.collect(),
+                        });
+                    }
+                    ServerMessage::LoginRejected { reason } => return Err(ProtocolError::LoginRejected(reason)),
+                    _ => return Err(ProtocolError::UnexpectedMessage),
+                }
+            }
+
+            if start.elapsed() > timeout {
+                return Err(ProtocolError::Timeout);
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Queues a message, it's actually sent by `flush`
+    pub fn send(&mut self, message: &ClientMessage) {
+        self.connection.send(message);
+    }
+
+    pub fn flush(&mut self) -> Result<(), ProtocolError> {
+        self.connection.flush()
+    }
+
+    /// Every message received since the last call
+    pub fn receive(&mut self) -> Result<Vec<ServerMessage>, ProtocolError> {
+        let mut messages = std::mem::take(&mut self.pending_messages);
+        messages.extend(self.connection.receive()?);
+        Ok(messages)
+    }
+}
@@ -0,0 +1,52 @@
+use nalgebra_glm::{Vec3, vec3};
+
+pub const HELP: &str = "\
+help                     Shows this message
+list                     Lists the connected players
+save                     Saves the world
+stop                     Saves the world and stops the server
+kick <player>            Disconnects a player
+tp <player> <x> <y> <z>  Teleports a player
+fly <player> on|off      Allows or forbids a player to fly";
+
+/// Admin commands typed in the console of the server
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerCommand {
+    Help,
+    List,
+    Save,
+    Stop,
+    Kick(String),
+    Teleport(String, Vec3),
+    Fly(String, bool),
+}
+
+impl ServerCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let arguments = words.collect::<Vec<_>>();
+
+        let command = match (command, arguments.as_slice()) {
+            ("help", []) => ServerCommand::Help,
+            ("list", []) => ServerCommand::List,
+            ("save", []) => ServerCommand::Save,
+            ("stop", []) => ServerCommand::Stop,
+            ("kick", [name]) => ServerCommand::Kick(name.to_string()),
+            ("tp", [name, x, y, z]) => {
+                let parse = |value: &str| value.parse::<f32>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| format!("Invalid coordinate {}", value));
+                ServerCommand::Teleport(name.to_string(), vec3(parse(x)?, parse(y)?, parse(z)?))
+            }
+            ("fly", [name, "on"]) => ServerCommand::Fly(name.to_string(), true),
+            ("fly", [name, "off"]) => ServerCommand::Fly(name.to_string(), false),
+            ("", _) => return Err("Type help for a list of commands".to_string()),
+            ("help", _) | ("list", _) | ("save", _) | ("stop", _) | ("kick", _) | ("tp", _) | ("fly", _) =>
+                return Err(format!("Wrong arguments for {}, type help for the usage", command)),
+            _ => return Err(format!("Unknown command {}, type help for a list of commands", command)),
+        };
+        Ok(command)
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpStream};
+
+use crate::network::protocol::{MAX_FRAME_SIZE, Message, ProtocolError};
+
+/// A non-blocking TCP stream carrying length-prefixed frames.
+/// Every frame is a big endian u32 length followed by one encoded message.
+/// `S` is the type of the messages sent, `R` the type of the messages received
+pub struct Connection<S: Message, R: Message> {
+    stream: TcpStream,
+    peer_address: SocketAddr,
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+    _messages: PhantomData<(S, R)>,
+}
+
+impl<S: Message, R: Message> Connection<S, R> {
+    pub fn new(stream: TcpStream) -> Result<Self, ProtocolError> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            peer_address: stream.peer_addr()?,
+            stream,
+            read_buffer: Vec::new(),
+            write_buffer: Vec::new(),
+            _messages: PhantomData,
+        })
+    }
+
+    pub fn peer_address(&self) -> SocketAddr {
+        self.peer_address
+    }
+
+    /// Queues a message, it's actually sent by `flush`
+    pub fn send(&mut self, message: &S) {
+        let start = self.write_buffer.len();
+        self.write_buffer.extend_from_slice(&[0; 4]);
+        message.encode(&mut self.write_buffer);
+        let length = (self.write_buffer.len() - start - 4) as u32;
+        self.write_buffer[start..start + 4].copy_from_slice(&length.to_be_bytes());
+    }
+
+    /// Writes as much of the queued messages as the socket accepts without blocking
+    pub fn flush(&mut self) -> Result<(), ProtocolError> {
+        while !self.write_buffer.is_empty() {
+            match self.stream.write(&self.write_buffer) {
+                Ok(0) => return Err(ProtocolError::Disconnected),
+                Ok(n) => {
+                    self.write_buffer.drain(..n);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bytes queued but not sent yet
+    pub fn pending_bytes(&self) -> usize {
+        self.write_buffer.len()
+    }
+
+    /// Every complete message received since the last call
+    pub fn receive(&mut self) -> Result<Vec<R>, ProtocolError> {
+        let mut chunk = [0u8; 16 * 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ProtocolError::Disconnected),
+                Ok(n) => self.read_buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while self.read_buffer.len() - offset >= 4 {
+            let header = &self.read_buffer[offset..offset + 4];
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            if length > MAX_FRAME_SIZE {
+                return Err(ProtocolError::FrameTooLarge(length));
+            }
+            if self.read_buffer.len() - offset - 4 < length {
+                break;
+            }
+            messages.push(R::decode(&self.read_buffer[offset + 4..offset + 4 + length])?);
+            offset += 4 + length;
+        }
+        self.read_buffer.drain(..offset);
+        Ok(messages)
+    }
+}
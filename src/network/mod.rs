@@ -0,0 +1,5 @@
+pub mod client;
+pub mod commands;
+pub mod connection;
+pub mod protocol;
+pub mod server;
@@ -0,0 +1,526 @@
+use std::fmt;
+use std::io;
+
+use nalgebra_glm::{Vec3, vec3};
+
+use crate::biome::Climate;
+use crate::block_state::BlockState;
+use crate::chunk::{BlockID, COLUMN_VOLUME};
+use crate::player::MovementInput;
+
+/// Bumped on every incompatible change to the messages below
+pub const PROTOCOL_VERSION: u16 = 7;
+
+/// Frames bigger than this are considered corrupted
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    Disconnected,
+    Truncated,
+    FrameTooLarge(usize),
+    UnknownMessage(u8),
+    UnexpectedMessage,
+    UnknownBlock(u8),
+    InvalidString,
+    InvalidColumn,
+    InvalidEvent,
+    VersionMismatch { client: u16, server: u16 },
+    LoginRejected(String),
+    Timeout,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Io(err) => write!(f, "I/O error: {}", err),
+            ProtocolError::Disconnected => write!(f, "Connection closed"),
+            ProtocolError::Truncated => write!(f, "Message truncated"),
+            ProtocolError::FrameTooLarge(size) => write!(f, "Frame of {} bytes is too large", size),
+            ProtocolError::UnknownMessage(tag) => write!(f, "Unknown message type {}", tag),
+            ProtocolError::UnexpectedMessage => write!(f, "Unexpected message"),
+            ProtocolError::UnknownBlock(id) => write!(f, "Unknown block id {}", id),
+            ProtocolError::InvalidString => write!(f, "String isn't valid UTF-8"),
+            ProtocolError::InvalidColumn => write!(f, "Chunk column doesn't have {} blocks", COLUMN_VOLUME),
+            ProtocolError::InvalidEvent => write!(f, "Invalid window event"),
+            ProtocolError::VersionMismatch { client, server } =>
+                write!(f, "Protocol version mismatch (client: {}, server: {})", client, server),
+            ProtocolError::LoginRejected(reason) => write!(f, "Login rejected: {}", reason),
+            ProtocolError::Timeout => write!(f, "Timed out"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(err: io::Error) -> Self {
+        ProtocolError::Io(err)
+    }
+}
+
+/// A message that can be sent over a `Connection`
+pub trait Message: Sized {
+    fn encode(&self, buffer: &mut Vec<u8>);
+    fn decode(payload: &[u8]) -> Result<Self, ProtocolError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Login {
+        protocol_version: u16,
+        name: String,
+    },
+    /// Predicted position of the player at `tick`, after simulating the `ticks` ticks before it with `input`.
+    /// The server simulates them too and corrects the player if it ends up elsewhere. `last_correction` is
+    /// the id of the last `PlayerCorrection` the client applied, older movements are ignored by the server
+    PlayerMovement {
+        sequence: u32,
+        tick: u64,
+        ticks: u16,
+        last_correction: u32,
+        input: MovementInput,
+        position: Vec3,
+        rotation: Vec3,
+    },
+    SetBlock {
+        x: i32,
+        y: i32,
+        z: i32,
+        block: BlockID,
+        state: BlockState,
+    },
+    SelectHotbarSlot {
+        slot: u8,
+    },
+    /// Puts the targeted block in the selected hotbar slot. The server checks the block and the reach,
+    /// and sends the `Inventory` back if it refuses
+    PickBlock {
+        x: i32,
+        y: i32,
+        z: i32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// `tick` is the tick of the server, the client counts its own ticks from it
+    LoginAccepted {
+        player_id: u32,
+        position: Vec3,
+        tick: u64,
+        can_fly: bool,
+    },
+    LoginRejected {
+        reason: String,
+    },
+    ChunkColumn {
+        x: i32,
+        z: i32,
+        blocks: Vec<BlockID>,
+        states: Vec<BlockState>,
+        /// Of each column of blocks, in `16 * z + x` order
+        climate: Vec<Climate>,
+    },
+    BlockChange {
+        x: i32,
+        y: i32,
+        z: i32,
+        block: BlockID,
+        state: BlockState,
+    },
+    /// The movement `sequence` was rejected, the player is moved back to `position` at `velocity`
+    PlayerCorrection {
+        correction: u32,
+        sequence: u32,
+        position: Vec3,
+        velocity: Vec3,
+    },
+    PlayerPosition {
+        player_id: u32,
+        position: Vec3,
+        rotation: Vec3,
+    },
+    PlayerLeft {
+        player_id: u32,
+    },
+    Inventory {
+        selected_hotbar_slot: u8,
+        slots: Vec<Option<BlockID>>,
+    },
+    /// An admin allowed or forbade the player to fly
+    FlyingAllowed {
+        can_fly: bool,
+    },
+}
+
+/// Big endian encoding of the primitive types, also used by the world storage
+pub(crate) struct Writer<'a>(pub(crate) &'a mut Vec<u8>);
+
+impl<'a> Writer<'a> {
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) -> &mut Self {
+        self.0.push(value);
+        self
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn i32(&mut self, value: i32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) -> &mut Self {
+        self.u32(value.to_bits())
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) -> &mut Self {
+        self.u64(value.to_bits())
+    }
+
+    pub(crate) fn vec3(&mut self, value: &Vec3) -> &mut Self {
+        self.f32(value.x).f32(value.y).f32(value.z)
+    }
+
+    pub(crate) fn string(&mut self, value: &str) -> &mut Self {
+        self.u16(value.len() as u16);
+        self.0.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    pub(crate) fn block(&mut self, block: BlockID) -> &mut Self {
+        self.u8(block as u8)
+    }
+
+    pub(crate) fn optional_block(&mut self, block: Option<BlockID>) -> &mut Self {
+        match block {
+            None => self.u8(0),
+            Some(block) => self.u8(1).block(block),
+        }
+    }
+
+    pub(crate) fn block_state(&mut self, state: BlockState) -> &mut Self {
+        self.u16(state.bits())
+    }
+
+    /// Run-length encoded, columns are mostly made of long runs of air and stone
+    pub(crate) fn blocks(&mut self, blocks: &[BlockID]) -> &mut Self {
+        let runs = runs_of(blocks);
+        self.u32(runs.len() as u32);
+        for (block, length) in runs {
+            self.block(block).u16(length);
+        }
+        self
+    }
+
+    /// Run-length encoded like the blocks, almost every block is in its default state
+    pub(crate) fn block_states(&mut self, states: &[BlockState]) -> &mut Self {
+        let runs = runs_of(states);
+        self.u32(runs.len() as u32);
+        for (state, length) in runs {
+            self.block_state(state).u16(length);
+        }
+        self
+    }
+
+    /// The 16x16 columns of blocks of a chunk column, the climate changes too often to be run-length encoded
+    pub(crate) fn climate(&mut self, climate: &[Climate]) -> &mut Self {
+        for column in climate {
+            self.u8(column.temperature).u8(column.humidity);
+        }
+        self
+    }
+}
+
+fn runs_of<T: Copy + PartialEq>(values: &[T]) -> Vec<(T, u16)> {
+    let mut runs = Vec::new();
+    for &value in values {
+        match runs.last_mut() {
+            Some((last, length)) if *last == value && *length < u16::MAX => *length += 1,
+            _ => runs.push((value, 1u16)),
+        }
+    }
+    runs
+}
+
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn bytes(&mut self, n: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.0.len() < n {
+            return Err(ProtocolError::Truncated);
+        }
+        let (bytes, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, ProtocolError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, ProtocolError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, ProtocolError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    pub(crate) fn i32(&mut self) -> Result<i32, ProtocolError> {
+        Ok(self.u32()? as i32)
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, ProtocolError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, ProtocolError> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    pub(crate) fn vec3(&mut self) -> Result<Vec3, ProtocolError> {
+        Ok(vec3(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, ProtocolError> {
+        let length = self.u16()? as usize;
+        String::from_utf8(self.bytes(length)?.to_vec())
+            .map_err(|_| ProtocolError::InvalidString)
+    }
+
+    pub(crate) fn block(&mut self) -> Result<BlockID, ProtocolError> {
+        let id = self.u8()?;
+        BlockID::ALL.get(id as usize).cloned().ok_or(ProtocolError::UnknownBlock(id))
+    }
+
+    pub(crate) fn optional_block(&mut self) -> Result<Option<BlockID>, ProtocolError> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.block()?)),
+        }
+    }
+
+    pub(crate) fn block_state(&mut self) -> Result<BlockState, ProtocolError> {
+        Ok(BlockState::from_bits(self.u16()?))
+    }
+
+    pub(crate) fn blocks(&mut self) -> Result<Vec<BlockID>, ProtocolError> {
+        self.runs(Self::block)
+    }
+
+    pub(crate) fn block_states(&mut self) -> Result<Vec<BlockState>, ProtocolError> {
+        self.runs(Self::block_state)
+    }
+
+    pub(crate) fn climate(&mut self) -> Result<Vec<Climate>, ProtocolError> {
+        (0..16 * 16)
+            .map(|_| Ok(Climate {
+                temperature: self.u8()?,
+                humidity: self.u8()?,
+            }))
+            .collect()
+    }
+
+    /// The run-length encoded values of a whole column
+    fn runs<T: Clone>(&mut self, read_value: impl Fn(&mut Self) -> Result<T, ProtocolError>) -> Result<Vec<T>, ProtocolError> {
+        let runs = self.u32()?;
+        let mut values = Vec::with_capacity(COLUMN_VOLUME);
+        for _ in 0..runs {
+            let value = read_value(self)?;
+            let length = self.u16()? as usize;
+            if values.len() + length > COLUMN_VOLUME {
+                return Err(ProtocolError::InvalidColumn);
+            }
+            values.extend(std::iter::repeat(value).take(length));
+        }
+        if values.len() != COLUMN_VOLUME {
+            return Err(ProtocolError::InvalidColumn);
+        }
+        Ok(values)
+    }
+}
+
+impl Message for ClientMessage {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let mut w = Writer(buffer);
+        match self {
+            ClientMessage::Login { protocol_version, name } => {
+                w.u8(0).u16(*protocol_version).string(name);
+            }
+            ClientMessage::PlayerMovement { sequence, tick, ticks, last_correction, input, position, rotation } => {
+                w.u8(1).u32(*sequence).u64(*tick).u16(*ticks).u32(*last_correction).u16(input.bits()).vec3(position).vec3(rotation);
+            }
+            ClientMessage::SetBlock { x, y, z, block, state } => {
+                w.u8(2).i32(*x).i32(*y).i32(*z).block(*block).block_state(*state);
+            }
+            ClientMessage::SelectHotbarSlot { slot } => {
+                w.u8(3).u8(*slot);
+            }
+            ClientMessage::PickBlock { x, y, z } => {
+                w.u8(4).i32(*x).i32(*y).i32(*z);
+            }
+        }
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let mut r = Reader(payload);
+        let message = match r.u8()? {
+            0 => ClientMessage::Login {
+                protocol_version: r.u16()?,
+                name: r.string()?,
+            },
+            1 => ClientMessage::PlayerMovement {
+                sequence: r.u32()?,
+                tick: r.u64()?,
+                ticks: r.u16()?,
+                last_correction: r.u32()?,
+                input: MovementInput::from_bits(r.u16()?),
+                position: r.vec3()?,
+                rotation: r.vec3()?,
+            },
+            2 => ClientMessage::SetBlock {
+                x: r.i32()?,
+                y: r.i32()?,
+                z: r.i32()?,
+                block: r.block()?,
+                state: r.block_state()?,
+            },
+            3 => ClientMessage::SelectHotbarSlot {
+                slot: r.u8()?,
+            },
+            4 => ClientMessage::PickBlock {
+                x: r.i32()?,
+                y: r.i32()?,
+                z: r.i32()?,
+            },
+            tag => return Err(ProtocolError::UnknownMessage(tag)),
+        };
+        Ok(message)
+    }
+}
+
+impl Message for ServerMessage {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let mut w = Writer(buffer);
+        match self {
+            ServerMessage::LoginAccepted { player_id, position, tick, can_fly } => {
+                w.u8(0).u32(*player_id).vec3(position).u64(*tick).u8(*can_fly as u8);
+            }
+            ServerMessage::LoginRejected { reason } => {
+                w.u8(1).string(reason);
+            }
+            ServerMessage::ChunkColumn { x, z, blocks, states, climate } => {
+                w.u8(2).i32(*x).i32(*z).blocks(blocks).block_states(states).climate(climate);
+            }
+            ServerMessage::BlockChange { x, y, z, block, state } => {
+                w.u8(3).i32(*x).i32(*y).i32(*z).block(*block).block_state(*state);
+            }
+            ServerMessage::PlayerCorrection { correction, sequence, position, velocity } => {
+                w.u8(4).u32(*correction).u32(*sequence).vec3(position).vec3(velocity);
+            }
+            ServerMessage::PlayerPosition { player_id, position, rotation } => {
+                w.u8(5).u32(*player_id).vec3(position).vec3(rotation);
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                w.u8(6).u32(*player_id);
+            }
+            ServerMessage::Inventory { selected_hotbar_slot, slots } => {
+                w.u8(7).u8(*selected_hotbar_slot).u8(slots.len() as u8);
+                for &slot in slots {
+                    w.optional_block(slot);
+                }
+            }
+            ServerMessage::FlyingAllowed { can_fly } => {
+                w.u8(8).u8(*can_fly as u8);
+            }
+        }
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let mut r = Reader(payload);
+        let message = match r.u8()? {
+            0 => ServerMessage::LoginAccepted {
+                player_id: r.u32()?,
+                position: r.vec3()?,
+                tick: r.u64()?,
+                can_fly: r.u8()? != 0,
+            },
+            1 => ServerMessage::LoginRejected {
+                reason: r.string()?,
+            },
+            2 => ServerMessage::ChunkColumn {
+                x: r.i32()?,
+                z: r.i32()?,
+                blocks: r.blocks()?,
+                states: r.block_states()?,
+                climate: r.climate()?,
+            },
+            3 => ServerMessage::BlockChange {
+                x: r.i32()?,
+                y: r.i32()?,
+                z: r.i32()?,
+                block: r.block()?,
+                state: r.block_state()?,
+            },
+            4 => ServerMessage::PlayerCorrection {
+                correction: r.u32()?,
+                sequence: r.u32()?,
+                position: r.vec3()?,
+                velocity: r.vec3()?,
+            },
+            5 => ServerMessage::PlayerPosition {
+                player_id: r.u32()?,
+                position: r.vec3()?,
+                rotation: r.vec3()?,
+            },
+            6 => ServerMessage::PlayerLeft {
+                player_id: r.u32()?,
+            },
+            7 => {
+                let selected_hotbar_slot = r.u8()?;
+                let n_slots = r.u8()?;
+                let mut slots = Vec::with_capacity(n_slots as usize);
+                for _ in 0..n_slots {
+                    slots.push(r.optional_block()?);
+                }
+                ServerMessage::Inventory {
+                    selected_hotbar_slot,
+                    slots,
+                }
+            }
+            8 => ServerMessage::FlyingAllowed {
+                can_fly: r.u8()? != 0,
+            },
+            tag => return Err(ProtocolError::UnknownMessage(tag)),
+        };
+        Ok(message)
+    }
+}
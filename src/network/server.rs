@@ -0,0 +1,625 @@
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::Receiver;
+use nalgebra_glm::{Vec3, vec3};
+use num_traits::abs;
+
+use crate::block_model::BlockModels;
+use crate::chunk::BlockID;
+use crate::chunk_manager::ChunkManager;
+use crate::chunk_pipeline::{ChunkPipeline, ColumnStage};
+use crate::constants::{AUTOSAVE_INTERVAL, COLUMNS_SENT_PER_TICK, MOVEMENT_TOLERANCE, PLAYER_EYES_HEIGHT, REACH_DISTANCE, TICKRATE};
+use crate::inventory::{HOTBAR_SIZE, INVENTORY_SIZE, STARTING_ITEMS};
+use crate::network::commands::{HELP, ServerCommand};
+use crate::network::connection::Connection;
+use crate::network::protocol::{ClientMessage, PROTOCOL_VERSION, ProtocolError, ServerMessage};
+use crate::physics_body::PhysicsBody;
+use crate::player::PlayerState;
+use crate::ticks::Ticks;
+use crate::world_storage::{is_valid_player_name, PlayerData, WorldStorage};
+
+type ClientConnection = Connection<ServerMessage, ClientMessage>;
+
+/// Don't queue more columns to a client that hasn't read this much yet
+const MAX_PENDING_BYTES: usize = 4 * 1024 * 1024;
+
+/// How far ahead of the server the tick of a movement may be, covers the clocks drifting apart
+const MAX_TICKS_AHEAD: u64 = (MOVEMENT_TOLERANCE * TICKRATE) as u64;
+
+/// How far the position predicted by a client may be from the one simulated by the server,
+/// covers the blocks changed while the movement was on its way
+const MAX_POSITION_ERROR: f32 = 0.05;
+
+struct ServerPlayer {
+    id: u32,
+    name: String,
+    /// Simulated from the inputs of the client, with the same code
+    body: PhysicsBody,
+    state: PlayerState,
+    moved: bool,
+    /// Tick of the last accepted movement
+    last_movement_tick: u64,
+    last_sequence: u32,
+    correction: u32,
+    selected_hotbar_slot: usize,
+    slots: [Option<BlockID>; INVENTORY_SIZE],
+    /// Granted by the `fly` command, saved with the player
+    can_fly: bool,
+}
+
+impl ServerPlayer {
+    fn new(id: u32, name: String, data: PlayerData, tick: u64) -> Self {
+        let mut slots = [None; INVENTORY_SIZE];
+        for (slot, &item) in slots.iter_mut().zip(data.slots.iter()) {
+            *slot = item;
+        }
+
+        Self {
+            id,
+            name,
+            body: PhysicsBody::new_player(data.position),
+            state: PlayerState::new(),
+            moved: true,
+            last_movement_tick: tick,
+            last_sequence: 0,
+            correction: 0,
+            selected_hotbar_slot: (data.selected_hotbar_slot as usize).min(HOTBAR_SIZE - 1),
+            slots,
+            can_fly: data.can_fly,
+        }
+    }
+
+    fn data(&self) -> PlayerData {
+        PlayerData {
+            position: self.body.position,
+            selected_hotbar_slot: self.selected_hotbar_slot as u8,
+            slots: self.slots.to_vec(),
+            can_fly: self.can_fly,
+        }
+    }
+
+    fn chunk_column(&self) -> (i32, i32) {
+        let position = self.body.position;
+        let (c_x, _, c_z, _, _, _) = ChunkManager::get_chunk_coords(
+            position.x as i32, position.y as i32, position.z as i32);
+        (c_x, c_z)
+    }
+
+    /// Moves the player back to where the server simulated it, the movements the client
+    /// predicted until it gets the correction are ignored
+    fn correct(&mut self, connection: &mut ClientConnection, sequence: u32) {
+        self.correction += 1;
+        connection.send(&ServerMessage::PlayerCorrection {
+            correction: self.correction,
+            sequence,
+            position: self.body.position,
+            velocity: self.body.velocity,
+        });
+    }
+}
+
+struct RemoteClient {
+    connection: ClientConnection,
+    /// None until the client logged in
+    player: Option<ServerPlayer>,
+    sent_columns: HashSet<(i32, i32)>,
+}
+
+/// Owns the world and simulates it for every connected client.
+/// Clients only get the chunk columns within `view_distance + 1` of their player,
+/// the extra ring lets them mesh the borders of their view
+pub struct Server {
+    listener: TcpListener,
+    storage: Arc<WorldStorage>,
+    chunk_manager: Arc<ChunkManager>,
+    chunk_pipeline: ChunkPipeline,
+    /// Columns changed by the players since they were last saved
+    modified_columns: HashSet<(i32, i32)>,
+    view_distance: i32,
+    spawn_position: Vec3,
+    clients: HashMap<u32, RemoteClient>,
+    next_client_id: u32,
+    ticks: Ticks,
+    /// Whether the movements of the players are checked and corrected
+    corrects_movements: bool,
+    /// Whether every player may fly, not only the ones allowed with the `fly` command
+    allows_flying: bool,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(address: A, view_distance: i32, storage: WorldStorage, block_models: Arc<BlockModels>) -> Result<Self, ProtocolError> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        info!("Server listening on {}", listener.local_addr()?);
+
+        let storage = Arc::new(storage);
+        let sent_distance = view_distance + ColumnStage::Meshed.neighbour_radius();
+        Ok(Self {
+            listener,
+            chunk_manager: Arc::new(ChunkManager::new(block_models)),
+            chunk_pipeline: ChunkPipeline::generated(sent_distance, ColumnStage::Lit, storage.seed())
+                .with_storage(Arc::clone(&storage)),
+            storage,
+            modified_columns: HashSet::new(),
+            view_distance,
+            spawn_position: vec3(8.0, 195.0, 8.0),
+            clients: HashMap::new(),
+            next_client_id: 1,
+            ticks: Ticks::default(),
+            corrects_movements: true,
+            allows_flying: false,
+        })
+    }
+
+    /// Lets every player fly, like in a single player world
+    pub fn allow_flying(mut self) -> Self {
+        self.allows_flying = true;
+        self
+    }
+
+    /// Accepts every movement of the players. Used to play the replays, whose movements happen on
+    /// their recorded ticks and not on the clock of the server
+    pub fn without_movement_corrections(mut self) -> Self {
+        self.corrects_movements = false;
+        self
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    pub fn chunk_manager(&self) -> &Arc<ChunkManager> {
+        &self.chunk_manager
+    }
+
+    /// Number of ticks since the server started
+    pub fn ticks(&self) -> u64 {
+        self.ticks.tick
+    }
+
+    /// Ticks the server at `TICKRATE` until a `stop` command is received on `commands`,
+    /// then saves the world. Late ticks are run back to back to catch up
+    pub fn run(mut self, commands: Receiver<String>) {
+        loop {
+            for line in commands.try_iter() {
+                match ServerCommand::parse(&line) {
+                    Ok(ServerCommand::Stop) => {
+                        info!("Stopping the server");
+                        self.save();
+                        return;
+                    }
+                    Ok(command) => println!("{}", self.execute(&command)),
+                    Err(err) => println!("{}", err),
+                }
+            }
+
+            let skipped = self.ticks.advance(Instant::now());
+            if skipped > 0 {
+                warn!("Can't keep up, skipping {} ticks", skipped);
+            }
+            for tick in self.ticks.pending_ticks() {
+                self.simulate(tick);
+            }
+            thread::sleep(self.ticks.until_next_tick());
+        }
+    }
+
+    /// Runs an admin command and returns the answer to print
+    pub fn execute(&mut self, command: &ServerCommand) -> String {
+        match command {
+            ServerCommand::Help => HELP.to_string(),
+            ServerCommand::List => {
+                let names = self.clients.values()
+                    .filter_map(|client| client.player.as_ref())
+                    .map(|player| player.name.as_str())
+                    .collect::<Vec<_>>();
+                format!("{} player(s) online: {}", names.len(), names.join(", "))
+            }
+            ServerCommand::Save => {
+                self.save();
+                "Saved the world".to_string()
+            }
+            ServerCommand::Stop => "Stopping the server".to_string(),
+            ServerCommand::Kick(name) => match self.find_player(name) {
+                Some(id) => {
+                    self.disconnect(id);
+                    format!("Kicked {}", name)
+                }
+                None => format!("{} isn't online", name),
+            },
+            ServerCommand::Teleport(name, position) => match self.find_player(name) {
+                Some(id) => {
+                    let client = self.clients.get_mut(&id).unwrap();
+                    let player = client.player.as_mut().unwrap();
+                    player.body = PhysicsBody::new_player(*position);
+                    player.moved = true;
+                    // Sent like a correction so that the movements predicted before it are ignored
+                    player.correct(&mut client.connection, player.last_sequence);
+                    format!("Teleported {} to {} {} {}", name, position.x, position.y, position.z)
+                }
+                None => format!("{} isn't online", name),
+            },
+            ServerCommand::Fly(name, can_fly) => match self.find_player(name) {
+                Some(_) if self.allows_flying => "Every player can fly on this server".to_string(),
+                Some(id) => {
+                    let client = self.clients.get_mut(&id).unwrap();
+                    let player = client.player.as_mut().unwrap();
+                    player.can_fly = *can_fly;
+                    player.state.is_flying &= *can_fly;
+                    client.connection.send(&ServerMessage::FlyingAllowed { can_fly: *can_fly });
+                    if *can_fly {
+                        format!("{} can fly", name)
+                    } else {
+                        format!("{} can't fly anymore", name)
+                    }
+                }
+                None => format!("{} isn't online", name),
+            },
+        }
+    }
+
+    fn find_player(&self, name: &str) -> Option<u32> {
+        self.clients.iter()
+            .find(|(_, client)| client.player.as_ref().map_or(false, |player| player.name == name))
+            .map(|(&id, _)| id)
+    }
+
+    /// Saves the modified chunk columns and every connected player
+    pub fn save(&mut self) {
+        let modified_columns = self.modified_columns.iter().cloned().collect::<Vec<_>>();
+        for (x, z) in modified_columns {
+            if let Some(column) = self.chunk_pipeline.finished_column(x, z) {
+                match self.storage.save_column((x, z), &column.blocks(), &column.states()) {
+                    Ok(()) => {
+                        self.modified_columns.remove(&(x, z));
+                    }
+                    Err(err) => error!("Couldn't save the chunk column ({}, {}): {}", x, z, err),
+                }
+            }
+        }
+
+        for player in self.clients.values().filter_map(|client| client.player.as_ref()) {
+            self.save_player(player);
+        }
+        info!("Saved the world in {}", self.storage.directory().display());
+    }
+
+    fn save_player(&self, player: &ServerPlayer) {
+        if let Err(err) = self.storage.save_player(&player.name, &player.data()) {
+            error!("Couldn't save {}: {}", player.name, err);
+        }
+    }
+
+    /// Drops the connection of a client, saves its player and tells the others that it left
+    fn disconnect(&mut self, id: u32) {
+        if let Some(client) = self.clients.remove(&id) {
+            if let Some(player) = client.player {
+                info!("{} left the game", player.name);
+                self.save_player(&player);
+                for client in self.clients.values_mut().filter(|client| client.player.is_some()) {
+                    client.connection.send(&ServerMessage::PlayerLeft { player_id: player.id });
+                }
+            }
+        }
+    }
+
+    /// Simulates one tick right away, whatever the time elapsed since the last one
+    pub fn tick(&mut self) {
+        self.ticks.step();
+        for tick in self.ticks.pending_ticks() {
+            self.simulate(tick);
+        }
+    }
+
+    fn simulate(&mut self, tick: u64) {
+        self.accept_clients();
+
+        // Handle the messages of every client
+        let mut broadcasts = Vec::new();
+        let mut disconnected = Vec::new();
+        let ids = self.clients.keys().cloned().collect::<Vec<_>>();
+        for id in ids {
+            let messages = match self.clients.get_mut(&id).unwrap().connection.receive() {
+                Ok(messages) => messages,
+                Err(err) => {
+                    info!("Client {} disconnected: {}", id, err);
+                    disconnected.push(id);
+                    continue;
+                }
+            };
+            for message in messages {
+                if let Err(err) = self.handle_message(id, message, &mut broadcasts) {
+                    info!("Client {} disconnected: {}", id, err);
+                    disconnected.push(id);
+                    break;
+                }
+            }
+        }
+
+        for id in disconnected {
+            self.disconnect(id);
+        }
+        // Only the clients mesh the chunks, they get the changes from the broadcasts
+        self.chunk_manager.take_block_changelist();
+
+        // Generate the world around the players
+        let centers = self.clients.values()
+            .filter_map(|client| client.player.as_ref())
+            .map(|player| player.chunk_column())
+            .collect::<Vec<_>>();
+        let storage = &self.storage;
+        let modified_columns = &mut self.modified_columns;
+        self.chunk_pipeline.update(&centers, &self.chunk_manager, &mut |xz, column| {
+            if modified_columns.remove(&xz) {
+                if let Err(err) = storage.save_column(xz, &column.blocks(), &column.states()) {
+                    error!("Couldn't save the chunk column {:?}: {}", xz, err);
+                }
+            }
+        });
+
+        // Player positions
+        for client in self.clients.values_mut() {
+            if let Some(player) = &mut client.player {
+                if player.moved {
+                    player.moved = false;
+                    broadcasts.push(ServerMessage::PlayerPosition {
+                        player_id: player.id,
+                        position: player.body.position,
+                        rotation: player.state.rotation,
+                    });
+                }
+            }
+        }
+
+        for client in self.clients.values_mut() {
+            if client.player.is_none() {
+                continue;
+            }
+            for message in &broadcasts {
+                client.connection.send(message);
+            }
+            Self::send_columns(client, &self.chunk_pipeline, self.view_distance);
+        }
+
+        let mut disconnected = Vec::new();
+        for (&id, client) in self.clients.iter_mut() {
+            if let Err(err) = client.connection.flush() {
+                info!("Client {} disconnected: {}", id, err);
+                disconnected.push(id);
+            }
+        }
+        for id in disconnected {
+            self.disconnect(id);
+        }
+
+        if tick % (AUTOSAVE_INTERVAL * TICKRATE) as u64 == 0 && tick > 0 {
+            self.save();
+        }
+    }
+
+    fn accept_clients(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    match Connection::new(stream) {
+                        Ok(connection) => {
+                            info!("Connection from {}", address);
+                            self.clients.insert(self.next_client_id, RemoteClient {
+                                connection,
+                                player: None,
+                                sent_columns: HashSet::new(),
+                            });
+                            self.next_client_id += 1;
+                        }
+                        Err(err) => error!("{}", err),
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    error!("{}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Sends the closest finished columns the client doesn't have yet, and forgets about
+    /// the ones it dropped
+    fn send_columns(client: &mut RemoteClient, chunk_pipeline: &ChunkPipeline, view_distance: i32) {
+        let (c_x, c_z) = client.player.as_ref().unwrap().chunk_column();
+        let sent_distance = view_distance + ColumnStage::Meshed.neighbour_radius();
+
+        client.sent_columns.retain(|&(x, z)| abs(x - c_x) <= sent_distance && abs(z - c_z) <= sent_distance);
+
+        let mut missing_columns = Vec::new();
+        for x in c_x - sent_distance..=c_x + sent_distance {
+            for z in c_z - sent_distance..=c_z + sent_distance {
+                if !client.sent_columns.contains(&(x, z)) {
+                    missing_columns.push((x, z));
+                }
+            }
+        }
+        missing_columns.sort_by_key(|&(x, z)| (x - c_x) * (x - c_x) + (z - c_z) * (z - c_z));
+
+        let mut sent = 0;
+        for (x, z) in missing_columns {
+            if sent >= COLUMNS_SENT_PER_TICK || client.connection.pending_bytes() > MAX_PENDING_BYTES {
+                break;
+            }
+            if let Some(column) = chunk_pipeline.finished_column(x, z) {
+                client.connection.send(&ServerMessage::ChunkColumn {
+                    x,
+                    z,
+                    blocks: column.blocks(),
+                    states: column.states(),
+                    climate: column.climate(),
+                });
+                client.sent_columns.insert((x, z));
+                sent += 1;
+            }
+        }
+    }
+
+    fn handle_message(&mut self, id: u32, message: ClientMessage, broadcasts: &mut Vec<ServerMessage>) -> Result<(), ProtocolError> {
+        let client = self.clients.get_mut(&id).unwrap();
+
+        let player = match &mut client.player {
+            Some(player) => player,
+            None => {
+                let (protocol_version, name) = match message {
+                    ClientMessage::Login { protocol_version, name } => (protocol_version, name),
+                    _ => return Err(ProtocolError::UnexpectedMessage),
+                };
+
+                if protocol_version != PROTOCOL_VERSION {
+                    client.connection.send(&ServerMessage::LoginRejected {
+                        reason: format!("The server uses protocol version {}", PROTOCOL_VERSION),
+                    });
+                    client.connection.flush()?;
+                    return Err(ProtocolError::VersionMismatch { client: protocol_version, server: PROTOCOL_VERSION });
+                }
+
+                let reason = if !is_valid_player_name(&name) {
+                    Some("Player names are made of 1 to 16 letters, digits or underscores")
+                } else if self.clients.values().any(|client| client.player.as_ref().map_or(false, |player| player.name == name)) {
+                    Some("A player with this name is already connected")
+                } else {
+                    None
+                };
+                let client = self.clients.get_mut(&id).unwrap();
+                if let Some(reason) = reason {
+                    client.connection.send(&ServerMessage::LoginRejected { reason: reason.to_string() });
+                    client.connection.flush()?;
+                    return Err(ProtocolError::LoginRejected(reason.to_string()));
+                }
+
+                let data = match self.storage.load_player(&name) {
+                    Ok(Some(data)) => data,
+                    Ok(None) => PlayerData {
+                        position: self.spawn_position,
+                        selected_hotbar_slot: 0,
+                        slots: STARTING_ITEMS.iter().map(|&item| Some(item)).collect(),
+                        can_fly: false,
+                    },
+                    Err(err) => {
+                        client.connection.send(&ServerMessage::LoginRejected { reason: "Couldn't load your player".to_string() });
+                        client.connection.flush()?;
+                        error!("Couldn't load {}: {}", name, err);
+                        return Err(ProtocolError::LoginRejected(err.to_string()));
+                    }
+                };
+
+                info!("{} joined the game from {}", name, client.connection.peer_address());
+                let player = ServerPlayer::new(id, name, data, self.ticks.tick);
+                client.connection.send(&ServerMessage::LoginAccepted {
+                    player_id: player.id,
+                    position: player.body.position,
+                    tick: self.ticks.tick,
+                    can_fly: self.allows_flying || player.can_fly,
+                });
+                client.connection.send(&ServerMessage::Inventory {
+                    selected_hotbar_slot: player.selected_hotbar_slot as u8,
+                    slots: player.slots.to_vec(),
+                });
+                client.player = Some(player);
+                return Ok(());
+            }
+        };
+
+        match message {
+            ClientMessage::PlayerMovement { sequence, tick, ticks, last_correction, input, position, rotation } => {
+                // Movements sent before the client applied our last correction are stale
+                if last_correction != player.correction || sequence <= player.last_sequence {
+                    return Ok(());
+                }
+                player.last_sequence = sequence;
+                player.state.rotation = rotation;
+                player.moved = true;
+
+                if !self.corrects_movements {
+                    player.body.teleport(position);
+                    player.last_movement_tick = tick;
+                    return Ok(());
+                }
+
+                // The client runs on the ticks of the server and only gets the finished columns, it can't
+                // be ahead of the server nor move where the world isn't generated yet
+                let start = player.body.position;
+                if tick > self.ticks.tick + MAX_TICKS_AHEAD || !self.chunk_pipeline.is_finished_around(start.x as i32, start.z as i32) {
+                    player.correct(&mut client.connection, sequence);
+                    return Ok(());
+                }
+
+                // Simulate the ticks the client did, from its inputs, except the ones already simulated
+                player.state.is_sneaking = input.is_sneaking;
+                player.state.is_sprinting = input.is_sprinting;
+                player.state.is_flying = input.is_flying && (self.allows_flying || player.can_fly);
+                let ticks = (ticks as u64).min(tick.saturating_sub(player.last_movement_tick));
+                for simulated_tick in tick - ticks..tick {
+                    player.body.step_player(&mut player.state, &input, simulated_tick, Ticks::DT, &self.chunk_manager);
+                }
+                player.last_movement_tick = tick;
+
+                if position.iter().all(|x| x.is_finite()) && (position - player.body.position).magnitude() <= MAX_POSITION_ERROR {
+                    // Don't let the rounding errors pile up
+                    player.body.teleport(position);
+                } else {
+                    info!("Correcting the movement of {} to {:?}", player.name, player.body.position);
+                    player.correct(&mut client.connection, sequence);
+                }
+            }
+
+            ClientMessage::SetBlock { x, y, z, block, state } => {
+                let in_reach = Self::is_in_reach(&player.body.position, x, y, z);
+                let has_item = block.is_air() || player.slots[player.selected_hotbar_slot] == Some(block);
+                let current = self.chunk_manager.get_block_with_state(x, y, z);
+
+                match current {
+                    Some(current) if in_reach && has_item && block.is_valid_state(state) && current != (block, state) => {
+                        self.chunk_manager.set_block_with_state(block, state, x, y, z);
+                        let (c_x, _, c_z, _, _, _) = ChunkManager::get_chunk_coords(x, y, z);
+                        self.modified_columns.insert((c_x, c_z));
+                        broadcasts.push(ServerMessage::BlockChange { x, y, z, block, state });
+                    }
+                    // Undo the client prediction
+                    Some((block, state)) => client.connection.send(&ServerMessage::BlockChange { x, y, z, block, state }),
+                    None => {}
+                }
+            }
+
+            ClientMessage::SelectHotbarSlot { slot } => {
+                if (slot as usize) < HOTBAR_SIZE {
+                    player.selected_hotbar_slot = slot as usize;
+                }
+            }
+
+            ClientMessage::PickBlock { x, y, z } => {
+                match self.chunk_manager.get_block(x, y, z) {
+                    Some(block) if !block.is_air() && Self::is_in_reach(&player.body.position, x, y, z) => {
+                        player.slots[player.selected_hotbar_slot] = Some(block);
+                    }
+                    // Undo the client prediction
+                    _ => client.connection.send(&ServerMessage::Inventory {
+                        selected_hotbar_slot: player.selected_hotbar_slot as u8,
+                        slots: player.slots.to_vec(),
+                    }),
+                }
+            }
+
+            ClientMessage::Login { .. } => return Err(ProtocolError::UnexpectedMessage),
+        }
+        Ok(())
+    }
+
+    /// Whether the center of a block is in reach of a player at `position`, with a block of tolerance for the latency
+    fn is_in_reach(position: &Vec3, x: i32, y: i32, z: i32) -> bool {
+        let eyes = position + vec3(0.0, PLAYER_EYES_HEIGHT, 0.0);
+        let center = vec3(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+        (center - eyes).magnitude() <= REACH_DISTANCE + 1.0
+    }
+}
@@ -0,0 +1,300 @@
+//! Rendering without a window, for the visual regression tests and `meinkraft-render`: a frame of a
+//! world generated from a fixed seed, seen from a fixed camera, is drawn into an `OffscreenTarget` by
+//! the same systems as in the game, read back and compared with a golden image.
+//!
+//! On Linux the OpenGL context is a `HeadlessContext` of EGL, which needs neither a window nor a display.
+//! Machines without a GPU use the software renderer of Mesa (llvmpipe), which needs its version
+//! overridden while it doesn't support OpenGL 4.6, like in the CI job of `.github/workflows/visual_regression.yml`:
+//! `LIBGL_ALWAYS_SOFTWARE=1 MESA_GL_VERSION_OVERRIDE=4.6 MESA_GLSL_VERSION_OVERRIDE=460 cargo test --test visual_regression`
+//!
+//! Elsewhere, or when EGL isn't available, the context comes from a hidden GLFW window.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use glfw::{Glfw, Window};
+use image::{Rgba, RgbaImage};
+use nalgebra_glm::{Vec3, vec3};
+use specs::{Builder, RunNow, World, WorldExt};
+
+use crate::block_model::BlockModels;
+use crate::chunk_manager::ChunkManager;
+use crate::chunk_mesh_arena::ChunkMeshArena;
+use crate::chunk_pipeline::{ChunkPipeline, ColumnStage};
+use crate::constants::{ASSET_DIRECTORY, CHUNK_MESH_ARENA_CAPACITY, FAR_PLANE, FOV, NEAR_PLANE};
+use crate::ecs::systems::RenderChunks;
+#[cfg(target_os = "linux")]
+use crate::egl::HeadlessContext;
+use crate::player::PlayerState;
+use crate::resource_pack::{ResourcePack, ResourcePacks};
+use crate::screenshot::{read_framebuffer, Screenshots};
+use crate::shader_compilation::compile_shaders;
+use crate::shader_preprocessor::ShaderPreprocessor;
+use crate::texture_pack::TexturePack;
+use crate::window::{create_hidden_window, init_gl_render_state};
+
+/// Where the golden images of the visual regression tests are
+pub const GOLDEN_IMAGE_DIRECTORY: &str = "tests/golden";
+/// Where the frames that don't match their golden image are saved, with their difference
+pub const VISUAL_REGRESSION_OUTPUT_DIRECTORY: &str = "target/visual_regression";
+/// How long the world of a scene may take to generate
+const WORLD_GENERATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A framebuffer with a colour and a depth buffer, drawn to instead of a window
+pub struct OffscreenTarget {
+    framebuffer: u32,
+    /// The colour and the depth buffer
+    renderbuffers: [u32; 2],
+    width: u32,
+    height: u32,
+}
+
+impl OffscreenTarget {
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let mut target = Self {
+            framebuffer: 0,
+            renderbuffers: [0; 2],
+            width,
+            height,
+        };
+        gl_call!(gl::CreateFramebuffers(1, &mut target.framebuffer));
+        gl_call!(gl::CreateRenderbuffers(2, target.renderbuffers.as_mut_ptr()));
+        let [color, depth] = target.renderbuffers;
+        gl_call!(gl::NamedRenderbufferStorage(color, gl::RGBA8, width as i32, height as i32));
+        gl_call!(gl::NamedRenderbufferStorage(depth, gl::DEPTH_COMPONENT24, width as i32, height as i32));
+        gl_call!(gl::NamedFramebufferRenderbuffer(target.framebuffer, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, color));
+        gl_call!(gl::NamedFramebufferRenderbuffer(target.framebuffer, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, depth));
+
+        let status = gl_call!(gl::CheckNamedFramebufferStatus(target.framebuffer, gl::FRAMEBUFFER));
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("The offscreen framebuffer of {}x{} is incomplete: status {:#x}", width, height, status));
+        }
+        Ok(target)
+    }
+
+    /// Draws to the target from now on
+    pub fn bind(&self) {
+        gl_call!(gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer));
+        gl_call!(gl::Viewport(0, 0, self.width as i32, self.height as i32));
+    }
+
+    /// What was drawn to the target
+    pub fn read(&self) -> RgbaImage {
+        read_framebuffer(self.framebuffer, self.width, self.height)
+    }
+}
+
+impl Drop for OffscreenTarget {
+    fn drop(&mut self) {
+        gl_call!(gl::DeleteFramebuffers(1, &self.framebuffer));
+        gl_call!(gl::DeleteRenderbuffers(2, self.renderbuffers.as_ptr()));
+    }
+}
+
+/// The OpenGL context the frames are rendered with, current until it's dropped
+enum RenderContext {
+    #[cfg(target_os = "linux")]
+    Headless {
+        _context: HeadlessContext,
+    },
+    HiddenWindow {
+        // Dropped before GLFW is terminated
+        _window: Window,
+        _glfw: Glfw,
+    },
+}
+
+impl RenderContext {
+    fn new(width: u32, height: u32) -> Result<Self, String> {
+        #[cfg(target_os = "linux")]
+        {
+            match HeadlessContext::new() {
+                Ok(context) => return Ok(RenderContext::Headless { _context: context }),
+                Err(err) => warn!("No headless OpenGL context, rendering with a hidden window: {}", err),
+            }
+        }
+        let (glfw, window) = create_hidden_window(width, height)?;
+        Ok(RenderContext::HiddenWindow {
+            _window: window,
+            _glfw: glfw,
+        })
+    }
+}
+
+/// The chunks of a generated world, and the coordinates of the meshed ones
+type GeneratedWorld = (Arc<ChunkManager>, Vec<(i32, i32, i32)>);
+
+/// A fixed view of a generated world
+#[derive(Debug, Clone)]
+pub struct Scene {
+    pub seed: u32,
+    /// In chunk columns around the camera
+    pub view_distance: i32,
+    pub camera_position: Vec3,
+    /// The point the camera looks at
+    pub camera_target: Vec3,
+}
+
+impl Scene {
+    /// Renders the scene into an image of `width` x `height`, with the textures and the shaders of the
+    /// working directory. The world is generated up to the meshing stage before the frame is drawn
+    pub fn render(&self, width: u32, height: u32) -> Result<RgbaImage, String> {
+        // Dropped last, every GL object needs the context
+        let _context = RenderContext::new(width, height)?;
+        init_gl_render_state();
+
+        let block_models = Arc::new(BlockModels::load(ASSET_DIRECTORY)?);
+        let (chunk_manager, chunks) = self.generate_world(Arc::clone(&block_models))?;
+        let texture_pack = TexturePack::load(ResourcePacks::new(ResourcePack::builtin(ASSET_DIRECTORY)), block_models);
+        let shaders = compile_shaders(&ShaderPreprocessor::from_settings())?;
+        let mut chunk_mesh_arena = ChunkMeshArena::new(CHUNK_MESH_ARENA_CAPACITY);
+        for (x, y, z) in chunks {
+            if let Some(chunk) = chunk_manager.get_chunk(x, y, z) {
+                chunk_mesh_arena.upload_chunk((x, y, z), &chunk, &chunk_manager, texture_pack.color_maps());
+            }
+        }
+
+        let mut player_state = PlayerState::new();
+        player_state.view_matrix = nalgebra_glm::look_at(&self.camera_position, &self.camera_target, &vec3(0.0, 1.0, 0.0));
+        player_state.projection_matrix = nalgebra_glm::perspective(width as f32 / height as f32, *FOV, NEAR_PLANE, FAR_PLANE);
+
+        let mut world = World::new();
+        world.register::<PlayerState>();
+        world.create_entity().with(player_state).build();
+        world.insert(chunk_manager);
+        world.insert(shaders);
+        world.insert(chunk_mesh_arena);
+        world.insert(Screenshots::default());
+
+        let target = OffscreenTarget::new(width, height)?;
+        target.bind();
+        RenderChunks.run_now(&world);
+        Ok(target.read())
+    }
+
+    /// The columns within the view distance of the camera, and their chunks to upload
+    fn generate_world(&self, block_models: Arc<BlockModels>) -> Result<GeneratedWorld, String> {
+        let (seed, view_distance) = (self.seed, self.view_distance);
+        let c_x = (self.camera_position.x / 16.0).floor() as i32;
+        let c_z = (self.camera_position.z / 16.0).floor() as i32;
+        let column_range = move |center: i32| center - view_distance..=center + view_distance;
+
+        // Chunk columns are built on the stack before being boxed
+        thread::Builder::new()
+            .name("world generation".to_string())
+            .stack_size(16 * 1024 * 1024)
+            .spawn(move || {
+                let chunk_manager = Arc::new(ChunkManager::new(block_models));
+                let mut chunk_pipeline = ChunkPipeline::generated(view_distance, ColumnStage::Meshed, seed);
+                let start = Instant::now();
+                loop {
+                    chunk_pipeline.update(&[(c_x, c_z)], &chunk_manager, &mut |_, _| {});
+                    let finished = column_range(c_x)
+                        .all(|x| column_range(c_z).all(|z| chunk_pipeline.finished_column(x, z).is_some()));
+                    if finished {
+                        break;
+                    }
+                    if start.elapsed() > WORLD_GENERATION_TIMEOUT {
+                        return Err(format!("The world of seed {} wasn't generated after {:?}", seed, WORLD_GENERATION_TIMEOUT));
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                let chunks = chunk_pipeline.meshed_chunks().try_iter().map(|chunk| *chunk).collect();
+                Ok((chunk_manager, chunks))
+            })
+            .unwrap()
+            .join()
+            .map_err(|_| "The world generation panicked".to_string())?
+    }
+}
+
+/// How far an image is from the expected one
+#[derive(Debug)]
+pub struct ImageDifference {
+    /// The pixels with a channel further than the tolerance from the expected one
+    pub differing_pixels: u32,
+    /// The largest difference of a channel
+    pub max_difference: u8,
+    /// The expected image, faded, with the differing pixels in red
+    pub diff: RgbaImage,
+}
+
+/// Compares the images pixel by pixel. Images of different sizes can't be compared
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Result<ImageDifference, String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!("The image is {:?} instead of {:?}", actual.dimensions(), expected.dimensions()));
+    }
+    let mut difference = ImageDifference {
+        differing_pixels: 0,
+        max_difference: 0,
+        diff: RgbaImage::new(expected.width(), expected.height()),
+    };
+    for ((actual, expected), diff) in actual.pixels().zip(expected.pixels()).zip(difference.diff.pixels_mut()) {
+        let max_difference = (0..4)
+            .map(|i| actual[i].max(expected[i]) - actual[i].min(expected[i]))
+            .max()
+            .unwrap();
+        difference.max_difference = difference.max_difference.max(max_difference);
+        *diff = if max_difference > tolerance {
+            difference.differing_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            Rgba([expected[0] / 4, expected[1] / 4, expected[2] / 4, 255])
+        };
+    }
+    Ok(difference)
+}
+
+/// Compares a frame with its golden image `tests/golden/<name>.png`. It fails if more than `max_differing`,
+/// a fraction of the pixels, are further than `tolerance` from it, and the frame and the difference are
+/// saved in `target/visual_regression/`.
+///
+/// A missing golden image is created from the frame, which fails so that it's looked at before being
+/// committed. `UPDATE_GOLDEN_IMAGES=1` replaces the golden images by the frames
+pub fn check_golden_image(name: &str, frame: &RgbaImage, tolerance: u8, max_differing: f32) -> Result<(), String> {
+    let golden_path = Path::new(GOLDEN_IMAGE_DIRECTORY).join(format!("{}.png", name));
+    let update = std::env::var("UPDATE_GOLDEN_IMAGES").ok().as_deref() == Some("1");
+    if update || !golden_path.exists() {
+        save_image(frame, &golden_path)?;
+        return if update {
+            Ok(())
+        } else {
+            Err(format!("{} was missing, it was created from the frame: check it before committing it", golden_path.display()))
+        };
+    }
+
+    let golden = image::open(&golden_path)
+        .map_err(|err| format!("Couldn't read {}: {}", golden_path.display(), err))?
+        .to_rgba();
+    let (actual_path, diff_path) = {
+        let directory = Path::new(VISUAL_REGRESSION_OUTPUT_DIRECTORY);
+        (directory.join(format!("{}.png", name)), directory.join(format!("{}.diff.png", name)))
+    };
+    let difference = match compare_images(frame, &golden, tolerance) {
+        Ok(difference) => difference,
+        Err(err) => {
+            save_image(frame, &actual_path)?;
+            return Err(format!("{} doesn't match {}: {}", actual_path.display(), golden_path.display(), err));
+        }
+    };
+
+    let pixels = golden.width() * golden.height();
+    if difference.differing_pixels as f32 > max_differing * pixels as f32 {
+        save_image(frame, &actual_path)?;
+        save_image(&difference.diff, &diff_path)?;
+        return Err(format!("{} differs from {} on {} pixels out of {}, by up to {}, see {}",
+                           actual_path.display(), golden_path.display(), difference.differing_pixels, pixels,
+                           difference.max_difference, diff_path.display()));
+    }
+    Ok(())
+}
+
+fn save_image(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|err| format!("Couldn't create {}: {}", directory.display(), err))?;
+    }
+    image.save(path).map_err(|err| format!("Couldn't save {}: {}", path.display(), err))
+}
@@ -0,0 +1,109 @@
+use std::ffi::c_void;
+use std::ptr::null;
+
+use itertools::Itertools;
+use nalgebra::Matrix4;
+use nalgebra_glm::{Mat4, vec4};
+
+use crate::particle_system::ParticleSystem;
+use crate::shader_compilation::ShaderProgram;
+use crate::shapes::quad_array_texture;
+
+/// GPU side of the particle systems, the vertices of the active particles are rebuilt every frame
+pub struct ParticleRenderer {
+    max_particles: usize,
+    vao: u32,
+    vbo: u32,
+}
+
+impl ParticleRenderer {
+    pub fn new(max_instances: usize) -> ParticleRenderer {
+        // Allocate VRAM for max_instances particles
+        let mut vao = 0;
+        gl_call!(gl::CreateVertexArrays(1, &mut vao));
+
+        // Position
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 0));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 0, 4 as i32, gl::FLOAT, gl::FALSE, 0));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 0, 0));
+
+        // Texture coords
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 1));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 1, 3 as i32, gl::FLOAT, gl::FALSE, (4 * std::mem::size_of::<f32>()) as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 1, 0));
+
+        // Pos and tex coords interleaved
+        let vbo = {
+            let mut vbo = 0;
+            gl_call!(gl::CreateBuffers(1, &mut vbo));
+            gl_call!(gl::NamedBufferData(vbo,
+                    (max_instances * 6 * 7 * std::mem::size_of::<f32>() as usize) as isize,
+                    null(),
+                    gl::DYNAMIC_DRAW));
+            gl_call!(gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, (7 * std::mem::size_of::<f32>()) as i32));
+            vbo
+        };
+
+        ParticleRenderer {
+            max_particles: max_instances,
+            vao,
+            vbo,
+        }
+    }
+
+    pub fn render(&self, particle_system: &ParticleSystem, alpha: f32, _shader: &mut ShaderProgram, view_matrix: &Mat4, projection_matrix: &Mat4) {
+        let mut vbo_data: Vec<f32> = Vec::new();
+
+        // Prepare the VBOs
+        let mut active_particles = 0;
+        for (position, scale, tex_coords) in particle_system.active_particles(alpha).take(self.max_particles) {
+            active_particles += 1;
+            let model_matrix = {
+                let translate_matrix = Matrix4::new_translation(&position);
+                let rotate_matrix = Matrix4::from_euler_angles(
+                    0.0f32,
+                    0.0,
+                    0.0,
+                );
+                translate_matrix * rotate_matrix
+            };
+
+            let mut model_view: Mat4 = view_matrix * model_matrix;
+            model_view.m11 = scale.x;
+            model_view.m12 = 0.;
+            model_view.m13 = 0.;
+
+            model_view.m21 = 0.;
+            model_view.m22 = scale.y;
+            model_view.m23 = 0.;
+
+            model_view.m31 = 0.;
+            model_view.m32 = 0.;
+            model_view.m33 = scale.z;
+
+            let mvp = projection_matrix * model_view;
+
+            let quad = quad_array_texture();
+            let pos_chunks = quad.iter().chunks(3);
+            let tex_chunks = tex_coords.iter().chunks(3);
+            let quad_vertices = pos_chunks.into_iter().zip(&tex_chunks);
+            for (mut pos, tex) in quad_vertices {
+                let pos = vec4(
+                    *pos.next().unwrap(),
+                    *pos.next().unwrap(),
+                    *pos.next().unwrap(),
+                    1.0);
+                vbo_data.extend(&(mvp * pos));
+                vbo_data.extend(tex);
+            }
+        }
+
+        gl_call!(gl::NamedBufferSubData(self.vbo,
+                    0,
+                    (vbo_data.len() * std::mem::size_of::<f32>()) as isize,
+                    vbo_data.as_ptr() as *mut c_void));
+
+        gl_call!(gl::BindVertexArray(self.vao));
+        gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, 6 * active_particles));
+    }
+}
@@ -1,11 +1,7 @@
-use std::time;
+//! The states of everything simulated on the fixed timestep of `Ticks` (player physics, particles,
+//! camera...), stepped once per tick. Interpolating between the last two ticks is only done for the rendering.
 
-use std::time::Instant;
-
-/// Fixed timestep physics simulation using the following method:
-/// https://gafferongames.com/post/fix_your_timestep/
-/// With this method, the physics are always deterministic and work independently
-/// of the performance of the game
+use crate::ticks::Ticks;
 
 pub trait Interpolatable {
     fn interpolate(&self, alpha: f32, other: &Self) -> Self;
@@ -17,32 +13,23 @@ impl Interpolatable for f32 {
     }
 }
 
+/// The states of something simulated on ticks, at the last two ticks
 pub struct Interpolator<T: Clone + Interpolatable> {
-    pub t: f32,
-    pub dt: f32,
-    pub current_time: time::Instant,
-    pub accumulator: f32,
     pub previous_state: T,
     pub current_state: T,
-    pub interpolated_state: T,
 }
 
 impl <T: Default + Clone + Interpolatable> Default for Interpolator<T> {
     fn default() -> Self {
-        Self::new(0., T::default())
+        Self::new(T::default())
     }
 }
 
 impl<T: Clone + Interpolatable> Interpolator<T> {
-    pub fn new(dt: f32, initial_state: T) -> Self {
+    pub fn new(initial_state: T) -> Self {
         Self {
-            t: 0.0,
-            dt,
-            current_time: time::Instant::now(),
-            accumulator: 0.0,
             previous_state: initial_state.clone(),
-            current_state: initial_state.clone(),
-            interpolated_state: initial_state
+            current_state: initial_state,
         }
     }
 
@@ -54,35 +41,21 @@ impl<T: Clone + Interpolatable> Interpolator<T> {
         &mut self.current_state
     }
 
-    pub fn get_interpolated_state(&self) -> &T {
-        &self.interpolated_state
+    /// The state between the last two ticks, `alpha` is `Ticks::alpha`
+    pub fn get_interpolated_state(&self, alpha: f32) -> T {
+        self.current_state.interpolate(alpha, &self.previous_state)
     }
 
-    /// Advances the physics for a given state.
-    pub fn step(&mut self, time: Instant, integrate: &mut dyn FnMut(&T, f32, f32) -> T) {
-        let now = time;
-        let mut frame_time = now.saturating_duration_since(self.current_time).as_secs_f32();
-        if frame_time > 0.25 {
-            frame_time = 0.25;
-        }
-        self.current_time = now;
-        self.accumulator += frame_time;
-
-        while self.accumulator >= self.dt {
-            self.previous_state = self.current_state.clone();
-            self.current_state = integrate(&self.previous_state, self.t, self.dt);
-            self.t += self.dt;
-            self.accumulator -= self.dt;
-        }
-
-        let alpha = self.accumulator / self.dt;
-        self.interpolated_state = self.current_state.interpolate(alpha, &self.previous_state);
+    /// Advances the simulation by one tick of `Ticks::DT` seconds
+    pub fn step(&mut self, integrate: &mut dyn FnMut(&T, f32) -> T) {
+        let next_state = integrate(&self.current_state, Ticks::DT);
+        self.previous_state = std::mem::replace(&mut self.current_state, next_state);
     }
 }
 
 impl Interpolator<f32> {
-    pub fn interpolate_fov(&mut self, time: Instant, target_fov: f32) {
-        self.step(time, &mut |&fov, _t, dt| {
+    pub fn interpolate_fov(&mut self, target_fov: f32) {
+        self.step(&mut |&fov, dt| {
             let convergence = 10.0;
             convergence * dt * target_fov + (1.0 - convergence * dt) * fov
         });
@@ -90,10 +63,10 @@ impl Interpolator<f32> {
 }
 
 impl Interpolator<f32> {
-    pub fn interpolate_camera_height(&mut self, time: Instant, target_camera_height: f32) {
-        self.step(time, &mut |&camera_height, _t, dt| {
+    pub fn interpolate_camera_height(&mut self, target_camera_height: f32) {
+        self.step(&mut |&camera_height, dt| {
             let convergence = 20.0;
             convergence * dt * target_camera_height + (1.0 - convergence * dt) * camera_height
         });
     }
-}
\ No newline at end of file
+}
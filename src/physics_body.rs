@@ -0,0 +1,279 @@
+use nalgebra_glm::{Vec3, vec3};
+use num_traits::Zero;
+
+use crate::aabb::AABB;
+use crate::chunk_manager::ChunkManager;
+use crate::constants::{GRAVITY, IN_AIR_FRICTION, MAX_VERTICAL_VELOCITY, ON_GROUND_FRICTION};
+use crate::physics::Interpolatable;
+
+/// An entity colliding with the blocks of the world: the player, the mobs, the dropped items and the particles.
+/// `position` is the center of the bottom face of `aabb`
+#[derive(Debug, Clone)]
+pub struct PhysicsBody {
+    pub position: Vec3,
+    pub aabb: AABB,
+    pub velocity: Vec3,
+    /// Reset after every tick, the forces applied to the body during a tick are added to it
+    pub acceleration: Vec3,
+    /// Size of the AABB on the x and z axes
+    pub width: f32,
+    pub height: f32,
+    /// 0 for the bodies that float, like a flying player
+    pub gravity_scale: f32,
+    pub ground_friction: f32,
+    pub air_friction: f32,
+    /// Height of the ledges the body climbs without jumping
+    pub step_height: f32,
+    pub is_on_ground: bool,
+}
+
+impl PhysicsBody {
+    pub fn new(position: Vec3, width: f32, height: f32) -> Self {
+        Self {
+            position,
+            aabb: Self::aabb_at(&position, width, height),
+            velocity: vec3(0.0, 0.0, 0.0),
+            acceleration: vec3(0.0, 0.0, 0.0),
+            width,
+            height,
+            gravity_scale: 1.0,
+            ground_friction: ON_GROUND_FRICTION,
+            air_friction: IN_AIR_FRICTION,
+            step_height: 0.0,
+            is_on_ground: false,
+        }
+    }
+
+    fn aabb_at(position: &Vec3, width: f32, height: f32) -> AABB {
+        let half_width = width / 2.0;
+        AABB::new(
+            vec3(position.x - half_width, position.y, position.z - half_width),
+            vec3(position.x + half_width, position.y + height, position.z + half_width))
+    }
+
+    /// Moves the body to `position` without colliding
+    pub fn teleport(&mut self, position: Vec3) {
+        self.position = position;
+        self.aabb = Self::aabb_at(&position, self.width, self.height);
+    }
+
+    /// Applies the gravity, the acceleration and the friction to the velocity
+    pub fn integrate_velocity(&mut self, dt: f32) {
+        self.acceleration.y += GRAVITY * self.gravity_scale;
+        self.velocity += self.acceleration * dt;
+
+        let friction = if self.is_on_ground {
+            self.ground_friction
+        } else {
+            self.air_friction
+        };
+
+        // We apply friction if the body is either slowing down (a = 0) or
+        // moves in the opposite direction
+        if self.acceleration.x.is_zero() || self.acceleration.x.signum() != self.velocity.x.signum() {
+            self.velocity.x -= friction * self.velocity.x * dt;
+        }
+        if self.acceleration.z.is_zero() || self.acceleration.z.signum() != self.velocity.z.signum() {
+            self.velocity.z -= friction * self.velocity.z * dt;
+        }
+
+        // Limit the free falling speed (vertical)
+        // https://www.planetminecraft.com/blog/the-acceleration-of-gravity-in-minecraft-and-terminal-velocity/
+        if self.velocity.y < -MAX_VERTICAL_VELOCITY {
+            self.velocity.y = -MAX_VERTICAL_VELOCITY;
+        }
+    }
+
+    /// Moves the body by its velocity, stopping at the blocks it collides with, then resets the acceleration.
+    /// With `hold_edges` a body standing on the ground doesn't fall off the edge of a block, like a sneaking player.
+    /// Returns how high the body stepped up onto the ledges in its way
+    pub fn move_and_collide(&mut self, dt: f32, chunk_manager: &ChunkManager, hold_edges: bool) -> f32 {
+        let will_hit_ground = |body: &PhysicsBody| {
+            body.sweep(1, body.velocity.y * dt, chunk_manager).is_some()
+        };
+
+        // We move the body along each axis separately, x and z first then y.
+        // Each move is swept: the body stops at the first block in its way, however fast it goes
+        let mut is_on_ground = false;
+        let mut climbed = 0.0;
+        for &axis in &[0, 2, 1] {
+            let distance = self.velocity[axis] * dt;
+            if distance.is_zero() {
+                continue;
+            }
+            let bk = self.clone();
+
+            let hit_face = self.sweep(axis, distance, chunk_manager);
+            let stepped = match hit_face {
+                Some(face) if axis != 1 && self.is_on_ground && self.step_height > 0.0 => {
+                    self.step_up(axis, distance, face, chunk_manager)
+                }
+                _ => None,
+            };
+
+            let mut step = 0.0;
+            if let Some((stepped, stepped_height)) = stepped {
+                *self = stepped;
+                step = stepped_height;
+            } else if let Some(face) = hit_face {
+                is_on_ground |= axis == 1 && distance < 0.0;
+                self.move_to_face(axis, distance, face);
+            } else {
+                self.aabb.mins[axis] += distance;
+                self.aabb.maxs[axis] += distance;
+            }
+
+            if hold_edges
+                && self.is_on_ground
+                && !will_hit_ground(self)
+                && self.velocity.y < 0. {
+                *self = bk;
+                step = 0.0;
+
+                if axis != 1 {
+                    self.velocity[axis] = 0.0;
+                }
+            }
+            climbed += step;
+        }
+        self.is_on_ground = is_on_ground;
+
+        // Update the position of the body and reset the acceleration
+        self.position.x = self.aabb.mins.x + self.width / 2.0;
+        self.position.y = self.aabb.mins.y;
+        self.position.z = self.aabb.mins.z + self.width / 2.0;
+
+        self.acceleration = vec3(0.0, 0.0, 0.0);
+        climbed
+    }
+
+    /// Puts the body against the block face it hit while moving by `distance` along `axis`
+    fn move_to_face(&mut self, axis: usize, distance: f32, face: f32) {
+        // The face is set exactly instead of translating the AABB
+        // because of the imprecision of floats
+        let size = if axis == 1 { self.height } else { self.width };
+        if distance < 0.0 {
+            self.aabb.mins[axis] = face;
+            self.aabb.maxs[axis] = face + size;
+        } else {
+            self.aabb.mins[axis] = face - size;
+            self.aabb.maxs[axis] = face;
+        }
+        self.velocity[axis] = 0.0;
+    }
+
+    /// Tries the horizontal move blocked at `face` again with the body lifted by `step_height`, then puts it
+    /// back down onto the obstacle. Returns the stepped body and how high it climbed, if it got further that way
+    fn step_up(&self, axis: usize, distance: f32, face: f32, chunk_manager: &ChunkManager) -> Option<(PhysicsBody, f32)> {
+        let mut stepped = self.clone();
+
+        // Only as high as the room above the body allows
+        let lift = match stepped.sweep(1, self.step_height, chunk_manager) {
+            Some(ceiling) => ceiling - stepped.aabb.maxs.y,
+            None => self.step_height,
+        };
+        stepped.aabb.mins.y += lift;
+        stepped.aabb.maxs.y += lift;
+
+        match stepped.sweep(axis, distance, chunk_manager) {
+            Some(obstacle) => stepped.move_to_face(axis, distance, obstacle),
+            None => {
+                stepped.aabb.mins[axis] += distance;
+                stepped.aabb.maxs[axis] += distance;
+            }
+        }
+
+        match stepped.sweep(1, -lift, chunk_manager) {
+            Some(ground) => {
+                stepped.aabb.mins.y = ground;
+                stepped.aabb.maxs.y = ground + self.height;
+            }
+            None => {
+                stepped.aabb.mins.y -= lift;
+                stepped.aabb.maxs.y -= lift;
+            }
+        }
+
+        let climbed = stepped.aabb.mins.y - self.aabb.mins.y;
+        let travelled = (stepped.aabb.mins[axis] - self.aabb.mins[axis]).abs();
+        let blocked_travel = if distance < 0.0 {
+            self.aabb.mins[axis] - face
+        } else {
+            face - self.aabb.maxs[axis]
+        };
+        if climbed > 0.0 && travelled > blocked_travel {
+            Some((stepped, climbed))
+        } else {
+            None
+        }
+    }
+
+    /// Sweeps the body by `distance` along `axis` (0, 1 or 2 for x, y or z) and returns the coordinate
+    /// of the first face of a collision box it would hit, if any.
+    /// Only the blocks in front of the body stop it, so a body stuck in a block can still get out of it
+    pub fn sweep(&self, axis: usize, distance: f32, chunk_manager: &ChunkManager) -> Option<f32> {
+        let mut swept = self.aabb;
+        if distance < 0.0 {
+            swept.mins[axis] += distance;
+        } else {
+            swept.maxs[axis] += distance;
+        }
+
+        let overlaps_across = |block_aabb: &AABB| {
+            (0..3).filter(|&other| other != axis).all(|other| {
+                self.aabb.mins[other] < block_aabb.maxs[other] && self.aabb.maxs[other] > block_aabb.mins[other]
+            })
+        };
+
+        // We query all the blocks the body goes through and keep the closest face in front of it.
+        // The blocks below are queried too, their collision boxes can be higher than a block like fences
+        let mut closest_face: Option<f32> = None;
+        for y in swept.mins.y.floor() as i32 - 1..=swept.maxs.y.floor() as i32 {
+            for z in swept.mins.z.floor() as i32..=swept.maxs.z.floor() as i32 {
+                for x in swept.mins.x.floor() as i32..=swept.maxs.x.floor() as i32 {
+                    let (block, state) = match chunk_manager.get_block_with_connections(x, y, z) {
+                        Some((block, state)) if !block.is_air() => (block, state),
+                        _ => continue,
+                    };
+                    for block_aabb in chunk_manager.block_models().collision_boxes_at(block, state, x, y, z) {
+                        if !overlaps_across(&block_aabb) {
+                            continue;
+                        }
+
+                        if distance < 0.0 {
+                            let face = block_aabb.maxs[axis];
+                            if face <= self.aabb.mins[axis] && face > swept.mins[axis] {
+                                closest_face = Some(closest_face.map_or(face, |closest| closest.max(face)));
+                            }
+                        } else {
+                            let face = block_aabb.mins[axis];
+                            if face >= self.aabb.maxs[axis] && face < swept.maxs[axis] {
+                                closest_face = Some(closest_face.map_or(face, |closest| closest.min(face)));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        closest_face
+    }
+}
+
+impl Interpolatable for PhysicsBody {
+    fn interpolate(&self, alpha: f32, other: &Self) -> Self {
+        let interpolate_vec3 = |from: &Vec3, to: &Vec3| {
+            alpha * from + (1.0 - alpha) * to
+        };
+
+        Self {
+            position: interpolate_vec3(&self.position, &other.position),
+            aabb: AABB {
+                mins: interpolate_vec3(&self.aabb.mins, &other.aabb.mins),
+                maxs: interpolate_vec3(&self.aabb.maxs, &other.aabb.maxs),
+            },
+            velocity: interpolate_vec3(&self.velocity, &other.velocity),
+            acceleration: interpolate_vec3(&self.acceleration, &other.acceleration),
+            ..self.clone()
+        }
+    }
+}
@@ -1,14 +1,13 @@
-use std::time::Instant;
-
 use nalgebra::{clamp, Vector3};
 use nalgebra_glm::{IVec3, Mat4, pi, vec2, Vec3, vec3};
 use num_traits::Zero;
 
-use crate::aabb::{AABB, get_block_aabb};
+use crate::constants::{FLYING_SPEED, FLYING_SPRINTING_SPEED, FOV, HORIZONTAL_ACCELERATION, JUMP_COOLDOWN_TICKS, JUMP_IMPULSE, MOUSE_SENSITIVITY_X, MOUSE_SENSITIVITY_Y, ON_GROUND_FRICTION, PLAYER_EYES_HEIGHT, PLAYER_HEIGHT, PLAYER_STEP_HEIGHT, PLAYER_WIDTH, SNEAKING_SPEED, SPRINTING_SPEED, WALKING_SPEED};
 use crate::chunk_manager::ChunkManager;
-use crate::constants::{FLYING_SPEED, FLYING_SPRINTING_SPEED, FOV, HORIZONTAL_ACCELERATION, IN_AIR_FRICTION, JUMP_IMPULSE, MAX_VERTICAL_VELOCITY, MOUSE_SENSITIVITY_X, MOUSE_SENSITIVITY_Y, ON_GROUND_FRICTION, PLAYER_EYES_HEIGHT, PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_WIDTH, SNEAKING_SPEED, SPRINTING_SPEED, WALKING_SPEED};
 use crate::input::InputCache;
-use crate::physics::{Interpolatable, Interpolator};
+use crate::keybindings::{DoubleTap, InputAction};
+use crate::physics::Interpolator;
+use crate::physics_body::PhysicsBody;
 use crate::util::Forward;
 
 pub struct PlayerState {
@@ -18,43 +17,45 @@ pub struct PlayerState {
     pub view_matrix: Mat4,
     pub projection_matrix: Mat4,
 
-    pub is_on_ground: bool,
     pub is_sneaking: bool,
     pub is_sprinting: bool,
     pub is_flying: bool,
+    /// Whether the server lets the player fly
+    pub can_fly: bool,
+    /// What moved the player during the last frame, sent to the server with its movement
+    pub movement_input: MovementInput,
 
     pub targeted_block: Option<((i32, i32, i32), IVec3)>,
 
-    pub(crate) jump_last_executed: Instant,
-    pub(crate) fly_throttle: bool,
-    pub(crate) fly_last_toggled: Instant,
-    pub(crate) sprint_throttle: bool,
-    pub(crate) sprint_last_toggled: Instant,
-    pub(crate) block_placing_last_executed: Instant,
+    /// Tick of the last jump
+    pub(crate) jump_last_executed: u64,
+    pub(crate) fly_double_tap: DoubleTap,
+    pub(crate) sprint_double_tap: DoubleTap,
+    /// Tick of the last block placed or broken
+    pub(crate) block_placing_last_executed: u64,
 }
 
 impl PlayerState {
     pub fn new() -> Self {
         PlayerState {
             rotation: vec3(0.0, 0.0, 0.0), // In radians
-            camera_height: Interpolator::new(1. / 30., PLAYER_EYES_HEIGHT),
-            fov: Interpolator::new(1.0 / 30.0, *FOV),
+            camera_height: Interpolator::new(PLAYER_EYES_HEIGHT),
+            fov: Interpolator::new(*FOV),
             view_matrix: Mat4::identity(),
             projection_matrix: Mat4::identity(),
 
-            is_on_ground: false,
             is_sneaking: false,
             is_sprinting: false,
             is_flying: false,
+            can_fly: false,
+            movement_input: MovementInput::default(),
 
             targeted_block: None,
 
-            jump_last_executed: Instant::now(),
-            fly_throttle: false,
-            fly_last_toggled: Instant::now(),
-            sprint_throttle: false,
-            sprint_last_toggled: Instant::now(),
-            block_placing_last_executed: Instant::now(),
+            jump_last_executed: 0,
+            fly_double_tap: DoubleTap::new(),
+            sprint_double_tap: DoubleTap::new(),
+            block_placing_last_executed: 0,
         }
     }
 
@@ -69,82 +70,121 @@ impl PlayerState {
     }
 }
 
-#[derive(Clone)]
-pub struct PlayerPhysicsState {
-    pub position: Vec3,
-    pub aabb: AABB,
-    pub velocity: Vec3,
-    pub acceleration: Vec3,
+/// The held movement keys and the movement modes of the player during a frame, from which the client
+/// and the server simulate the same ticks
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MovementInput {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub jump: bool,
+    pub sneak: bool,
+    pub is_sneaking: bool,
+    pub is_sprinting: bool,
+    pub is_flying: bool,
 }
 
-impl PlayerPhysicsState {
-    pub fn new_at_position(position: Vec3) -> Self {
-        PlayerPhysicsState {
-            position,
-            aabb: {
-                let mins = vec3(position.x - PLAYER_HALF_WIDTH, position.y, position.z - PLAYER_HALF_WIDTH);
-                let maxs = vec3(position.x + PLAYER_HALF_WIDTH, position.y + PLAYER_HEIGHT, position.z + PLAYER_HALF_WIDTH);
-                AABB::new(mins, maxs)
-            },
-            velocity: vec3(0.0, 0.0, 0.0),
-            acceleration: vec3(0.0, 0.0, 0.0),
+impl MovementInput {
+    pub fn new(input_cache: &InputCache, player_state: &PlayerState) -> Self {
+        Self {
+            forward: input_cache.is_action_active(InputAction::MoveForward),
+            backward: input_cache.is_action_active(InputAction::MoveBackward),
+            left: input_cache.is_action_active(InputAction::MoveLeft),
+            right: input_cache.is_action_active(InputAction::MoveRight),
+            jump: input_cache.is_action_active(InputAction::Jump),
+            sneak: input_cache.is_action_active(InputAction::Sneak),
+            is_sneaking: player_state.is_sneaking,
+            is_sprinting: player_state.is_sprinting,
+            is_flying: player_state.is_flying,
         }
     }
-}
 
-impl Interpolatable for PlayerPhysicsState {
-    fn interpolate(&self, alpha: f32, other: &Self) -> Self {
-        let interpolate_vec3 = |from: &Vec3, to: &Vec3| {
-            alpha * from + (1.0 - alpha) * to
-        };
+    /// One bit per field, in their order
+    pub fn bits(&self) -> u16 {
+        [self.forward, self.backward, self.left, self.right, self.jump, self.sneak,
+            self.is_sneaking, self.is_sprinting, self.is_flying]
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &bit)| bits | (bit as u16) << i)
+    }
 
+    pub fn from_bits(bits: u16) -> Self {
+        let bit = |i: u16| bits & (1 << i) != 0;
         Self {
-            position: interpolate_vec3(&self.position, &other.position),
-            aabb: AABB {
-                mins: interpolate_vec3(&self.aabb.mins, &other.aabb.mins),
-                maxs: interpolate_vec3(&self.aabb.maxs, &other.aabb.maxs),
-            },
-            velocity: interpolate_vec3(&self.velocity, &other.velocity),
-            acceleration: interpolate_vec3(&self.acceleration, &other.acceleration),
+            forward: bit(0),
+            backward: bit(1),
+            left: bit(2),
+            right: bit(3),
+            jump: bit(4),
+            sneak: bit(5),
+            is_sneaking: bit(6),
+            is_sprinting: bit(7),
+            is_flying: bit(8),
         }
     }
 }
 
-impl PlayerPhysicsState {
-    pub fn apply_keyboard_mouvement(&mut self, player_properties: &mut PlayerState, input_cache: &InputCache) {
+impl PhysicsBody {
+    pub fn new_player(position: Vec3) -> Self {
+        Self {
+            step_height: PLAYER_STEP_HEIGHT,
+            ..Self::new(position, PLAYER_WIDTH, PLAYER_HEIGHT)
+        }
+    }
+
+    /// Simulates the tick `tick` of a player, on the client and on the server.
+    /// Returns the height the player climbed without jumping
+    pub fn step_player(&mut self, player_properties: &mut PlayerState, input: &MovementInput, tick: u64, dt: f32, chunk_manager: &ChunkManager) -> f32 {
+        self.gravity_scale = if player_properties.is_flying { 0.0 } else { 1.0 };
+
+        self.apply_keyboard_mouvement(player_properties, input, tick);
+        self.integrate_velocity(dt);
+        if player_properties.is_flying {
+            self.apply_flying_friction(dt);
+        }
+        self.limit_velocity(player_properties);
+
+        // Don't let the player fall if he's sneaking on the block
+        let climbed = self.move_and_collide(dt, chunk_manager, input.sneak);
+        if self.is_on_ground {
+            player_properties.is_flying = false;
+        }
+        climbed
+    }
+
+    /// `tick` is the tick being simulated
+    pub fn apply_keyboard_mouvement(&mut self, player_properties: &mut PlayerState, input: &MovementInput, tick: u64) {
         let rotation = &player_properties.rotation;
         if player_properties.is_flying {
-            if input_cache.is_key_pressed(glfw::Key::Space) {
+            if input.jump {
                 self.acceleration = vec3(0.0, 100.0, 0.0);
             }
-            if input_cache.is_key_pressed(glfw::Key::LeftShift) {
+            if input.sneak {
                 self.acceleration = vec3(0.0, -100.0, 0.0);
             }
         }
 
         // Jump
-        if input_cache.is_key_pressed(glfw::Key::Space) {
-            let now = Instant::now();
-            if now.duration_since(player_properties.jump_last_executed).as_secs_f32() >= 0.475 {
-                if player_properties.is_on_ground {
-                    self.velocity.y = *JUMP_IMPULSE;
-                    player_properties.jump_last_executed = now;
-                }
-            }
+        if input.jump
+            && tick.saturating_sub(player_properties.jump_last_executed) >= JUMP_COOLDOWN_TICKS
+            && self.is_on_ground {
+            self.velocity.y = *JUMP_IMPULSE;
+            player_properties.jump_last_executed = tick;
         }
         // Walk
         let mut horizontal_acceleration = vec3(0.0, 0.0, 0.0);
 
-        if input_cache.is_key_pressed(glfw::Key::W) {
+        if input.forward {
             horizontal_acceleration += -rotation.forward().cross(&Vector3::y()).cross(&Vector3::y())
         }
-        if input_cache.is_key_pressed(glfw::Key::S) {
+        if input.backward {
             horizontal_acceleration += rotation.forward().cross(&Vector3::y()).cross(&Vector3::y())
         }
-        if input_cache.is_key_pressed(glfw::Key::A) {
+        if input.left {
             horizontal_acceleration += -rotation.forward().cross(&Vector3::y())
         }
-        if input_cache.is_key_pressed(glfw::Key::D) {
+        if input.right {
             horizontal_acceleration += rotation.forward().cross(&Vector3::y())
         }
 
@@ -154,104 +194,10 @@ impl PlayerPhysicsState {
         }
     }
 
-    pub fn get_colliding_block_coords(&self, chunk_manager: &ChunkManager) -> Option<Vec3> {
-        let player_mins = &self.aabb.mins;
-        let player_maxs = &self.aabb.maxs;
-
-        let block_mins = vec3(
-            player_mins.x.floor() as i32, player_mins.y.floor() as i32, player_mins.z.floor() as i32,
-        );
-        let block_maxs = vec3(
-            player_maxs.x.floor() as i32, player_maxs.y.floor() as i32, player_maxs.z.floor() as i32,
-        );
-
-        // We query all the blocks around the player to check whether it's colliding with one of them
-        let mut colliding_block = None;
-        for y in block_mins.y..=block_maxs.y {
-            for z in block_mins.z..=block_maxs.z {
-                for x in block_mins.x..=block_maxs.x {
-                    if let Some(block) = chunk_manager.get_block(x, y, z) {
-                        if !block.is_air() {
-                            let block_aabb = get_block_aabb(&vec3(x as f32, y as f32, z as f32));
-                            if self.aabb.intersects(&block_aabb) {
-                                colliding_block = Some(vec3(x as f32, y as f32, z as f32));
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        colliding_block
-    }
-
-    pub fn separate_from_block(&mut self, v: &Vec3, block_coords: &Vec3) -> bool {
-        let mut is_player_on_ground = false;
-        let block_aabb = get_block_aabb(&block_coords);
-
-        if !v.x.is_zero() {
-            if v.x < 0.0 {
-                // I've opted to create a new AABB instead of translating the old one
-                // because of the imprecision of floats.
-                self.aabb = AABB::new(
-                    vec3(block_aabb.maxs.x, self.aabb.mins.y, self.aabb.mins.z),
-                    vec3(block_aabb.maxs.x + PLAYER_WIDTH, self.aabb.maxs.y, self.aabb.maxs.z));
-            } else {
-                self.aabb = AABB::new(
-                    vec3(block_aabb.mins.x - PLAYER_WIDTH, self.aabb.mins.y, self.aabb.mins.z),
-                    vec3(block_aabb.mins.x, self.aabb.maxs.y, self.aabb.maxs.z));
-            }
-            self.velocity.x = 0.0
-        }
-
-        if !v.y.is_zero() {
-            if v.y < 0.0 {
-                self.aabb = AABB::new(
-                    vec3(self.aabb.mins.x, block_aabb.maxs.y, self.aabb.mins.z),
-                    vec3(self.aabb.maxs.x, block_aabb.maxs.y + PLAYER_HEIGHT, self.aabb.maxs.z));
-                is_player_on_ground = true;
-            } else {
-                self.aabb = AABB::new(
-                    vec3(self.aabb.mins.x, block_aabb.mins.y - PLAYER_HEIGHT, self.aabb.mins.z),
-                    vec3(self.aabb.maxs.x, block_aabb.mins.y, self.aabb.maxs.z));
-            }
-            self.velocity.y = 0.0;
-        }
-
-        if !v.z.is_zero() {
-            if v.z < 0.0 {
-                self.aabb = AABB::new(
-                    vec3(self.aabb.mins.x, self.aabb.mins.y, block_aabb.maxs.z),
-                    vec3(self.aabb.maxs.x, self.aabb.maxs.y, block_aabb.maxs.z + PLAYER_WIDTH));
-            } else {
-                self.aabb = AABB::new(
-                    vec3(self.aabb.mins.x, self.aabb.mins.y, block_aabb.mins.z - PLAYER_WIDTH),
-                    vec3(self.aabb.maxs.x, self.aabb.maxs.y, block_aabb.mins.z));
-            }
-            self.velocity.z = 0.0
-        }
-        is_player_on_ground
-    }
-
-    pub fn apply_friction(&mut self, dt: f32, player_state: &PlayerState) {
-        let friction = if player_state.is_on_ground {
-            ON_GROUND_FRICTION
-        } else {
-            IN_AIR_FRICTION
-        };
-
-        // We apply friction if the player is either slowing down (a = 0) or
-        // walks in the opposite direction
-        if self.acceleration.x.is_zero() || self.acceleration.x.signum() != self.velocity.x.signum() {
-            self.velocity.x -= friction * self.velocity.x * dt;
-        }
-        if self.acceleration.z.is_zero() || self.acceleration.z.signum() != self.velocity.z.signum() {
-            self.velocity.z -= friction * self.velocity.z * dt;
-        }
-        if player_state.is_flying {
-            if self.acceleration.y.is_zero() || self.acceleration.y.signum() != self.velocity.y.signum() {
-                self.velocity.y -= ON_GROUND_FRICTION * self.velocity.y * dt;
-            }
+    /// The vertical friction of a flying player, the horizontal one is applied by `integrate_velocity`
+    pub fn apply_flying_friction(&mut self, dt: f32) {
+        if self.acceleration.y.is_zero() || self.acceleration.y.signum() != self.velocity.y.signum() {
+            self.velocity.y -= ON_GROUND_FRICTION * self.velocity.y * dt;
         }
     }
 
@@ -283,12 +229,6 @@ impl PlayerPhysicsState {
 
         self.velocity.x = horizontal_vel.x;
         self.velocity.z = horizontal_vel.y;
-
-        // Limit the free falling speed (vertical)
-        // https://www.planetminecraft.com/blog/the-acceleration-of-gravity-in-minecraft-and-terminal-velocity/
-        if self.velocity.y < -MAX_VERTICAL_VELOCITY {
-            self.velocity.y = -MAX_VERTICAL_VELOCITY;
-        }
     }
 }
 
@@ -1,12 +1,16 @@
 // Algorithm translated from https://github.com/andyhall/fast-voxel-raycast
 // Paper: http://www.cse.chalmers.se/edu/year/2010/course/TDA361/grid.pdf
 
-use nalgebra_glm::{Vec3, floor, IVec3};
+use nalgebra_glm::{Vec3, floor, IVec3, vec3};
 use num_traits::float::FloatCore;
 
+use crate::aabb::AABB;
+
 // direction must be normalized
-pub fn raycast(is_solid_block_at: &dyn Fn(i32, i32, i32) -> bool,
-                  origin: &Vec3, direction: &Vec3, distance: f32) -> Option<((i32, i32, i32), IVec3)> {
+// selection_boxes_at returns the boxes of the block at (x, y, z) in block coordinates, none for air.
+// The ray goes through the parts of the blocks outside of their boxes, like above a slab
+pub fn raycast<'a>(selection_boxes_at: &dyn Fn(i32, i32, i32) -> &'a [AABB],
+                   origin: &Vec3, direction: &Vec3, distance: f32) -> Option<((i32, i32, i32), IVec3)> {
 
     let mut t = 0.0f32;
     let mut i: IVec3 = floor(&origin).map(|x| x as i32);
@@ -33,15 +37,22 @@ pub fn raycast(is_solid_block_at: &dyn Fn(i32, i32, i32) -> bool,
     let mut stepped_index = -1;
     while t <= distance {
         // exit check
-        if is_solid_block_at(i.x, i.y, i.z) {
-            _hit_pos = origin.zip_map(&direction, |p, d| p + t * d);
-            if stepped_index == 0 {
+        let block_position = vec3(i.x as f32, i.y as f32, i.z as f32);
+        let hit = selection_boxes_at(i.x, i.y, i.z).iter()
+            .filter_map(|b| intersect_box(&AABB::new(b.mins + block_position, b.maxs + block_position), origin, direction))
+            .filter(|&(t_enter, _)| t_enter <= distance)
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        if let Some((t_enter, entry_axis)) = hit {
+            _hit_pos = origin.zip_map(&direction, |p, d| p + t_enter.max(t) * d);
+            // The ray starts inside of the box, the normal is the face of the voxel it came from
+            let axis = if t_enter < 0.0 { stepped_index } else { entry_axis as i32 };
+            if axis == 0 {
                 hit_norm[0] = -step.x;
             }
-            if stepped_index == 1 {
+            if axis == 1 {
                 hit_norm[1] = -step.y;
             }
-            if stepped_index == 2 {
+            if axis == 2 {
                 hit_norm[2] = -step.z;
             }
             return Some(((i.x, i.y, i.z), hit_norm));
@@ -78,4 +89,33 @@ pub fn raycast(is_solid_block_at: &dyn Fn(i32, i32, i32) -> bool,
     // no voxel hit found
     _hit_pos = origin.zip_map(&direction, |p, d| p + t * d);
     return None;
-}
\ No newline at end of file
+}
+
+/// Slab test of the ray against a box, returns the distance at which the ray enters it and the axis
+/// of the face it enters through. The distance is negative when the ray starts inside of the box
+fn intersect_box(b: &AABB, origin: &Vec3, direction: &Vec3) -> Option<(f32, usize)> {
+    let mut t_enter = f32::neg_infinity();
+    let mut t_exit = f32::infinity();
+    let mut entry_axis = 0;
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < b.mins[axis] || origin[axis] > b.maxs[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (b.mins[axis] - origin[axis]) / direction[axis];
+        let t2 = (b.maxs[axis] - origin[axis]) / direction[axis];
+        let (near, far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+        if near > t_enter {
+            t_enter = near;
+            entry_axis = axis;
+        }
+        t_exit = t_exit.min(far);
+    }
+    if t_enter <= t_exit && t_exit >= 0.0 {
+        Some((t_enter, entry_axis))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::block_state::BlockState;
+use crate::chunk::BlockID;
+use crate::input::{Action, InputCache, InputEvent, Key, MouseButton};
+use crate::network::protocol::{ProtocolError, Reader, Writer};
+use crate::ticks::Ticks;
+use crate::timer::Timer;
+use crate::world_storage::{PlayerData, WorldStorage};
+
+/// Bumped on every incompatible change to the format below
+const REPLAY_VERSION: u16 = 5;
+const REPLAY_MAGIC: &[u8; 4] = b"MKRP";
+
+/// The input events read during one frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayFrame {
+    /// Time of the global `Timer` since the first frame
+    pub time: Duration,
+    /// `Ticks::tick` once the ticks of the frame were scheduled
+    pub tick: u64,
+    /// `Ticks::pending`, the ticks simulated during the frame. The others since the
+    /// previous frame were skipped
+    pub pending: u32,
+    pub events: Vec<InputEvent>,
+}
+
+/// The blocks and the states of a column modified by the players
+pub type SavedColumn = ((i32, i32), Vec<BlockID>, Vec<BlockState>);
+
+/// A recorded session, played again by feeding the same events during the same ticks in a world
+/// generated from the same seed, with the player and the modified columns saved when the recording started.
+/// The file is made of the magic, the version, the seed, the player and the columns,
+/// followed by the frames until the end of the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u32,
+    /// None if the player had never played in the world
+    pub player: Option<PlayerData>,
+    pub columns: Vec<SavedColumn>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = header(self.seed, self.player.as_ref(), &self.columns);
+        for frame in &self.frames {
+            encode_frame(&mut bytes, frame.time, frame.tick, frame.pending, &frame.events);
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut r = Reader(bytes);
+        if r.bytes(4).map_err(invalid_data)? != REPLAY_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a replay file"));
+        }
+        let version = r.u16().map_err(invalid_data)?;
+        if version != REPLAY_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("Unsupported replay version {}", version)));
+        }
+        let seed = r.u32().map_err(invalid_data)?;
+        let (player, columns) = decode_world(&mut r).map_err(invalid_data)?;
+
+        let mut frames = Vec::new();
+        while !r.0.is_empty() {
+            frames.push(decode_frame(&mut r).map_err(invalid_data)?);
+        }
+        Ok(Self {
+            seed,
+            player,
+            columns,
+            frames,
+        })
+    }
+
+    /// Saves the player and the columns of the replay in `storage`, a new world generated from its seed
+    pub fn restore_world(&self, storage: &WorldStorage, player_name: &str) -> io::Result<()> {
+        if let Some(player) = &self.player {
+            storage.save_player(player_name, player)?;
+        }
+        for (xz, blocks, states) in &self.columns {
+            storage.save_column(*xz, blocks, states)?;
+        }
+        Ok(())
+    }
+}
+
+/// Appends the frames of the session to a replay file as they are played,
+/// so that the file is usable even if the game crashes
+pub struct ReplayRecorder {
+    file: BufWriter<File>,
+    start: Option<Instant>,
+}
+
+impl ReplayRecorder {
+    /// Starts with the saved state of the world in `storage`, before the server opens it
+    pub fn create<P: AsRef<Path>>(path: P, storage: &WorldStorage, player_name: &str) -> io::Result<Self> {
+        let player = storage.load_player(player_name)?;
+        let mut columns = Vec::new();
+        for xz in storage.saved_columns()? {
+            if let Some((blocks, states)) = storage.load_column(xz)? {
+                columns.push((xz, blocks, states));
+            }
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&header(storage.seed(), player.as_ref(), &columns))?;
+        Ok(Self {
+            file,
+            start: None,
+        })
+    }
+
+    /// `now` is the time of the global `Timer` at the start of the frame and `ticks` the ticks scheduled for it
+    pub fn record_frame(&mut self, now: Instant, ticks: &Ticks, events: &[InputEvent]) -> io::Result<()> {
+        let time = now.saturating_duration_since(*self.start.get_or_insert(now));
+        let mut bytes = Vec::new();
+        encode_frame(&mut bytes, time, ticks.tick, ticks.pending, events);
+        self.file.write_all(&bytes)?;
+        self.file.flush()
+    }
+}
+
+/// Plays the frames of a `Replay` one by one
+pub struct ReplayPlayer {
+    frames: VecDeque<ReplayFrame>,
+    start: Option<Instant>,
+    last_tick: Option<u64>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            frames: replay.frames.into(),
+            start: None,
+            last_tick: None,
+        }
+    }
+
+    /// Feeds the events of the next frame to `input_cache`, schedules its ticks and moves `timer`
+    /// to the time of the frame, `timer` must be a `Timer::manual`. Returns false once every frame was played
+    pub fn play_frame(&mut self, input_cache: &mut InputCache, timer: &mut Timer, ticks: &mut Ticks) -> bool {
+        let frame = match self.frames.pop_front() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let start = *self.start.get_or_insert(timer.time());
+        timer.advance((start + frame.time).saturating_duration_since(timer.time()));
+
+        // The counter of the replay starts at the tick of the server it plays on, only the
+        // numbers of ticks between the frames are replayed
+        let pending = frame.pending as u64;
+        let last_tick = self.last_tick.unwrap_or_else(|| frame.tick.saturating_sub(pending));
+        ticks.replay(frame.tick.saturating_sub(last_tick).saturating_sub(pending), frame.pending);
+        self.last_tick = Some(frame.tick);
+
+        for event in &frame.events {
+            input_cache.handle_event(event);
+        }
+        true
+    }
+
+    /// Plays nothing during this frame, the replay resumes where it was on the next call to `play_frame`
+    pub fn hold_frame(&mut self, ticks: &mut Ticks) {
+        ticks.replay(0, 0);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+fn header(seed: u32, player: Option<&PlayerData>, columns: &[SavedColumn]) -> Vec<u8> {
+    let mut bytes = REPLAY_MAGIC.to_vec();
+    let mut w = Writer(&mut bytes);
+    w.u16(REPLAY_VERSION).u32(seed);
+    match player {
+        Some(player) => {
+            let player = player.encode();
+            w.u8(1).u32(player.len() as u32).bytes(&player);
+        }
+        None => {
+            w.u8(0);
+        }
+    }
+    w.u32(columns.len() as u32);
+    for ((x, z), blocks, states) in columns {
+        w.i32(*x).i32(*z).blocks(blocks).block_states(states);
+    }
+    bytes
+}
+
+fn decode_world(r: &mut Reader) -> Result<(Option<PlayerData>, Vec<SavedColumn>), ProtocolError> {
+    let player = match r.u8()? {
+        0 => None,
+        _ => {
+            let length = r.u32()? as usize;
+            Some(PlayerData::decode(r.bytes(length)?)?)
+        }
+    };
+    let n_columns = r.u32()?;
+    let mut columns = Vec::new();
+    for _ in 0..n_columns {
+        let xz = (r.i32()?, r.i32()?);
+        columns.push((xz, r.blocks()?, r.block_states()?));
+    }
+    Ok((player, columns))
+}
+
+fn encode_frame(bytes: &mut Vec<u8>, time: Duration, tick: u64, pending: u32, events: &[InputEvent]) {
+    let mut w = Writer(bytes);
+    w.u64(time.as_micros() as u64).u64(tick).u32(pending).u32(events.len() as u32);
+    for event in events {
+        match *event {
+            InputEvent::Key(key, action) => {
+                w.u8(0).i32(key as i32).u8(action as u8);
+            }
+            InputEvent::MouseButton(button, action) => {
+                w.u8(1).i32(button as i32).u8(action as u8);
+            }
+            InputEvent::CursorPos(x, y) => {
+                w.u8(2).f64(x).f64(y);
+            }
+            InputEvent::Scroll(x, y) => {
+                w.u8(3).f64(x).f64(y);
+            }
+        }
+    }
+}
+
+fn decode_frame(r: &mut Reader) -> Result<ReplayFrame, ProtocolError> {
+    let time = Duration::from_micros(r.u64()?);
+    let tick = r.u64()?;
+    let pending = r.u32()?;
+    let n_events = r.u32()?;
+    let mut events = Vec::new();
+    for _ in 0..n_events {
+        let event = match r.u8()? {
+            0 => {
+                let key = Key::from_code(r.i32()?).ok_or(ProtocolError::InvalidEvent)?;
+                InputEvent::Key(key, decode_action(r.u8()?)?)
+            }
+            1 => {
+                let button = MouseButton::from_code(r.i32()?).ok_or(ProtocolError::InvalidEvent)?;
+                InputEvent::MouseButton(button, decode_action(r.u8()?)?)
+            }
+            2 => InputEvent::CursorPos(r.f64()?, r.f64()?),
+            3 => InputEvent::Scroll(r.f64()?, r.f64()?),
+            _ => return Err(ProtocolError::InvalidEvent),
+        };
+        events.push(event);
+    }
+    Ok(ReplayFrame {
+        time,
+        tick,
+        pending,
+        events,
+    })
+}
+
+fn decode_action(action: u8) -> Result<Action, ProtocolError> {
+    Action::from_code(action).ok_or(ProtocolError::InvalidEvent)
+}
+
+fn invalid_data(err: ProtocolError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,176 @@
+//! Resource packs replace the textures of the game. A pack is a directory or a zip archive with a
+//! `pack.json` manifest at its root and the same layout as the game: `textures/blocks/stone.png`
+//! replaces the texture of the stone.
+//!
+//! The packs are stacked on top of the built-in one, the working directory of the game. A file is
+//! read from the pack with the highest priority that has it
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use crate::constants::ASSET_DIRECTORY;
+
+/// The directory the packs are looked for in
+pub const RESOURCE_PACKS_DIRECTORY: &str = "resourcepacks";
+/// Lists the enabled packs, one per line from the highest priority to the lowest
+pub const ENABLED_RESOURCE_PACKS_FILE: &str = "resourcepacks.txt";
+
+/// The `pack.json` at the root of a pack
+#[derive(Debug, Clone, Deserialize)]
+pub struct PackManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+enum PackFiles {
+    Directory(PathBuf),
+    /// Reading a file of an archive needs to seek in it
+    Zip(Mutex<ZipArchive<File>>),
+}
+
+pub struct ResourcePack {
+    manifest: PackManifest,
+    files: PackFiles,
+}
+
+impl ResourcePack {
+    /// The files of the game, under `root`
+    pub fn builtin<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            manifest: PackManifest {
+                name: "builtin".to_string(),
+                description: "The textures of the game".to_string(),
+            },
+            files: PackFiles::Directory(root.as_ref().to_path_buf()),
+        }
+    }
+
+    /// Opens the pack in a directory or in a `.zip` archive
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            PackFiles::Directory(path.to_path_buf())
+        } else {
+            let archive = File::open(path)
+                .map_err(|err| err.to_string())
+                .and_then(|file| ZipArchive::new(file).map_err(|err| err.to_string()))
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+            PackFiles::Zip(Mutex::new(archive))
+        };
+
+        let manifest = files.read("pack.json")
+            .map_err(|err| format!("{}: pack.json: {}", path.display(), err))?
+            .ok_or_else(|| format!("{}: not a resource pack, pack.json is missing", path.display()))?;
+        let manifest = serde_json::from_slice(&manifest)
+            .map_err(|err| format!("{}: pack.json: {}", path.display(), err))?;
+        Ok(Self {
+            manifest,
+            files,
+        })
+    }
+
+    pub fn manifest(&self) -> &PackManifest {
+        &self.manifest
+    }
+
+    /// The content of the file at `path` in the pack, None if the pack doesn't have it
+    pub fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        self.files.read(path)
+    }
+}
+
+impl PackFiles {
+    fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            PackFiles::Directory(root) => match fs::read(root.join(path)) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            },
+            PackFiles::Zip(archive) => {
+                let mut archive = archive.lock();
+                let mut file = match archive.by_name(path) {
+                    Ok(file) => file,
+                    Err(ZipError::FileNotFound) => return Ok(None),
+                    Err(ZipError::Io(err)) => return Err(err),
+                    Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string())),
+                };
+                let mut bytes = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut bytes)?;
+                Ok(Some(bytes))
+            }
+        }
+    }
+}
+
+/// The enabled packs, from the highest priority to the built-in one
+pub struct ResourcePacks {
+    packs: Vec<ResourcePack>,
+}
+
+impl ResourcePacks {
+    /// Only the files of the game
+    pub fn new(builtin: ResourcePack) -> Self {
+        Self {
+            packs: vec![builtin],
+        }
+    }
+
+    /// The built-in pack in the working directory with the packs of `resourcepacks.txt` on top.
+    /// The packs that can't be opened are skipped
+    pub fn load_enabled() -> Self {
+        let mut packs = Self::new(ResourcePack::builtin(ASSET_DIRECTORY));
+        let enabled = match fs::read_to_string(ENABLED_RESOURCE_PACKS_FILE) {
+            Ok(text) => text,
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    error!("Couldn't read {}: {}", ENABLED_RESOURCE_PACKS_FILE, err);
+                }
+                return packs;
+            }
+        };
+
+        // The last line has the lowest priority, it's pushed first
+        let names = enabled.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect::<Vec<_>>();
+        for name in names.into_iter().rev() {
+            match ResourcePack::open(Path::new(RESOURCE_PACKS_DIRECTORY).join(name)) {
+                Ok(pack) => packs.push(pack),
+                Err(err) => error!("Couldn't open the resource pack {}: {}", name, err),
+            }
+        }
+        packs
+    }
+
+    /// Adds a pack with a higher priority than the others
+    pub fn push(&mut self, pack: ResourcePack) {
+        info!("Using the resource pack {}", pack.manifest().name);
+        self.packs.insert(0, pack);
+    }
+
+    /// The packs from the highest priority to the lowest
+    pub fn packs(&self) -> &[ResourcePack] {
+        &self.packs
+    }
+
+    /// The file at `path` in the pack with the highest priority that has it, None if no pack has it.
+    /// A pack failing to read the file is skipped
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        self.packs.iter().find_map(|pack| match pack.read(path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!("Couldn't read {} from the resource pack {}: {}", path, pack.manifest().name, err);
+                None
+            }
+        })
+    }
+}
@@ -0,0 +1,176 @@
+//! Screenshots of the window, saved as PNG in `screenshots/`. The default framebuffer is read back
+//! at the end of the frame and the image is encoded on another thread.
+//!
+//! The high resolution screenshots are `tiles` times the size of the window on each side: the world
+//! is rendered once per tile over the next frames, each with a projection zoomed on its part of the screen
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::png::PNGEncoder;
+use image::{imageops, ColorType, RgbaImage};
+use nalgebra_glm::Mat4;
+
+/// The state of the screenshots, shared with the render passes
+#[derive(Default)]
+pub struct Screenshots {
+    tiled: Option<TiledScreenshot>,
+}
+
+impl Screenshots {
+    /// Renders the next frames tile by tile into a screenshot `tiles` times the size of the window
+    pub fn start_tiled(&mut self, tiles: u32, width: u32, height: u32) {
+        if self.tiled.is_none() {
+            self.tiled = Some(TiledScreenshot::new(tiles, width, height));
+        }
+    }
+
+    /// Whether the frame is a tile of a high resolution screenshot. The GUI and the hand aren't drawn in those
+    pub fn is_tiling(&self) -> bool {
+        self.tiled.is_some()
+    }
+
+    /// The projection of the frame, zoomed on the current tile when taking a high resolution screenshot
+    pub fn projection(&self, projection: &Mat4) -> Mat4 {
+        match &self.tiled {
+            Some(tiled) => {
+                let (x, y) = tiled.tile_coords();
+                tile_projection(projection, tiled.tiles, x, y)
+            }
+            None => *projection,
+        }
+    }
+
+    /// Reads the frame into the current tile, and returns the whole image once the last tile is read
+    pub fn capture_tile(&mut self) -> Option<RgbaImage> {
+        let tiled = self.tiled.as_mut()?;
+        let (x, y) = tiled.tile_coords();
+        let tile = read_framebuffer(0, tiled.tile_width, tiled.tile_height);
+        place_tile(&mut tiled.image, &tile, tiled.tiles, x, y);
+        tiled.next_tile += 1;
+        if tiled.next_tile < tiled.tiles * tiled.tiles {
+            return None;
+        }
+        self.tiled.take().map(|tiled| tiled.image)
+    }
+}
+
+/// A high resolution screenshot being rendered
+struct TiledScreenshot {
+    tiles: u32,
+    tile_width: u32,
+    tile_height: u32,
+    /// The tiles are rendered row by row from the bottom left
+    next_tile: u32,
+    image: RgbaImage,
+}
+
+impl TiledScreenshot {
+    fn new(tiles: u32, tile_width: u32, tile_height: u32) -> Self {
+        Self {
+            tiles,
+            tile_width,
+            tile_height,
+            next_tile: 0,
+            image: RgbaImage::new(tiles * tile_width, tiles * tile_height),
+        }
+    }
+
+    fn tile_coords(&self) -> (u32, u32) {
+        (self.next_tile % self.tiles, self.next_tile / self.tiles)
+    }
+}
+
+/// Scales `projection` so that the tile (x, y) out of `tiles` on each side fills the screen.
+/// The tiles are counted from the bottom left, like the normalized device coordinates
+pub fn tile_projection(projection: &Mat4, tiles: u32, x: u32, y: u32) -> Mat4 {
+    let scale = tiles as f32;
+    // The center of the tile in normalized device coordinates
+    let center_x = -1.0 + (2 * x + 1) as f32 / scale;
+    let center_y = -1.0 + (2 * y + 1) as f32 / scale;
+    let zoom = Mat4::new(
+        scale, 0.0, 0.0, -scale * center_x,
+        0.0, scale, 0.0, -scale * center_y,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    zoom * projection
+}
+
+/// Copies a tile, read from the top, to its place in the image. `y` counts from the bottom
+pub fn place_tile(image: &mut RgbaImage, tile: &RgbaImage, tiles: u32, x: u32, y: u32) {
+    imageops::replace(image, tile, x * tile.width(), (tiles - 1 - y) * tile.height());
+}
+
+/// Reads a framebuffer, 0 for the default one, from the top like images are stored
+pub fn read_framebuffer(framebuffer: u32, width: u32, height: u32) -> RgbaImage {
+    let mut pixels = vec![0u8; (4 * width * height) as usize];
+    gl_call!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer));
+    gl_call!(gl::PixelStorei(gl::PACK_ALIGNMENT, 1));
+    gl_call!(gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE,
+                            pixels.as_mut_ptr() as *mut std::ffi::c_void));
+    let mut image = RgbaImage::from_raw(width, height, pixels).unwrap();
+    // OpenGL reads the rows from the bottom
+    imageops::flip_vertical_in_place(&mut image);
+    // The alpha of the framebuffer isn't meant to be seen
+    image.pixels_mut().for_each(|pixel| pixel[3] = 255);
+    image
+}
+
+/// Saves the image in `directory` on another thread, named after the current time
+pub fn save_screenshot(image: RgbaImage, directory: &Path) {
+    let directory = directory.to_path_buf();
+    thread::spawn(move || {
+        let result = fs::create_dir_all(&directory)
+            .map_err(|err| err.to_string())
+            .and_then(|_| create_screenshot_file(&directory, SystemTime::now()).map_err(|err| err.to_string()))
+            .and_then(|(path, file)| {
+                PNGEncoder::new(BufWriter::new(file))
+                    .encode(&image, image.width(), image.height(), ColorType::RGBA(8))
+                    .map(|_| path)
+                    .map_err(|err| err.to_string())
+            });
+        match result {
+            Ok(path) => info!("Saved the screenshot {}", path.display()),
+            Err(err) => error!("Couldn't save the screenshot in {}: {}", directory.display(), err),
+        }
+    });
+}
+
+/// Creates `<directory>/2020-06-21_14.03.52.png` in UTC, with `_2`, `_3`... if several are taken in the same second.
+/// The name is taken by creating the file, so that screenshots saved at the same time never overwrite each other
+pub fn create_screenshot_file(directory: &Path, time: SystemTime) -> io::Result<(PathBuf, File)> {
+    let name = format_timestamp(time);
+    let mut path = directory.join(format!("{}.png", name));
+    let mut index = 2;
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                path = directory.join(format!("{}_{}.png", name, index));
+                index += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The UTC date and time as `2020-06-21_14.03.52`
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    let (days, seconds) = ((seconds / 86400) as i64, seconds % 86400);
+    // The civil date of a day since 1970-01-01, from Howard Hinnant's `civil_from_days`
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}_{:02}.{:02}.{:02}", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
@@ -4,7 +4,10 @@ use std::ffi::{CString, CStr};
 use std::collections::HashMap;
 use crate::gl_call;
 use std::sync::Mutex;
-use std::fs::read_to_string;
+use std::fs;
+use std::time::SystemTime;
+use crate::shader_preprocessor::ShaderPreprocessor;
+use crate::types::Shaders;
 
 #[derive(Debug)]
 pub struct ShaderPart {
@@ -72,7 +75,11 @@ fn create_whitespace_cstring_with_len(len: usize) -> CString {
 //#[derive(Clone)]
 pub struct ShaderProgram {
     id: u32,
-    uniform_cache: Mutex<HashMap<String, i32>>
+    uniform_cache: Mutex<HashMap<String, i32>>,
+    /// The vertex and the fragment shader the program was compiled from, if it comes from files
+    shaders: Option<(String, String)>,
+    /// Every file read to compile the program, with the included ones
+    files: Vec<String>,
 }
 
 impl ShaderProgram {
@@ -80,17 +87,18 @@ impl ShaderProgram {
         gl_call!(gl::UseProgram(self.id));
     }
 
+    /// The location of a uniform, -1 if the program doesn't have it. Setting a uniform at -1 does nothing,
+    /// like the uniforms the driver optimized away while the shader is being worked on
     fn get_uniform_location(&mut self, name: &str) -> i32 {
         let location = self.uniform_cache.get_mut().unwrap().get(name).cloned();
         match location {
             None => {
                 let c_name = CString::new(name).unwrap();
                 let location = gl_call!(gl::GetUniformLocation(self.id, c_name.as_ptr()));
-                // Error checking
+                // Only warned about once, the location is cached
                 if location == -1 {
-                    panic!("Can't find uniform '{}' in program with id: {}", name, self.id);
+                    warn!("The shader program {} has no uniform '{}'", self.name(), name);
                 }
-                // println!("New uniform location {}: {}", &name, &location);
                 self.uniform_cache.get_mut().unwrap().insert(name.to_owned(), location);
                 location
             },
@@ -98,6 +106,19 @@ impl ShaderProgram {
         }
     }
 
+    /// The files of the program for the logs
+    fn name(&self) -> String {
+        match &self.shaders {
+            Some((vertex, fragment)) => format!("{} + {}", vertex, fragment),
+            None => format!("with id {}", self.id),
+        }
+    }
+
+    /// The files the program was compiled from, with the ones its shaders include
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
     pub fn set_uniform2f(&mut self, name: &str, values: &[f32]) -> &Self {
         let location = self.get_uniform_location(name);
         gl_call!(gl::Uniform2f(location, values[0], values[1]));
@@ -170,25 +191,101 @@ impl ShaderProgram {
                 error.as_ptr() as *mut gl::types::GLchar
             ));
 
+            gl_call!(gl::DeleteProgram(program_id));
             return Err(error.to_string_lossy().into_owned());
         }
 
         gl_call!(gl::DetachShader(program_id, vertex.id));
         gl_call!(gl::DetachShader(program_id, fragment.id));
-        Ok(ShaderProgram { id: program_id, uniform_cache: Mutex::new(HashMap::new()) })
+        Ok(ShaderProgram { id: program_id, uniform_cache: Mutex::new(HashMap::new()), shaders: None, files: Vec::new() })
     }
 
-    pub fn compile(vertex: &str, fragment: &str) -> ShaderProgram {
-        let vert = ShaderPart::from_vert_source(
-            &CString::new(read_to_string(vertex).unwrap()).unwrap()).unwrap();
-        let frag = ShaderPart::from_frag_source(
-            &CString::new(read_to_string(fragment).unwrap()).unwrap()).unwrap();
-        ShaderProgram::from_shaders(vert, frag).unwrap()
+    /// Compiles and links the program from the files of its shaders in the directory of `preprocessor`.
+    /// The errors carry the log of the driver, pointing at the files and the lines of the shaders
+    pub fn compile(preprocessor: &ShaderPreprocessor, vertex: &str, fragment: &str) -> Result<ShaderProgram, String> {
+        let vertex_source = preprocessor.process(vertex)?;
+        let fragment_source = preprocessor.process(fragment)?;
+        let vert = ShaderPart::from_vert_source(&to_cstring(vertex, vertex_source.source())?)
+            .map_err(|log| format!("Couldn't compile {}:\n{}", vertex, vertex_source.map_log(&log)))?;
+        let frag = ShaderPart::from_frag_source(&to_cstring(fragment, fragment_source.source())?)
+            .map_err(|log| format!("Couldn't compile {}:\n{}", fragment, fragment_source.map_log(&log)))?;
+        let mut program = ShaderProgram::from_shaders(vert, frag)
+            .map_err(|log| format!("Couldn't link {} with {}: {}", vertex, fragment, log))?;
+        program.shaders = Some((vertex.to_string(), fragment.to_string()));
+        for file in vertex_source.files().iter().chain(fragment_source.files()) {
+            if !program.files.contains(file) {
+                program.files.push(file.clone());
+            }
+        }
+        Ok(program)
+    }
+
+    /// Compiles the program again from its files. The program is left untouched if that fails
+    pub fn reload(&mut self, preprocessor: &ShaderPreprocessor) -> Result<(), String> {
+        let (vertex, fragment) = match &self.shaders {
+            Some(shaders) => shaders.clone(),
+            None => return Err(format!("The program {} wasn't compiled from files", self.name())),
+        };
+        // The old program is deleted when the new one replaces it
+        *self = ShaderProgram::compile(preprocessor, &vertex, &fragment)?;
+        Ok(())
     }
 }
 
+fn to_cstring(path: &str, source: &str) -> Result<CString, String> {
+    CString::new(source).map_err(|_| format!("Couldn't read {}: it contains a nul byte", path))
+}
+
 impl Drop for ShaderProgram {
     fn drop(&mut self) {
         gl_call!(gl::DeleteProgram(self.id));
     }
-}
\ No newline at end of file
+}
+
+/// Compiles the shaders used by the rendering systems
+pub fn compile_shaders(preprocessor: &ShaderPreprocessor) -> Result<Shaders, String> {
+    let mut shaders = Shaders::new();
+    shaders.insert("voxel_shader", ShaderProgram::compile(preprocessor, "voxel.vert", "voxel.frag")?);
+    shaders.insert("gui_shader", ShaderProgram::compile(preprocessor, "gui.vert", "gui.frag")?);
+    shaders.insert("outline_shader", ShaderProgram::compile(preprocessor, "outline.vert", "outline.frag")?);
+    shaders.insert("item_shader", ShaderProgram::compile(preprocessor, "item.vert", "item.frag")?);
+    shaders.insert("particle_shader", ShaderProgram::compile(preprocessor, "particle.vert", "particle.frag")?);
+    shaders.insert("player_shader", ShaderProgram::compile(preprocessor, "player.vert", "player.frag")?);
+    shaders.insert("hand_shader", ShaderProgram::compile(preprocessor, "hand.vert", "hand.frag")?);
+    shaders.insert("text_shader", ShaderProgram::compile(preprocessor, "text.vert", "text.frag")?);
+    Ok(shaders)
+}
+
+/// Finds the shader programs whose files changed on disk, by polling their modification times
+#[derive(Default)]
+pub struct ShaderWatcher {
+    modified: HashMap<String, SystemTime>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The programs with a file modified since the previous call. The files seen for the first time
+    /// aren't considered modified
+    pub fn modified_programs(&mut self, shaders: &Shaders) -> Vec<&'static str> {
+        let mut modified_programs = Vec::new();
+        for (&name, program) in shaders {
+            let mut modified = false;
+            for file in program.files() {
+                let time = match fs::metadata(file).and_then(|metadata| metadata.modified()) {
+                    Ok(time) => time,
+                    Err(_) => continue,
+                };
+                if let Some(previous) = self.modified.insert(file.to_string(), time) {
+                    modified |= previous != time;
+                }
+            }
+            if modified {
+                modified_programs.push(name);
+            }
+        }
+        modified_programs
+    }
+}
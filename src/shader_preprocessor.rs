@@ -0,0 +1,176 @@
+//! A small GLSL preprocessor run on the shaders before the driver compiles them:
+//! * `#include "common/fog.glsl"` pastes a file of the shader directory, each file only once per shader
+//! * the `#define`s of the settings are added after `#version`, like `ENABLE_FOG`
+//!
+//! Every file is a source string number for the driver, set with `#line`, so the errors in its log
+//! can be mapped back to the file and the line they come from
+
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use crate::constants::{ENABLE_FOG, SHADER_DIRECTORY};
+
+/// Reads the shaders of a directory, with a set of `#define`s
+#[derive(Debug, Clone)]
+pub struct ShaderPreprocessor {
+    directory: PathBuf,
+    defines: Vec<(String, String)>,
+}
+
+impl ShaderPreprocessor {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            defines: Vec::new(),
+        }
+    }
+
+    /// The shaders of the game, with the defines of the settings
+    pub fn from_settings() -> Self {
+        let mut preprocessor = Self::new(SHADER_DIRECTORY);
+        if ENABLE_FOG {
+            preprocessor.define("ENABLE_FOG", "");
+        }
+        preprocessor
+    }
+
+    /// Adds `#define name value` to every shader
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// The source of the shader at `path` in the directory, with its includes and the defines
+    pub fn process(&self, path: &str) -> Result<ShaderSource, String> {
+        let mut shader = ShaderSource {
+            source: String::new(),
+            files: Vec::new(),
+        };
+        self.process_file(&mut shader, path, None)?;
+        Ok(shader)
+    }
+
+    /// Appends the file at `path` to the source. `included_from` is the file and the line of the
+    /// `#include`, none for the shader itself
+    fn process_file(&self, shader: &mut ShaderSource, path: &str, included_from: Option<(&str, usize)>) -> Result<(), String> {
+        let file = self.directory.join(path).to_string_lossy().into_owned();
+        if shader.files.contains(&file) {
+            return Ok(());
+        }
+        let source = read_to_string(&file).map_err(|err| match included_from {
+            Some((parent, line)) => format!("{}:{}: Couldn't include {}: {}", parent, line, file, err),
+            None => format!("Couldn't read {}: {}", file, err),
+        })?;
+        let index = shader.files.len();
+        shader.files.push(file.clone());
+
+        if included_from.is_some() {
+            shader.push_line(&format!("#line 1 {}", index));
+        }
+        for (i, line) in source.lines().enumerate() {
+            let directive = line.trim_start();
+            if directive.starts_with("#include") {
+                let include = parse_include(directive)
+                    .ok_or_else(|| format!("{}:{}: Expected #include \"<file>\"", file, i + 1))?;
+                self.process_file(shader, include, Some((&file, i + 1)))?;
+                // Back to the line after the include
+                shader.push_line(&format!("#line {} {}", i + 2, index));
+            } else if directive.starts_with("#version") && included_from.is_none() {
+                shader.push_line(line);
+                for (name, value) in &self.defines {
+                    shader.push_line(format!("#define {} {}", name, value).trim_end());
+                }
+                shader.push_line(&format!("#line {} {}", i + 2, index));
+            } else {
+                shader.push_line(line);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The path in `#include "<path>"`
+fn parse_include(directive: &str) -> Option<&str> {
+    let path = directive["#include".len()..].trim();
+    if path.len() >= 2 && path.starts_with('"') && path.ends_with('"') {
+        Some(&path[1..path.len() - 1])
+    } else {
+        None
+    }
+}
+
+/// A preprocessed shader and the files it was read from
+#[derive(Debug, Clone)]
+pub struct ShaderSource {
+    source: String,
+    /// Indexed by their source string number, the shader itself first
+    files: Vec<String>,
+}
+
+impl ShaderSource {
+    fn push_line(&mut self, line: &str) {
+        self.source.push_str(line);
+        self.source.push('\n');
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Replaces the locations in a log of the driver, `0:12` or `0(12)` depending on the vendor,
+    /// by the file and the line they point at
+    pub fn map_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.map_log_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Maps the first location of the line
+    fn map_log_line(&self, line: &str) -> String {
+        let bytes = line.as_bytes();
+        for (start, byte) in bytes.iter().enumerate() {
+            if !byte.is_ascii_digit() || (start > 0 && bytes[start - 1].is_ascii_alphanumeric()) {
+                continue;
+            }
+            if let Some((index, line_number, len)) = parse_location(&line[start..]) {
+                if let Some(file) = self.files.get(index) {
+                    return format!("{}{}:{}{}", &line[..start], file, line_number, &line[start + len..]);
+                }
+            }
+        }
+        line.to_string()
+    }
+}
+
+/// The source string number and the line of `<number>:<line>` or `<number>(<line>)` at the start of
+/// `text`, and the length of the location
+fn parse_location(text: &str) -> Option<(usize, usize, usize)> {
+    let (index, rest) = split_number(text)?;
+    let separator = *rest.as_bytes().first()?;
+    if separator != b':' && separator != b'(' {
+        return None;
+    }
+    let (line, after) = split_number(&rest[1..])?;
+    let after = match separator {
+        b':' => after,
+        _ if after.starts_with(')') => &after[1..],
+        _ => return None,
+    };
+    Some((index, line, text.len() - after.len()))
+}
+
+/// The number at the start of `text` and what follows it
+fn split_number(text: &str) -> Option<(usize, &str)> {
+    let len = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let number = text[..len].parse().ok()?;
+    Some((number, &text[len..]))
+}
@@ -0,0 +1,9 @@
+// The colour of a face of a block model: its texture times its tint. Transparent pixels are discarded
+vec4 block_color(sampler2DArray tex, vec3 texture_coords, vec3 tint) {
+    vec4 color = texture(tex, texture_coords);
+    if (color.a == 0.0) {
+        discard;
+    }
+    color.rgb *= tint;
+    return color;
+}
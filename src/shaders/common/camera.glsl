@@ -0,0 +1,3 @@
+// The matrices of the player's camera
+uniform mat4 view;
+uniform mat4 projection;
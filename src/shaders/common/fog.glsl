@@ -0,0 +1,13 @@
+// The fog hiding the end of the render distance, in the colour of the sky
+
+const float fog_gradient = 20.0;
+
+// How much of a fragment at `distance` from the camera is seen through the fog, from 0 to 1
+float fog_visibility(float distance, float render_distance) {
+    float fog_density = 0.066 / render_distance;
+    return exp(-pow(distance * fog_density, fog_gradient));
+}
+
+vec4 apply_fog(vec4 color, vec3 fog_color, float visibility) {
+    return mix(vec4(fog_color, 1.0), color, visibility);
+}
@@ -0,0 +1,5 @@
+// The vertices of the block models drawn as items, in the hand or in the GUI
+layout (location = 0) in vec3 pos;
+layout (location = 1) in vec3 texture_coords;
+layout (location = 2) in vec3 normal;
+layout (location = 3) in vec3 tint;
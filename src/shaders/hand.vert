@@ -1,21 +1,19 @@
 #version 450 core
 
-//uniform mat4 model_view;
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+#include "common/camera.glsl"
+#include "common/item_vertex.glsl"
 
-layout (location = 0) in vec3 pos;
-layout (location = 1) in vec3 texture_coords;
-layout (location = 2) in vec3 normal;
+uniform mat4 model;
 
 out VertexAttributes {
     vec3 texture_coords;
     vec3 normal;
+    vec3 tint;
 } attrs;
 
 void main() {
     attrs.texture_coords = texture_coords;
+    attrs.tint = tint;
     attrs.normal = vec3(model * vec4(normal, 0.0));
     gl_Position = projection * view * model * vec4(pos, 1.0);
 }
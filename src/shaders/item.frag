@@ -2,19 +2,18 @@
 
 out vec4 Color;
 
+#include "common/block_color.glsl"
+
 uniform sampler2DArray tex;
 
 in VertexAttributes {
     vec3 texture_coords;
     vec3 normal;
+    vec3 tint;
 } attrs;
 
 void main() {
-    vec4 diffuse_frag = texture(tex, attrs.texture_coords);
-    if (diffuse_frag.a == 0.0) {
-        discard;
-    }
-    Color = diffuse_frag;
+    Color = block_color(tex, attrs.texture_coords, attrs.tint);
     if (attrs.normal.z == 1.0) {
         Color.rgb *= 0.5;
     } else if (attrs.normal.x == -1.0) {
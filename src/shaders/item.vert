@@ -3,17 +3,17 @@
 uniform mat4 model;
 uniform mat4 projection;
 
-layout (location = 0) in vec3 pos;
-layout (location = 1) in vec3 texture_coords;
-layout (location = 2) in vec3 normal;
+#include "common/item_vertex.glsl"
 
 out VertexAttributes {
     vec3 texture_coords;
     vec3 normal;
+    vec3 tint;
 } attrs;
 
 void main() {
     attrs.texture_coords = texture_coords;
+    attrs.tint = tint;
     attrs.normal = normal;
     gl_Position = projection * model * vec4(pos, 1.0);
 }
@@ -1,8 +1,8 @@
 #version 450 core
 
+#include "common/camera.glsl"
+
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
 
 layout (location = 0) in vec3 pos;
 
@@ -0,0 +1,15 @@
+#version 450 core
+
+out vec4 Color;
+
+uniform vec3 color;
+
+in vec3 world_normal;
+
+void main() {
+    // The same shading as the blocks in the hand, darker on the sides than on the top
+    Color = vec4(color, 1.0);
+    Color.rgb *= 1.0 - abs(world_normal.z) * 0.2;
+    Color.rgb *= 1.0 - abs(world_normal.x) * 0.4;
+    Color.rgb *= world_normal.y < -0.5 ? 0.5 : 1.0;
+}
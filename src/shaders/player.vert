@@ -0,0 +1,15 @@
+#version 450 core
+
+#include "common/camera.glsl"
+
+uniform mat4 model;
+
+layout (location = 0) in vec3 pos;
+layout (location = 1) in vec3 normal;
+
+out vec3 world_normal;
+
+void main() {
+    world_normal = normalize(vec3(model * vec4(normal, 0.0)));
+    gl_Position = projection * view * model * vec4(pos, 1.0);
+}
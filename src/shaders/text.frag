@@ -0,0 +1,18 @@
+#version 450 core
+
+out vec4 Color;
+
+uniform sampler2D font;
+
+in VertexAttributes {
+    vec2 texture_coords;
+    vec4 color;
+} attrs;
+
+void main() {
+    vec4 glyph = texture(font, attrs.texture_coords);
+    if (glyph.a == 0.0) {
+        discard;
+    }
+    Color = glyph * attrs.color;
+}
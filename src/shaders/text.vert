@@ -0,0 +1,18 @@
+#version 450 core
+
+uniform mat4 projection;
+
+layout (location = 0) in vec2 pos;
+layout (location = 1) in vec2 texture_coords;
+layout (location = 2) in vec4 color;
+
+out VertexAttributes {
+    vec2 texture_coords;
+    vec4 color;
+} attrs;
+
+void main() {
+    attrs.texture_coords = texture_coords;
+    attrs.color = color;
+    gl_Position = projection * vec4(pos, 0.0, 1.0);
+}
@@ -2,23 +2,22 @@
 
 out vec4 Color;
 
+#include "common/block_color.glsl"
+#include "common/fog.glsl"
+
 uniform sampler2DArray array_texture;
-uniform bool enable_fog;
 uniform vec3 sky_color;
 
 in VertexAttributes {
     vec3 texture_coords;
     vec3 normal;
     float ao;
+    vec3 tint;
     float visibility;
 } attrs;
 
 void main() {
-    vec4 diffuse_frag = texture(array_texture, attrs.texture_coords);
-    if (diffuse_frag.a == 0) {
-        discard;
-    }
-    Color = diffuse_frag;
+    Color = block_color(array_texture, attrs.texture_coords, attrs.tint);
     if (attrs.normal.x != 0.0) {
         Color.rgb *= 0.65;
     } else if (attrs.normal.z != 0.0) {
@@ -28,7 +27,7 @@ void main() {
     }
     Color.rgb *= (1.0 - attrs.ao * 0.15);
 
-    if (enable_fog) {
-        Color = mix(vec4(sky_color, 1.0), Color, attrs.visibility);
-    }
-}
\ No newline at end of file
+#ifdef ENABLE_FOG
+    Color = apply_fog(Color, sky_color, attrs.visibility);
+#endif
+}
@@ -1,21 +1,26 @@
-#version 450 core
+#version 460 core
 
-const float fog_gradient = 20.0;
+#include "common/camera.glsl"
+#include "common/fog.glsl"
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
 uniform float render_distance;
 
 layout (location = 0) in vec3 pos;
 layout (location = 1) in vec3 texture_coords;
 layout (location = 2) in vec3 normal;
 layout (location = 3) in float ao;
+layout (location = 4) in vec3 tint;
+
+// World position of each chunk drawn by the multi-draw call, indexed by gl_DrawID
+layout (std430, binding = 0) readonly buffer ChunkPositions {
+    vec4 chunk_positions[];
+};
 
 out VertexAttributes {
     vec3 texture_coords;
     vec3 normal;
     float ao;
+    vec3 tint;
     float visibility;
 } attrs;
 
@@ -23,12 +28,12 @@ void main() {
     attrs.texture_coords = texture_coords;
     attrs.normal = normal;
     attrs.ao = ao;
+    attrs.tint = tint;
     attrs.visibility = 1.0;
-    vec4 frag_pos = view * model * vec4(pos, 1.0f);
+    vec4 frag_pos = view * vec4(pos + chunk_positions[gl_DrawID].xyz, 1.0f);
     gl_Position = projection * frag_pos;
 
-    // Fog
-    float fog_density = 0.066 / render_distance;
-    float distance = length(frag_pos.xyz);
-    attrs.visibility = exp(-pow(distance * fog_density, fog_gradient));
+#ifdef ENABLE_FOG
+    attrs.visibility = fog_visibility(length(frag_pos.xyz), render_distance);
+#endif
 }
\ No newline at end of file
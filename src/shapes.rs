@@ -1,4 +1,4 @@
-use crate::types::{UVFaces, TextureLayer};
+use crate::block_model::BlockModel;
 
 
 pub fn quad(uv: (f32, f32, f32, f32)) -> Vec<f32> {
@@ -23,94 +23,39 @@ pub fn quad_array_texture() -> Vec<f32> {
     ]).to_vec()
 }
 
-// bl = bottom left
-// tr = top right
-// Creates and write the vertices of a cube directly into "ptr" (usually a VBO mapped to virtual memory)
-pub unsafe fn write_unit_cube_to_ptr(ptr: *mut f32, x: f32, y: f32, z: f32,
-                                     (front_layer, back_layer, top_layer, bottom_layer, left_layer, right_layer): (TextureLayer, TextureLayer, TextureLayer, TextureLayer, TextureLayer, TextureLayer),
-                                     [right, left, top, bottom, front, back]: [bool; 6],
-                                     ao: [[u8; 4]; 6]) -> u32 {
-    let vertex_size = 10;
+/// Writes the quads of a block model that aren't culled by the inactive faces of the block directly into
+/// "ptr" (usually a VBO mapped to virtual memory), returns the number of vertices written.
+/// The tinted quads are coloured with `tint`, the others are white
+pub unsafe fn write_model_to_ptr(ptr: *mut f32, (x, y, z): (f32, f32, f32),
+                                 model: &BlockModel,
+                                 active_faces: [bool; 6],
+                                 ao: [[u8; 4]; 6],
+                                 tint: [f32; 3]) -> u32 {
+    let vertex_size = 13;
     let vertices_per_face = 6;
     let face_size = vertex_size * vertices_per_face;
 
     let mut i = 0;
     let mut copied_vertices = 0;
-
-    let uv = (0.0, 0.0, 1.0, 1.0);
-
-    // First 3 floats contain the position, last 2 are the UV coordinates
-    if front {
-        ptr.offset(i).copy_from_nonoverlapping([
-            0.0f32 + x,  0.0 + y, 1.0 + z, uv.0, uv.1, front_layer as f32, 0.0, 0.0, 1.0, ao[4][0] as f32,
-            1.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.1, front_layer as f32, 0.0, 0.0, 1.0, ao[4][1] as f32,
-            1.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, front_layer as f32, 0.0, 0.0, 1.0, ao[4][2] as f32,
-            1.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, front_layer as f32, 0.0, 0.0, 1.0, ao[4][2] as f32,
-            0.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.3, front_layer as f32, 0.0, 0.0, 1.0, ao[4][3] as f32,
-            0.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.1, front_layer as f32, 0.0, 0.0, 1.0, ao[4][0] as f32,
-        ].as_ptr(), face_size);
+    for quad in model.visible_quads(&active_faces) {
+        // First 3 floats contain the position, then the UV coordinates and the layer, the normal, the AO
+        // and the tint
+        let mut vertices = [0.0f32; 78];
+        let color = if quad.tinted { tint } else { [1.0; 3] };
+        for (vertex, &corner) in vertices.chunks_exact_mut(vertex_size).zip(&[0, 1, 2, 2, 3, 0]) {
+            let (position, uv) = (quad.positions[corner], quad.uvs[corner]);
+            let ao = quad.ao.map_or(0, |(face, corners)| ao[face][corners[corner]]);
+            vertex.copy_from_slice(&[
+                position.x + x, position.y + y, position.z + z,
+                uv.0, uv.1, quad.layer as f32,
+                quad.normal.x, quad.normal.y, quad.normal.z,
+                ao as f32,
+                color[0], color[1], color[2]]);
+        }
+        ptr.offset(i).copy_from_nonoverlapping(vertices.as_ptr(), face_size);
         i += face_size as isize;
         copied_vertices += vertices_per_face;
     }
-    if back {
-        ptr.offset(i).copy_from_nonoverlapping([
-            1.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, back_layer as f32, 0.0, 0.0, -1.0, ao[5][0] as f32,
-            0.0 + x,  0.0 + y,  0.0 + z, uv.2, uv.1, back_layer as f32, 0.0, 0.0, -1.0, ao[5][1] as f32,
-            0.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, back_layer as f32, 0.0, 0.0, -1.0, ao[5][2] as f32,
-            0.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, back_layer as f32, 0.0, 0.0, -1.0, ao[5][2] as f32,
-            1.0 + x,  1.0 + y,  0.0 + z, uv.0, uv.3, back_layer as f32, 0.0, 0.0, -1.0, ao[5][3] as f32,
-            1.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, back_layer as f32, 0.0, 0.0, -1.0, ao[5][0] as f32,
-        ].as_ptr(), face_size);
-        i += face_size as isize;
-        copied_vertices += vertices_per_face;
-    }
-    if left {
-        ptr.offset(i).copy_from_nonoverlapping([
-            0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, left_layer as f32, -1.0, 0.0, 0.0, ao[1][0] as f32,
-            0.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.1, left_layer as f32, -1.0, 0.0, 0.0, ao[1][1] as f32,
-            0.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, left_layer as f32, -1.0, 0.0, 0.0, ao[1][2] as f32,
-            0.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, left_layer as f32, -1.0, 0.0, 0.0, ao[1][2] as f32,
-            0.0 + x,  1.0 + y,  0.0 + z, uv.0, uv.3, left_layer as f32, -1.0, 0.0, 0.0, ao[1][3] as f32,
-            0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, left_layer as f32, -1.0, 0.0, 0.0, ao[1][0] as f32,
-        ].as_ptr(), face_size);
-        i += face_size as isize;
-        copied_vertices += vertices_per_face;
-    }
-    if right {
-        ptr.offset(i).copy_from_nonoverlapping([
-            1.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.1, right_layer as f32, 1.0, 0.0, 0.0, ao[0][0] as f32,
-            1.0 + x,  0.0 + y,  0.0 + z, uv.2, uv.1, right_layer as f32, 1.0, 0.0, 0.0, ao[0][1] as f32,
-            1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, right_layer as f32, 1.0, 0.0, 0.0, ao[0][2] as f32,
-            1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, right_layer as f32, 1.0, 0.0, 0.0, ao[0][2] as f32,
-            1.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.3, right_layer as f32, 1.0, 0.0, 0.0, ao[0][3] as f32,
-            1.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.1, right_layer as f32, 1.0, 0.0, 0.0, ao[0][0] as f32,
-        ].as_ptr(), face_size);
-        i += face_size as isize;
-        copied_vertices += vertices_per_face;
-    }
-    if top {
-        ptr.offset(i).copy_from_nonoverlapping([
-            0.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.1, top_layer as f32, 0.0, 1.0, 0.0, ao[2][0] as f32,
-            1.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.1, top_layer as f32, 0.0, 1.0, 0.0, ao[2][1] as f32,
-            1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, top_layer as f32, 0.0, 1.0, 0.0, ao[2][2] as f32,
-            1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, top_layer as f32, 0.0, 1.0, 0.0, ao[2][2] as f32,
-            0.0 + x,  1.0 + y,  0.0 + z, uv.0, uv.3, top_layer as f32, 0.0, 1.0, 0.0, ao[2][3] as f32,
-            0.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.1, top_layer as f32, 0.0, 1.0, 0.0, ao[2][0] as f32,
-        ].as_ptr(), face_size);
-        i += face_size as isize;
-        copied_vertices += vertices_per_face;
-    }
-    if bottom {
-        ptr.offset(i).copy_from_nonoverlapping([
-            0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, bottom_layer as f32, 0.0, -1.0, 0.0, ao[3][0] as f32,
-            1.0 + x,  0.0 + y,  0.0 + z, uv.2, uv.1, bottom_layer as f32, 0.0, -1.0, 0.0, ao[3][1] as f32,
-            1.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.3, bottom_layer as f32, 0.0, -1.0, 0.0, ao[3][2] as f32,
-            1.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.3, bottom_layer as f32, 0.0, -1.0, 0.0, ao[3][2] as f32,
-            0.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.3, bottom_layer as f32, 0.0, -1.0, 0.0, ao[3][3] as f32,
-            0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, bottom_layer as f32, 0.0, -1.0, 0.0, ao[3][0] as f32,
-        ].as_ptr(), face_size);
-        copied_vertices += vertices_per_face;
-    }
     copied_vertices as u32
 }
 
@@ -134,53 +79,47 @@ pub fn block_outline() -> &'static [f32; 72] {
     ]
 }
 
-pub fn centered_unit_cube(x: f32, y: f32, z: f32, (front_layer, back_layer, top_layer, bottom_layer, left_layer, right_layer): UVFaces) -> Vec<f32> {
-    // Position: 3 floats
-    // UV coords: 3 floats
-    // Normal: 3 floats
-    let uv = (0.0, 0.0, 1.0, 1.0);
-
-    [
-        0.0f32 + x,  0.0 + y, 1.0 + z, uv.0, uv.1, front_layer as f32, 0.0, 0.0, 1.0,
-        1.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.1, front_layer as f32, 0.0, 0.0, 1.0,
-        1.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, front_layer as f32, 0.0, 0.0, 1.0,
-        1.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, front_layer as f32, 0.0, 0.0, 1.0,
-        0.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.3, front_layer as f32, 0.0, 0.0, 1.0,
-        0.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.1, front_layer as f32, 0.0, 0.0, 1.0,
-
-        1.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, back_layer as f32, 0.0, 0.0, -1.0,
-        0.0 + x,  0.0 + y,  0.0 + z, uv.2, uv.1, back_layer as f32, 0.0, 0.0, -1.0,
-        0.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, back_layer as f32, 0.0, 0.0, -1.0,
-        0.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, back_layer as f32, 0.0, 0.0, -1.0,
-        1.0 + x,  1.0 + y,  0.0 + z, uv.0, uv.3, back_layer as f32, 0.0, 0.0, -1.0,
-        1.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, back_layer as f32, 0.0, 0.0, -1.0,
-
-        0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, left_layer as f32, -1.0, 0.0, 0.0,
-        0.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.1, left_layer as f32, -1.0, 0.0, 0.0,
-        0.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, left_layer as f32, -1.0, 0.0, 0.0,
-        0.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.3, left_layer as f32, -1.0, 0.0, 0.0,
-        0.0 + x,  1.0 + y,  0.0 + z, uv.0, uv.3, left_layer as f32, -1.0, 0.0, 0.0,
-        0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, left_layer as f32, -1.0, 0.0, 0.0,
-
-        1.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.1, right_layer as f32, 1.0, 0.0, 0.0,
-        1.0 + x,  0.0 + y,  0.0 + z, uv.2, uv.1, right_layer as f32, 1.0, 0.0, 0.0,
-        1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, right_layer as f32, 1.0, 0.0, 0.0,
-        1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, right_layer as f32, 1.0, 0.0, 0.0,
-        1.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.3, right_layer as f32, 1.0, 0.0, 0.0,
-        1.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.1, right_layer as f32, 1.0, 0.0, 0.0,
-
-        0.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.1, top_layer as f32, 0.0, 1.0, 0.0,
-        1.0 + x,  1.0 + y,  1.0 + z, uv.2, uv.1, top_layer as f32, 0.0, 1.0, 0.0,
-        1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, top_layer as f32, 0.0, 1.0, 0.0,
-        1.0 + x,  1.0 + y,  0.0 + z, uv.2, uv.3, top_layer as f32, 0.0, 1.0, 0.0,
-        0.0 + x,  1.0 + y,  0.0 + z, uv.0, uv.3, top_layer as f32, 0.0, 1.0, 0.0,
-        0.0 + x,  1.0 + y,  1.0 + z, uv.0, uv.1, top_layer as f32, 0.0, 1.0, 0.0,
+/// The triangles of a unit cube from (0, 0, 0) to (1, 1, 1), each vertex made of its position and its normal
+pub fn cube() -> Vec<f32> {
+    // The normal of each face, then two axes along it whose cross product is the normal,
+    // so that the triangles are counter-clockwise seen from outside
+    let faces = [
+        ([1.0f32, 0.0, 0.0], [0.0f32, 1.0, 0.0], [0.0f32, 0.0, 1.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+    ];
+    let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)];
+
+    let mut vertices = Vec::with_capacity(6 * 6 * 6);
+    for (normal, u, v) in &faces {
+        for (a, b) in &corners {
+            for axis in 0..3 {
+                vertices.push(0.5 + 0.5 * normal[axis] + (a - 0.5) * u[axis] + (b - 0.5) * v[axis]);
+            }
+            vertices.extend_from_slice(normal);
+        }
+    }
+    vertices
+}
 
-        0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, bottom_layer as f32, 0.0, -1.0, 0.0,
-        1.0 + x,  0.0 + y,  0.0 + z, uv.2, uv.1, bottom_layer as f32, 0.0, -1.0, 0.0,
-        1.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.3, bottom_layer as f32, 0.0, -1.0, 0.0,
-        1.0 + x,  0.0 + y,  1.0 + z, uv.2, uv.3, bottom_layer as f32, 0.0, -1.0, 0.0,
-        0.0 + x,  0.0 + y,  1.0 + z, uv.0, uv.3, bottom_layer as f32, 0.0, -1.0, 0.0,
-        0.0 + x,  0.0 + y,  0.0 + z, uv.0, uv.1, bottom_layer as f32, 0.0, -1.0, 0.0,
-    ].to_vec()
-}
\ No newline at end of file
+/// The vertices of every quad of a block model, like an item in the GUI or in the hand.
+/// Each vertex is made of its position, its UV coordinates and layer, its normal and its colour:
+/// `tint` for the tinted quads, white for the others
+pub fn model_vertices(x: f32, y: f32, z: f32, model: &BlockModel, tint: [f32; 3]) -> Vec<f32> {
+    let mut vertices = Vec::with_capacity(12 * 6 * model.quads.len());
+    for quad in &model.quads {
+        let color = if quad.tinted { tint } else { [1.0; 3] };
+        for &corner in &[0, 1, 2, 2, 3, 0] {
+            let (position, uv) = (quad.positions[corner], quad.uvs[corner]);
+            vertices.extend_from_slice(&[
+                position.x + x, position.y + y, position.z + z,
+                uv.0, uv.1, quad.layer as f32,
+                quad.normal.x, quad.normal.y, quad.normal.z,
+                color[0], color[1], color[2]]);
+        }
+    }
+    vertices
+}
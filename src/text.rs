@@ -0,0 +1,177 @@
+//! Text drawn with the bitmap font of the resource packs, `textures/font/ascii.png` like in Minecraft:
+//! the 256 characters of code page 437 in a grid of 16x16 cells. Each glyph is as wide as its
+//! rightmost opaque column, and the text and the rectangles behind it are batched into a single mesh
+
+use std::ffi::c_void;
+
+use image::RgbaImage;
+
+use crate::shader_compilation::ShaderProgram;
+
+/// The side of a glyph, in font pixels. The cells of larger fonts are scaled down to it
+pub const GLYPH_SIZE: f32 = 8.0;
+/// The distance between two lines, in font pixels
+pub const LINE_HEIGHT: f32 = 9.0;
+/// The full block of code page 437, stretched over the rectangles
+pub const SOLID_GLYPH: u8 = 219;
+/// The texture unit `TexturePack` binds the font to
+pub const FONT_TEXTURE_UNIT: i32 = 3;
+/// A space has no pixels to be measured by
+const SPACE_ADVANCE: f32 = 4.0;
+/// Position, texture coordinates and RGBA colour
+const TEXT_VERTEX_SIZE: usize = 8;
+
+/// The widths of the glyphs of a font texture
+pub struct Font {
+    /// In font pixels, with the pixel between two glyphs
+    advances: [f32; 256],
+}
+
+impl Font {
+    /// Measures the glyphs of a font texture. An image too small for the grid has glyphs of a pixel
+    pub fn from_image(image: &RgbaImage) -> Self {
+        let cell = image.width().min(image.height()) / 16;
+        let mut advances = [1.0; 256];
+        if cell > 0 {
+            for (code, advance) in advances.iter_mut().enumerate() {
+                let (cell_x, cell_y) = (code as u32 % 16 * cell, code as u32 / 16 * cell);
+                let width = (0..cell).rev()
+                    .find(|&x| (0..cell).any(|y| image.get_pixel(cell_x + x, cell_y + y)[3] != 0))
+                    .map_or(0, |x| x + 1);
+                *advance = width as f32 * GLYPH_SIZE / cell as f32 + 1.0;
+            }
+        }
+        advances[b' ' as usize] = SPACE_ADVANCE;
+        Self { advances }
+    }
+
+    /// How far the next glyph is drawn after this one, in font pixels
+    pub fn advance(&self, c: char) -> f32 {
+        self.advances[glyph(c) as usize]
+    }
+
+    /// The width of a line, in font pixels
+    pub fn width(&self, text: &str) -> f32 {
+        // Without the pixel after the last glyph
+        (text.chars().map(|c| self.advance(c)).sum::<f32>() - 1.0).max(0.0)
+    }
+}
+
+/// The cell of a character, `?` for the ones outside of ASCII
+fn glyph(c: char) -> u8 {
+    if c.is_ascii() { c as u8 } else { b'?' }
+}
+
+/// Quads of text and rectangles, in pixels from the top left corner of the screen
+#[derive(Default)]
+pub struct TextMesh {
+    vertices: Vec<f32>,
+}
+
+impl TextMesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn vertices(&self) -> &[f32] {
+        &self.vertices
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / TEXT_VERTEX_SIZE
+    }
+
+    /// Adds a line with its top left corner at (x, y). A font pixel is `scale` pixels wide
+    pub fn add_text(&mut self, font: &Font, text: &str, (x, y): (f32, f32), scale: f32, color: [f32; 4]) {
+        let mut x = x;
+        for c in text.chars() {
+            if c != ' ' {
+                let code = glyph(c);
+                let (u, v) = ((code % 16) as f32 / 16.0, (code / 16) as f32 / 16.0);
+                let size = GLYPH_SIZE * scale;
+                self.add_quad((x, y, size, size), (u, v, 1.0 / 16.0, 1.0 / 16.0), color);
+            }
+            x += font.advance(c) * scale;
+        }
+    }
+
+    /// Adds a rectangle of (x, y, width, height)
+    pub fn add_rectangle(&mut self, rectangle: (f32, f32, f32, f32), color: [f32; 4]) {
+        // The middle of the full block
+        let u = ((SOLID_GLYPH % 16) as f32 + 0.5) / 16.0;
+        let v = ((SOLID_GLYPH / 16) as f32 + 0.5) / 16.0;
+        self.add_quad(rectangle, (u, v, 0.0, 0.0), color);
+    }
+
+    fn add_quad(&mut self, (x, y, width, height): (f32, f32, f32, f32), (u, v, du, dv): (f32, f32, f32, f32), color: [f32; 4]) {
+        // Counter-clockwise once the y axis is flipped by the projection
+        for &(corner_x, corner_y) in &[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.0, 0.0), (1.0, 1.0), (1.0, 0.0)] {
+            self.vertices.extend_from_slice(&[x + corner_x * width, y + corner_y * height, u + corner_x * du, v + corner_y * dv]);
+            self.vertices.extend_from_slice(&color);
+        }
+    }
+}
+
+/// Draws `TextMesh`es with the text shader
+pub struct TextRenderer {
+    vao: u32,
+    vbo: u32,
+}
+
+impl TextRenderer {
+    pub fn new() -> Self {
+        let mut vao = 0;
+        gl_call!(gl::CreateVertexArrays(1, &mut vao));
+
+        // Position
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 0));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 0, 2 as i32, gl::FLOAT, gl::FALSE, 0));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 0, 0));
+
+        // Texture coords
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 1));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 1, 2 as i32, gl::FLOAT, gl::FALSE, 2 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 1, 0));
+
+        // Colour
+        gl_call!(gl::EnableVertexArrayAttrib(vao, 2));
+        gl_call!(gl::VertexArrayAttribFormat(vao, 2, 4 as i32, gl::FLOAT, gl::FALSE, 4 * std::mem::size_of::<f32>() as u32));
+        gl_call!(gl::VertexArrayAttribBinding(vao, 2, 0));
+
+        let mut vbo = 0;
+        gl_call!(gl::CreateBuffers(1, &mut vbo));
+        gl_call!(gl::VertexArrayVertexBuffer(vao, 0, vbo, 0, (TEXT_VERTEX_SIZE * std::mem::size_of::<f32>()) as i32));
+
+        Self { vao, vbo }
+    }
+
+    /// Draws the mesh over a screen of `width` x `height` pixels
+    pub fn draw(&self, mesh: &TextMesh, shader: &mut ShaderProgram, width: f32, height: f32) {
+        if mesh.vertex_count() == 0 {
+            return;
+        }
+        gl_call!(gl::NamedBufferData(self.vbo,
+                (mesh.vertices().len() * std::mem::size_of::<f32>()) as isize,
+                mesh.vertices().as_ptr() as *const c_void,
+                gl::STREAM_DRAW));
+
+        let projection_matrix = nalgebra_glm::ortho(0.0, width, height, 0.0, -1.0, 1.0);
+        shader.use_program();
+        shader.set_uniform_matrix4fv("projection", projection_matrix.as_ptr());
+        shader.set_uniform1i("font", FONT_TEXTURE_UNIT);
+
+        gl_call!(gl::BindVertexArray(self.vao));
+        gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, mesh.vertex_count() as i32));
+    }
+}
+
+impl Drop for TextRenderer {
+    fn drop(&mut self) {
+        gl_call!(gl::DeleteBuffers(1, &self.vbo));
+        gl_call!(gl::DeleteVertexArrays(1, &self.vao));
+    }
+}
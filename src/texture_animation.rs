@@ -0,0 +1,94 @@
+//! Animated textures, like in Minecraft: the frames of the animation are stacked from top to bottom
+//! in the image, and `<texture>.png.mcmeta` next to it says how long each of them is shown:
+//!
+//! ```json
+//! { "animation": { "frametime": 2, "frames": [0, 1, { "index": 2, "time": 10 }] } }
+//! ```
+//!
+//! Times are in animation ticks of 50 ms. Without `frames`, the frames of the image are shown from
+//! top to bottom, each for `frametime`
+
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// The unit of the times of the animations, the 20 ticks per second of Minecraft
+pub const ANIMATION_TICK: Duration = Duration::from_millis(50);
+
+#[derive(Deserialize)]
+struct MetadataFile {
+    animation: AnimationFile,
+}
+
+#[derive(Deserialize)]
+struct AnimationFile {
+    #[serde(default = "default_frametime")]
+    frametime: u32,
+    frames: Option<Vec<FrameFile>>,
+}
+
+fn default_frametime() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameFile {
+    Index(u32),
+    Timed {
+        index: u32,
+        time: u32,
+    },
+}
+
+/// The frames of an animated texture and how long each of them is shown
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureAnimation {
+    /// The frame of the image and its time in animation ticks, in the order they are shown
+    frames: Vec<(u32, u32)>,
+    /// The time of the whole animation in animation ticks
+    duration: u32,
+}
+
+impl TextureAnimation {
+    /// Parses the `.mcmeta` of an image of `frame_count` frames
+    pub fn parse(bytes: &[u8], frame_count: u32) -> Result<Self, String> {
+        let file: MetadataFile = serde_json::from_slice(bytes).map_err(|err| err.to_string())?;
+        let animation = file.animation;
+        let frametime = animation.frametime;
+        let frames = match animation.frames {
+            Some(frames) => frames.into_iter()
+                .map(|frame| match frame {
+                    FrameFile::Index(index) => (index, frametime),
+                    FrameFile::Timed { index, time } => (index, time),
+                })
+                .collect::<Vec<_>>(),
+            None => (0..frame_count).map(|index| (index, frametime)).collect(),
+        };
+
+        if let Some(&(index, _)) = frames.iter().find(|&&(index, _)| index >= frame_count) {
+            return Err(format!("frame {} is out of the {} frames of the image", index, frame_count));
+        }
+        let duration = frames.iter().map(|&(_, time)| time).sum();
+        if duration == 0 {
+            return Err("the animation has no frames or all of them last 0 ticks".to_string());
+        }
+        Ok(Self {
+            frames,
+            duration,
+        })
+    }
+
+    /// The frame of the image shown `elapsed` after the start of the animation, which loops
+    pub fn frame_at(&self, elapsed: Duration) -> u32 {
+        let ticks = (elapsed.as_millis() / ANIMATION_TICK.as_millis()) as u64;
+        let mut tick = (ticks % self.duration as u64) as u32;
+        for &(index, time) in &self.frames {
+            if tick < time {
+                return index;
+            }
+            tick -= time;
+        }
+        self.frames[0].0
+    }
+}
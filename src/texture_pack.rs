@@ -1,116 +1,292 @@
-use std::collections::HashMap;
 use std::os::raw::c_void;
+use std::sync::Arc;
+use std::time::Instant;
 
-use image::{DynamicImage, GenericImageView};
+use image::{DynamicImage, FilterType, GenericImageView};
 
-use crate::block_texture_faces::BlockFaces;
-use crate::chunk::BlockID;
-use crate::constants::ITEM_ARRAY_TEXTURE_LAYERS;
-use crate::types::{TextureLayer, TexturePack};
+use crate::biome::{ColorMap, ColorMaps, Tint};
+use crate::block_model::BlockModels;
+use crate::constants::MAX_BLOCK_TEXTURE_SIZE;
+use crate::gui::create_gui_texture;
+use crate::resource_pack::ResourcePacks;
+use crate::text::{Font, FONT_TEXTURE_UNIT};
+use crate::texture_animation::TextureAnimation;
 
-pub fn generate_array_texture() -> (u32, TexturePack) {
-    let face_images = create_face_images_map();
-    let array_texture = create_array_texture(ITEM_ARRAY_TEXTURE_LAYERS as i32);
-    let face_uvs = create_face_uvs_map(array_texture, face_images);
-    (array_texture, face_uvs)
-}
+/// Replaces the textures that are missing or can't be read
+const MISSING_TEXTURE: &str = "textures/blocks/debug.png";
 
-fn create_face_images_map() -> HashMap<BlockID, BlockFaces<&'static str>> {
-    let mut face_images: HashMap<BlockID, BlockFaces<&str>> = HashMap::new();
-    face_images.insert(BlockID::Dirt, BlockFaces::All("textures/blocks/dirt.png"));
-    face_images.insert(BlockID::GrassBlock, BlockFaces::Sides {
-        sides: "textures/blocks/grass_block_side.png",
-        top: "textures/blocks/grass_block_top.png",
-        bottom: "textures/blocks/dirt.png",
-    });
-    face_images.insert(BlockID::Stone, BlockFaces::All("textures/blocks/stone.png"));
-    face_images.insert(BlockID::Cobblestone, BlockFaces::All("textures/blocks/cobblestone.png"));
-    face_images.insert(BlockID::Bedrock, BlockFaces::All("textures/blocks/bedrock.png"));
-    face_images.insert(BlockID::Obsidian, BlockFaces::All("textures/blocks/obsidian.png"));
-    face_images.insert(BlockID::OakLog, BlockFaces::Sides {
-        sides: "textures/blocks/oak_log.png",
-        top: "textures/blocks/oak_log_top.png",
-        bottom: "textures/blocks/oak_log_top.png",
-    });
-    face_images.insert(BlockID::OakLeaves, BlockFaces::All("textures/blocks/oak_leaves_mod.png"));
-    face_images.insert(BlockID::OakPlanks, BlockFaces::All("textures/blocks/oak_planks.png"));
-    face_images.insert(BlockID::Glass, BlockFaces::All("textures/blocks/glass.png"));
-    face_images.insert(BlockID::Urss, BlockFaces::All("textures/blocks/urss.png"));
-    face_images.insert(BlockID::Hitler, BlockFaces::All("textures/blocks/hitler.png"));
-    face_images.insert(BlockID::Debug, BlockFaces::All("textures/blocks/debug.png"));
-    face_images.insert(BlockID::Debug2, BlockFaces::All("textures/blocks/debug2.png"));
-    face_images
+/// The textures of the enabled resource packs, bound to their texture units:
+/// the blocks to the unit 0, the GUI icons to the unit 1, the widgets to the unit 2 and the font to the unit 3.
+/// The colour maps of the biomes stay on the CPU, the chunks are tinted when they are meshed
+pub struct TexturePack {
+    packs: ResourcePacks,
+    /// The models whose textures make up the array texture
+    block_models: Arc<BlockModels>,
+    array_texture: u32,
+    gui_icons_texture: u32,
+    gui_widgets_texture: u32,
+    font_texture: u32,
+    font: Font,
+    color_maps: ColorMaps,
+    animations: Vec<AnimatedTexture>,
+    /// When the animations were first shown since the textures were created
+    animations_start: Option<Instant>,
 }
 
-fn create_array_texture(layers: i32) -> u32 {
-    let mut item_array_texture: u32 = 0;
-    gl_call!(gl::CreateTextures(gl::TEXTURE_2D_ARRAY, 1, &mut item_array_texture));
-    gl_call!(gl::TextureParameteri(item_array_texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_LINEAR as i32));
-    gl_call!(gl::TextureParameteri(item_array_texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32));
-    gl_call!(gl::TextureStorage3D(item_array_texture, 1, gl::RGBA8, 16, 16, layers));
-    item_array_texture
+/// A texture of the array with a `.mcmeta`, its frames are copied to its layer in turn
+struct AnimatedTexture {
+    layer: i32,
+    animation: TextureAnimation,
+    /// Each frame with its mipmaps, ready to be copied
+    frames: Vec<Vec<DynamicImage>>,
+    shown_frame: u32,
 }
 
-fn create_face_uvs_map(array_texture: u32, face_images: HashMap<BlockID, BlockFaces<&str>>) -> HashMap<BlockID, BlockFaces<TextureLayer>> {
-    // Load all the images and fill the UV map for all the blocks
-    // TODO don't load the same texture multiple times if reused for another block
+impl TexturePack {
+    pub fn load(packs: ResourcePacks, block_models: Arc<BlockModels>) -> Self {
+        let mut texture_pack = Self {
+            packs,
+            block_models,
+            array_texture: 0,
+            gui_icons_texture: 0,
+            gui_widgets_texture: 0,
+            font_texture: 0,
+            font: Font::from_image(&image::RgbaImage::new(0, 0)),
+            color_maps: ColorMaps::default(),
+            animations: Vec::new(),
+            animations_start: None,
+        };
+        texture_pack.create_textures();
+        texture_pack
+    }
 
-    let mut layer: u32 = 0;
+    /// Swaps the resource packs, the textures are created again from the new ones
+    pub fn reload(&mut self, packs: ResourcePacks) {
+        let textures = [self.array_texture, self.gui_icons_texture, self.gui_widgets_texture, self.font_texture];
+        gl_call!(gl::DeleteTextures(textures.len() as i32, textures.as_ptr()));
+        self.packs = packs;
+        self.create_textures();
+    }
 
-    // Puts an image into the array texture at layer "layer"
-    let mut put_image_into_array_texture = |image: &DynamicImage| {
-        let layer_blit = layer;
-        blit_image_to_texture(image, array_texture, layer_blit as i32);
-        // Advance to the next available layer in the texture
-        layer += 1;
-        // Return layer where we put the texture
-        layer_blit
-    };
+    pub fn packs(&self) -> &ResourcePacks {
+        &self.packs
+    }
 
-    let mut face_uvs = HashMap::<BlockID, BlockFaces<TextureLayer>>::new();
-    for (block, faces) in face_images {
-        match faces {
-            BlockFaces::All(all) => {
-                face_uvs.insert(block, BlockFaces::All(put_image_into_array_texture(&mut read_image(all))));
-            }
-            BlockFaces::Sides { sides, top, bottom } => {
-                face_uvs.insert(block, BlockFaces::Sides {
-                    sides: put_image_into_array_texture(&mut read_image(sides)),
-                    top: put_image_into_array_texture(&mut read_image(top)),
-                    bottom: put_image_into_array_texture(&mut read_image(bottom)),
-                });
-            }
-            BlockFaces::Each { top, bottom, front, back, left, right } => {
-                face_uvs.insert(block, BlockFaces::Each {
-                    top: put_image_into_array_texture(&mut read_image(top)),
-                    bottom: put_image_into_array_texture(&mut read_image(bottom)),
-                    front: put_image_into_array_texture(&mut read_image(front)),
-                    back: put_image_into_array_texture(&mut read_image(back)),
-                    left: put_image_into_array_texture(&mut read_image(left)),
-                    right: put_image_into_array_texture(&mut read_image(right)),
-                });
+    pub fn color_maps(&self) -> &ColorMaps {
+        &self.color_maps
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Shows the frames of the animated textures at `time`. The frames are copied into the layers of
+    /// the textures, so the meshes don't change
+    pub fn animate(&mut self, time: Instant) {
+        let elapsed = time.saturating_duration_since(*self.animations_start.get_or_insert(time));
+        for animated in &mut self.animations {
+            let frame = animated.animation.frame_at(elapsed);
+            if frame != animated.shown_frame {
+                for (level, image) in animated.frames[frame as usize].iter().enumerate() {
+                    blit_image_to_texture(image, self.array_texture, level as i32, animated.layer);
+                }
+                animated.shown_frame = frame;
             }
         }
     }
-    face_uvs
+
+    fn create_textures(&mut self) {
+        let (array_texture, animations) = generate_array_texture(&self.packs, &self.block_models);
+        self.array_texture = array_texture;
+        self.animations = animations;
+        self.animations_start = None;
+        self.gui_icons_texture = create_gui_texture(&read_image(&self.packs, "textures/gui/icons.png"));
+        self.gui_widgets_texture = create_gui_texture(&read_image(&self.packs, "textures/gui/widgets.png"));
+        let font_image = read_image(&self.packs, "textures/font/ascii.png");
+        self.font_texture = create_gui_texture(&font_image);
+        self.font = Font::from_image(&font_image.to_rgba());
+        let packs = &self.packs;
+        let read_color_map = |tint: Tint| {
+            let image = read_image(packs, tint.color_map_path());
+            ColorMap::from_rgba(image.width(), image.height(), &image.raw_pixels())
+        };
+        self.color_maps = ColorMaps::new(read_color_map(Tint::Grass), read_color_map(Tint::Foliage));
+
+        gl_call!(gl::BindTextureUnit(0, self.array_texture));
+        gl_call!(gl::BindTextureUnit(1, self.gui_icons_texture));
+        gl_call!(gl::BindTextureUnit(2, self.gui_widgets_texture));
+        gl_call!(gl::BindTextureUnit(FONT_TEXTURE_UNIT as u32, self.font_texture));
+    }
 }
 
-fn read_image(image_path: &str) -> DynamicImage {
-    let img = match image::open(image_path) {
-        Ok(img) => img.flipv(), // We flip the y axis for OpenGL
-        Err(err) => panic!("Filename: {}, error: {}", image_path, err.to_string())
+/// Creates the array texture of the blocks, with each texture used by the block models
+/// on the layer the models refer to it by, and the animations of the animated ones.
+/// The array has a layer per texture and the side of the largest of them, the smaller ones are scaled up to it
+fn generate_array_texture(packs: &ResourcePacks, block_models: &BlockModels) -> (u32, Vec<AnimatedTexture>) {
+    let textures = block_models.textures().iter()
+        .map(|texture| {
+            let path = format!("textures/{}.png", texture);
+            let (frames, animation) = read_block_texture(packs, &path);
+            (path, frames, animation)
+        })
+        .collect::<Vec<_>>();
+
+    let size = block_texture_size(textures.iter().map(|(_, frames, _)| &frames[0]));
+    let mut max_layers = 0;
+    gl_call!(gl::GetIntegerv(gl::MAX_ARRAY_TEXTURE_LAYERS, &mut max_layers));
+    assert!(textures.len() <= max_layers as usize,
+            "The block models use {} textures, more than the {} layers an array texture can have",
+            textures.len(), max_layers);
+
+    let array_texture = create_array_texture(size, textures.len().max(1) as i32);
+    let mut animations = Vec::new();
+    for (layer, (path, frames, animation)) in textures.into_iter().enumerate() {
+        if frames[0].width() != size {
+            warn!("The texture {} is {}x{}, scaling it to {}x{}",
+                  path, frames[0].width(), frames[0].height(), size, size);
+        }
+        // We flip the y axis for OpenGL
+        let frames = frames.iter()
+            .map(|frame| {
+                let frame = if frame.width() != size { scale_block_texture(frame, size) } else { frame.clone() };
+                frame.flipv()
+            })
+            .collect::<Vec<_>>();
+        blit_image_to_texture(&frames[0], array_texture, 0, layer as i32);
+
+        if let Some(animation) = animation {
+            animations.push(AnimatedTexture {
+                layer: layer as i32,
+                animation,
+                frames: frames.iter().map(mipmaps).collect(),
+                shown_frame: 0,
+            });
+        }
+    }
+    gl_call!(gl::GenerateTextureMipmap(array_texture));
+    (array_texture, animations)
+}
+
+/// The frames of the texture at `path`, with its animation if it has a `.mcmeta` next to it.
+/// The debug texture replaces the textures that aren't valid block textures
+fn read_block_texture(packs: &ResourcePacks, path: &str) -> (Vec<DynamicImage>, Option<TextureAnimation>) {
+    let image = read_image(packs, path);
+    let animation = packs.read(&format!("{}.mcmeta", path)).and_then(|bytes| {
+        TextureAnimation::parse(&bytes, image.height() / image.width().max(1))
+            .map_err(|err| error!("Couldn't load the animation {}.mcmeta, the texture isn't animated: {}", path, err))
+            .ok()
+    });
+    let frames = match animation {
+        Some(_) => animation_frames(&image),
+        None => vec![image],
     };
-    match img.color() {
-        image::RGBA(8) => {}
-        _ => panic!("Texture format not supported")
+
+    match frames.iter().try_for_each(|frame| check_block_texture(path, frame)) {
+        Ok(()) => (frames, animation),
+        Err(err) => {
+            error!("{}, using the debug texture instead", err);
+            let debug = read_image(packs, MISSING_TEXTURE);
+            let debug = check_block_texture(MISSING_TEXTURE, &debug).map(|_| debug).unwrap_or_else(|err| {
+                error!("{}", err);
+                checkerboard()
+            });
+            (vec![debug], None)
+        }
+    }
+}
+
+/// The square frames of an animated texture, stacked from top to bottom in `image`
+pub fn animation_frames(image: &DynamicImage) -> Vec<DynamicImage> {
+    let size = image.width();
+    // Cropping doesn't change the image but needs it mutable
+    let mut image = image.clone();
+    (0..image.height() / size.max(1))
+        .map(|frame| image.crop(0, frame * size, size, size))
+        .collect()
+}
+
+/// `image` and its mipmaps, halving its side down to 1x1
+fn mipmaps(image: &DynamicImage) -> Vec<DynamicImage> {
+    let mut levels = vec![image.clone()];
+    while levels.last().unwrap().width() > 1 {
+        let side = levels.last().unwrap().width() / 2;
+        let level = levels.last().unwrap().resize_exact(side, side, FilterType::Triangle);
+        levels.push(level);
+    }
+    levels
+}
+
+/// The block textures need to be square with a power of two side, so that they can all be scaled to
+/// the size of the array texture and halved down to its mipmaps
+pub fn check_block_texture(path: &str, image: &DynamicImage) -> Result<(), String> {
+    let (width, height) = image.dimensions();
+    if width != height {
+        Err(format!("The texture {} is {}x{}, block textures need to be square", path, width, height))
+    } else if !width.is_power_of_two() {
+        Err(format!("The texture {} is {}x{}, the side of block textures needs to be a power of two", path, width, height))
+    } else {
+        Ok(())
+    }
+}
+
+/// The side of the layers of the array texture: the side of the largest texture, up to
+/// `MAX_BLOCK_TEXTURE_SIZE`
+pub fn block_texture_size<'a>(images: impl IntoIterator<Item = &'a DynamicImage>) -> u32 {
+    images.into_iter()
+        .map(|image| image.width())
+        .max()
+        .unwrap_or(16)
+        .min(MAX_BLOCK_TEXTURE_SIZE)
+}
+
+/// Scales a square texture to `size`. Scaling up repeats the pixels to keep them sharp
+pub fn scale_block_texture(image: &DynamicImage, size: u32) -> DynamicImage {
+    let filter = if image.width() < size { FilterType::Nearest } else { FilterType::Triangle };
+    image.resize_exact(size, size, filter)
+}
+
+/// An array texture of `layers` layers of `size` by `size` pixels, with all its mipmap levels down to 1x1
+fn create_array_texture(size: u32, layers: i32) -> u32 {
+    let levels = size.trailing_zeros() as i32 + 1;
+    let mut item_array_texture: u32 = 0;
+    gl_call!(gl::CreateTextures(gl::TEXTURE_2D_ARRAY, 1, &mut item_array_texture));
+    gl_call!(gl::TextureParameteri(item_array_texture, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_LINEAR as i32));
+    gl_call!(gl::TextureParameteri(item_array_texture, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32));
+    gl_call!(gl::TextureStorage3D(item_array_texture, levels, gl::RGBA8, size as i32, size as i32, layers));
+    item_array_texture
+}
+
+/// The image at `path` in the resource packs, converted to RGBA. The debug texture replaces the images
+/// that are missing or can't be decoded, and a checkerboard replaces the debug texture
+fn read_image(packs: &ResourcePacks, path: &str) -> DynamicImage {
+    let decode = |path: &str| -> Result<DynamicImage, String> {
+        let bytes = packs.read(path).ok_or_else(|| "not found in the resource packs".to_string())?;
+        image::load_from_memory(&bytes).map_err(|err| err.to_string())
     };
-    img
+    let image = decode(path).or_else(|err| {
+        error!("Couldn't load the texture {}, using the debug texture instead: {}", path, err);
+        decode(MISSING_TEXTURE)
+    }).unwrap_or_else(|err| {
+        error!("Couldn't load the debug texture {}: {}", MISSING_TEXTURE, err);
+        checkerboard()
+    });
+    DynamicImage::ImageRgba8(image.to_rgba())
 }
 
-fn blit_image_to_texture(src: &DynamicImage, texture: u32, layer: i32) {
+fn checkerboard() -> DynamicImage {
+    DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 16, |x, y| {
+        if (x / 8 + y / 8) % 2 == 0 {
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    }))
+}
+
+fn blit_image_to_texture(src: &DynamicImage, texture: u32, level: i32, layer: i32) {
     gl_call!(gl::TextureSubImage3D(
-            texture, 0,
+            texture, level,
             0, 0, layer, src.width() as i32, src.height() as i32, 1,
             gl::RGBA, gl::UNSIGNED_BYTE,
             src.raw_pixels().as_ptr() as *mut c_void));
-}
\ No newline at end of file
+}
@@ -0,0 +1,98 @@
+//! Fixed timestep simulation using the following method:
+//! https://gafferongames.com/post/fix_your_timestep/
+//! With this method, the physics are always deterministic and work independently
+//! of the performance of the game.
+//!
+//! The time of the frames is accumulated by `Ticks` and spent in ticks of `Ticks::DT` seconds.
+//! The client, the server and the replays count the same ticks: the client starts at the tick of the
+//! server it logged in to, and tells it the tick of each of its movements. A replay doesn't measure
+//! the time of its frames, it schedules the ticks they were recorded with.
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::constants::TICKRATE;
+
+/// Frames longer than this, like after a freeze, are cut short. The ticks of the time cut
+/// are counted but not simulated, so the counter keeps up with the other side of the connection
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/// The world tick counter, the only fixed timestep scheduler
+#[derive(Default)]
+pub struct Ticks {
+    /// Ticks elapsed since the start of the world
+    pub tick: u64,
+    /// Ticks to simulate during this frame
+    pub pending: u32,
+    /// Fraction of a tick elapsed since the last one, from 0 to 1
+    pub alpha: f32,
+    accumulator: f32,
+    /// Time of the last frame, None until the first one so that every clock
+    /// (wall, fixed step or replayed) starts the simulation at its own time
+    last_time: Option<Instant>,
+}
+
+impl Ticks {
+    /// Duration of a tick in seconds
+    pub const DT: f32 = 1.0 / TICKRATE;
+
+    /// A counter at `tick`, like the one of the server when the client logged in
+    pub fn starting_at(tick: u64) -> Self {
+        Self {
+            tick,
+            ..Self::default()
+        }
+    }
+
+    /// Schedules the ticks elapsed between the last frame and `now`, the time of the global `Timer`.
+    /// Returns the number of ticks skipped because the frame was too long
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let last_time = self.last_time.unwrap_or(now);
+        let mut frame_time = now.saturating_duration_since(last_time).as_secs_f32();
+        let mut skipped = 0;
+        if frame_time > MAX_FRAME_TIME {
+            skipped = ((frame_time - MAX_FRAME_TIME) / Self::DT) as u32;
+            frame_time -= skipped as f32 * Self::DT;
+        }
+        self.last_time = Some(now);
+        self.accumulator += frame_time;
+
+        self.pending = 0;
+        while self.accumulator >= Self::DT {
+            self.pending += 1;
+            self.accumulator -= Self::DT;
+        }
+        self.tick += skipped as u64 + self.pending as u64;
+        self.alpha = self.accumulator / Self::DT;
+        skipped
+    }
+
+    /// Schedules exactly one tick, whatever the time elapsed
+    pub fn step(&mut self) {
+        self.pending = 1;
+        self.tick += 1;
+        self.alpha = 0.0;
+    }
+
+    /// Schedules the ticks of a replayed frame: `skipped` ticks counted but not simulated, then `pending` ticks
+    pub fn replay(&mut self, skipped: u64, pending: u32) {
+        self.tick += skipped + pending as u64;
+        self.pending = pending;
+        self.alpha = 0.0;
+    }
+
+    /// Ticks elapsed since the start of the world, with the fraction of the current one
+    pub fn time(&self) -> f64 {
+        self.tick as f64 + self.alpha as f64
+    }
+
+    /// The numbers of the ticks to simulate during this frame
+    pub fn pending_ticks(&self) -> Range<u64> {
+        self.tick - self.pending as u64..self.tick
+    }
+
+    /// How long until the next tick is due, from the time of the last frame
+    pub fn until_next_tick(&self) -> Duration {
+        Duration::from_secs_f32((Self::DT - self.accumulator).max(0.0))
+    }
+}
@@ -1,10 +1,21 @@
 use std::time::{Instant, Duration};
 use std::ops::Sub;
 
+/// Where the time of a `Timer` comes from
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Clock {
+    Wall,
+    /// For simulations that must not depend on the wall clock
+    FixedStep(Duration),
+    /// Only moved by `advance`, replays set the time of every frame
+    Manual,
+}
+
 pub struct Timer {
     current: Instant,
     time_paused: Duration,
     paused: bool,
+    clock: Clock,
 }
 
 impl Default for Timer {
@@ -19,11 +30,30 @@ impl Timer {
             current: Instant::now(),
             time_paused: Duration::new(0, 0),
             paused: false,
+            clock: Clock::Wall,
+        }
+    }
+
+    /// A timer that advances by exactly `step` on every tick, whatever the real time elapsed
+    pub fn with_fixed_step(step: Duration) -> Self {
+        Self {
+            clock: Clock::FixedStep(step),
+            ..Self::new()
+        }
+    }
+
+    /// A timer that doesn't move on its own, see `advance`
+    pub fn manual() -> Self {
+        Self {
+            clock: Clock::Manual,
+            ..Self::new()
         }
     }
 
     pub fn restart(&mut self) {
-        self.current = Instant::now();
+        if self.clock == Clock::Wall {
+            self.current = Instant::now();
+        }
         self.time_paused = Duration::new(0, 0);
     }
 
@@ -36,10 +66,23 @@ impl Timer {
     }
 
     pub fn tick(&mut self) {
-        if self.paused {
-            self.time_paused = Instant::now().duration_since(self.current);
-        } else {
-            self.current = Instant::now().sub(self.time_paused);
+        match self.clock {
+            Clock::FixedStep(step) => if !self.paused {
+                self.current += step;
+            },
+            Clock::Wall => if self.paused {
+                self.time_paused = Instant::now().duration_since(self.current);
+            } else {
+                self.current = Instant::now().sub(self.time_paused);
+            },
+            Clock::Manual => {}
+        }
+    }
+
+    /// Moves a fixed step or manual timer forward by `duration`, ignored by real time timers
+    pub fn advance(&mut self, duration: Duration) {
+        if self.clock != Clock::Wall {
+            self.current += duration;
         }
     }
 
@@ -50,4 +93,4 @@ impl Timer {
     pub fn is_paused(&self) -> bool {
         self.paused
     }
-}
\ No newline at end of file
+}
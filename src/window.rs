@@ -3,15 +3,20 @@ use std::sync::mpsc::Receiver;
 use glfw::{Context, CursorMode, Glfw, OpenGlProfileHint, Window, WindowEvent, WindowHint};
 
 use crate::constants::{OPENGL_MAJOR_VERSION, OPENGL_MINOR_VERSION};
+use crate::debugging::debug_message_callback;
 #[allow(unused_imports)]
 use glfw::ffi::glfwSwapInterval;
 
-pub fn create_window(width: u32, height: u32, title: &str) -> (Glfw, Window, Receiver<(f64, WindowEvent)>) {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+fn set_context_hints(glfw: &mut Glfw) {
     glfw.window_hint(WindowHint::ContextVersionMajor(OPENGL_MAJOR_VERSION));
     glfw.window_hint(WindowHint::ContextVersionMinor(OPENGL_MINOR_VERSION));
     glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
     glfw.window_hint(WindowHint::OpenGlDebugContext(true));
+}
+
+pub fn create_window(width: u32, height: u32, title: &str) -> (Glfw, Window, Receiver<(f64, WindowEvent)>) {
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+    set_context_hints(&mut glfw);
 
     // TODO implement an artificial FPS limiter instead of using V-SYNC because it introduces annoying input lag
 
@@ -31,4 +36,38 @@ pub fn create_window(width: u32, height: u32, title: &str) -> (Glfw, Window, Rec
     window.set_scroll_polling(true);
     window.set_cursor_mode(CursorMode::Disabled);
     (glfw, window, events)
-}
\ No newline at end of file
+}
+
+/// An invisible window, only used for its OpenGL context, which is made current. Nothing is drawn to
+/// the window itself, see `OffscreenTarget`. Unlike `create_window`, failing isn't fatal, there may be
+/// no display to open it on
+pub fn create_hidden_window(width: u32, height: u32) -> Result<(Glfw, Window), String> {
+    let mut glfw = glfw::init(glfw::LOG_ERRORS).map_err(|err| format!("Couldn't initialize GLFW: {}", err))?;
+    set_context_hints(&mut glfw);
+    glfw.window_hint(WindowHint::Visible(false));
+
+    let (mut window, _) = glfw.create_window(width, height, "", glfw::WindowMode::Windowed)
+        .ok_or_else(|| format!("Couldn't create an OpenGL {}.{} context", OPENGL_MAJOR_VERSION, OPENGL_MINOR_VERSION))?;
+    gl::load_with(|s| window.get_proc_address(s) as *const _);
+    window.make_current();
+    Ok((glfw, window))
+}
+
+/// Enables the debug output and sets the global GL state used by every render pass
+pub fn init_gl_state(window: &Window) {
+    init_gl_render_state();
+    let window_size = window.get_size();
+    gl_call!(gl::Viewport(0, 0, window_size.0, window_size.1));
+}
+
+/// `init_gl_state` for a context without a window, the viewport is set by the target drawn to
+pub fn init_gl_render_state() {
+    gl_call!(gl::Enable(gl::DEBUG_OUTPUT));
+    gl_call!(gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS));
+    gl_call!(gl::DebugMessageCallback(Some(debug_message_callback), std::ptr::null()));
+    gl_call!(gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE, 0, std::ptr::null(), gl::TRUE));
+    gl_call!(gl::Enable(gl::CULL_FACE));
+    gl_call!(gl::CullFace(gl::BACK));
+    gl_call!(gl::Enable(gl::DEPTH_TEST));
+    gl_call!(gl::Enable(gl::BLEND));
+}
@@ -0,0 +1,268 @@
+use noise::{NoiseFn, Point2, Point3, SuperSimplex};
+
+use crate::biome::Climate;
+use crate::chunk::{BlockID, ChunkColumn};
+
+/// Height of the trunk of the trees
+const TREE_HEIGHT: i32 = 5;
+/// How far the leaves of a tree reach from its trunk
+const TREE_RADIUS: i32 = 2;
+
+/// Whether the terrain has stone at the block (x, y, z), before the caves are carved
+fn is_stone(noise_fn: &SuperSimplex, x: i32, y: i32, z: i32) -> bool {
+    let scale = 90.0;
+
+    // Scale the input for the noise function
+    let (xf, yf, zf) = (
+        x as f64 / scale,
+        y as f64 / (scale / 1.0),
+        z as f64 / scale);
+
+    let height = y as f64;
+    let noise = noise_fn.get(Point3::from([xf, yf, zf])) * 80.0
+        + 64.0 + height * 1.7;
+
+    noise < 256.0
+}
+
+/// The height of the grass at the block (x, z), the highest stone block of the terrain
+fn surface_height(noise_fn: &SuperSimplex, x: i32, z: i32) -> i32 {
+    (0..256).rev()
+        .find(|&y| is_stone(noise_fn, x, y, z))
+        .unwrap_or(0)
+}
+
+/// Fills a freshly reset column with stone, grass, dirt and bedrock
+pub fn generate_terrain(column: &ChunkColumn, x: i32, z: i32, noise_fn: &SuperSimplex) {
+    // Stone
+    for y in (0..16).rev() {
+        let y = 16 * y;
+        for b_y in 0..16 {
+            for b_x in 0..16 {
+                for b_z in 0..16 {
+                    if is_stone(noise_fn, 16 * x + b_x as i32, y + b_y as i32, 16 * z + b_z as i32) {
+                        column.set_block(BlockID::Stone, b_x, y as u32 + b_y, b_z);
+                    }
+                };
+            }
+        }
+    }
+
+    // Grass and dirt
+    for b_x in 0..16 {
+        for b_z in 0..16 {
+            let y = column.heighest_blocks.read()[16 * b_z + b_x] as i32;
+
+            let chunk_y = (y / 16) as i32;
+            let block_y = (y % 16) as usize;
+            column.get_chunk(chunk_y).set_block(BlockID::GrassBlock, b_x as u32, block_y as u32, b_z as u32);
+
+            for y in (y - 3)..y {
+                let chunk_y = (y / 16) as i32;
+                let block_y = (y % 16) as usize;
+
+                let chunk = column.get_chunk(chunk_y);
+                if chunk.get_block(b_x as u32, block_y as u32, b_z as u32).is_air() {
+                    continue;
+                }
+                chunk.set_block(BlockID::Dirt, b_x as u32, block_y as u32, b_z as u32);
+            }
+        }
+    }
+
+    // Tall grass and flowers, above the heightmap so that the trees still grow from the grass
+    for b_x in 0..16 {
+        for b_z in 0..16 {
+            let y = column.heighest_blocks.read()[16 * b_z + b_x] as i32 + 1;
+            if y >= 256 {
+                continue;
+            }
+
+            let (xf, zf) = (
+                (16 * x + b_x as i32) as f64 * 0.9 + 5000.0,
+                (16 * z + b_z as i32) as f64 * 0.9 + 5000.0);
+            let noise = noise_fn.get(Point2::from([xf, zf]));
+            let plant = if noise > 0.55 {
+                BlockID::TallGrass
+            } else if noise < -0.7 {
+                BlockID::Dandelion
+            } else {
+                continue;
+            };
+            column.get_chunk(y / 16).set_block(plant, b_x as u32, (y % 16) as u32, b_z as u32);
+        }
+    }
+
+    // Bedrock
+    let chunk = column.get_chunk(0);
+    for b_x in 0..16 {
+        for b_z in 0..16 {
+            chunk.set_block(BlockID::Bedrock, b_x, 0, b_z);
+            chunk.set_block(BlockID::Bedrock, b_x, 1, b_z);
+            chunk.set_block(BlockID::Bedrock, b_x, 2, b_z);
+        }
+    }
+}
+
+/// Sets the climate of the columns of blocks from two noise fields, far larger than the terrain
+/// so that the biomes are hundreds of blocks wide
+pub fn generate_climate(column: &ChunkColumn, x: i32, z: i32, noise_fn: &SuperSimplex) {
+    let scale = 400.0;
+    let mut climate = column.climate.write();
+    for b_x in 0..16 {
+        for b_z in 0..16 {
+            let (xf, zf) = (
+                (16 * x + b_x as i32) as f64 / scale,
+                (16 * z + b_z as i32) as f64 / scale);
+            let temperature = 0.5 + 0.6 * noise_fn.get(Point2::from([xf - 20000.0, zf]));
+            let humidity = 0.5 + 0.6 * noise_fn.get(Point2::from([xf + 20000.0, zf]));
+            climate[16 * b_z + b_x] = Climate::new(temperature as f32, humidity as f32);
+        }
+    }
+}
+
+/// Digs "spaghetti" caves where two independent noise fields are both close to zero.
+/// Only touches blocks of this column and stays below the surface layer, under the grass the trees grow from
+pub fn carve_caves(column: &ChunkColumn, x: i32, z: i32, cave_noise_fn: &SuperSimplex) {
+    let scale = 24.0;
+    let threshold = 0.06;
+
+    for b_x in 0..16 {
+        for b_z in 0..16 {
+            let surface = column.heighest_blocks.read()[16 * b_z + b_x] as i32;
+            let (xf, zf) = (
+                (16 * x + b_x as i32) as f64 / scale,
+                (16 * z + b_z as i32) as f64 / scale);
+
+            for y in 3..surface - 4 {
+                let yf = y as f64 / (scale * 0.75);
+                let first = cave_noise_fn.get(Point3::from([xf, yf, zf]));
+                if first.abs() >= threshold {
+                    continue;
+                }
+                let second = cave_noise_fn.get(Point3::from([xf + 1000.0, yf, zf + 1000.0]));
+                if second.abs() >= threshold {
+                    continue;
+                }
+                column.get_chunk(y / 16).set_block(BlockID::Air, b_x as u32, (y % 16) as u32, b_z as u32);
+            }
+        }
+    }
+}
+
+pub fn compute_tree_placement_in_chunk(noise: &SuperSimplex, x: f64, z: f64) -> Vec<(u32, u32)> {
+    let mut maximums = Vec::new();
+
+    #[inline]
+    fn index(i: i32, j: i32) -> usize {
+        (18 * i + j) as usize
+    }
+
+    let mut samples: [f64; 18 * 18] = [0.0; 18 * 18];
+    for i in -1..=16 {
+        for j in -1..=16 {
+            let x = x + j as f64 * 0.075;
+            let z = z + i as f64 * 0.075;
+            samples[index(i + 1, j + 1)] = noise.get(Point2::from([x, z]))
+        }
+    }
+
+    for i in 1..17 {
+        for j in 1..17 {
+            let center = samples[index(i, j)];
+            let is_max = (|| {
+                for ni in i - 1..=i + 1 {
+                    for nj in j - 1..=j + 1 {
+                        if ni == i && nj == j {
+                            continue;
+                        }
+                        if samples[index(ni, nj)] >= center {
+                            return false;
+                        }
+                    }
+                }
+                return true;
+            })();
+            if is_max {
+                maximums.push(((j - 1) as u32, (i - 1) as u32));
+            }
+        }
+    }
+    maximums
+}
+
+/// Places the parts of the trees that reach into column (cx, cz): the ones rooted in it and the leaves
+/// of the ones rooted in its neighbours. The trees only depend on the seed, so a column is decorated
+/// the same way whatever the stage of its neighbours, even when it's generated again next to them
+pub fn place_trees(column: &ChunkColumn, cx: i32, cz: i32, noise_fn: &SuperSimplex) {
+    let (min_x, min_z) = (16 * cx, 16 * cz);
+    let reaches_column = |x: i32, z: i32| {
+        (min_x - TREE_RADIUS..min_x + 16 + TREE_RADIUS).contains(&x)
+            && (min_z - TREE_RADIUS..min_z + 16 + TREE_RADIUS).contains(&z)
+    };
+    let set_block = |block: BlockID, x: i32, y: i32, z: i32| {
+        if (min_x..min_x + 16).contains(&x) && (min_z..min_z + 16).contains(&z) && (0..256).contains(&y) {
+            column.set_block(block, (x - min_x) as u32, y as u32, (z - min_z) as u32);
+        }
+    };
+
+    for n_x in cx - 1..=cx + 1 {
+        for n_z in cz - 1..=cz + 1 {
+            for (x, z) in compute_tree_placement_in_chunk(noise_fn, (n_x * 16) as f64, (n_z * 16) as f64) {
+                let x = n_x * 16 + x as i32;
+                let z = n_z * 16 + z as i32;
+                if !reaches_column(x, z) {
+                    continue;
+                }
+                let y = surface_height(noise_fn, x, z);
+                let h = TREE_HEIGHT;
+
+                for i in y + 1..y + 1 + h {
+                    set_block(BlockID::OakLog, x, i, z);
+                }
+
+                for yy in y + h - 2..=y + h - 1 {
+                    for xx in x - TREE_RADIUS..=x + TREE_RADIUS {
+                        for zz in z - TREE_RADIUS..=z + TREE_RADIUS {
+                            if xx != x || zz != z {
+                                set_block(BlockID::OakLeaves, xx, yy, zz);
+                            }
+                        }
+                    }
+                }
+
+                for xx in x - 1..=x + 1 {
+                    for zz in z - 1..=z + 1 {
+                        if xx != x || zz != z {
+                            set_block(BlockID::OakLeaves, xx, y + h, zz);
+                        }
+                    }
+                }
+
+                set_block(BlockID::OakLeaves, x, y + h + 1, z);
+                set_block(BlockID::OakLeaves, x + 1, y + h + 1, z);
+                set_block(BlockID::OakLeaves, x - 1, y + h + 1, z);
+                set_block(BlockID::OakLeaves, x, y + h + 1, z + 1);
+                set_block(BlockID::OakLeaves, x, y + h + 1, z - 1);
+            }
+        }
+    }
+}
+
+/// Recomputes the sunlight heightmap of a column (the highest non-air block of every (x, z))
+/// once the blocks of the column, including the foliage of its neighbours, are final
+pub fn compute_sunlight_heightmap(column: &ChunkColumn) {
+    let mut heighest_blocks = column.heighest_blocks.write();
+    for b_x in 0..16 {
+        for b_z in 0..16 {
+            let mut height = 0;
+            for y in (0..256).rev() {
+                if !column.get_chunk(y / 16).get_block(b_x, (y % 16) as u32, b_z).is_air() {
+                    height = y as u8;
+                    break;
+                }
+            }
+            heighest_blocks[(16 * b_z + b_x) as usize] = height;
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use nalgebra_glm::Vec3;
+
+use crate::block_state::BlockState;
+use crate::chunk::{BlockID, COLUMN_VOLUME};
+use crate::constants::WORLD_SEED;
+use crate::network::protocol::{ProtocolError, Reader, Writer};
+
+/// Bumped on every incompatible change to the files below
+const STORAGE_VERSION: u16 = 1;
+const LEVEL_MAGIC: &[u8; 4] = b"MKWD";
+
+/// What the server remembers about a player between two sessions
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerData {
+    pub position: Vec3,
+    pub selected_hotbar_slot: u8,
+    pub slots: Vec<Option<BlockID>>,
+    /// Granted by the `fly` command of the server
+    pub can_fly: bool,
+}
+
+impl PlayerData {
+    /// The content of the player files, also saved in the replays
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut w = Writer(&mut bytes);
+        w.vec3(&self.position).u8(self.selected_hotbar_slot).u8(self.slots.len() as u8);
+        for &slot in &self.slots {
+            w.optional_block(slot);
+        }
+        w.u8(self.can_fly as u8);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let mut r = Reader(bytes);
+        let position = r.vec3()?;
+        let selected_hotbar_slot = r.u8()?;
+        let n_slots = r.u8()?;
+        let mut slots = Vec::with_capacity(n_slots as usize);
+        for _ in 0..n_slots {
+            slots.push(r.optional_block()?);
+        }
+        // The players saved before flying was a permission end here
+        let can_fly = !r.0.is_empty() && r.u8()? != 0;
+        Ok(Self {
+            position,
+            selected_hotbar_slot,
+            slots,
+            can_fly,
+        })
+    }
+}
+
+/// A world saved on disk:
+/// * `level.dat`: the seed
+/// * `columns/<x>.<z>.col`: the run-length encoded blocks of the columns modified by the players
+///   followed by their states, the others are generated again from the seed
+/// * `players/<name>.dat`: the position and the inventory of the players
+pub struct WorldStorage {
+    directory: PathBuf,
+    seed: u32,
+}
+
+impl WorldStorage {
+    /// Opens the world in `directory`, or creates a new one with a random seed
+    pub fn open<P: AsRef<Path>>(directory: P) -> io::Result<Self> {
+        Self::open_or_create(directory.as_ref(), || *WORLD_SEED)
+    }
+
+    /// Opens the world in `directory`, or creates a new one with `seed`
+    pub fn open_with_seed<P: AsRef<Path>>(directory: P, seed: u32) -> io::Result<Self> {
+        Self::open_or_create(directory.as_ref(), || seed)
+    }
+
+    fn open_or_create(directory: &Path, new_seed: impl FnOnce() -> u32) -> io::Result<Self> {
+        let directory = directory.to_path_buf();
+        fs::create_dir_all(directory.join("columns"))?;
+        fs::create_dir_all(directory.join("players"))?;
+
+        let level_path = directory.join("level.dat");
+        let seed = if level_path.exists() {
+            let bytes = fs::read(&level_path)?;
+            let mut r = Reader(&bytes);
+            if r.bytes(4).map_err(invalid_data)? != LEVEL_MAGIC {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a world file"));
+            }
+            let version = r.u16().map_err(invalid_data)?;
+            if version != STORAGE_VERSION {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("Unsupported world version {}", version)));
+            }
+            r.u32().map_err(invalid_data)?
+        } else {
+            let seed = new_seed();
+            let mut bytes = LEVEL_MAGIC.to_vec();
+            Writer(&mut bytes).u16(STORAGE_VERSION).u32(seed);
+            write_atomically(&level_path, &bytes)?;
+            info!("Created a new world in {}", directory.display());
+            seed
+        };
+
+        Ok(Self {
+            directory,
+            seed,
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    fn column_path(&self, (x, z): (i32, i32)) -> PathBuf {
+        self.directory.join("columns").join(format!("{}.{}.col", x, z))
+    }
+
+    fn player_path(&self, name: &str) -> PathBuf {
+        self.directory.join("players").join(format!("{}.dat", name))
+    }
+
+    /// The saved blocks of a column and their states, None if it was never modified
+    pub fn load_column(&self, xz: (i32, i32)) -> io::Result<Option<(Vec<BlockID>, Vec<BlockState>)>> {
+        let bytes = match fs::read(self.column_path(xz)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut r = Reader(&bytes);
+        let blocks = r.blocks().map_err(invalid_data)?;
+        // The columns saved before the blocks had states end here
+        let states = if r.0.is_empty() {
+            vec![BlockState::default(); COLUMN_VOLUME]
+        } else {
+            r.block_states().map_err(invalid_data)?
+        };
+        Ok(Some((blocks, states)))
+    }
+
+    /// The coordinates of the columns modified by the players
+    pub fn saved_columns(&self) -> io::Result<Vec<(i32, i32)>> {
+        let mut columns = Vec::new();
+        for entry in fs::read_dir(self.directory.join("columns"))? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("col") {
+                continue;
+            }
+            let name = path.file_stem().and_then(|name| name.to_str()).unwrap_or_default();
+            let mut coordinates = name.split('.').map(|coordinate| coordinate.parse::<i32>());
+            if let (Some(Ok(x)), Some(Ok(z)), None) = (coordinates.next(), coordinates.next(), coordinates.next()) {
+                columns.push((x, z));
+            }
+        }
+        Ok(columns)
+    }
+
+    pub fn save_column(&self, xz: (i32, i32), blocks: &[BlockID], states: &[BlockState]) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        Writer(&mut bytes).blocks(blocks).block_states(states);
+        write_atomically(&self.column_path(xz), &bytes)
+    }
+
+    /// The data of a player, None if they never played in this world.
+    /// `name` must have been checked with `is_valid_player_name`
+    pub fn load_player(&self, name: &str) -> io::Result<Option<PlayerData>> {
+        let bytes = match fs::read(self.player_path(name)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        PlayerData::decode(&bytes).map(Some).map_err(invalid_data)
+    }
+
+    pub fn save_player(&self, name: &str, player: &PlayerData) -> io::Result<()> {
+        write_atomically(&self.player_path(name), &player.encode())
+    }
+}
+
+/// Player names are used as file names, so they are restricted to 1 to 16 letters, digits and underscores
+pub fn is_valid_player_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 16
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn invalid_data(err: ProtocolError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Writes to a temporary file first so that a crash never leaves a half written file behind
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)
+}
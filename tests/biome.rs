@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use meinkraft::biome::{blended_tints, Climate, ColorMap, Tint, TINT_BLEND_RADIUS};
+use meinkraft::block_model::TOP;
+use meinkraft::chunk::{BlockID, ChunkColumn};
+use meinkraft::chunk_manager::ChunkManager;
+
+use common::{block_models, run_on_big_stack};
+
+mod common;
+
+/// A 2x2 colour map: hot and wet is red, hot and dry green, cold blue
+fn corners_color_map() -> ColorMap {
+    ColorMap::from_rgba(2, 2, &[
+        255, 0, 0, 255, 0, 0, 0, 255,
+        0, 255, 0, 255, 0, 0, 255, 255,
+    ])
+}
+
+#[test]
+fn the_climate_picks_the_corners_of_the_color_map() {
+    let color_map = corners_color_map();
+    assert_eq!(color_map.color(Climate::new(1.0, 1.0)), [1.0, 0.0, 0.0]);
+    assert_eq!(color_map.color(Climate::new(1.0, 0.0)), [0.0, 1.0, 0.0]);
+    // Cold places are dry, whatever their humidity
+    assert_eq!(color_map.color(Climate::new(0.0, 1.0)), [0.0, 0.0, 1.0]);
+    assert_eq!(Climate::new(2.0, -1.0), Climate::new(1.0, 0.0));
+}
+
+#[test]
+fn tints_blend_between_neighbouring_columns() {
+    run_on_big_stack(|| {
+        let chunk_manager = ChunkManager::new(block_models());
+        let hot = Arc::new(ChunkColumn::new());
+        hot.set_climate(&[Climate::new(1.0, 1.0); 256]);
+        let cold = Arc::new(ChunkColumn::new());
+        cold.set_climate(&[Climate::new(0.0, 0.0); 256]);
+        chunk_manager.add_chunk_column((0, 0), hot);
+        chunk_manager.add_chunk_column((1, 0), cold);
+
+        let tints = blended_tints(&chunk_manager, &corners_color_map(), 0, 0);
+        // Far from the cold column, and on the border where half of the columns around are cold
+        assert_eq!(tints[16 * 8], [1.0, 0.0, 0.0]);
+        let border = tints[16 * 8 + 15];
+        let cold_share = TINT_BLEND_RADIUS as f32 / (2 * TINT_BLEND_RADIUS + 1) as f32;
+        assert!((border[2] - cold_share).abs() < 1e-5, "{:?}", border);
+        assert!((border[0] - (1.0 - cold_share)).abs() < 1e-5, "{:?}", border);
+        // The tint fades towards the border
+        assert!(tints[16 * 8 + 14][2] < border[2]);
+    });
+}
+
+#[test]
+fn only_the_faces_with_a_tint_index_are_tinted() {
+    let models = block_models();
+    let tinted = |block: BlockID| models.default_shape(block).model.quads.iter()
+        .map(|quad| (quad.cull_face, quad.tinted))
+        .collect::<Vec<_>>();
+
+    assert_eq!(BlockID::GrassBlock.tint(), Some(Tint::Grass));
+    for (cull_face, tinted) in tinted(BlockID::GrassBlock) {
+        assert_eq!(tinted, cull_face == Some(TOP));
+    }
+    assert_eq!(BlockID::OakLeaves.tint(), Some(Tint::Foliage));
+    assert!(tinted(BlockID::OakLeaves).iter().all(|&(_, tinted)| tinted));
+    assert!(tinted(BlockID::Stone).iter().all(|&(_, tinted)| !tinted));
+}
@@ -0,0 +1,282 @@
+use std::sync::Arc;
+use std::thread;
+
+use nalgebra_glm::{IVec3, vec3};
+
+use meinkraft::block_model::{BACK, BOTTOM, FRONT, LEFT, RIGHT, TOP};
+use meinkraft::block_model::json::ModelLoader;
+use meinkraft::block_state::{Axis, BlockState, Facing, Half};
+use meinkraft::chunk::{BlockID, ChunkColumn};
+use meinkraft::chunk_manager::ChunkManager;
+use meinkraft::raycast::raycast;
+
+use common::{block_models, TempDir};
+
+mod common;
+
+/// A single column with the given blocks, built on a big stack like the chunk columns need
+fn world_with(blocks: &[(BlockID, i32, i32, i32)]) -> ChunkManager {
+    world_with_states(blocks.iter().map(|&(block, x, y, z)| (block, BlockState::default(), x, y, z)).collect())
+}
+
+fn world_with_states(blocks: Vec<(BlockID, BlockState, i32, i32, i32)>) -> ChunkManager {
+    thread::Builder::new()
+        .stack_size(16 * 1024 * 1024)
+        .spawn(move || {
+            let chunk_manager = ChunkManager::new(block_models());
+            chunk_manager.add_chunk_column((0, 0), Arc::new(ChunkColumn::new()));
+            for (block, state, x, y, z) in blocks {
+                assert!(chunk_manager.set_block_with_state(block, state, x, y, z));
+            }
+            chunk_manager
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+fn raycast_in(chunk_manager: &ChunkManager, origin: (f32, f32, f32), direction: (f32, f32, f32)) -> Option<((i32, i32, i32), IVec3)> {
+    let selection_boxes_at = |x: i32, y: i32, z: i32| {
+        chunk_manager.get_block(x, y, z)
+            .map_or(&[][..], |block| chunk_manager.block_models().default_shape(block).selection_boxes.as_slice())
+    };
+    let direction = vec3(direction.0, direction.1, direction.2).normalize();
+    raycast(&selection_boxes_at, &vec3(origin.0, origin.1, origin.2), &direction, 10.0)
+}
+
+#[test]
+fn partial_blocks_only_hide_the_faces_they_cover() {
+    let models = block_models();
+    let slab = &models.default_shape(BlockID::OakSlab).model;
+    assert!(slab.covers_face(BOTTOM));
+    assert!(!slab.covers_face(TOP));
+    assert!(!slab.covers_face(RIGHT));
+
+    // The bottom and the back of the stairs are each covered by two boxes
+    let stairs = &models.default_shape(BlockID::OakStairs).model;
+    assert!(stairs.covers_face(BOTTOM));
+    assert!(stairs.covers_face(BACK));
+    assert!(!stairs.covers_face(FRONT));
+    assert!(!stairs.covers_face(TOP));
+
+    assert!(!models.default_shape(BlockID::OakFence).model.covers_face(BOTTOM));
+    assert!(!models.default_shape(BlockID::TallGrass).model.covers_face(BOTTOM));
+    assert!(models.default_shape(BlockID::Stone).model.covers_face(LEFT));
+}
+
+#[test]
+fn faces_next_to_partial_blocks_are_drawn() {
+    let chunk_manager = world_with(&[
+        (BlockID::Stone, 5, 5, 5),
+        (BlockID::OakSlab, 5, 6, 5),
+        (BlockID::OakSlab, 6, 5, 5),
+        (BlockID::Stone, 4, 5, 5),
+        (BlockID::TallGrass, 5, 5, 6),
+    ]);
+
+    let [right, left, top, _, front, _] = chunk_manager.get_active_faces_of_block(5, 5, 5);
+    // Next to the side of a slab and next to a plant
+    assert!(right);
+    assert!(front);
+    // Next to a full block and under the bottom of a slab
+    assert!(!left);
+    assert!(!top);
+
+    // The bottom of the slab sits on the stone, its top is open
+    let [_, _, top, bottom, _, _] = chunk_manager.get_active_faces_of_block(5, 6, 5);
+    assert!(!bottom);
+    assert!(top);
+}
+
+#[test]
+fn raycast_hits_the_shape_of_the_blocks() {
+    let chunk_manager = world_with(&[
+        (BlockID::OakSlab, 5, 5, 5),
+        (BlockID::Stone, 8, 5, 5),
+        (BlockID::TallGrass, 5, 5, 8),
+    ]);
+
+    // Down onto the top of the slab
+    assert_eq!(raycast_in(&chunk_manager, (5.5, 8.0, 5.5), (0.0, -1.0, 0.0)), Some(((5, 5, 5), IVec3::new(0, 1, 0))));
+
+    // Above the slab, through the empty half of its block
+    assert_eq!(raycast_in(&chunk_manager, (3.5, 5.75, 5.5), (1.0, 0.0, 0.0)), Some(((8, 5, 5), IVec3::new(-1, 0, 0))));
+    // Into the side of the slab
+    assert_eq!(raycast_in(&chunk_manager, (3.5, 5.25, 5.5), (1.0, 0.0, 0.0)), Some(((5, 5, 5), IVec3::new(-1, 0, 0))));
+
+    // Plants are targeted, through the selection box smaller than the block
+    assert_eq!(raycast_in(&chunk_manager, (5.5, 5.5, 6.5), (0.0, 0.0, 1.0)), Some(((5, 5, 8), IVec3::new(0, 0, -1))));
+    assert_eq!(raycast_in(&chunk_manager, (5.5, 5.9, 6.5), (0.0, 0.0, 1.0)), None);
+}
+
+#[test]
+fn blockstates_rotate_the_models_they_inherit() {
+    let temp_dir = TempDir::new("models");
+    let root = temp_dir.path();
+    // The north half of a block, textured by its child
+    temp_dir.write_file("models/block/half.json", r##"{
+        "elements": [{
+            "from": [0, 0, 0], "to": [16, 16, 8],
+            "faces": {
+                "north": { "texture": "#side", "cullface": "north" },
+                "south": { "texture": "#side" },
+                "up": { "texture": "#side", "uv": [0, 0, 16, 8], "cullface": "up" }
+            }
+        }]
+    }"##);
+    temp_dir.write_file("models/block/stone_half.json", r#"{
+        "parent": "block/half",
+        "textures": { "side": "blocks/stone" }
+    }"#);
+    temp_dir.write_file("blockstates/stone_half.json", r#"{
+        "variants": {
+            "": { "model": "block/stone_half" },
+            "facing=east": { "model": "block/stone_half", "y": 90 }
+        }
+    }"#);
+
+    let mut loader = ModelLoader::new(root);
+    let variants = loader.load_blockstate("stone_half").unwrap();
+    assert_eq!(loader.into_textures(), vec!["blocks/stone".to_string()]);
+
+    let north = &variants[""];
+    assert!(north.model.covers_face(BACK));
+    assert!(!north.model.covers_face(TOP));
+    assert_eq!(north.model.quads.len(), 3);
+
+    // Turned clockwise, the half fills the east of the block
+    let east = &variants["facing=east"];
+    assert!(east.model.covers_face(RIGHT));
+    assert!(!east.model.covers_face(BACK));
+    let b = east.collision_boxes[0];
+    assert_eq!((b.mins.x, b.maxs.x, b.mins.z, b.maxs.z), (0.5, 1.0, 0.0, 1.0));
+    let top = east.model.quads.iter().find(|quad| quad.cull_face == Some(TOP)).unwrap();
+    assert_eq!(top.uvs[0], (0.0, 0.5));
+}
+
+#[test]
+fn broken_models_are_reported() {
+    let temp_dir = TempDir::new("broken-models");
+    let root = temp_dir.path();
+    temp_dir.write_file("models/block/loop.json", r#"{ "parent": "block/loop" }"#);
+    temp_dir.write_file("blockstates/loop.json", r#"{ "variants": { "": { "model": "block/loop" } } }"#);
+    temp_dir.write_file("blockstates/missing.json", r#"{ "variants": { "": { "model": "block/missing" } } }"#);
+
+    let mut loader = ModelLoader::new(root);
+    let looping = loader.load_blockstate("loop").err().unwrap();
+    let missing = loader.load_blockstate("missing").err().unwrap();
+    assert!(looping.contains("too many parents"), "{}", looping);
+    assert!(missing.contains("models/block/missing.json"), "{}", missing);
+}
+
+#[test]
+fn plants_are_two_unshaded_diagonals() {
+    let models = block_models();
+    let model = &models.default_shape(BlockID::TallGrass).model;
+    // Both sides of both diagonals
+    assert_eq!(model.quads.len(), 4);
+    for quad in &model.quads {
+        assert_eq!(quad.cull_face, None);
+        assert!(quad.ao.is_none());
+        assert_eq!(quad.normal, vec3(0.0, 1.0, 0.0));
+        // From a corner of the block to the opposite one
+        let diagonal = quad.positions[1] - quad.positions[0];
+        assert!((diagonal.x.abs() - 0.9).abs() < 1e-4 && (diagonal.z.abs() - 0.9).abs() < 1e-4, "{:?}", diagonal);
+    }
+}
+
+#[test]
+fn states_pick_the_rotated_variants() {
+    let models = block_models();
+    assert_eq!(BlockID::OakStairs.states().len(), 8);
+    assert_eq!(BlockID::Stone.states(), vec![BlockState::default()]);
+
+    let east = BlockState::default().with_facing(Facing::East);
+    assert_eq!(east.variant_key(BlockID::OakStairs.properties()), "facing=east,half=bottom");
+    let stairs = &models.shape(BlockID::OakStairs, east).model;
+    assert!(stairs.covers_face(RIGHT));
+    assert!(stairs.covers_face(BOTTOM));
+    assert!(!stairs.covers_face(BACK));
+
+    let upside_down = &models.shape(BlockID::OakStairs, BlockState::default().with_half(Half::Top)).model;
+    assert!(upside_down.covers_face(TOP));
+    assert!(upside_down.covers_face(BACK));
+    assert!(!upside_down.covers_face(BOTTOM));
+
+    // The ends of a log lying along x are on its sides
+    let layer_of = |state: BlockState, face: usize| {
+        models.shape(BlockID::OakLog, state).model.quads.iter().find(|quad| quad.cull_face == Some(face)).unwrap().layer
+    };
+    let (standing, lying) = (BlockState::default(), BlockState::default().with_axis(Axis::X));
+    assert_ne!(layer_of(standing, TOP), layer_of(standing, RIGHT));
+    assert_eq!(layer_of(lying, RIGHT), layer_of(standing, TOP));
+    assert_eq!(layer_of(lying, TOP), layer_of(standing, RIGHT));
+
+    // The states a block can't be in look like its default state
+    let stone = models.shape(BlockID::Stone, east);
+    assert_eq!(stone.model.quads.len(), models.default_shape(BlockID::Stone).model.quads.len());
+}
+
+#[test]
+fn placed_blocks_face_the_player_and_the_wall() {
+    let north = vec3(0.0, -0.5, -1.0);
+    let against_wall = IVec3::new(1, 0, 0);
+    let on_ground = IVec3::new(0, 1, 0);
+    let under_ceiling = IVec3::new(0, -1, 0);
+
+    assert_eq!(BlockID::OakLog.placement_state(&against_wall, &north).axis(), Axis::X);
+    assert_eq!(BlockID::OakLog.placement_state(&on_ground, &north).axis(), Axis::Y);
+    assert_eq!(BlockID::OakStairs.placement_state(&on_ground, &north).facing(), Facing::North);
+    assert_eq!(BlockID::OakStairs.placement_state(&on_ground, &vec3(0.8, 0.0, 0.6)).facing(), Facing::East);
+    assert_eq!(BlockID::OakSlab.placement_state(&under_ceiling, &north).half(), Half::Top);
+    assert_eq!(BlockID::Stone.placement_state(&against_wall, &north), BlockState::default());
+
+    // A slab in the top half of its block hides the face above it but not the one below
+    let top = BlockState::default().with_half(Half::Top);
+    let chunk_manager = world_with_states(vec![
+        (BlockID::Stone, BlockState::default(), 5, 5, 5),
+        (BlockID::OakSlab, top, 5, 6, 5),
+        (BlockID::Stone, BlockState::default(), 5, 7, 5),
+    ]);
+    assert_eq!(chunk_manager.get_block_with_state(5, 6, 5), Some((BlockID::OakSlab, top)));
+    let [_, _, top_of_stone, _, _, _] = chunk_manager.get_active_faces_of_block(5, 5, 5);
+    assert!(top_of_stone);
+    let [_, _, _, bottom_of_stone, _, _] = chunk_manager.get_active_faces_of_block(5, 7, 5);
+    assert!(!bottom_of_stone);
+}
+
+#[test]
+fn fences_connect_to_each_other_and_to_solid_blocks() {
+    let chunk_manager = world_with(&[
+        (BlockID::OakFence, 5, 5, 5),
+        (BlockID::OakFence, 6, 5, 5),
+        (BlockID::Stone, 5, 5, 4),
+        // Neither glass nor plants are solid enough
+        (BlockID::Glass, 4, 5, 5),
+        (BlockID::TallGrass, 5, 5, 6),
+    ]);
+    // The connections aren't stored, they follow the neighbours
+    assert_eq!(chunk_manager.get_block_with_state(5, 5, 5), Some((BlockID::OakFence, BlockState::default())));
+
+    let (_, state) = chunk_manager.get_block_with_connections(5, 5, 5).unwrap();
+    assert!(state.is_connected(Facing::East));
+    assert!(state.is_connected(Facing::North));
+    assert!(!state.is_connected(Facing::West));
+    assert!(!state.is_connected(Facing::South));
+    assert_eq!(state.variant_key(BlockID::OakFence.properties()), "east=true,north=true,south=false,west=false");
+    let (_, neighbour_state) = chunk_manager.get_block_with_connections(6, 5, 5).unwrap();
+    assert!(neighbour_state.is_connected(Facing::West));
+
+    // The arms reach the edges of the block towards the connections, and are walls as high as the post
+    let models = chunk_manager.block_models();
+    let collision_boxes = models.collision_boxes_at(BlockID::OakFence, state, 5, 5, 5).collect::<Vec<_>>();
+    assert!(collision_boxes.iter().any(|b| b.maxs.x == 6.0 && b.maxs.y == 6.5));
+    assert!(collision_boxes.iter().any(|b| b.mins.z == 5.0 && b.maxs.y == 6.5));
+    assert!(collision_boxes.iter().all(|b| b.mins.x > 5.0 && b.maxs.z < 6.0));
+    assert!(models.shape(BlockID::OakFence, state).model.quads.len()
+        > models.default_shape(BlockID::OakFence).model.quads.len());
+
+    // Breaking the wall takes the arm away
+    chunk_manager.set_block(BlockID::Air, 5, 5, 4);
+    assert!(!chunk_manager.get_block_with_connections(5, 5, 5).unwrap().1.is_connected(Facing::North));
+}
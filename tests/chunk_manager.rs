@@ -0,0 +1,146 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::unbounded;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use meinkraft::chunk::{BlockID, BlockIterator, Chunk, ChunkColumn};
+use meinkraft::chunk_manager::ChunkManager;
+
+use common::block_models;
+
+mod common;
+
+#[test]
+fn put_block_during_meshing_doesnt_deadlock_or_drift() {
+    let chunk_manager = Arc::new(ChunkManager::new(block_models()));
+    {
+        // Chunk columns are built on the stack before being boxed
+        let chunk_manager = Arc::clone(&chunk_manager);
+        thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(move || {
+            for x in 0..2 {
+                for z in 0..2 {
+                    chunk_manager.add_chunk_column((x, z), Arc::new(ChunkColumn::new()));
+                }
+            }
+        }).unwrap().join().unwrap();
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (done_tx, done_rx) = unbounded();
+    let mut handles = Vec::new();
+
+    // Players placing and breaking blocks
+    for seed in 0..4 {
+        let chunk_manager = Arc::clone(&chunk_manager);
+        let stop = Arc::clone(&stop);
+        let done_tx = done_tx.clone();
+        handles.push(thread::spawn(move || {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let blocks = [BlockID::Air, BlockID::Stone, BlockID::Glass, BlockID::OakLeaves];
+            while !stop.load(Ordering::Relaxed) {
+                let block = blocks[rng.gen_range(0, blocks.len())];
+                chunk_manager.put_block(block, rng.gen_range(0, 32), rng.gen_range(0, 48), rng.gen_range(0, 32));
+            }
+            done_tx.send(()).unwrap();
+        }));
+    }
+
+    // World generation meshing every chunk over and over
+    for _ in 0..2 {
+        let chunk_manager = Arc::clone(&chunk_manager);
+        let stop = Arc::clone(&stop);
+        let done_tx = done_tx.clone();
+        handles.push(thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                for x in 0..2 {
+                    for y in 0..3 {
+                        for z in 0..2 {
+                            chunk_manager.update_blocks(x, y, z, BlockIterator::new());
+                        }
+                    }
+                }
+            }
+            done_tx.send(()).unwrap();
+        }));
+    }
+
+    // Main thread consuming the changelist
+    {
+        let chunk_manager = Arc::clone(&chunk_manager);
+        let stop = Arc::clone(&stop);
+        let done_tx = done_tx.clone();
+        handles.push(thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                chunk_manager.take_block_changelist();
+            }
+            done_tx.send(()).unwrap();
+        }));
+    }
+
+    thread::sleep(Duration::from_secs(2));
+    stop.store(true, Ordering::Relaxed);
+    for _ in 0..handles.len() {
+        done_rx.recv_timeout(Duration::from_secs(10))
+            .expect("A thread didn't finish, the chunk locks are deadlocked");
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    for x in 0..2 {
+        for z in 0..2 {
+            let column = chunk_manager.get_column(x, z).unwrap();
+            for chunk in column.chunks.iter() {
+                let data = chunk.read();
+                let (mut opaque, mut transparent) = (0, 0);
+                for (b_x, b_y, b_z) in BlockIterator::new() {
+                    let block = data.get_block(b_x, b_y, b_z);
+                    if block.is_transparent_not_air() {
+                        transparent += 1;
+                    } else if block.is_opaque() {
+                        opaque += 1;
+                    }
+                }
+                assert_eq!(data.number_of_opaque_blocks, opaque);
+                assert_eq!(data.number_of_transparent_blocks, transparent);
+            }
+        }
+    }
+}
+
+#[test]
+fn blocks_set_before_the_upload_are_meshed_again() {
+    let chunk_manager = Arc::new(ChunkManager::new(block_models()));
+    {
+        let chunk_manager = Arc::clone(&chunk_manager);
+        thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(move || {
+            chunk_manager.add_chunk_column((0, 0), Arc::new(ChunkColumn::new()));
+        }).unwrap().join().unwrap();
+    }
+
+    // Meshed but not uploaded yet, like a chunk waiting in `ChunkUploads`
+    assert!(!chunk_manager.get_chunk(0, 4, 0).unwrap().is_uploaded_to_gpu());
+    assert!(chunk_manager.put_block(BlockID::Stone, 3, 70, 5));
+    assert!(chunk_manager.take_block_changelist().contains(&(1, BlockID::Stone, 3, 70, 5)));
+    assert!(chunk_manager.take_block_changelist().is_empty());
+}
+
+#[test]
+fn reset_chunks_forget_their_mesh() {
+    let chunk = Chunk::full_of_block(BlockID::Stone);
+    {
+        let mut data = chunk.write();
+        data.active_faces.set(42, true);
+        data.ao_vertices[7][2] = [1, 2, 3, 0];
+    }
+
+    chunk.reset();
+    let data = chunk.read();
+    assert!(chunk.is_empty());
+    assert!(data.active_faces.none());
+    assert_eq!(data.ao_vertices[7][2], [0; 4]);
+}
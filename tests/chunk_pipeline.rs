@@ -0,0 +1,122 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use meinkraft::chunk::BlockID;
+use meinkraft::chunk_manager::ChunkManager;
+use meinkraft::chunk_pipeline::{ChunkPipeline, ColumnStage};
+
+use common::block_models;
+
+mod common;
+
+const VIEW_DISTANCE: i32 = 1;
+
+/// The stage that must be reached by the neighbours of a column before `stage` runs on it
+fn previous_stage(stage: ColumnStage) -> ColumnStage {
+    match stage.index() {
+        0 => ColumnStage::Empty,
+        i => ColumnStage::STAGES[i - 1],
+    }
+}
+
+/// Every column that completed a stage had its `neighbour_radius` neighbours at the previous stage.
+/// Stages only go forward, so they must still be there
+fn assert_neighbours_were_ready(pipeline: &ChunkPipeline, loaded_distance: i32) {
+    for x in -loaded_distance..=loaded_distance {
+        for z in -loaded_distance..=loaded_distance {
+            let stage = pipeline.column_stage(x, z).unwrap();
+            if stage == ColumnStage::Empty {
+                continue;
+            }
+            let radius = stage.neighbour_radius();
+            for n_x in x - radius..=x + radius {
+                for n_z in z - radius..=z + radius {
+                    let neighbour_stage = pipeline.column_stage(n_x, n_z);
+                    assert!(neighbour_stage >= Some(previous_stage(stage)),
+                            "({}, {}) is {:?} but its neighbour ({}, {}) is {:?}", x, z, stage, n_x, n_z, neighbour_stage);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn stages_wait_for_their_neighbours_and_the_margin_is_never_meshed() {
+    // Chunk columns are built on the stack before being boxed
+    thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+        let chunk_manager = Arc::new(ChunkManager::new(block_models()));
+        let mut pipeline = ChunkPipeline::generated(VIEW_DISTANCE, ColumnStage::Meshed, 42);
+        let loaded_distance = pipeline.loaded_distance();
+        assert_eq!(loaded_distance, VIEW_DISTANCE + ColumnStage::loading_margin(ColumnStage::Meshed));
+
+        let is_view_meshed = |pipeline: &ChunkPipeline| {
+            (-VIEW_DISTANCE..=VIEW_DISTANCE)
+                .all(|x| (-VIEW_DISTANCE..=VIEW_DISTANCE).all(|z| pipeline.finished_column(x, z).is_some()))
+        };
+        let start = Instant::now();
+        loop {
+            pipeline.update(&[(0, 0)], &chunk_manager, &mut |_, _| {});
+            assert_neighbours_were_ready(&pipeline, loaded_distance);
+            let stats = pipeline.stats();
+            if is_view_meshed(&pipeline) && stats.running.iter().sum::<usize>() == 0 {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(120), "The pipeline is stuck: {:?}", stats);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // The margin is lit, only the meshing reads the neighbours
+        for x in -loaded_distance..=loaded_distance {
+            for z in -loaded_distance..=loaded_distance {
+                let ring = x.abs().max(z.abs());
+                let expected = if ring <= VIEW_DISTANCE {
+                    ColumnStage::Meshed
+                } else {
+                    ColumnStage::Lit
+                };
+                assert_eq!(pipeline.column_stage(x, z), Some(expected), "({}, {})", x, z);
+            }
+        }
+        assert_eq!(pipeline.column_stage(loaded_distance + 1, 0), None);
+    }).unwrap().join().unwrap();
+}
+
+/// Updates the pipeline around `center` until the columns within `VIEW_DISTANCE` of it are meshed
+fn mesh_view(pipeline: &mut ChunkPipeline, chunk_manager: &Arc<ChunkManager>, (c_x, c_z): (i32, i32)) {
+    let start = Instant::now();
+    loop {
+        pipeline.update(&[(c_x, c_z)], chunk_manager, &mut |_, _| {});
+        let is_view_meshed = (c_x - VIEW_DISTANCE..=c_x + VIEW_DISTANCE)
+            .all(|x| (c_z - VIEW_DISTANCE..=c_z + VIEW_DISTANCE).all(|z| pipeline.finished_column(x, z).is_some()));
+        if is_view_meshed && pipeline.stats().running.iter().sum::<usize>() == 0 {
+            return;
+        }
+        assert!(start.elapsed() < Duration::from_secs(120), "The pipeline is stuck: {:?}", pipeline.stats());
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn columns_generated_again_next_to_their_neighbours_get_the_same_trees() {
+    thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+        let chunk_manager = Arc::new(ChunkManager::new(block_models()));
+        let mut pipeline = ChunkPipeline::generated(VIEW_DISTANCE, ColumnStage::Meshed, 42);
+        mesh_view(&mut pipeline, &chunk_manager, (0, 0));
+        let columns = (-1..=1)
+            .map(|z| pipeline.finished_column(0, z).unwrap().blocks())
+            .collect::<Vec<_>>();
+        assert!(columns.iter().flatten().any(|&block| block == BlockID::OakLeaves), "No trees to compare");
+
+        // Drop the columns at x = 0 but keep their neighbours at x = 1
+        let far = (1 + pipeline.loaded_distance(), 0);
+        mesh_view(&mut pipeline, &chunk_manager, far);
+        assert_eq!(pipeline.column_stage(0, 0), None);
+        assert!(pipeline.column_stage(1, 0).is_some());
+
+        mesh_view(&mut pipeline, &chunk_manager, (0, 0));
+        for (z, blocks) in (-1..=1).zip(columns) {
+            assert!(pipeline.finished_column(0, z).unwrap().blocks() == blocks, "(0, {}) changed", z);
+        }
+    }).unwrap().join().unwrap();
+}
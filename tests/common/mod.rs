@@ -0,0 +1,58 @@
+//! Fixtures shared by the integration tests, included with `mod common;`
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use meinkraft::block_model::BlockModels;
+use meinkraft::constants::ASSET_DIRECTORY;
+
+/// The block models of the game, the tests run from the root of the repository
+pub fn block_models() -> Arc<BlockModels> {
+    Arc::new(BlockModels::load(ASSET_DIRECTORY).unwrap())
+}
+
+/// Runs `f` on a thread with a big stack, chunk columns are built on the stack before being boxed
+pub fn run_on_big_stack(f: impl FnOnce() + Send + 'static) {
+    thread::Builder::new()
+        .stack_size(16 * 1024 * 1024)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+/// A directory in the temp directory of the system, removed when dropped even if the test panics
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// `name` must be unique among the tests, the id of the process is appended to it
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("meinkraft-{}-{}", name, std::process::id()));
+        // Left over by a run that was killed
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a file relative to the directory, creating its parents
+    pub fn write_file<C: AsRef<[u8]>>(&self, path: &str, contents: C) {
+        let path = self.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,61 @@
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+use nalgebra_glm::vec3;
+
+use meinkraft::chunk::BlockID;
+use meinkraft::chunk_pipeline::ChunkPipelineStats;
+use meinkraft::debug_info::{facing, DebugInfo, FrameTimes, FRAME_TIME_HISTORY};
+
+fn debug_info() -> DebugInfo {
+    DebugInfo {
+        fps: 60,
+        average_frame_time: Duration::from_millis(16),
+        max_frame_time: Duration::from_millis(40),
+        position: vec3(-0.5, 70.25, 33.0),
+        // Looking up a bit, towards positive Z
+        rotation: vec3(0.3, FRAC_PI_2, 0.0),
+        targeted_block: Some(((-1, 69, 34), BlockID::GrassBlock)),
+        loaded_columns: 4,
+        uploaded_chunks: 60,
+        drawn_chunks: 10,
+        culled_chunks: 30,
+        pipeline: ChunkPipelineStats::default(),
+        particles: 7,
+    }
+}
+
+#[test]
+fn the_facing_is_the_closest_horizontal_direction() {
+    assert_eq!(facing(&vec3(1.0, 0.0, 0.2)).0, "east");
+    assert_eq!(facing(&vec3(-1.0, 0.9, 0.5)).0, "west");
+    assert_eq!(facing(&vec3(0.1, -1.0, 0.3)).0, "south");
+    assert_eq!(facing(&vec3(0.2, 0.0, -0.4)), ("north", "Towards negative Z"));
+}
+
+#[test]
+fn the_world_lines_locate_the_player() {
+    let lines = debug_info().world_lines();
+    assert_eq!(lines[0], "XYZ: -0.500 / 70.250 / 33.000");
+    // Negative coordinates are floored, not truncated
+    assert_eq!(lines[1], "Block: -1 70 33");
+    assert_eq!(lines[2], "Chunk: 15 6 1 in -1 4 2");
+    assert!(lines[3].starts_with("Facing: south (Towards positive Z) (90.0 / 17.2)"), "{}", lines[3]);
+    assert_eq!(lines[4], format!("Targeted block: -1 69 34: {} (id {})", BlockID::GrassBlock.name(), BlockID::GrassBlock as u8));
+    assert!(lines.contains(&"Chunks: 10 drawn, 30 culled, 20 empty, 4 not uploaded".to_string()), "{:?}", lines);
+    assert!(lines.contains(&"Particles: 7".to_string()), "{:?}", lines);
+}
+
+#[test]
+fn frame_times_keep_the_last_frames() {
+    let mut frame_times = FrameTimes::default();
+    assert_eq!(frame_times.average(), Duration::default());
+    for i in 0..FRAME_TIME_HISTORY + 10 {
+        frame_times.push(Duration::from_millis(i as u64));
+    }
+    assert_eq!(frame_times.times().count(), FRAME_TIME_HISTORY);
+    assert_eq!(frame_times.times().next(), Some(&Duration::from_millis(10)));
+    assert_eq!(frame_times.max(), Duration::from_millis(FRAME_TIME_HISTORY as u64 + 9));
+    let average = (10 + FRAME_TIME_HISTORY as u64 + 9) as f32 / 2.0;
+    assert!((frame_times.average().as_secs_f32() * 1000.0 - average).abs() < 1e-3);
+}
@@ -0,0 +1,39 @@
+use nalgebra_glm::{look_at, perspective, vec3, Vec3};
+
+use meinkraft::aabb::AABB;
+use meinkraft::frustum::Frustum;
+
+fn chunk_at(x: f32, y: f32, z: f32) -> AABB {
+    let mins = vec3(x, y, z);
+    AABB::new(mins, mins + vec3(16.0, 16.0, 16.0))
+}
+
+/// A camera at (8, 8, 8) looking towards +x, with a horizontal field of view of 90°
+fn frustum() -> Frustum {
+    let eye = vec3(8.0, 8.0, 8.0);
+    let view = look_at(&eye, &(eye + vec3(1.0, 0.0, 0.0)), &Vec3::y());
+    let projection = perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+    Frustum::from_matrix(&(projection * view))
+}
+
+#[test]
+fn chunks_in_front_of_the_camera_are_visible() {
+    let frustum = frustum();
+    // The chunk of the camera, the next one and one at the edge of the field of view
+    assert!(frustum.intersects_aabb(&chunk_at(0.0, 0.0, 0.0)));
+    assert!(frustum.intersects_aabb(&chunk_at(16.0, 0.0, 0.0)));
+    assert!(frustum.intersects_aabb(&chunk_at(32.0, 0.0, 16.0)));
+    assert!(frustum.intersects_aabb(&chunk_at(48.0, -32.0, 0.0)));
+}
+
+#[test]
+fn chunks_outside_of_the_view_are_culled() {
+    let frustum = frustum();
+    // Behind the camera
+    assert!(!frustum.intersects_aabb(&chunk_at(-32.0, 0.0, 0.0)));
+    // Beside it, out of the field of view
+    assert!(!frustum.intersects_aabb(&chunk_at(16.0, 0.0, 48.0)));
+    assert!(!frustum.intersects_aabb(&chunk_at(16.0, 48.0, 0.0)));
+    // Beyond the far plane
+    assert!(!frustum.intersects_aabb(&chunk_at(112.0, 0.0, 0.0)));
+}
@@ -0,0 +1,121 @@
+use nalgebra_glm::vec2;
+
+use meinkraft::input::{Action, InputCache, InputEvent, Key, MouseButton};
+use meinkraft::keybindings::{Binding, DoubleTap, InputAction, KeyBindings};
+
+#[test]
+fn parse_overrides_the_default_bindings() {
+    let key_bindings = KeyBindings::parse("\
+        # ZQSD layout
+        move_forward = Z
+        move_left = Q
+        attack = Mouse1, F   # both
+        hotbar_9 =
+    ").unwrap();
+
+    assert_eq!(key_bindings.bindings(InputAction::MoveForward), &[Binding::Key(Key::Z)]);
+    assert_eq!(key_bindings.bindings(InputAction::MoveLeft), &[Binding::Key(Key::Q)]);
+    assert_eq!(key_bindings.bindings(InputAction::MoveRight), &[Binding::Key(Key::D)]);
+    assert_eq!(key_bindings.bindings(InputAction::Attack),
+               &[Binding::MouseButton(MouseButton::Button1), Binding::Key(Key::F)]);
+    assert!(key_bindings.bindings(InputAction::HotbarSlot(8)).is_empty());
+    assert_eq!(key_bindings.actions(Binding::Key(Key::Z)).collect::<Vec<_>>(), vec![InputAction::MoveForward]);
+}
+
+#[test]
+fn parse_errors_name_the_line() {
+    assert_eq!(KeyBindings::parse("jump = Space\nfly = F").unwrap_err(), "line 2: unknown action fly");
+    assert_eq!(KeyBindings::parse("jump = Spacebar").unwrap_err(), "line 1: unknown key Spacebar");
+    assert_eq!(KeyBindings::parse("hotbar_10 = Num0").unwrap_err(), "line 1: unknown action hotbar_10");
+}
+
+#[test]
+fn double_tap() {
+    let interval = 15;
+    let mut double_tap = DoubleTap::new();
+
+    // The first press right after the start isn't a double tap
+    assert!(!double_tap.press(0, interval));
+    assert!(!double_tap.press(60, interval));
+    assert!(double_tap.press(72, interval));
+    // The third press starts over
+    assert!(!double_tap.press(78, interval));
+    assert!(!double_tap.press(120, interval));
+    assert!(double_tap.press(126, interval));
+}
+
+#[test]
+fn actions_follow_the_bindings() {
+    let mut key_bindings = KeyBindings::default();
+    key_bindings.bind(InputAction::Jump, vec![Binding::Key(Key::Space), Binding::MouseButton(MouseButton::Button4)]);
+    let mut input_cache = InputCache::new(key_bindings);
+
+    input_cache.handle_event(&InputEvent::MouseButton(MouseButton::Button4, Action::Press));
+    input_cache.handle_event(&InputEvent::Key(Key::Num3, Action::Press));
+    input_cache.handle_event(&InputEvent::CursorPos(1.0, 2.0));
+    assert!(input_cache.is_action_active(InputAction::Jump));
+    assert!(!input_cache.is_action_active(InputAction::MoveForward));
+    assert_eq!(input_cache.action_events().collect::<Vec<_>>(), vec![
+        (InputAction::Jump, Action::Press),
+        (InputAction::HotbarSlot(2), Action::Press),
+    ]);
+
+    input_cache.clear_events();
+    input_cache.handle_event(&InputEvent::MouseButton(MouseButton::Button4, Action::Release));
+    assert!(!input_cache.is_action_active(InputAction::Jump));
+    assert_eq!(input_cache.action_events().collect::<Vec<_>>(), vec![(InputAction::Jump, Action::Release)]);
+}
+
+#[test]
+fn the_actions_of_a_key_come_in_a_fixed_order() {
+    let key_bindings = KeyBindings::parse("\
+        quit = Q
+        jump = Q
+        hotbar_2 = Q
+        move_forward = Q
+    ").unwrap();
+
+    assert_eq!(key_bindings.actions(Binding::Key(Key::Q)).collect::<Vec<_>>(), vec![
+        InputAction::MoveForward,
+        InputAction::Jump,
+        InputAction::HotbarSlot(1),
+        InputAction::Quit,
+    ]);
+}
+
+#[test]
+fn the_cursor_movements_of_a_frame_add_up() {
+    let mut input_cache = InputCache::default();
+    input_cache.handle_event(&InputEvent::CursorPos(10.0, 5.0));
+    input_cache.clear_events();
+    input_cache.handle_event(&InputEvent::CursorPos(40.0, 5.0));
+    input_cache.handle_event(&InputEvent::CursorPos(100.0, -15.0));
+    assert_eq!(input_cache.cursor_rel_pos, vec2(90.0, -20.0));
+    input_cache.clear_events();
+    assert_eq!(input_cache.cursor_rel_pos, vec2(0.0, 0.0));
+}
+
+#[test]
+fn key_codes_and_names() {
+    assert_eq!(Key::from_code(Key::LeftShift as i32), Some(Key::LeftShift));
+    assert_eq!(Key::from_code(-1), None);
+    assert_eq!(Key::from_name("KpEnter"), Some(Key::KpEnter));
+    assert_eq!(Key::from_name("Unknown"), None);
+    assert_eq!(MouseButton::from_code(MouseButton::Button8 as i32), Some(MouseButton::Button8));
+    assert_eq!(MouseButton::from_code(8), None);
+}
+
+#[cfg(feature = "rendering")]
+#[test]
+fn only_input_window_events_are_converted() {
+    use glfw::{Modifiers, WindowEvent};
+
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::Key(glfw::Key::Escape, 0, glfw::Action::Press, Modifiers::Shift)),
+               Some(InputEvent::Key(Key::Escape, Action::Press)));
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::MouseButton(glfw::MouseButton::Button2, glfw::Action::Release, Modifiers::empty())),
+               Some(InputEvent::MouseButton(MouseButton::Button2, Action::Release)));
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::Scroll(0.0, 1.0)), Some(InputEvent::Scroll(0.0, 1.0)));
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::Key(glfw::Key::Unknown, 0, glfw::Action::Press, Modifiers::empty())), None);
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::FramebufferSize(800, 600)), None);
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::Focus(false)), None);
+}
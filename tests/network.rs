@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::unbounded;
+use nalgebra_glm::{vec3, Vec3};
+
+use meinkraft::block_state::BlockState;
+use meinkraft::chunk::BlockID;
+use meinkraft::ecs::systems::network::RemotePlayer;
+use meinkraft::network::client::ServerConnection;
+use meinkraft::network::protocol::{ClientMessage, ServerMessage};
+use meinkraft::network::commands::ServerCommand;
+use meinkraft::network::server::Server;
+use meinkraft::player::MovementInput;
+use meinkraft::world_storage::WorldStorage;
+
+use common::{block_models, TempDir};
+
+mod common;
+
+/// Polls `client` until `predicate` matches one of the received messages
+fn wait_for(client: &mut ServerConnection, predicate: &mut dyn FnMut(&ServerMessage) -> bool) -> ServerMessage {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(60) {
+        client.flush().unwrap();
+        for message in client.receive().unwrap() {
+            if predicate(&message) {
+                return message;
+            }
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    panic!("Timed out waiting for a message");
+}
+
+#[test]
+fn two_clients_on_localhost() {
+    // Chunk columns are built on the stack before being boxed
+    let (address_tx, address_rx) = unbounded();
+    let (commands_tx, commands_rx) = unbounded();
+    let stop = Arc::new(AtomicBool::new(false));
+    let temp_dir = TempDir::new("network");
+    let world_directory = temp_dir.path().to_path_buf();
+    let server_thread = {
+        let stop = Arc::clone(&stop);
+        let world_directory = world_directory.clone();
+        thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(move || {
+            let storage = WorldStorage::open(world_directory).unwrap();
+            let mut server = Server::bind(("127.0.0.1", 0), 1, storage, block_models()).unwrap();
+            address_tx.send(server.local_address()).unwrap();
+            while !stop.load(Ordering::Relaxed) {
+                for command in commands_rx.try_iter() {
+                    server.execute(&command);
+                }
+                server.tick();
+                thread::sleep(Duration::from_millis(5));
+            }
+            server.execute(&ServerCommand::Save);
+        }).unwrap()
+    };
+    let address = address_rx.recv().unwrap();
+
+    let timeout = Duration::from_secs(5);
+    let mut alice = ServerConnection::connect(address, "alice", timeout).unwrap();
+    let mut bob = ServerConnection::connect(address, "bob", timeout).unwrap();
+    assert_ne!(alice.player_id, bob.player_id);
+
+    // The columns around the players are generated and sent
+    let mut columns = HashSet::new();
+    wait_for(&mut alice, &mut |message| {
+        if let ServerMessage::ChunkColumn { x, z, blocks, climate, .. } = message {
+            assert_eq!(blocks[0], BlockID::Bedrock);
+            assert_eq!(climate.len(), 16 * 16);
+            columns.insert((*x, *z));
+        }
+        (-1..=1).all(|x| (-1..=1).all(|z| columns.contains(&(x, z))))
+    });
+
+    // A block placed by a client is broadcast to the others
+    let state = BlockState::default();
+    alice.send(&ClientMessage::SetBlock { x: 9, y: 196, z: 8, block: BlockID::Dirt, state });
+    alice.flush().unwrap();
+    wait_for(&mut bob, &mut |message| {
+        message == &ServerMessage::BlockChange { x: 9, y: 196, z: 8, block: BlockID::Dirt, state }
+    });
+
+    // Picking a block out of reach is refused and the inventory sent back, one in reach fills the selected slot
+    alice.send(&ClientMessage::SelectHotbarSlot { slot: 2 });
+    alice.send(&ClientMessage::PickBlock { x: 9, y: 0, z: 8 });
+    alice.flush().unwrap();
+    wait_for(&mut alice, &mut |message| matches!(message,
+        ServerMessage::Inventory { selected_hotbar_slot: 2, slots } if slots[2] == Some(BlockID::Cobblestone)));
+    alice.send(&ClientMessage::PickBlock { x: 9, y: 196, z: 8 });
+    alice.send(&ClientMessage::PickBlock { x: 9, y: 0, z: 8 });
+    alice.flush().unwrap();
+    wait_for(&mut alice, &mut |message| matches!(message,
+        ServerMessage::Inventory { selected_hotbar_slot: 2, slots } if slots[2] == Some(BlockID::Dirt)));
+
+    // The server simulates the movements from the inputs: a player that isn't allowed to fly falls
+    let sky = vec3(8.5, 250.0, 8.5);
+    let movement = |sequence, tick, ticks, last_correction, input, position| ClientMessage::PlayerMovement {
+        sequence,
+        tick,
+        ticks,
+        last_correction,
+        input,
+        position,
+        rotation: vec3(0.0, 0.0, 0.0),
+    };
+    let flying = MovementInput { is_flying: true, ..MovementInput::default() };
+    let correction = |message: &ServerMessage| match message {
+        ServerMessage::PlayerCorrection { correction, sequence, position, velocity } => Some((*correction, *sequence, *position, *velocity)),
+        _ => None,
+    };
+    commands_tx.send(ServerCommand::Teleport("alice".to_string(), sky)).unwrap();
+    let teleport = wait_for(&mut alice, &mut |message| correction(message).is_some());
+    assert_eq!(correction(&teleport), Some((1, 0, sky, Vec3::zeros())));
+
+    alice.send(&movement(1, alice.tick + 10, 10, 1, flying, sky));
+    alice.flush().unwrap();
+    let fall = wait_for(&mut alice, &mut |message| correction(message).is_some());
+    let (id, sequence, position, velocity) = correction(&fall).unwrap();
+    assert_eq!((id, sequence), (2, 1));
+    assert!(position.y < sky.y - 0.2 && velocity.y < 0.0, "{:?} {:?}", position, velocity);
+
+    // Once allowed, the player hovers
+    commands_tx.send(ServerCommand::Fly("alice".to_string(), true)).unwrap();
+    wait_for(&mut alice, &mut |message| message == &ServerMessage::FlyingAllowed { can_fly: true });
+    commands_tx.send(ServerCommand::Teleport("alice".to_string(), sky)).unwrap();
+    wait_for(&mut alice, &mut |message| correction(message).map_or(false, |(id, ..)| id == 3));
+    alice.send(&movement(2, alice.tick + 15, 5, 3, flying, sky));
+
+    // Impossible movements are corrected back to the simulated position
+    alice.send(&movement(3, alice.tick + 16, 1, 3, flying, vec3(1000.0, 195.0, 1000.0)));
+    alice.flush().unwrap();
+    let back = wait_for(&mut alice, &mut |message| correction(message).is_some());
+    assert_eq!(correction(&back), Some((4, 3, sky, Vec3::zeros())));
+
+    stop.store(true, Ordering::Relaxed);
+    server_thread.join().unwrap();
+
+    // The modified column and the players were saved
+    let storage = WorldStorage::open(&world_directory).unwrap();
+    let (blocks, _) = storage.load_column((0, 0)).unwrap().unwrap();
+    assert_eq!(blocks[256 * 196 + 16 * 8 + 9], BlockID::Dirt);
+    let player = storage.load_player("bob").unwrap().unwrap();
+    assert_eq!(player.position, bob.spawn_position);
+    assert!(!player.can_fly);
+    assert!(storage.load_player("alice").unwrap().unwrap().can_fly);
+}
+
+#[test]
+fn remote_players_move_smoothly_between_their_positions() {
+    let mut player = RemotePlayer::new(vec3(0.0, 64.0, 0.0), vec3(0.0, 0.0, 0.0), 100.0);
+    assert_eq!(player.interpolated(100.5), (vec3(0.0, 64.0, 0.0), vec3(0.0, 0.0, 0.0)));
+
+    // Positions two ticks apart are drawn over two ticks
+    player.update(vec3(1.0, 64.0, 0.0), vec3(0.0, 1.0, 0.0), 102.0);
+    assert_eq!(player.interpolated(102.0).0, vec3(0.0, 64.0, 0.0));
+    assert_eq!(player.interpolated(103.0), (vec3(0.5, 64.0, 0.0), vec3(0.0, 0.5, 0.0)));
+    assert_eq!(player.interpolated(110.0).0, vec3(1.0, 64.0, 0.0));
+
+    // A position arriving halfway starts from where the player is drawn, not from the last position
+    player.update(vec3(2.0, 64.0, 0.0), vec3(0.0, 1.0, 0.0), 103.0);
+    assert_eq!(player.interpolated(103.0).0, vec3(0.5, 64.0, 0.0));
+    assert_eq!(player.interpolated(104.0).0, vec3(2.0, 64.0, 0.0));
+
+    // After standing still, the player catches up with its new position in a few ticks
+    player.update(vec3(3.0, 64.0, 0.0), vec3(0.0, 1.0, 0.0), 1000.0);
+    assert_eq!(player.interpolated(1010.0).0, vec3(3.0, 64.0, 0.0));
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use nalgebra_glm::vec3;
+
+use meinkraft::block_state::BlockState;
+use meinkraft::chunk::{BlockID, COLUMN_VOLUME};
+use meinkraft::input::{Action, InputCache, InputEvent, Key, MouseButton};
+use meinkraft::replay::{Replay, ReplayFrame, ReplayPlayer, ReplayRecorder};
+use meinkraft::ticks::Ticks;
+use meinkraft::timer::Timer;
+use meinkraft::world_storage::{PlayerData, WorldStorage};
+
+use common::TempDir;
+
+mod common;
+
+fn player() -> PlayerData {
+    PlayerData {
+        position: vec3(-3.5, 80.0, 12.25),
+        selected_hotbar_slot: 4,
+        slots: vec![Some(BlockID::Dirt), None, Some(BlockID::OakSlab)],
+        can_fly: true,
+    }
+}
+
+fn column(block: BlockID) -> (Vec<BlockID>, Vec<BlockState>) {
+    let mut blocks = vec![BlockID::Air; COLUMN_VOLUME];
+    blocks[..256 * 64].fill(BlockID::Stone);
+    blocks[256 * 64 + 17] = block;
+    (blocks, vec![BlockState::default(); COLUMN_VOLUME])
+}
+
+#[test]
+fn encode_decode() {
+    let replay = Replay {
+        seed: 0xdead_beef,
+        player: Some(player()),
+        columns: vec![((-1, 7), column(BlockID::Dirt).0, column(BlockID::Dirt).1)],
+        frames: vec![
+            ReplayFrame {
+                time: Duration::from_micros(0),
+                tick: 0,
+                pending: 0,
+                events: vec![
+                    InputEvent::Key(Key::LeftShift, Action::Repeat),
+                    InputEvent::MouseButton(MouseButton::Button2, Action::Press),
+                ],
+            },
+            ReplayFrame {
+                time: Duration::from_micros(16_667),
+                tick: 1,
+                pending: 1,
+                events: vec![],
+            },
+            ReplayFrame {
+                time: Duration::from_secs(3600),
+                tick: 216_000,
+                pending: 3,
+                events: vec![
+                    InputEvent::CursorPos(-1.5, 1e10),
+                    InputEvent::Scroll(0.0, -1.0),
+                ],
+            },
+        ],
+    };
+    assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+}
+
+#[test]
+fn decode_rejects_other_files() {
+    assert!(Replay::decode(b"MKWD\0\x01\0\0\0\0").is_err());
+    let mut bytes = Replay { seed: 1, player: None, columns: vec![], frames: vec![] }.encode();
+    // A frame with one event of an unknown type
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 9]);
+    assert!(Replay::decode(&bytes).is_err());
+}
+
+#[test]
+fn frames_are_played_on_their_recorded_ticks() {
+    let frame = |millis, tick, pending| ReplayFrame {
+        time: Duration::from_millis(millis),
+        tick,
+        pending,
+        events: vec![],
+    };
+    // The second frame was a freeze: 4 ticks elapsed but only 2 were simulated
+    let mut player = ReplayPlayer::new(Replay {
+        seed: 0,
+        player: None,
+        columns: vec![],
+        frames: vec![frame(0, 50, 0), frame(70, 54, 2), frame(90, 55, 1)],
+    });
+    let mut input_cache = InputCache::default();
+    let mut timer = Timer::manual();
+    let start = timer.time();
+    // The server of the replay isn't at the recorded tick
+    let mut ticks = Ticks::starting_at(1000);
+
+    assert!(player.play_frame(&mut input_cache, &mut timer, &mut ticks));
+    assert_eq!(ticks.pending_ticks(), 1000..1000);
+    assert!(player.play_frame(&mut input_cache, &mut timer, &mut ticks));
+    assert_eq!(ticks.pending_ticks(), 1002..1004);
+    assert_eq!(timer.time() - start, Duration::from_millis(70));
+
+    player.hold_frame(&mut ticks);
+    assert_eq!(ticks.pending_ticks(), 1004..1004);
+    assert!(player.play_frame(&mut input_cache, &mut timer, &mut ticks));
+    assert_eq!(ticks.pending_ticks(), 1004..1005);
+    assert!(!player.play_frame(&mut input_cache, &mut timer, &mut ticks));
+}
+
+#[test]
+fn recordings_start_from_the_saved_world() {
+    let temp_dir = TempDir::new("replay-world");
+    let (blocks, states) = column(BlockID::Cobblestone);
+    {
+        let storage = WorldStorage::open_with_seed(temp_dir.path().join("recorded"), 42).unwrap();
+        storage.save_player("alice", &player()).unwrap();
+        storage.save_column((2, -3), &blocks, &states).unwrap();
+        ReplayRecorder::create(temp_dir.path().join("session.replay"), &storage, "alice").unwrap();
+    }
+
+    let replay = Replay::load(temp_dir.path().join("session.replay")).unwrap();
+    assert_eq!(replay.seed, 42);
+    assert_eq!(replay.player, Some(player()));
+    assert_eq!(replay.columns, vec![((2, -3), blocks.clone(), states.clone())]);
+
+    // Played under another name, in a new world
+    let storage = WorldStorage::open_with_seed(temp_dir.path().join("replayed"), replay.seed).unwrap();
+    replay.restore_world(&storage, "Player").unwrap();
+    assert_eq!(storage.load_player("Player").unwrap(), Some(player()));
+    assert_eq!(storage.saved_columns().unwrap(), vec![(2, -3)]);
+    assert_eq!(storage.load_column((2, -3)).unwrap(), Some((blocks, states)));
+}
@@ -0,0 +1,68 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use meinkraft::resource_pack::{ResourcePack, ResourcePacks};
+
+use common::TempDir;
+
+mod common;
+
+fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for &(name, bytes) in files {
+        zip.start_file(name, FileOptions::default().compression_method(CompressionMethod::Stored)).unwrap();
+        zip.write_all(bytes).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn packs_override_the_builtin_files_by_priority() {
+    let temp_dir = TempDir::new("resource-packs");
+    let root = temp_dir.path();
+    temp_dir.write_file("builtin/textures/blocks/stone.png", b"builtin stone");
+    temp_dir.write_file("builtin/textures/blocks/dirt.png", b"builtin dirt");
+    temp_dir.write_file("builtin/textures/gui/icons.png", b"builtin icons");
+    temp_dir.write_file("directory/pack.json", br#"{ "name": "Directory", "description": "Smooth stone" }"#);
+    temp_dir.write_file("directory/textures/blocks/stone.png", b"directory stone");
+    temp_dir.write_file("directory/textures/blocks/dirt.png", b"directory dirt");
+    write_zip(&root.join("archive.zip"), &[
+        ("pack.json", br#"{ "name": "Archive" }"#),
+        ("textures/blocks/stone.png", b"archive stone"),
+    ]);
+
+    let mut packs = ResourcePacks::new(ResourcePack::builtin(root.join("builtin")));
+    packs.push(ResourcePack::open(root.join("directory")).unwrap());
+    packs.push(ResourcePack::open(root.join("archive.zip")).unwrap());
+
+    let names = packs.packs().iter().map(|pack| pack.manifest().name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Archive", "Directory", "builtin"]);
+    let stone = packs.read("textures/blocks/stone.png");
+    let dirt = packs.read("textures/blocks/dirt.png");
+    let icons = packs.read("textures/gui/icons.png");
+    let glass = packs.read("textures/blocks/glass.png");
+    assert_eq!(stone.unwrap(), b"archive stone");
+    assert_eq!(dirt.unwrap(), b"directory dirt");
+    assert_eq!(icons.unwrap(), b"builtin icons");
+    assert_eq!(glass, None);
+}
+
+#[test]
+fn packs_need_a_manifest() {
+    let temp_dir = TempDir::new("broken-resource-packs");
+    let root = temp_dir.path();
+    temp_dir.write_file("no_manifest/textures/blocks/stone.png", b"stone");
+    temp_dir.write_file("bad_manifest/pack.json", b"{ \"description\": \"No name\" }");
+    temp_dir.write_file("not_a_zip.zip", b"stone");
+
+    let no_manifest = ResourcePack::open(root.join("no_manifest")).err().unwrap();
+    let bad_manifest = ResourcePack::open(root.join("bad_manifest")).err().unwrap();
+    let not_a_zip = ResourcePack::open(root.join("not_a_zip.zip")).err().unwrap();
+    assert!(no_manifest.contains("pack.json is missing"), "{}", no_manifest);
+    assert!(bad_manifest.contains("name"), "{}", bad_manifest);
+    assert!(not_a_zip.contains("not_a_zip.zip"), "{}", not_a_zip);
+}
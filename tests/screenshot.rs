@@ -0,0 +1,53 @@
+#![cfg(feature = "rendering")]
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use image::{Rgba, RgbaImage};
+use nalgebra_glm::{vec4, Mat4};
+
+use meinkraft::screenshot::{create_screenshot_file, format_timestamp, place_tile, tile_projection};
+
+use common::TempDir;
+
+mod common;
+
+#[test]
+fn each_tile_fills_the_screen_with_its_part_of_the_view() {
+    let projection = nalgebra_glm::perspective(16.0 / 9.0, 1.2, 0.1, 100.0);
+    let point = vec4(3.0, -1.5, -10.0, 1.0);
+    let ndc = |projection: &Mat4| {
+        let clip = projection * point;
+        (clip.x / clip.w, clip.y / clip.w)
+    };
+    // The point is in the bottom right quarter of the screen, so in the tile (1, 0) out of 2x2
+    let (x, y) = ndc(&projection);
+    assert!(x > 0.0 && y < 0.0);
+    let (tile_x, tile_y) = ndc(&tile_projection(&projection, 2, 1, 0));
+    assert!((tile_x - (2.0 * x - 1.0)).abs() < 1e-5, "{} {}", x, tile_x);
+    assert!((tile_y - (2.0 * y + 1.0)).abs() < 1e-5, "{} {}", y, tile_y);
+    assert_eq!(tile_projection(&projection, 1, 0, 0), projection);
+}
+
+#[test]
+fn tiles_are_placed_from_the_bottom_left() {
+    let mut image = RgbaImage::new(4, 2);
+    place_tile(&mut image, &RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255])), 2, 1, 0);
+    assert_eq!(image.get_pixel(3, 1), &Rgba([255, 0, 0, 255]));
+    assert_eq!(image.get_pixel(2, 1), &Rgba([255, 0, 0, 255]));
+    assert_eq!(image.get_pixel(3, 0), &Rgba([0, 0, 0, 0]));
+    assert_eq!(image.get_pixel(1, 1), &Rgba([0, 0, 0, 0]));
+}
+
+#[test]
+fn screenshots_are_named_after_the_time() {
+    assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01_00.00.00");
+    assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)), "2000-02-29_12.34.56");
+    assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(1_592_748_232)), "2020-06-21_14.03.52");
+
+    let temp_dir = TempDir::new("screenshots");
+    let directory = temp_dir.path();
+    let (first, _) = create_screenshot_file(directory, UNIX_EPOCH).unwrap();
+    let (second, _) = create_screenshot_file(directory, UNIX_EPOCH).unwrap();
+    assert_eq!(first, directory.join("1970-01-01_00.00.00.png"));
+    assert_eq!(second, directory.join("1970-01-01_00.00.00_2.png"));
+}
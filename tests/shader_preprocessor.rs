@@ -0,0 +1,64 @@
+use meinkraft::shader_preprocessor::ShaderPreprocessor;
+
+use common::TempDir;
+
+mod common;
+
+#[test]
+fn includes_and_defines_are_pasted_in_the_shader() {
+    let temp_dir = TempDir::new("shaders");
+    let root = temp_dir.path();
+    temp_dir.write_file("common/fog.glsl", "float fog() {\n    return 1.0;\n}\n");
+    temp_dir.write_file("common/camera.glsl", "#include \"common/fog.glsl\"\nuniform mat4 view;\n");
+    temp_dir.write_file("voxel.frag", "#version 450 core\n#include \"common/camera.glsl\"\n#include \"common/fog.glsl\"\nvoid main() {}\n");
+    temp_dir.write_file("broken.frag", "#version 450 core\n#include <common/fog.glsl>\n");
+    temp_dir.write_file("missing.frag", "#version 450 core\n\n#include \"common/missing.glsl\"\n");
+
+    let mut preprocessor = ShaderPreprocessor::new(root);
+    preprocessor.define("ENABLE_FOG", "").define("RENDER_DISTANCE", "10");
+    let shader = preprocessor.process("voxel.frag");
+    let broken = preprocessor.process("broken.frag").unwrap_err();
+    let missing = preprocessor.process("missing.frag").unwrap_err();
+
+    let shader = shader.unwrap();
+    let file = |path: &str| root.join(path).to_string_lossy().into_owned();
+    assert_eq!(shader.files(), &[file("voxel.frag"), file("common/camera.glsl"), file("common/fog.glsl")]);
+    // The files are included once, and the lines numbered like in them
+    assert_eq!(shader.source(), "\
+#version 450 core
+#define ENABLE_FOG
+#define RENDER_DISTANCE 10
+#line 2 0
+#line 1 1
+#line 1 2
+float fog() {
+    return 1.0;
+}
+#line 2 1
+uniform mat4 view;
+#line 3 0
+#line 4 0
+void main() {}
+");
+    assert!(broken.contains(&format!("{}:2", file("broken.frag"))), "{}", broken);
+    assert!(missing.contains(&format!("{}:3", file("missing.frag"))) && missing.contains("common/missing.glsl"), "{}", missing);
+}
+
+#[test]
+fn driver_logs_point_at_the_original_files() {
+    let temp_dir = TempDir::new("shader-logs");
+    let root = temp_dir.path();
+    temp_dir.write_file("common/fog.glsl", "float fog() {}\n");
+    temp_dir.write_file("voxel.vert", "#version 450 core\n#include \"common/fog.glsl\"\n");
+    let shader = ShaderPreprocessor::new(root).process("voxel.vert");
+
+    let shader = shader.unwrap();
+    let fog = root.join("common/fog.glsl").to_string_lossy().into_owned();
+    let vert = root.join("voxel.vert").to_string_lossy().into_owned();
+    // Mesa, NVIDIA and AMD
+    let log = "0:2(5): error: syntax error\n1(1) : error C0000: missing return\nERROR: 1:1: 'fog' : no return\nlinked 12:30";
+    assert_eq!(shader.map_log(log), format!(
+        "{}:2(5): error: syntax error\n{}:1 : error C0000: missing return\nERROR: {}:1: 'fog' : no return\nlinked 12:30",
+        vert, fog, fog,
+    ));
+}
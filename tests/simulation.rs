@@ -0,0 +1,373 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nalgebra_glm::{Vec3, vec3};
+use specs::{Builder, Dispatcher, Entity, World, WorldExt};
+
+use meinkraft::biome::Climate;
+use meinkraft::block_state::BlockState;
+use meinkraft::chunk::{BlockID, COLUMN_VOLUME};
+use meinkraft::chunk_manager::ChunkManager;
+use meinkraft::constants::{MAX_VERTICAL_VELOCITY, PLAYER_EYES_HEIGHT, TICKRATE, WALKING_SPEED};
+use meinkraft::ecs::simulation::{replay_dispatcher, simulation_dispatcher, simulation_world};
+use meinkraft::ecs::systems::{IncomingColumns, InputScript, NetworkOutbox};
+use meinkraft::input::{Action, InputEvent, Key, MouseButton};
+use meinkraft::inventory::Inventory;
+use meinkraft::network::protocol::ClientMessage;
+use meinkraft::physics::Interpolator;
+use meinkraft::physics_body::PhysicsBody;
+use meinkraft::player::PlayerState;
+use meinkraft::replay::{Replay, ReplayFrame};
+use meinkraft::ticks::Ticks;
+use meinkraft::timer::Timer;
+
+use common::{block_models, run_on_big_stack};
+
+mod common;
+
+const FRAME: f32 = 1.0 / 60.0;
+const GROUND_HEIGHT: i32 = 64;
+
+/// Stone up to GROUND_HEIGHT, with a 2 blocks high wall at `wall_distance` blocks around (8, 8)
+fn flat_column((c_x, c_z): (i32, i32), wall_distance: Option<i32>) -> Vec<BlockID> {
+    let mut blocks = vec![BlockID::Air; COLUMN_VOLUME];
+    for y in 0..GROUND_HEIGHT + 2 {
+        for z in 0..16 {
+            for x in 0..16 {
+                let (w_x, w_z) = (c_x * 16 + x, c_z * 16 + z);
+                let is_wall = wall_distance.map_or(false, |d| {
+                    (w_x - 8).abs().max((w_z - 8).abs()) == d
+                });
+                if y < GROUND_HEIGHT || is_wall {
+                    blocks[(256 * y + 16 * z + x) as usize] = BlockID::Stone;
+                }
+            }
+        }
+    }
+    blocks
+}
+
+struct Simulation {
+    world: World,
+    dispatcher: Dispatcher<'static, 'static>,
+    player: Entity,
+}
+
+impl Simulation {
+    fn new(script: InputScript, wall_distance: Option<i32>) -> Self {
+        let timer = Timer::with_fixed_step(Duration::from_secs_f32(FRAME));
+        Self::build(timer, simulation_dispatcher(script).build(), wall_distance)
+    }
+
+    /// Frames of `frame_time` seconds instead of `FRAME`, `run_for` still counts in frames of `FRAME`
+    fn at_frame_time(script: InputScript, frame_time: f32) -> Self {
+        let timer = Timer::with_fixed_step(Duration::from_secs_f32(frame_time));
+        Self::build(timer, simulation_dispatcher(script).build(), None)
+    }
+
+    fn replaying(replay: Replay, wall_distance: Option<i32>) -> Self {
+        Self::build(Timer::manual(), replay_dispatcher(replay).build(), wall_distance)
+    }
+
+    fn build(timer: Timer, mut dispatcher: Dispatcher<'static, 'static>, wall_distance: Option<i32>) -> Self {
+        let mut world = simulation_world(timer, block_models());
+        dispatcher.setup(&mut world);
+
+        {
+            let mut incoming_columns = world.write_resource::<IncomingColumns>();
+            for x in -2..=2 {
+                for z in -2..=2 {
+                    let states = vec![BlockState::default(); COLUMN_VOLUME];
+                    let climate = vec![Climate::default(); 16 * 16];
+                    incoming_columns.columns.push(((x, z), flat_column((x, z), wall_distance), states, climate));
+                }
+            }
+        }
+
+        let player = world.create_entity()
+            .with(PlayerState::new())
+            .with(Interpolator::new(PhysicsBody::new_player(vec3(8.5, GROUND_HEIGHT as f32, 8.5))))
+            .with(Inventory::new())
+            .build();
+
+        Self {
+            world,
+            dispatcher,
+            player,
+        }
+    }
+
+    fn run_for(&mut self, seconds: f32) {
+        self.run_frames((seconds / FRAME).round() as usize);
+    }
+
+    fn run_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.dispatcher.dispatch(&self.world);
+            self.world.maintain();
+        }
+    }
+
+    fn body(&self) -> PhysicsBody {
+        let storage = self.world.read_storage::<Interpolator<PhysicsBody>>();
+        storage.get(self.player).unwrap().get_latest_state().clone()
+    }
+
+    fn position(&self) -> Vec3 {
+        self.body().position
+    }
+
+    fn player_state<T>(&self, f: impl FnOnce(&PlayerState) -> T) -> T {
+        f(self.world.read_storage::<PlayerState>().get(self.player).unwrap())
+    }
+}
+
+#[test]
+fn walk_forward_on_flat_ground() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new().hold_key(0.5, 2.5, Key::W), None);
+        simulation.run_for(0.5);
+        let start = simulation.position();
+        assert_eq!(start.y, GROUND_HEIGHT as f32);
+        assert!(simulation.body().is_on_ground);
+
+        simulation.run_for(3.0);
+        let end = simulation.position();
+        let walked = vec3(end.x - start.x, 0.0, end.z - start.z).norm();
+        // The player accelerates for a few frames and slides a bit after releasing the key
+        assert!((walked - 2.0 * WALKING_SPEED).abs() < 0.5, "walked {}", walked);
+        assert_eq!(end.y, GROUND_HEIGHT as f32);
+        assert!(!simulation.player_state(|p| p.is_sprinting));
+    });
+}
+
+#[test]
+fn every_frame_simulates_the_ticks_elapsed() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new(), None);
+        simulation.run_for(2.0);
+        // The first frame starts the clock
+        let tick = simulation.world.read_resource::<Ticks>().tick as f32;
+        assert!((tick - 2.0 * TICKRATE).abs() <= 2.0, "{} ticks", tick);
+    });
+}
+
+/// Spawns a 0.25 wide body at `position` and returns its state after `seconds`
+fn throw_body(simulation: &mut Simulation, position: Vec3, velocity: Vec3, seconds: f32) -> PhysicsBody {
+    let mut body = PhysicsBody::new(position, 0.25, 0.25);
+    body.velocity = velocity;
+    let body = simulation.world.create_entity()
+        .with(Interpolator::new(body))
+        .build();
+    simulation.run_for(seconds);
+
+    let storage = simulation.world.read_storage::<Interpolator<PhysicsBody>>();
+    storage.get(body).unwrap().get_latest_state().clone()
+}
+
+#[test]
+fn other_bodies_collide_like_the_player() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new(), Some(3));
+        let position = vec3(8.5, GROUND_HEIGHT as f32 + 1.0, 8.5);
+        let item = throw_body(&mut simulation, position, vec3(20.0, 0.0, 0.0), 2.0);
+        assert!(item.is_on_ground);
+        assert_eq!(item.position.y, GROUND_HEIGHT as f32);
+        // Stopped by the wall 3 blocks away
+        assert!(item.aabb.maxs.x <= 8.0 + 3.0, "went through the wall: {:?}", item.position);
+    });
+}
+
+#[test]
+fn fast_bodies_dont_tunnel_through_walls() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new(), Some(3));
+        // 2 blocks per tick, more than the thickness of the wall
+        let position = vec3(8.5, GROUND_HEIGHT as f32 + 1.0, 8.5);
+        let item = throw_body(&mut simulation, position, vec3(2.0 * TICKRATE, 0.0, 0.0), 1.0);
+        assert_eq!(item.aabb.maxs.x, 8.0 + 3.0, "went through the wall: {:?}", item.position);
+        assert_eq!(item.position.y, GROUND_HEIGHT as f32);
+    });
+}
+
+#[test]
+fn bodies_falling_at_terminal_velocity_land_on_the_ground() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new(), None);
+        // 1.5 blocks per tick
+        let position = vec3(5.5, GROUND_HEIGHT as f32 + 40.0, 5.5);
+        let item = throw_body(&mut simulation, position, vec3(0.0, -MAX_VERTICAL_VELOCITY, 0.0), 2.0);
+        assert!(item.is_on_ground);
+        assert_eq!(item.position.y, GROUND_HEIGHT as f32);
+    });
+}
+
+#[test]
+fn bodies_land_on_slabs_and_fall_through_plants() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new(), None);
+        // The columns are loaded by the first frame
+        simulation.run_frames(1);
+        {
+            let chunk_manager = simulation.world.read_resource::<Arc<ChunkManager>>();
+            assert!(chunk_manager.set_block(BlockID::OakSlab, 5, GROUND_HEIGHT, 5));
+            assert!(chunk_manager.set_block(BlockID::TallGrass, 5, GROUND_HEIGHT, 11));
+        }
+
+        let on_slab = throw_body(&mut simulation, vec3(5.5, GROUND_HEIGHT as f32 + 3.0, 5.5), vec3(0.0, 0.0, 0.0), 1.0);
+        assert!(on_slab.is_on_ground);
+        assert_eq!(on_slab.position.y, GROUND_HEIGHT as f32 + 0.5);
+
+        let in_grass = throw_body(&mut simulation, vec3(5.5, GROUND_HEIGHT as f32 + 3.0, 11.5), vec3(0.0, 0.0, 0.0), 1.0);
+        assert!(in_grass.is_on_ground);
+        assert_eq!(in_grass.position.y, GROUND_HEIGHT as f32);
+    });
+}
+
+#[test]
+fn walls_stop_the_player() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new().hold_key(0.5, 5.0, Key::W), Some(3));
+        simulation.run_for(4.0);
+        let blocked = simulation.position();
+        simulation.run_for(0.5);
+        let end = simulation.position();
+
+        assert_eq!(blocked, end);
+        assert!((end.x - 8.5).abs() < 3.0 && (end.z - 8.5).abs() < 3.0, "went through the wall: {:?}", end);
+        assert_eq!(end.y, GROUND_HEIGHT as f32);
+    });
+}
+
+/// Walks forward for 2 seconds towards a plateau of `block` on the ground, 3 blocks away from the player.
+/// Returns where the player stopped
+fn walk_onto_plateau(simulation: &mut Simulation, block: BlockID) -> Vec3 {
+    // The columns are loaded by the first frame
+    simulation.run_frames(1);
+    {
+        let chunk_manager = simulation.world.read_resource::<Arc<ChunkManager>>();
+        for z in -24..=40i32 {
+            for x in -24..=40i32 {
+                if (x - 8).abs().max((z - 8).abs()) >= 3 {
+                    assert!(chunk_manager.set_block(block, x, GROUND_HEIGHT, z));
+                }
+            }
+        }
+    }
+
+    simulation.run_for(3.0);
+    simulation.position()
+}
+
+#[test]
+fn the_player_steps_onto_slabs() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new().hold_key(0.5, 2.5, Key::W), None);
+        let end = walk_onto_plateau(&mut simulation, BlockID::OakSlab);
+        assert!(vec3(end.x - 8.5, 0.0, end.z - 8.5).norm() > 4.0, "stopped at the slabs: {:?}", end);
+        assert_eq!(end.y, GROUND_HEIGHT as f32 + 0.5);
+        // The camera caught up with the player
+        let camera_height = simulation.player_state(|p| *p.camera_height.get_latest_state());
+        assert!((camera_height - PLAYER_EYES_HEIGHT).abs() < 0.01, "camera at {}", camera_height);
+    });
+}
+
+#[test]
+fn one_block_ledges_need_a_jump() {
+    run_on_big_stack(|| {
+        let mut simulation = Simulation::new(InputScript::new().hold_key(0.5, 2.5, Key::W), None);
+        let end = walk_onto_plateau(&mut simulation, BlockID::Stone);
+        assert!(vec3(end.x - 8.5, 0.0, end.z - 8.5).norm() < 3.0, "walked onto the ledge: {:?}", end);
+        assert_eq!(end.y, GROUND_HEIGHT as f32);
+    });
+}
+
+#[test]
+fn jumps_dont_depend_on_the_frame_rate() {
+    run_on_big_stack(|| {
+        let jump_height = |frame_time: f32| {
+            let script = InputScript::new().hold_key(0.5, 0.6, Key::Space);
+            let mut simulation = Simulation::at_frame_time(script, frame_time);
+            let mut highest = GROUND_HEIGHT as f32;
+            for _ in 0..(1.5 / frame_time) as usize {
+                simulation.run_frames(1);
+                highest = highest.max(simulation.position().y);
+            }
+            assert!(simulation.body().is_on_ground);
+            highest - GROUND_HEIGHT as f32
+        };
+
+        let (slow, fast) = (jump_height(1.0 / 20.0), jump_height(1.0 / 144.0));
+        assert!(fast > 1.0, "jumped {}", fast);
+        // The height is only sampled once per frame, the slow frames can miss the top by a bit
+        assert!((fast - slow).abs() < 0.05, "jumped {} at 144 FPS and {} at 20 FPS", fast, slow);
+    });
+}
+
+#[test]
+fn double_tapping_forward_sprints() {
+    run_on_big_stack(|| {
+        let script = InputScript::new()
+            .hold_key(0.5, 0.6, Key::W)
+            .hold_key(0.7, 1.5, Key::W);
+        let mut simulation = Simulation::new(script, None);
+        simulation.run_for(1.0);
+        assert!(simulation.player_state(|p| p.is_sprinting));
+        simulation.run_for(1.0);
+        assert!(!simulation.player_state(|p| p.is_sprinting));
+    });
+}
+
+#[test]
+fn breaking_the_targeted_block_is_sent_to_the_server() {
+    run_on_big_stack(|| {
+        // Look down at the block under the player
+        let script = InputScript::new()
+            .move_cursor(0.0, 0.0, 0.0)
+            .move_cursor(0.1, 0.0, 10_000.0)
+            .click(0.5, MouseButton::Button1);
+        let mut simulation = Simulation::new(script, None);
+        simulation.run_for(1.0);
+
+        let outbox = simulation.world.read_resource::<NetworkOutbox>();
+        let broken = outbox.messages.iter().find_map(|message| match message {
+            ClientMessage::SetBlock { x, y, z, block: BlockID::Air, .. } => Some((*x, *y, *z)),
+            _ => None,
+        });
+        assert_eq!(broken, Some((8, GROUND_HEIGHT - 1, 8)));
+    });
+}
+
+#[test]
+fn replays_play_out_identically() {
+    run_on_big_stack(|| {
+        // Uneven frame times, like a real session, recorded with their ticks
+        let start = Instant::now();
+        let mut ticks = Ticks::default();
+        let mut time = Duration::from_secs(0);
+        let frames = (0..300).map(|i| {
+            time += Duration::from_millis(if i % 3 == 0 { 31 } else { 9 });
+            let key = |key, action| vec![InputEvent::Key(key, action)];
+            let events = match i {
+                30 => key(Key::W, Action::Press),
+                80 => key(Key::Space, Action::Press),
+                82 => key(Key::Space, Action::Release),
+                150 => vec![InputEvent::CursorPos(0.0, 0.0), InputEvent::CursorPos(300.0, 0.0)],
+                250 => key(Key::W, Action::Release),
+                _ => vec![],
+            };
+            ticks.advance(start + time);
+            ReplayFrame { time, tick: ticks.tick, pending: ticks.pending, events }
+        }).collect();
+        let replay = Replay { seed: 0, player: None, columns: vec![], frames };
+
+        let mut first = Simulation::replaying(replay.clone(), None);
+        first.run_frames(replay.frames.len());
+        let mut second = Simulation::replaying(Replay::decode(&replay.encode()).unwrap(), None);
+        second.run_frames(replay.frames.len());
+
+        let end = first.position();
+        assert!(vec3(end.x - 8.5, 0.0, end.z - 8.5).norm() > 2.0, "didn't move: {:?}", end);
+        assert_eq!(end, second.position());
+    });
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "rendering")]
+
+use image::{Rgba, RgbaImage};
+
+use meinkraft::text::{Font, TextMesh};
+
+/// A font of 16x16 pixel cells where `A` is 3 pixels wide and `i` 1 pixel wide
+fn font() -> Font {
+    let mut image = RgbaImage::new(256, 256);
+    let mut fill = |c: u8, width: u32| {
+        let (x, y) = (u32::from(c % 16) * 16, u32::from(c / 16) * 16);
+        for i in 0..width {
+            image.put_pixel(x + i, y + 5, Rgba([255, 255, 255, 255]));
+        }
+    };
+    fill(b'A', 3);
+    fill(b'i', 1);
+    Font::from_image(&image)
+}
+
+#[test]
+fn glyphs_are_as_wide_as_their_pixels() {
+    let font = font();
+    // The cells are scaled down to 8 pixels, with a pixel between glyphs
+    assert_eq!(font.advance('A'), 1.5 + 1.0);
+    assert_eq!(font.advance('i'), 0.5 + 1.0);
+    assert_eq!(font.advance(' '), 4.0);
+    assert_eq!(font.advance('é'), font.advance('?'));
+    assert_eq!(font.width("Ai"), 2.5 + 0.5);
+    assert_eq!(font.width(""), 0.0);
+}
+
+#[test]
+fn spaces_advance_without_a_quad() {
+    let font = font();
+    let mut mesh = TextMesh::new();
+    mesh.add_text(&font, "A i", (10.0, 20.0), 2.0, [1.0; 4]);
+    assert_eq!(mesh.vertex_count(), 2 * 6);
+    // Position, texture coordinates and colour; the second glyph starts after `A` and the space
+    let second_glyph = &mesh.vertices()[6 * 8..];
+    assert_eq!(&second_glyph[..2], &[10.0 + 2.0 * (2.5 + 4.0), 20.0]);
+    assert_eq!(&second_glyph[2..4], &[f32::from(b'i' % 16) / 16.0, f32::from(b'i' / 16) / 16.0]);
+
+    mesh.clear();
+    mesh.add_rectangle((0.0, 0.0, 5.0, 3.0), [0.0, 0.0, 0.0, 0.5]);
+    let corners = mesh.vertices().chunks(8).map(|vertex| (vertex[0], vertex[1])).collect::<Vec<_>>();
+    assert!(corners.contains(&(5.0, 3.0)) && corners.iter().all(|&(x, y)| x <= 5.0 && y <= 3.0));
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use meinkraft::texture_animation::{ANIMATION_TICK, TextureAnimation};
+
+fn at_tick(animation: &TextureAnimation, tick: u32) -> u32 {
+    animation.frame_at(ANIMATION_TICK * tick + Duration::from_millis(1))
+}
+
+#[test]
+fn frames_are_shown_from_top_to_bottom_by_default() {
+    let animation = TextureAnimation::parse(br#"{ "animation": { "frametime": 2 } }"#, 3).unwrap();
+    let frames = (0..8).map(|tick| at_tick(&animation, tick)).collect::<Vec<_>>();
+    assert_eq!(frames, vec![0, 0, 1, 1, 2, 2, 0, 0]);
+
+    let animation = TextureAnimation::parse(br#"{ "animation": {} }"#, 2).unwrap();
+    assert_eq!((0..3).map(|tick| at_tick(&animation, tick)).collect::<Vec<_>>(), vec![0, 1, 0]);
+}
+
+#[test]
+fn frames_can_be_reordered_and_timed() {
+    let animation = TextureAnimation::parse(
+        br#"{ "animation": { "frametime": 1, "frames": [2, { "index": 0, "time": 3 }, 1] } }"#, 3).unwrap();
+    let frames = (0..6).map(|tick| at_tick(&animation, tick)).collect::<Vec<_>>();
+    assert_eq!(frames, vec![2, 0, 0, 0, 1, 2]);
+}
+
+#[test]
+fn broken_animations_are_rejected() {
+    let out_of_range = TextureAnimation::parse(br#"{ "animation": { "frames": [0, 4] } }"#, 2).unwrap_err();
+    assert!(out_of_range.contains("frame 4"), "{}", out_of_range);
+    assert!(TextureAnimation::parse(br#"{ "animation": {} }"#, 0).is_err());
+    assert!(TextureAnimation::parse(br#"{ "animation": { "frametime": 0 } }"#, 2).is_err());
+    assert!(TextureAnimation::parse(b"{}", 2).is_err());
+}
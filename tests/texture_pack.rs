@@ -0,0 +1,45 @@
+#![cfg(feature = "rendering")]
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use meinkraft::constants::MAX_BLOCK_TEXTURE_SIZE;
+use meinkraft::texture_pack::{animation_frames, block_texture_size, check_block_texture, scale_block_texture};
+
+fn texture(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, _| {
+        if x < width / 2 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) }
+    }))
+}
+
+#[test]
+fn block_textures_are_square_powers_of_two() {
+    assert_eq!(check_block_texture("textures/blocks/stone.png", &texture(32, 32)), Ok(()));
+    let not_square = check_block_texture("textures/blocks/stone.png", &texture(16, 32)).unwrap_err();
+    let not_power_of_two = check_block_texture("textures/blocks/dirt.png", &texture(24, 24)).unwrap_err();
+    assert!(not_square.contains("textures/blocks/stone.png") && not_square.contains("16x32"), "{}", not_square);
+    assert!(not_power_of_two.contains("textures/blocks/dirt.png") && not_power_of_two.contains("24x24"), "{}", not_power_of_two);
+}
+
+#[test]
+fn the_array_fits_the_largest_texture() {
+    assert_eq!(block_texture_size(&[texture(16, 16), texture(64, 64), texture(32, 32)]), 64);
+    assert_eq!(block_texture_size(&[texture(MAX_BLOCK_TEXTURE_SIZE * 2, MAX_BLOCK_TEXTURE_SIZE * 2)]), MAX_BLOCK_TEXTURE_SIZE);
+
+    // Scaling up keeps the pixels sharp
+    let scaled = scale_block_texture(&texture(16, 16), 64);
+    assert_eq!(scaled.dimensions(), (64, 64));
+    assert_eq!(scaled.get_pixel(31, 10), Rgba([255, 0, 0, 255]));
+    assert_eq!(scaled.get_pixel(32, 10), Rgba([0, 0, 255, 255]));
+    assert_eq!(scale_block_texture(&texture(64, 64), 16).dimensions(), (16, 16));
+}
+
+#[test]
+fn animations_are_split_into_square_frames() {
+    let strip = DynamicImage::ImageRgba8(RgbaImage::from_fn(16, 48, |_, y| Rgba([(y / 16) as u8, 0, 0, 255])));
+    let frames = animation_frames(&strip);
+    assert_eq!(frames.len(), 3);
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.dimensions(), (16, 16));
+        assert_eq!(frame.get_pixel(8, 8), Rgba([i as u8, 0, 0, 255]));
+    }
+}
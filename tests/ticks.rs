@@ -0,0 +1,33 @@
+use std::time::{Duration, Instant};
+
+use meinkraft::ticks::Ticks;
+
+#[test]
+fn frames_are_spent_in_ticks() {
+    let start = Instant::now();
+    let mut ticks = Ticks::starting_at(100);
+    // The first frame starts the clock
+    assert_eq!(ticks.advance(start), 0);
+    assert_eq!(ticks.pending_ticks(), 100..100);
+
+    ticks.advance(start + Duration::from_secs_f32(2.5 * Ticks::DT));
+    assert_eq!(ticks.pending_ticks(), 100..102);
+    assert!((ticks.alpha - 0.5).abs() < 1e-3, "{}", ticks.alpha);
+    assert!(ticks.until_next_tick() < Duration::from_secs_f32(0.6 * Ticks::DT));
+
+    ticks.step();
+    assert_eq!(ticks.pending_ticks(), 102..103);
+}
+
+#[test]
+fn the_ticks_of_a_freeze_are_counted_but_not_simulated() {
+    let start = Instant::now();
+    let mut ticks = Ticks::default();
+    ticks.advance(start);
+    let skipped = ticks.advance(start + Duration::from_secs(10));
+    assert!(skipped > 0);
+    assert!(ticks.pending <= (0.25 / Ticks::DT) as u32 + 1, "{} ticks", ticks.pending);
+    let counted = (skipped + ticks.pending) as f32;
+    assert!((counted - 10.0 / Ticks::DT).abs() <= 1.0, "{} ticks", counted);
+    assert_eq!(ticks.tick, skipped as u64 + ticks.pending as u64);
+}
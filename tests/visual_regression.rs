@@ -0,0 +1,35 @@
+#![cfg(feature = "rendering")]
+
+use image::{Rgba, RgbaImage};
+use nalgebra_glm::vec3;
+
+use meinkraft::offscreen::{check_golden_image, compare_images, Scene};
+
+#[test]
+fn pixels_within_the_tolerance_match() {
+    let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 150, 200, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, Rgba([103, 150, 200, 255]));
+    actual.put_pixel(3, 3, Rgba([100, 150, 220, 255]));
+
+    let difference = compare_images(&actual, &expected, 4).unwrap();
+    assert_eq!(difference.differing_pixels, 1);
+    assert_eq!(difference.max_difference, 20);
+    assert_eq!(difference.diff.get_pixel(3, 3), &Rgba([255, 0, 0, 255]));
+    assert_ne!(difference.diff.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    assert!(compare_images(&RgbaImage::new(4, 2), &expected, 4).is_err());
+}
+
+/// Needs an OpenGL 4.6 context, llvmpipe gives one with the overrides in `meinkraft::offscreen`
+#[test]
+fn the_world_looks_like_the_golden_image() {
+    let scene = Scene {
+        seed: 42,
+        view_distance: 3,
+        camera_position: vec3(8.0, 180.0, 8.0),
+        camera_target: vec3(40.0, 90.0, 40.0),
+    };
+    let frame = scene.render(320, 240).unwrap();
+    // Software and hardware rasterizers round a few edges differently
+    check_golden_image("seed_42_hills", &frame, 8, 0.01).unwrap();
+}